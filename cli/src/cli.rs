@@ -38,7 +38,12 @@ pub enum CreateCommands {
     /// A block waiting for finalization.
     Block,
     /// An agenda waiting for governance approval.
-    Agenda,
+    Agenda {
+        /// If enabled, it creates a new agenda even if the same one
+        /// (except the timestamp) already exists.
+        #[clap(long, action)]
+        force: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
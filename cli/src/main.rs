@@ -4,7 +4,7 @@ use simperby::{types::*, Client};
 use simperby_cli::cli::{self, Commands, CreateCommands, PeerCommands, SignCommands};
 use simperby_core::{utils::get_timestamp, *};
 use simperby_repository::{
-    raw::RawRepository,
+    raw::{CloneOptions, RawRepository},
     server::{build_simple_git_server, PushVerifier},
 };
 
@@ -24,7 +24,23 @@ async fn run(
         (Commands::Genesis, _, _, _) => Client::genesis(&path).await,
         (Commands::Init, _, _, _) => Client::init(&path).await,
         (Commands::Clone { url }, _, _, _) => {
-            RawRepository::clone(&path, &url).await?;
+            RawRepository::clone_with_options(
+                &path,
+                &url,
+                CloneOptions {
+                    progress: Some(std::sync::Arc::new(|progress| {
+                        eprint!(
+                            "\rReceiving objects: {}/{} ({} bytes)",
+                            progress.received_objects,
+                            progress.total_objects,
+                            progress.received_bytes
+                        )
+                    })),
+                    ..Default::default()
+                },
+            )
+            .await?;
+            eprintln!();
             std::env::set_current_dir(path.clone())?;
             Client::init(&path).await
         }
@@ -192,21 +208,14 @@ async fn run(
                     |_| Ok(()),
                 )
         }
-        (Commands::Create(CreateCommands::Agenda), Some(config), Some(auth), _) => {
-            let mut client = Client::open(&path, config, auth.clone()).await?;
-            let reserved_state = client
-                .repository()
-                .get_raw()
-                .read()
-                .await
-                .read_reserved_state()
-                .await?;
-            let name = reserved_state
-                .query_name(&auth.private_key.public_key())
+        (Commands::Create(CreateCommands::Agenda { force }), Some(config), Some(auth), _) => {
+            let mut client = Client::open(&path, config, auth).await?;
+            let name = client
+                .my_member_name()
                 .ok_or(eyre!("member does not exist with the public key"))?;
             client
                 .repository_mut()
-                .create_agenda(name)
+                .create_agenda(name, force)
                 .await
                 .map_or_else(
                     |err| Err(eyre!("failed to create an agenda: {}", err)),
@@ -229,8 +238,8 @@ async fn run(
         }
         (Commands::Veto { revision }, Some(config), Some(auth), _) => {
             let mut client = Client::open(&path, config, auth.clone()).await?;
-            if revision.is_none() {
-                client.veto_round().await
+            let receipt = if revision.is_none() {
+                client.veto_round().await?
             } else {
                 let commit_hash = client
                     .repository()
@@ -239,17 +248,23 @@ async fn run(
                     .await
                     .retrieve_commit_hash(revision.expect("revision is not none"))
                     .await?;
-                client.veto_block(commit_hash).await
-            }
+                client.veto_block(commit_hash).await?
+            };
+            println!("{:?}", receipt);
+            Ok(())
         }
         (Commands::Consensus { show }, Some(config), Some(auth), _) => {
             let mut client = Client::open(&path, config, auth.clone()).await?;
             if show {
-                todo!("showing the status of the consensus is not implemented yet")
+                println!("{:?}", client.get_consensus_status().await?);
+                Ok(())
             } else {
                 let result = client.progress_for_consensus().await;
                 match result {
-                    Ok(_) => Ok(()),
+                    Ok(progress) => {
+                        println!("{progress:?}");
+                        Ok(())
+                    }
                     Err(err) => Err(eyre!("failed to make a progress for consensus: {}", err)),
                 }
             }
@@ -6,6 +6,7 @@ use simperby_test_suite::*;
 
 fn generate_server_config() -> ServerConfig {
     ServerConfig {
+        address: "127.0.0.1".parse().unwrap(),
         peers_port: dispense_port(),
         governance_port: dispense_port(),
         consensus_port: dispense_port(),
@@ -73,10 +74,11 @@ async fn cli() {
         clients_path.push(dir.clone());
         run_command(format!("cp -a {server_dir}/. {dir}/")).await;
 
-        let config = Config {};
+        let config = Config::default();
         let config = serde_spb::to_string(&config).unwrap();
         let auth = Auth {
             private_key: key.clone(),
+            public_key: None,
         };
         let auth = serde_spb::to_string(&auth).unwrap();
         let port = server_config.peers_port;
@@ -95,10 +97,11 @@ async fn cli() {
     }
 
     // Add files for cli.
-    let config = Config {};
+    let config = Config::default();
     let config = serde_spb::to_string(&config).unwrap();
     let auth = Auth {
         private_key: keys[3].1.clone(),
+        public_key: None,
     };
     let auth = serde_spb::to_string(&auth).unwrap();
     let server_config = serde_spb::to_string(&server_config).unwrap();
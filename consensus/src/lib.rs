@@ -6,7 +6,7 @@ use simperby_core::utils::get_timestamp;
 use simperby_core::*;
 use simperby_network::*;
 use state::*;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -18,6 +18,7 @@ pub use vetomint::ConsensusParams;
 const STATE_FILE_NAME: &str = "state.json";
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
 pub enum ProgressResult {
     Proposed(ConsensusRound, Hash256, Timestamp),
     NonNilPreVoted(ConsensusRound, Hash256, Timestamp),
@@ -26,15 +27,61 @@ pub enum ProgressResult {
     NilPreCommitted(ConsensusRound, Timestamp),
     Finalized(Finalization),
     ViolationReported(PublicKey, String, Timestamp),
+    /// The given round timed out and the node moved on to the next round.
+    RoundTimeout(ConsensusRound, Timestamp),
+    /// The proposer of the round proposed a block that this node doesn't have yet.
+    ///
+    /// The node won't prevote for it until the block is fetched and registered as verified.
+    NeedBlockBody(Hash256),
+}
+
+/// Why a progress made nothing, as observed by this node in its current round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StallReason {
+    /// The leader of the round hasn't proposed yet, and this node has no block to vote for.
+    WaitingForProposal {
+        round: ConsensusRound,
+        leader: PublicKey,
+    },
+    /// This node has a block to propose, but it is not the leader of the round.
+    NotLeader {
+        round: ConsensusRound,
+        leader: PublicKey,
+    },
+    /// This node is the leader of the round, but no block candidate has been selected.
+    NoBlockCandidate { round: ConsensusRound },
+    /// The block of the round has been voted for, and the votes of the others are awaited.
+    WaitingForVotes { round: ConsensusRound },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Finalization {
     pub block_hash: Hash256,
+    /// The header of the finalized block, whose hash is `block_hash`.
+    pub header: BlockHeader,
     pub timestamp: Timestamp,
     pub proof: FinalizationProof,
 }
 
+/// The voting power collected for a block in a round.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoteTally {
+    pub prevote: VotingPower,
+    pub precommit: VotingPower,
+}
+
+/// How close a block is to the finalization, as observed by this node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockStatus {
+    /// The current round of this node.
+    pub round: ConsensusRound,
+    /// Whether the block is proposed in the current round.
+    pub proposed: bool,
+    /// The votes for the block in the DMS, for each round that has any.
+    pub votes: BTreeMap<ConsensusRound, VoteTally>,
+    pub total_voting_power: VotingPower,
+}
+
 /// The consensus module
 pub struct Consensus {
     /// The distributed consensus message set.
@@ -44,30 +91,56 @@ pub struct Consensus {
 }
 
 impl Consensus {
-    /// Creates a consensus instance.
+    /// Creates a consensus instance for the next height of the last finalized block.
+    ///
+    /// The validator set and the leader order of the height are derived from
+    /// the reserved state of `last_finalization_info`.
     ///
     /// It clears and re-initializes the DMS and the stroage
     /// if the block header is different from the last one.
+    ///
+    /// The messages of this node found in the DMS are recorded as broadcasted,
+    /// in case the node crashed before `flush()` committed the state.
+    ///
+    /// With `this_node_key` of `None` (or of a non-validator), the instance only follows
+    /// the messages of the validators and never proposes nor votes.
     pub async fn new(
         dms: Arc<RwLock<Dms<ConsensusMessage>>>,
         state_storage: StorageImpl,
-        block_header: BlockHeader,
+        last_finalization_info: FinalizationInfo,
         consensus_parameters: ConsensusParams,
-        round_zero_timestamp: Timestamp,
         this_node_key: Option<PrivateKey>,
     ) -> Result<Self, Error> {
         let mut this = Self { dms, state_storage };
         // Prepare new state in case of storage reset.
         let new_state = State::new(
-            &block_header,
+            &last_finalization_info,
             consensus_parameters,
-            round_zero_timestamp,
-            this_node_key.clone().unwrap(),
+            this_node_key.clone(),
         )?;
-        if let Ok(state) = this.read_state().await {
-            if block_header != *state.block_header() {
+        if let Ok(mut state) = this.read_state().await {
+            if last_finalization_info.header != *state.block_header() {
                 return Err(eyre!("different block header in the storage"));
             }
+            if let Some(this_node_key) = &this_node_key {
+                let public_key = this_node_key.public_key();
+                let broadcasted = this
+                    .dms
+                    .read()
+                    .await
+                    .read_messages()
+                    .await?
+                    .into_iter()
+                    .filter(|message| {
+                        message
+                            .committers
+                            .iter()
+                            .any(|proof| proof.committer == public_key)
+                    })
+                    .map(|message| message.message.to_hash256());
+                state.mark_broadcasted(broadcasted);
+                this.commit_state(&state).await?;
+            }
         } else {
             this.dms.write().await.clear().await?;
             this.state_storage.remove_all_files().await?;
@@ -82,8 +155,8 @@ impl Consensus {
             .members
             .iter()
             .collect::<BTreeSet<_>>()
-            != block_header
-                .validator_set
+            != new_state
+                .validator_set()
                 .iter()
                 .map(|(pubkey, _)| pubkey)
                 .collect::<BTreeSet<_>>()
@@ -98,20 +171,107 @@ impl Consensus {
         Ok(state.block_header().clone())
     }
 
+    /// Returns the validators of the current height, in the consensus leader order.
+    pub async fn get_validator_set(&self) -> Result<Vec<(PublicKey, VotingPower)>, Error> {
+        let state = self.read_state().await?;
+        Ok(state.validator_set().to_vec())
+    }
+
+    /// Returns the current round of this node.
+    pub async fn get_round(&self) -> Result<ConsensusRound, Error> {
+        let state = self.read_state().await?;
+        Ok(state.round())
+    }
+
+    /// Returns the rounds that this node has vetoed.
+    pub async fn get_vetoed_rounds(&self) -> Result<BTreeSet<ConsensusRound>, Error> {
+        let state = self.read_state().await?;
+        Ok(state.vetoed_rounds().clone())
+    }
+
+    /// Returns the blocks that this node has vetoed.
+    pub async fn get_vetoed_block_hashes(&self) -> Result<BTreeSet<Hash256>, Error> {
+        let state = self.read_state().await?;
+        Ok(state.vetoed_block_hashes().clone())
+    }
+
     /// Checks whether the consensus is finalized.
     pub async fn check_finalized(&self) -> Result<Option<Finalization>, Error> {
         let state = self.read_state().await?;
         Ok(state.check_finalized())
     }
 
-    pub async fn register_verified_block_hash(&mut self, block_hash: Hash256) -> Result<(), Error> {
+    /// Tallies the messages in the DMS for the given block of the current height.
+    ///
+    /// Note that the messages of this node are counted only after `flush()`.
+    pub async fn get_block_status(&self, block_hash: Hash256) -> Result<BlockStatus, Error> {
+        let state = self.read_state().await?;
+        let voting_power = state
+            .validator_set()
+            .iter()
+            .cloned()
+            .collect::<BTreeMap<_, _>>();
+        let round = state.round();
+        let mut status = BlockStatus {
+            round,
+            proposed: false,
+            votes: BTreeMap::new(),
+            total_voting_power: voting_power.values().sum(),
+        };
+        for message in self.dms.read().await.read_messages().await? {
+            let power = message
+                .committers
+                .iter()
+                .filter_map(|commitment| voting_power.get(&commitment.committer))
+                .sum::<VotingPower>();
+            match message.message {
+                ConsensusMessage::Proposal {
+                    round: proposal_round,
+                    block_hash: proposal,
+                    ..
+                } if proposal == block_hash && proposal_round == round => status.proposed = true,
+                ConsensusMessage::NonNilPreVoted(vote_round, vote) if vote == block_hash => {
+                    status.votes.entry(vote_round).or_default().prevote += power;
+                }
+                ConsensusMessage::NonNilPreCommitted(vote_round, vote) if vote == block_hash => {
+                    status.votes.entry(vote_round).or_default().precommit += power;
+                }
+                _ => (),
+            }
+        }
+        Ok(status)
+    }
+
+    /// Registers a block that passed the verification, so that this node can vote for it.
+    pub async fn register_verified_block(
+        &mut self,
+        block_header: BlockHeader,
+    ) -> Result<(), Error> {
+        let mut state = self.read_state().await?;
+        state.register_verified_block(block_header);
+        self.commit_state(&state).await?;
+        Ok(())
+    }
+
+    /// Registers a block that failed the verification,
+    /// so that this node never votes for it in this height.
+    pub async fn register_invalid_block_hash(&mut self, block_hash: Hash256) -> Result<(), Error> {
         let mut state = self.read_state().await?;
-        state.register_verified_block_hash(block_hash);
+        state.register_invalid_block_hash(block_hash);
         self.commit_state(&state).await?;
         Ok(())
     }
 
+    /// Returns the cached verification verdict of the block (`Some(true)` if valid),
+    /// or `None` if it has not been verified in this height yet.
+    pub async fn get_block_verdict(&self, block_hash: Hash256) -> Result<Option<bool>, Error> {
+        Ok(self.read_state().await?.get_block_verdict(&block_hash))
+    }
+
     /// Makes a progress in the consensus process.
+    ///
+    /// The messages to broadcast are committed together with the state transition,
+    /// and the ones already broadcasted are never queued again, so it is safe to replay.
     pub async fn progress(&mut self, timestamp: Timestamp) -> Result<Vec<ProgressResult>, Error> {
         let mut state = self.read_state().await?;
         let result = state.progress(timestamp);
@@ -119,6 +279,13 @@ impl Consensus {
         Ok(result)
     }
 
+    /// Diagnoses why the consensus is not making any progress in the current round.
+    ///
+    /// It is meaningful only after `progress()` returned nothing.
+    pub async fn diagnose_stall(&self) -> Result<StallReason, Error> {
+        Ok(self.read_state().await?.diagnose_stall())
+    }
+
     pub async fn set_proposal_candidate(
         &mut self,
         block_hash: Hash256,
@@ -130,6 +297,28 @@ impl Consensus {
         Ok(())
     }
 
+    /// Sets the best verified block as the proposal candidate
+    /// if this node is the leader of the current round, returning the chosen block.
+    ///
+    /// A non-leader node is left untouched and `None` is returned.
+    pub async fn select_proposal_candidate(
+        &mut self,
+        timestamp: Timestamp,
+    ) -> Result<Option<Hash256>, Error> {
+        let mut state = self.read_state().await?;
+        if !state.is_leader() {
+            return Ok(None);
+        }
+        let block_hash = if let Some(block_hash) = state.get_best_proposal_candidate() {
+            block_hash
+        } else {
+            return Ok(None);
+        };
+        state.set_proposal_candidate(block_hash, timestamp)?;
+        self.commit_state(&state).await?;
+        Ok(Some(block_hash))
+    }
+
     pub async fn veto_block(&mut self, block_hash: Hash256) -> Result<(), Error> {
         let mut state = self.read_state().await?;
         state.veto_block(block_hash);
@@ -152,13 +341,16 @@ impl Consensus {
         Arc::clone(&self.dms)
     }
 
+    /// Commits the messages of this node to the DMS, recording them as broadcasted.
     pub async fn flush(&mut self) -> Result<(), Error> {
         // TODO: filter unverified messages (due to the lack of the block verification)
         let mut state = self.read_state().await?;
         let messages = state.drain_messages_to_broadcast();
-        for message in messages {
-            self.dms.write().await.commit_message(&message).await?;
+        for message in &messages {
+            self.dms.write().await.commit_message(message).await?;
         }
+        state.mark_broadcasted(messages.iter().map(|message| message.to_hash256()));
+        self.commit_state(&state).await?;
         Ok(())
     }
 
@@ -5,7 +5,8 @@ use simperby_core::*;
 use simperby_network::*;
 use std::collections::{BTreeMap, BTreeSet};
 use vetomint::{
-    BlockIdentifier, ConsensusEvent, ConsensusParams, ConsensusResponse, HeightInfo, Vetomint,
+    decide_proposer, BlockIdentifier, ConsensusEvent, ConsensusParams, ConsensusResponse,
+    HeightInfo, Misbehavior, Vetomint,
 };
 
 pub type Error = eyre::Error;
@@ -24,6 +25,8 @@ pub enum ConsensusMessage {
     NonNilPreCommitted(ConsensusRound, Hash256),
     NilPreVoted(ConsensusRound),
     NilPreCommitted(ConsensusRound),
+    /// A vote to skip the round, made by the user's veto (see `State::veto_round()`).
+    SkipRound(ConsensusRound),
 }
 
 impl ToHash256 for ConsensusMessage {
@@ -49,14 +52,15 @@ impl DmsMessage for ConsensusMessage {
     {
         Ok(MessageCommitmentProof {
             signature: match self {
-                ConsensusMessage::NonNilPreCommitted(round, block_hash) => Signature::sign(
-                    FinalizationSignTarget {
+                // It is a signature for the finalization proof as well.
+                ConsensusMessage::NonNilPreCommitted(round, block_hash) => TypedSignature::sign(
+                    &FinalizationSignTarget {
                         block_hash: *block_hash,
                         round: *round,
-                    }
-                    .to_hash256(),
+                    },
                     private_key,
-                )?,
+                )?
+                .get_raw_signature(),
                 _ => Signature::sign(
                     self.to_hash256().aggregate(&dms_key.to_hash256()),
                     private_key,
@@ -72,14 +76,16 @@ impl DmsMessage for ConsensusMessage {
         dms_key: &DmsKey,
     ) -> Result<(), simperby_core::CryptoError> {
         match self {
-            ConsensusMessage::NonNilPreCommitted(round, block_hash) => proof.signature.verify(
-                FinalizationSignTarget {
+            ConsensusMessage::NonNilPreCommitted(round, block_hash) => {
+                TypedSignature::<FinalizationSignTarget>::new(
+                    proof.signature.clone(),
+                    proof.committer.clone(),
+                )
+                .verify(&FinalizationSignTarget {
                     block_hash: *block_hash,
                     round: *round,
-                }
-                .to_hash256(),
-                &proof.committer,
-            ),
+                })
+            }
             _ => proof.signature.verify(
                 self.to_hash256().aggregate(&dms_key.to_hash256()),
                 &proof.committer,
@@ -94,18 +100,37 @@ pub struct State {
     vetomint: Vetomint,
     /// The block header that this consensus is performing on.
     block_header: BlockHeader,
+    /// The validators of this height, in the consensus leader order.
+    validator_set: Vec<(PublicKey, VotingPower)>,
     /// An increasing counter for assigning block identifiers.
     block_identifier_count: BlockIdentifier,
-    /// The list of the block hashes that have been verified.
+    /// The list of the block hashes that have been verified, whether valid or not.
+    ///
+    /// A block is verified only once in a height;
+    /// a re-proposal in a later round reuses the verdict.
     verified_block_hashes: BTreeMap<Hash256, BlockIdentifier>,
+    /// The subset of `verified_block_hashes` that turned out to be invalid.
+    invalid_block_hashes: BTreeSet<Hash256>,
+    /// The headers of the valid blocks in `verified_block_hashes`.
+    block_headers: BTreeMap<Hash256, BlockHeader>,
     /// The set of hashes of the block that are valid but vetoed by the user.
     vetoed_block_hashes: BTreeSet<Hash256>,
+    /// The rounds that this node has voted to skip by the user's veto.
+    vetoed_rounds: BTreeSet<ConsensusRound>,
+    /// The hashes of the blocks proposed by a round's proposer but not verified by this node yet.
+    missing_block_hashes: BTreeSet<Hash256>,
+    /// The missing blocks that are to be reported as `ProgressResult::NeedBlockBody`.
+    block_bodies_to_request: Vec<Hash256>,
     /// The list of the events that are to be processed.
     to_be_processed_events: Vec<(ConsensusEvent, Timestamp)>,
     /// The set of messages that have been already updated to the Vetomint state machine.
     updated_events: BTreeSet<ConsensusEvent>,
     /// Messages by this node, which are to be broadcasted.
     messages_to_broadcast: Vec<ConsensusMessage>,
+    /// Hashes of the messages by this node that have been already committed to the DMS.
+    ///
+    /// They are never queued again, even if the progress is replayed after a crash.
+    broadcasted_messages: BTreeSet<Hash256>,
     /// Precommits collected so far, for each `(block, round)`.
     precommits: BTreeMap<(Hash256, ConsensusRound), Vec<TypedSignature<FinalizationSignTarget>>>,
     /// If `Some`, any operation on the consensus module will fail;
@@ -114,27 +139,42 @@ pub struct State {
 }
 
 impl State {
+    /// Creates the consensus state for the next height of the given finalized block.
+    ///
+    /// The validator set and the leader order are derived from the reserved state of the block.
+    /// Round 0 begins at the timestamp of the block header,
+    /// so that every validator agrees on the timeout schedule.
+    ///
+    /// `this_node_key` is `None` for a node that only observes the consensus.
     pub fn new(
-        block_header: &BlockHeader,
+        last_finalization_info: &FinalizationInfo,
         consensus_parameters: ConsensusParams,
-        round_zero_timestamp: Timestamp,
-        this_node_key: PrivateKey,
+        this_node_key: Option<PrivateKey>,
     ) -> Result<State, Error> {
+        let round_zero_timestamp = last_finalization_info.header.timestamp;
+        let validator_set = generate_validator_set(&last_finalization_info.reserved_state)?;
         let height_info = generate_height_info(
-            block_header,
+            &validator_set,
             consensus_parameters,
             round_zero_timestamp,
             this_node_key,
         )?;
         let state = State {
-            vetomint: Vetomint::new(height_info),
-            block_header: block_header.clone(),
+            vetomint: Vetomint::try_new(height_info).map_err(|e| eyre!(e))?,
+            block_header: last_finalization_info.header.clone(),
+            validator_set,
             block_identifier_count: 0,
             to_be_processed_events: vec![(ConsensusEvent::Start, round_zero_timestamp)],
             updated_events: BTreeSet::new(),
             verified_block_hashes: BTreeMap::new(),
+            invalid_block_hashes: BTreeSet::new(),
+            block_headers: BTreeMap::new(),
             vetoed_block_hashes: BTreeSet::new(),
+            vetoed_rounds: BTreeSet::new(),
+            missing_block_hashes: BTreeSet::new(),
+            block_bodies_to_request: Vec::new(),
             messages_to_broadcast: Vec::new(),
+            broadcasted_messages: BTreeSet::new(),
             precommits: BTreeMap::new(),
             finalized: None,
         };
@@ -149,14 +189,32 @@ impl State {
         &self.block_header
     }
 
-    pub fn register_verified_block_hash(&mut self, block_hash: Hash256) {
-        self.assert_not_finalized();
-        if self.verified_block_hashes.contains_key(&block_hash) {
-            return;
+    /// Returns the validators of this height, in the consensus leader order.
+    pub fn validator_set(&self) -> &[(PublicKey, VotingPower)] {
+        &self.validator_set
+    }
+
+    /// Registers a block that has been verified to be valid,
+    /// keeping its header to report when the block is finalized.
+    pub fn register_verified_block(&mut self, block_header: BlockHeader) {
+        let block_hash = block_header.to_hash256();
+        if self.get_block_verdict(&block_hash).is_none() {
+            self.block_headers.insert(block_hash, block_header);
         }
+        self.register_block_verdict(block_hash, true);
+    }
+
+    /// Registers a block that has been verified to be invalid,
+    /// so that its proposals are rejected without verifying it again.
+    pub fn register_invalid_block_hash(&mut self, block_hash: Hash256) {
+        self.register_block_verdict(block_hash, false);
+    }
+
+    /// Returns the cached verification verdict of the block, if it has been verified.
+    pub fn get_block_verdict(&self, block_hash: &Hash256) -> Option<bool> {
         self.verified_block_hashes
-            .insert(block_hash, self.block_identifier_count);
-        self.block_identifier_count += 1;
+            .contains_key(block_hash)
+            .then(|| !self.invalid_block_hashes.contains(block_hash))
     }
 
     pub fn set_proposal_candidate(
@@ -166,6 +224,9 @@ impl State {
     ) -> Result<(), Error> {
         self.assert_not_finalized();
         let block_index = self.get_block_index(&block_hash)?;
+        if self.invalid_block_hashes.contains(&block_hash) {
+            return Err(eyre!("block {block_hash} is invalid"));
+        }
         let consensus_event = ConsensusEvent::BlockCandidateUpdated {
             proposal: block_index,
         };
@@ -174,6 +235,59 @@ impl State {
         Ok(())
     }
 
+    /// Returns whether this node is the proposer of the current round.
+    pub fn is_leader(&self) -> bool {
+        let height_info = self.vetomint.get_height_info();
+        height_info.this_node_index.is_some()
+            && decide_proposer(self.vetomint.get_round(), height_info).ok()
+                == height_info.this_node_index
+    }
+
+    /// Returns the current round of this node.
+    pub fn round(&self) -> ConsensusRound {
+        self.vetomint.get_round() as ConsensusRound
+    }
+
+    /// Diagnoses why this node is stuck in the current round.
+    pub fn diagnose_stall(&self) -> StallReason {
+        let round = self.round();
+        let height_info = self.vetomint.get_height_info();
+        let leader = decide_proposer(self.vetomint.get_round(), height_info)
+            .map(|index| self.validator_set[index].0.clone())
+            .expect("the validator set is checked to be non-empty on creation");
+        if !self.vetomint.is_in_propose_step() {
+            StallReason::WaitingForVotes { round }
+        } else if self.is_leader() {
+            StallReason::NoBlockCandidate { round }
+        } else if height_info.this_node_index.is_some()
+            && self.get_best_proposal_candidate().is_some()
+        {
+            StallReason::NotLeader { round, leader }
+        } else {
+            StallReason::WaitingForProposal { round, leader }
+        }
+    }
+
+    /// Returns the earliest verified block that has not been vetoed by the user.
+    pub fn get_best_proposal_candidate(&self) -> Option<Hash256> {
+        self.verified_block_hashes
+            .iter()
+            .filter(|(block_hash, _)| {
+                !self.vetoed_block_hashes.contains(block_hash)
+                    && !self.invalid_block_hashes.contains(block_hash)
+            })
+            .min_by_key(|(_, block_identifier)| **block_identifier)
+            .map(|(block_hash, _)| *block_hash)
+    }
+
+    pub fn vetoed_block_hashes(&self) -> &BTreeSet<Hash256> {
+        &self.vetoed_block_hashes
+    }
+
+    pub fn vetoed_rounds(&self) -> &BTreeSet<ConsensusRound> {
+        &self.vetoed_rounds
+    }
+
     pub fn veto_block(&mut self, block_hash: Hash256) {
         self.assert_not_finalized();
         self.vetoed_block_hashes.insert(block_hash);
@@ -181,11 +295,26 @@ impl State {
 
     pub fn veto_round(&mut self, round: ConsensusRound, timestamp: Timestamp) {
         self.assert_not_finalized();
+        let signer = if let Some(index) = self.vetomint.get_height_info().this_node_index {
+            index
+        } else {
+            // A non-validator node has no say in the round.
+            return;
+        };
+        self.vetoed_rounds.insert(round);
         let consensus_event = ConsensusEvent::SkipRound {
+            signer,
             round: round as usize,
         };
         self.to_be_processed_events
             .push((consensus_event, timestamp));
+        // The other validators count the skip vote as well.
+        let message = ConsensusMessage::SkipRound(round);
+        if !self.broadcasted_messages.contains(&message.to_hash256())
+            && !self.messages_to_broadcast.contains(&message)
+        {
+            self.messages_to_broadcast.push(message);
+        }
     }
 
     pub fn add_consensus_messages(
@@ -196,6 +325,7 @@ impl State {
         self.assert_not_finalized();
         for (message, author, signature) in messages {
             if !self.is_consensus_message_acceptable(&message) {
+                self.check_missing_block_body(&message, &author);
                 continue;
             }
             let event = self.convert_consensus_message_to_event(
@@ -208,28 +338,44 @@ impl State {
             }
             self.to_be_processed_events.push((event, timestamp));
             if let ConsensusMessage::NonNilPreCommitted(round, block_hash) = message {
-                self.precommits
-                    .entry((block_hash, round))
-                    .and_modify(|v| v.push(TypedSignature::new(signature.clone(), author.clone())))
-                    .or_insert(vec![TypedSignature::new(signature, author)]);
+                // A finalization proof must not contain the same signer twice.
+                let precommits = self.precommits.entry((block_hash, round)).or_default();
+                if !precommits.iter().any(|s| s.signer() == &author) {
+                    precommits.push(TypedSignature::new(signature, author));
+                }
             }
         }
     }
 
     pub fn progress(&mut self, timestamp: Timestamp) -> Vec<ProgressResult> {
         self.assert_not_finalized();
-        let mut result = Vec::new();
+        let mut result = self
+            .block_bodies_to_request
+            .drain(..)
+            .map(ProgressResult::NeedBlockBody)
+            .collect::<Vec<_>>();
         self.to_be_processed_events
             .push((ConsensusEvent::Timer, timestamp));
         while let Some((event, timestamp)) = self.to_be_processed_events.pop() {
+            let round = self.vetomint.get_round();
             let responses = self.vetomint.progress(event.clone(), timestamp);
+            if event == ConsensusEvent::Timer && self.vetomint.get_round() > round {
+                result.push(ProgressResult::RoundTimeout(
+                    round as ConsensusRound,
+                    timestamp,
+                ));
+            }
             self.updated_events.insert(event);
             for response in responses {
                 let (x, message) =
                     self.process_consensus_response_to_progress_result(response, timestamp);
                 result.push(x);
                 if let Some(message) = message {
-                    self.messages_to_broadcast.push(message);
+                    if !self.broadcasted_messages.contains(&message.to_hash256())
+                        && !self.messages_to_broadcast.contains(&message)
+                    {
+                        self.messages_to_broadcast.push(message);
+                    }
                 }
             }
         }
@@ -240,15 +386,120 @@ impl State {
         self.assert_not_finalized();
         std::mem::take(&mut self.messages_to_broadcast)
     }
+
+    /// Records that the messages have been committed to the DMS,
+    /// removing them from the messages to broadcast.
+    pub fn mark_broadcasted(&mut self, message_hashes: impl IntoIterator<Item = Hash256>) {
+        self.broadcasted_messages.extend(message_hashes);
+        self.messages_to_broadcast
+            .retain(|message| !self.broadcasted_messages.contains(&message.to_hash256()));
+    }
 }
 
 impl State {
+    /// Converts a misbehavior detected by Vetomint into a report.
+    ///
+    /// The validator index is resolved against `validator_set()`,
+    /// from which the `HeightInfo` (and so `decide_proposer()`) of this height is made.
+    pub fn misbehavior_to_report(&self, misbehavior: &Misbehavior) -> Result<TxReport, Error> {
+        let block_hash = |index: BlockIdentifier| {
+            self.verified_block_hashes
+                .iter()
+                .find(|(_, &v)| v == index)
+                .map(|(k, _)| *k)
+                .ok_or_else(|| eyre!("unknown block identifier: {index}"))
+        };
+        let vote = |index: Option<BlockIdentifier>| index.map(block_hash).transpose();
+        let (byzantine_node, round, misbehavior) = match misbehavior {
+            Misbehavior::DoubleProposal {
+                byzantine_node,
+                round,
+                proposals: (a, b),
+            } => (
+                byzantine_node,
+                round,
+                ReportedMisbehavior::DoubleProposal(block_hash(*a)?, block_hash(*b)?),
+            ),
+            Misbehavior::DoublePrevote {
+                byzantine_node,
+                round,
+                proposals: (a, b),
+            } => (
+                byzantine_node,
+                round,
+                ReportedMisbehavior::DoublePrevote(vote(*a)?, vote(*b)?),
+            ),
+            Misbehavior::DoublePrecommit {
+                byzantine_node,
+                round,
+                proposals: (a, b),
+            } => (
+                byzantine_node,
+                round,
+                ReportedMisbehavior::DoublePrecommit(vote(*a)?, vote(*b)?),
+            ),
+            Misbehavior::InvalidProposal {
+                byzantine_node,
+                round,
+                proposal,
+            } => (
+                byzantine_node,
+                round,
+                ReportedMisbehavior::InvalidProposal(block_hash(*proposal)?),
+            ),
+            Misbehavior::InvalidPrevote {
+                byzantine_node,
+                round,
+                proposal,
+            } => (
+                byzantine_node,
+                round,
+                ReportedMisbehavior::InvalidPrevote(block_hash(*proposal)?),
+            ),
+            Misbehavior::InvalidPrecommit {
+                byzantine_node,
+                round,
+                proposal,
+            } => (
+                byzantine_node,
+                round,
+                ReportedMisbehavior::InvalidPrecommit(block_hash(*proposal)?),
+            ),
+        };
+        let (byzantine_node, _) = self
+            .validator_set
+            .get(*byzantine_node)
+            .ok_or_else(|| eyre!("unknown validator index: {byzantine_node}"))?;
+        Ok(TxReport {
+            height: self.block_header.height + 1,
+            round: *round as ConsensusRound,
+            byzantine_node: byzantine_node.clone(),
+            misbehavior,
+        })
+    }
+
     fn assert_not_finalized(&self) {
         if self.finalized.is_some() {
             panic!("mutable operations on finalized state");
         }
     }
 
+    /// Caches the verdict of the block; the first verdict for a block is final.
+    fn register_block_verdict(&mut self, block_hash: Hash256, valid: bool) {
+        self.assert_not_finalized();
+        if self.verified_block_hashes.contains_key(&block_hash) {
+            return;
+        }
+        self.missing_block_hashes.remove(&block_hash);
+        self.block_bodies_to_request.retain(|x| x != &block_hash);
+        self.verified_block_hashes
+            .insert(block_hash, self.block_identifier_count);
+        if !valid {
+            self.invalid_block_hashes.insert(block_hash);
+        }
+        self.block_identifier_count += 1;
+    }
+
     fn get_block_index(&self, block_hash: &Hash256) -> Result<usize, Error> {
         self.verified_block_hashes
             .get(block_hash)
@@ -257,8 +508,7 @@ impl State {
     }
 
     fn get_validator_index(&self, public_key: &PublicKey) -> Result<usize, Error> {
-        self.block_header
-            .validator_set
+        self.validator_set
             .iter()
             .position(|(x, _)| x == public_key)
             .ok_or_else(|| eyre!("validator not found"))
@@ -281,6 +531,29 @@ impl State {
         }
     }
 
+    /// Records the block of a proposal that is not acceptable yet because this node lacks it,
+    /// so that the block body is requested once.
+    ///
+    /// Only the proposals made by the proposer of the current (or a later) round are considered;
+    /// anyone else's proposal would be ignored by Vetomint anyway.
+    fn check_missing_block_body(&mut self, message: &ConsensusMessage, author: &PublicKey) {
+        let (round, block_hash) = match message {
+            ConsensusMessage::Proposal {
+                round, block_hash, ..
+            } => (*round as usize, *block_hash),
+            _ => return,
+        };
+        if round < self.vetomint.get_round() || self.missing_block_hashes.contains(&block_hash) {
+            return;
+        }
+        let proposer = decide_proposer(round, self.vetomint.get_height_info()).ok();
+        if proposer.is_none() || self.get_validator_index(author).ok() != proposer {
+            return;
+        }
+        self.missing_block_hashes.insert(block_hash);
+        self.block_bodies_to_request.push(block_hash);
+    }
+
     fn process_consensus_response_to_progress_result(
         &mut self,
         response: ConsensusResponse,
@@ -339,39 +612,53 @@ impl State {
                 (progress_result, Some(consensus_message))
             }
             ConsensusResponse::FinalizeBlock {
-                proposal, round, ..
+                proposal,
+                proof,
+                round,
             } => {
                 let round = round as ConsensusRound;
                 let block_hash = get_block_hash(self, proposal);
+                let header = self
+                    .block_headers
+                    .get(&block_hash)
+                    .cloned()
+                    .expect("the finalized block must have been verified as valid");
                 let signatures = self
                     .precommits
                     .get(&(block_hash, round))
                     .cloned()
                     .expect("there must be valid precommits for the finalized block");
+                // The precommit of this node is counted as soon as it is made,
+                // but its signature is known only after it is read back from the DMS.
+                let this_node_index = self.vetomint.get_height_info().this_node_index;
+                match verify::validator_index_map(&header, &self.validator_set) {
+                    Ok((public_keys, _)) => debug_assert!(
+                        proof.iter().all(|index| Some(*index) == this_node_index
+                            || signatures
+                                .iter()
+                                .any(|signature| signature.signer() == &public_keys[*index])),
+                        "the finalization proof of vetomint must refer to the precommit signers"
+                    ),
+                    Err(e) => log::warn!("the finalized block {block_hash} is inconsistent: {e}"),
+                }
                 let finalization = Finalization {
                     block_hash,
+                    header,
                     timestamp,
                     proof: FinalizationProof { round, signatures },
                 };
                 self.finalized = Some(finalization.clone());
                 (ProgressResult::Finalized(finalization), None)
             }
-            ConsensusResponse::ViolationReport {
-                violator,
-                misbehavior,
-            } => {
-                let pubkey = self
-                    .block_header
-                    .validator_set
-                    .get(violator)
-                    .expect("the violator must be in the validator set")
-                    .0
-                    .clone();
+            ConsensusResponse::ViolationReport { misbehavior, .. } => {
+                let report = self
+                    .misbehavior_to_report(&misbehavior)
+                    .expect("the violator and the blocks must be known to this height");
                 (
-                    // TODO: add misbehavior handling
+                    // TODO: submit the report as an extra-agenda transaction
                     ProgressResult::ViolationReported(
-                        pubkey,
-                        format!("{misbehavior:?}"),
+                        report.byzantine_node,
+                        format!("{:?}", report.misbehavior),
                         timestamp,
                     ),
                     None,
@@ -397,9 +684,7 @@ impl State {
                     .expect("this must be already verified by the message filter");
                 ConsensusEvent::BlockProposalReceived {
                     proposal: index,
-                    // Todo, Note: For now, all proposals are regarded as valid.
-                    // See issue#201 (https://github.com/postech-dao/simperby/issues/201).
-                    valid: true,
+                    valid: !self.invalid_block_hashes.contains(block_hash),
                     valid_round,
                     proposer: signer,
                     round: *round as usize,
@@ -436,30 +721,650 @@ impl State {
                 signer,
                 round: *round as usize,
             },
+            ConsensusMessage::SkipRound(round) => ConsensusEvent::SkipRound {
+                signer,
+                round: *round as usize,
+            },
         }
     }
 }
 
+/// Derives the validator set from the reserved state, ordered by the consensus leader order.
+///
+/// Expelled members are excluded and the voting power of a member who delegated
+/// its consensus right is counted for the end of its delegation chain
+/// (see `ReservedState::effective_consensus_set()`).
+/// Validators which are not in the leader order follow the leaders.
+fn generate_validator_set(
+    reserved_state: &ReservedState,
+) -> Result<Vec<(PublicKey, VotingPower)>, Error> {
+    let mut validators = reserved_state
+        .effective_consensus_set()
+        .map_err(|e| eyre!(e))?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    let mut validator_set = Vec::new();
+    for name in &reserved_state.consensus_leader_order {
+        let member = reserved_state
+            .members
+            .iter()
+            .find(|member| member.name == *name)
+            .ok_or_else(|| eyre!("the consensus leader {name} is not a member"))?;
+        if member.expelled {
+            continue;
+        }
+        let name = reserved_state
+            .resolve_consensus_delegatee(name)
+            .map_err(|e| eyre!(e))?;
+        let public_key = reserved_state
+            .query_public_key(&name)
+            .ok_or_else(|| eyre!("the public key of {name} is not found in the reserved state"))?;
+        if let Some(voting_power) = validators.remove(&public_key) {
+            validator_set.push((public_key, voting_power));
+        }
+    }
+    validator_set.extend(validators);
+    Ok(validator_set)
+}
+
 fn generate_height_info(
-    header: &BlockHeader,
+    validator_set: &[(PublicKey, VotingPower)],
     consensus_params: ConsensusParams,
     round_zero_timestamp: Timestamp,
-    this_node_key: PrivateKey,
+    this_node_key: Option<PrivateKey>,
 ) -> Result<HeightInfo, Error> {
-    let this_node_index = header
-        .validator_set
-        .iter()
-        .position(|(pubkey, _)| *pubkey == this_node_key.public_key());
-    let info = HeightInfo {
-        validators: header
-            .validator_set
+    let this_node_index = this_node_key.and_then(|this_node_key| {
+        validator_set
             .iter()
-            .map(|(_, power)| *power)
-            .collect(),
+            .position(|(pubkey, _)| *pubkey == this_node_key.public_key())
+    });
+    let info = HeightInfo {
+        validators: validator_set.iter().map(|(_, power)| *power).collect(),
         this_node_index,
         timestamp: round_zero_timestamp,
         consensus_params,
-        initial_block_candidate: 0 as BlockIdentifier,
+        initial_block_candidate: None,
     };
     Ok(info)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(
+        message: ConsensusMessage,
+        key: &PrivateKey,
+    ) -> (ConsensusMessage, PublicKey, Signature) {
+        let signature = Signature::sign(message.to_hash256(), key).unwrap();
+        (message, key.public_key(), signature)
+    }
+
+    fn block_header(fi: &FinalizationInfo, name: &str) -> BlockHeader {
+        BlockHeader {
+            height: fi.header.height + 1,
+            commit_merkle_root: Hash256::hash(name),
+            ..fi.header.clone()
+        }
+    }
+
+    #[test]
+    fn round_timeout() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let mut state = State::new(&fi, params, Some(keys[3].clone())).unwrap();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_verified_block(block);
+        state.progress(0);
+
+        // The leader proposes and this node prevotes and precommits on the block.
+        let proposal = ConsensusMessage::Proposal {
+            round: 0,
+            valid_round: None,
+            block_hash,
+        };
+        state.add_consensus_messages(vec![message(proposal, &keys[0])], 0);
+        state.progress(0);
+        let prevotes = vec![
+            message(ConsensusMessage::NonNilPreVoted(0, block_hash), &keys[0]),
+            message(ConsensusMessage::NonNilPreVoted(0, block_hash), &keys[1]),
+            message(ConsensusMessage::NilPreVoted(0), &keys[2]),
+        ];
+        state.add_consensus_messages(prevotes, 0);
+        state.progress(0);
+
+        // Precommits are split, so neither the block nor nil reaches the quorum.
+        let precommits = vec![
+            message(
+                ConsensusMessage::NonNilPreCommitted(0, block_hash),
+                &keys[0],
+            ),
+            message(ConsensusMessage::NilPreCommitted(0), &keys[1]),
+            message(ConsensusMessage::NilPreCommitted(0), &keys[2]),
+        ];
+        state.add_consensus_messages(precommits, 0);
+        assert!(!state
+            .progress(0)
+            .iter()
+            .any(|result| matches!(result, ProgressResult::RoundTimeout(..))));
+
+        let result = state.progress(1000);
+        assert!(result.contains(&ProgressResult::RoundTimeout(0, 1000)));
+        assert!(state.check_finalized().is_none());
+    }
+
+    #[test]
+    fn reproposal_reuses_verdict() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let mut state = State::new(&fi, params, Some(keys[3].clone())).unwrap();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_verified_block(block);
+        state.progress(0);
+
+        // The block is proposed in round 0, but the validators precommit nil.
+        let proposal = ConsensusMessage::Proposal {
+            round: 0,
+            valid_round: None,
+            block_hash,
+        };
+        state.add_consensus_messages(vec![message(proposal, &keys[0])], 0);
+        assert!(state
+            .progress(0)
+            .contains(&ProgressResult::NonNilPreVoted(0, block_hash, 0)));
+        let precommits = (0..3)
+            .map(|i| message(ConsensusMessage::NilPreCommitted(0), &keys[i]))
+            .collect();
+        state.add_consensus_messages(precommits, 0);
+        state.progress(0);
+        assert_eq!(state.round(), 1);
+
+        // The re-proposal in round 1 is voted with the cached verdict,
+        // without requesting or verifying the block again.
+        let reproposal = message(
+            ConsensusMessage::Proposal {
+                round: 1,
+                valid_round: None,
+                block_hash,
+            },
+            &keys[0],
+        );
+        let mut vetoing_state = state.clone();
+        state.add_consensus_messages(vec![reproposal.clone()], 0);
+        let result = state.progress(0);
+        assert!(result.contains(&ProgressResult::NonNilPreVoted(1, block_hash, 0)));
+        assert!(!result
+            .iter()
+            .any(|result| matches!(result, ProgressResult::NeedBlockBody(..))));
+        assert_eq!(state.get_block_verdict(&block_hash), Some(true));
+        assert_eq!(state.block_identifier_count, 1);
+
+        // A veto made after the verification is still honored.
+        vetoing_state.veto_block(block_hash);
+        vetoing_state.add_consensus_messages(vec![reproposal], 0);
+        assert!(vetoing_state
+            .progress(0)
+            .contains(&ProgressResult::NilPreVoted(1, 0)));
+    }
+
+    #[test]
+    fn invalid_block_verdict() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let mut state = State::new(&fi, params, Some(keys[1].clone())).unwrap();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_invalid_block_hash(block_hash);
+        // The first verdict is final.
+        state.register_verified_block(block);
+        assert_eq!(state.get_block_verdict(&block_hash), Some(false));
+        assert_eq!(state.get_block_verdict(&Hash256::hash("other")), None);
+        state.progress(0);
+
+        let proposal = ConsensusMessage::Proposal {
+            round: 0,
+            valid_round: None,
+            block_hash,
+        };
+        state.add_consensus_messages(vec![message(proposal, &keys[0])], 0);
+        assert!(state
+            .progress(0)
+            .contains(&ProgressResult::NilPreVoted(0, 0)));
+        assert_eq!(state.get_best_proposal_candidate(), None);
+    }
+
+    #[test]
+    fn finalized_header() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let mut state = State::new(&fi, params, Some(keys[3].clone())).unwrap();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_verified_block(block.clone());
+        state.register_verified_block(block_header(&fi, "other block"));
+        state.progress(0);
+
+        let proposal = ConsensusMessage::Proposal {
+            round: 0,
+            valid_round: None,
+            block_hash,
+        };
+        state.add_consensus_messages(vec![message(proposal, &keys[0])], 0);
+        state.progress(0);
+        let votes = (0..3)
+            .flat_map(|i| {
+                [
+                    message(ConsensusMessage::NonNilPreVoted(0, block_hash), &keys[i]),
+                    message(
+                        ConsensusMessage::NonNilPreCommitted(0, block_hash),
+                        &keys[i],
+                    ),
+                ]
+            })
+            .collect();
+        state.add_consensus_messages(votes, 0);
+        let finalization = state
+            .progress(0)
+            .into_iter()
+            .find_map(|result| match result {
+                ProgressResult::Finalized(finalization) => Some(finalization),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(finalization.block_hash, block_hash);
+        assert_eq!(finalization.header, block);
+    }
+
+    #[test]
+    fn finalization_proof_signers_in_leader_order() {
+        let (mut fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        // Reverse the leader order so that the indices differ from the order of the header.
+        fi.reserved_state.consensus_leader_order.reverse();
+        let mut state = State::new(&fi, params, Some(keys[0].clone())).unwrap();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_verified_block(block.clone());
+        state.progress(0);
+
+        let proposal = ConsensusMessage::Proposal {
+            round: 0,
+            valid_round: None,
+            block_hash,
+        };
+        state.add_consensus_messages(vec![message(proposal, &keys[3])], 0);
+        state.progress(0);
+        let votes = (1..4)
+            .flat_map(|i| {
+                [
+                    message(ConsensusMessage::NonNilPreVoted(0, block_hash), &keys[i]),
+                    message(
+                        ConsensusMessage::NonNilPreCommitted(0, block_hash),
+                        &keys[i],
+                    ),
+                ]
+            })
+            .collect();
+        state.add_consensus_messages(votes, 0);
+        let finalization = state
+            .progress(0)
+            .into_iter()
+            .find_map(|result| match result {
+                ProgressResult::Finalized(finalization) => Some(finalization),
+                _ => None,
+            })
+            .unwrap();
+
+        // The indices of vetomint are checked to refer to these signers (in debug builds).
+        // The precommit of this node is counted, but its signature is not known yet.
+        let signers = finalization
+            .proof
+            .signatures
+            .iter()
+            .map(|signature| signature.signer().clone())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            signers,
+            (1..4)
+                .map(|i| keys[i].public_key())
+                .collect::<BTreeSet<_>>()
+        );
+        let (_, indices) = verify::validator_index_map(&block, state.validator_set()).unwrap();
+        assert_eq!(indices[&keys[3].public_key()], 0);
+        assert_eq!(indices[&keys[0].public_key()], 3);
+    }
+
+    #[test]
+    fn need_block_body() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let mut state = State::new(&fi, params, Some(keys[1].clone())).unwrap();
+        state.progress(0);
+
+        // The leader proposes a block that this node doesn't have.
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        let proposal = ConsensusMessage::Proposal {
+            round: 0,
+            valid_round: None,
+            block_hash,
+        };
+        state.add_consensus_messages(vec![message(proposal.clone(), &keys[0])], 0);
+        let result = state.progress(0);
+        assert!(result.contains(&ProgressResult::NeedBlockBody(block_hash)));
+        assert!(!result
+            .iter()
+            .any(|result| matches!(result, ProgressResult::NonNilPreVoted(..))));
+
+        // It is requested only once.
+        state.add_consensus_messages(vec![message(proposal.clone(), &keys[0])], 0);
+        assert!(!state
+            .progress(0)
+            .contains(&ProgressResult::NeedBlockBody(block_hash)));
+
+        // A proposal by a non-proposer is not worth fetching.
+        let other_proposal = ConsensusMessage::Proposal {
+            round: 0,
+            valid_round: None,
+            block_hash: Hash256::hash("other block"),
+        };
+        state.add_consensus_messages(vec![message(other_proposal, &keys[2])], 0);
+        assert!(!state
+            .progress(0)
+            .iter()
+            .any(|result| matches!(result, ProgressResult::NeedBlockBody(..))));
+
+        // Once the block is fetched, the node prevotes for it.
+        state.register_verified_block(block);
+        state.add_consensus_messages(vec![message(proposal, &keys[0])], 0);
+        assert!(state
+            .progress(0)
+            .contains(&ProgressResult::NonNilPreVoted(0, block_hash, 0)));
+    }
+
+    #[test]
+    fn replayed_progress_does_not_rebroadcast() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let mut state = State::new(&fi, params, Some(keys[0].clone())).unwrap();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_verified_block(block);
+        state.set_proposal_candidate(block_hash, 0).unwrap();
+        let persisted = serde_spb::to_vec(&state).unwrap();
+
+        // The proposal is broadcasted, but the node crashes before the state is committed.
+        assert!(state
+            .progress(0)
+            .contains(&ProgressResult::Proposed(0, block_hash, 0)));
+        let broadcasted = state.drain_messages_to_broadcast();
+        assert!(!broadcasted.is_empty());
+
+        // On restart, the broadcasted messages are recovered from the DMS.
+        let mut state: State = serde_spb::from_slice(&persisted).unwrap();
+        state.mark_broadcasted(broadcasted.iter().map(|message| message.to_hash256()));
+        assert!(state
+            .progress(0)
+            .contains(&ProgressResult::Proposed(0, block_hash, 0)));
+        assert!(state.drain_messages_to_broadcast().is_empty());
+    }
+
+    #[test]
+    fn round_zero_begins_at_header_timestamp() {
+        let (mut fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        fi.header.timestamp = 5000;
+
+        // Two nodes that are not the leader, created and progressed at different times,
+        // time out the proposal of round 0 at the same moment.
+        for (key, first_progress) in [(&keys[1], 5000), (&keys[2], 5500)] {
+            let mut state = State::new(&fi, params.clone(), Some(key.clone())).unwrap();
+            assert!(!state
+                .progress(first_progress)
+                .contains(&ProgressResult::NilPreVoted(0, first_progress)));
+            assert!(!state
+                .progress(5999)
+                .contains(&ProgressResult::NilPreVoted(0, 5999)));
+            assert!(state
+                .progress(6000)
+                .contains(&ProgressResult::NilPreVoted(0, 6000)));
+        }
+    }
+
+    #[test]
+    fn best_proposal_candidate() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let leader_state = State::new(&fi, params.clone(), Some(keys[0].clone())).unwrap();
+        let other_state = State::new(&fi, params, Some(keys[1].clone())).unwrap();
+        assert!(leader_state.is_leader());
+        assert!(!other_state.is_leader());
+
+        let mut state = leader_state;
+        assert_eq!(state.get_best_proposal_candidate(), None);
+        let vetoed_block = block_header(&fi, "vetoed block");
+        let vetoed_block_hash = vetoed_block.to_hash256();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_verified_block(vetoed_block);
+        state.register_verified_block(block);
+        state.veto_block(vetoed_block_hash);
+        assert_eq!(state.get_best_proposal_candidate(), Some(block_hash));
+
+        state.set_proposal_candidate(block_hash, 0).unwrap();
+        assert!(state
+            .progress(0)
+            .contains(&ProgressResult::Proposed(0, block_hash, 0)));
+    }
+
+    #[test]
+    fn validator_set_from_reserved_state() {
+        let (mut fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+
+        // The finalized block expels `member-0000`, makes `member-0001` delegate
+        // its consensus right to `member-0002` and changes the leader order.
+        let reserved_state = &mut fi.reserved_state;
+        reserved_state.members[0].expelled = true;
+        reserved_state.members[1].consensus_delegatee = Some("member-0002".to_string());
+        reserved_state.consensus_leader_order = vec![
+            "member-0003".to_string(),
+            "member-0001".to_string(),
+            "member-0000".to_string(),
+        ];
+        fi.header.height = 1;
+        fi.header.validator_set = reserved_state.get_validator_set().unwrap();
+
+        let state = State::new(&fi, params.clone(), Some(keys[3].clone())).unwrap();
+        assert_eq!(
+            state.validator_set(),
+            &[(keys[3].public_key(), 1), (keys[2].public_key(), 2)]
+        );
+        assert!(state.is_leader());
+        let state = State::new(&fi, params.clone(), Some(keys[2].clone())).unwrap();
+        assert!(!state.is_leader());
+        let state = State::new(&fi, params, Some(keys[0].clone())).unwrap();
+        assert!(!state.is_leader());
+    }
+
+    #[test]
+    fn validator_set_with_delegation_chain() {
+        let (mut fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+
+        // `member-0000` delegates to `member-0001`, who delegates to `member-0002`.
+        let reserved_state = &mut fi.reserved_state;
+        reserved_state.members[0].consensus_delegatee = Some("member-0001".to_string());
+        reserved_state.members[1].consensus_delegatee = Some("member-0002".to_string());
+        fi.header.height = 1;
+        fi.header.validator_set = reserved_state.get_validator_set().unwrap();
+
+        // The leading rounds of both delegators are led by the end of the chain.
+        let state = State::new(&fi, params, Some(keys[2].clone())).unwrap();
+        assert_eq!(
+            state.validator_set(),
+            &[(keys[2].public_key(), 3), (keys[3].public_key(), 1)]
+        );
+        assert!(state.is_leader());
+        assert_eq!(
+            state
+                .validator_set()
+                .iter()
+                .map(|(_, power)| power)
+                .sum::<VotingPower>(),
+            4
+        );
+    }
+
+    #[test]
+    fn misbehavior_to_report() {
+        let (mut fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        // Reverse the leader order so that validator indices differ from the member order.
+        fi.reserved_state.consensus_leader_order.reverse();
+        let mut state = State::new(&fi, params, Some(keys[0].clone())).unwrap();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+        state.register_verified_block(block);
+
+        let report = state
+            .misbehavior_to_report(&Misbehavior::DoublePrevote {
+                byzantine_node: 0,
+                round: 2,
+                proposals: (Some(0), None),
+            })
+            .unwrap();
+        assert_eq!(
+            report,
+            TxReport {
+                height: fi.header.height + 1,
+                round: 2,
+                byzantine_node: keys[3].public_key(),
+                misbehavior: ReportedMisbehavior::DoublePrevote(Some(block_hash), None),
+            }
+        );
+        // The same validator is the proposer of the first round.
+        let proposer = decide_proposer(0, state.vetomint.get_height_info()).unwrap();
+        assert_eq!(state.validator_set()[proposer].0, keys[3].public_key());
+
+        assert!(state
+            .misbehavior_to_report(&Misbehavior::DoublePrevote {
+                byzantine_node: 4,
+                round: 2,
+                proposals: (Some(0), None),
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn diagnose_stall() {
+        let (fi, keys) = test_utils::generate_fi(4);
+        let keys = keys.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+        let params = ConsensusParams {
+            timeout_ms: 1000,
+            repeat_round_for_first_leader: 10,
+        };
+        let leader = keys[0].public_key();
+        let block = block_header(&fi, "block");
+        let block_hash = block.to_hash256();
+
+        // The leader waits for a block candidate, and then proposes it.
+        let mut state = State::new(&fi, params.clone(), Some(keys[0].clone())).unwrap();
+        assert_eq!(state.progress(0), vec![]);
+        assert_eq!(
+            state.diagnose_stall(),
+            StallReason::NoBlockCandidate { round: 0 }
+        );
+        state.register_verified_block(block.clone());
+        state.set_proposal_candidate(block_hash, 0).unwrap();
+        assert!(state
+            .progress(0)
+            .contains(&ProgressResult::Proposed(0, block_hash, 0)));
+        assert_eq!(state.progress(0), vec![]);
+        assert_eq!(
+            state.diagnose_stall(),
+            StallReason::WaitingForVotes { round: 0 }
+        );
+
+        // A validator without a block waits for the proposal.
+        let mut state = State::new(&fi, params.clone(), Some(keys[1].clone())).unwrap();
+        assert_eq!(state.progress(0), vec![]);
+        assert_eq!(
+            state.diagnose_stall(),
+            StallReason::WaitingForProposal {
+                round: 0,
+                leader: leader.clone(),
+            }
+        );
+
+        // So does a non-validator, even with a verified block.
+        let mut observer = State::new(&fi, params.clone(), None).unwrap();
+        observer.register_verified_block(block.clone());
+        assert_eq!(observer.progress(0), vec![]);
+        assert_eq!(
+            observer.diagnose_stall(),
+            StallReason::WaitingForProposal {
+                round: 0,
+                leader: leader.clone(),
+            }
+        );
+
+        // A validator with a block of its own can't propose it.
+        state.register_verified_block(block);
+        assert_eq!(state.progress(0), vec![]);
+        assert_eq!(
+            state.diagnose_stall(),
+            StallReason::NotLeader { round: 0, leader }
+        );
+    }
+}
@@ -26,12 +26,11 @@ async fn basic_1() {
             .await,
         )),
         storage,
-        fi.header.clone(),
+        fi.clone(),
         ConsensusParams {
             timeout_ms: 6000,
             repeat_round_for_first_leader: 10,
         },
-        0,
         Some(server_private_key),
     )
     .await
@@ -49,12 +48,11 @@ async fn basic_1() {
                     create_test_dms(network_id.clone(), members.clone(), private_key.clone()).await,
                 )),
                 storage,
-                fi.header.clone(),
+                fi.clone(),
                 ConsensusParams {
                     timeout_ms: 6000,
                     repeat_round_for_first_leader: 10,
                 },
-                0,
                 Some(private_key.clone()),
             )
             .await
@@ -63,13 +61,20 @@ async fn basic_1() {
         ));
     }
 
-    let block_hash = Hash256::hash("block");
+    let block_header = BlockHeader {
+        height: fi.header.height + 1,
+        commit_merkle_root: Hash256::hash("block"),
+        ..fi.header.clone()
+    };
+    let block_hash = block_header.to_hash256();
     server_node
-        .register_verified_block_hash(block_hash)
+        .register_verified_block(block_header.clone())
         .await
         .unwrap();
     for (node, _) in client_nodes.iter_mut() {
-        node.register_verified_block_hash(block_hash).await.unwrap();
+        node.register_verified_block(block_header.clone())
+            .await
+            .unwrap();
     }
 
     let serve_task = tokio::spawn(async move {
@@ -130,14 +135,84 @@ async fn basic_1() {
         node.progress(0).await.unwrap();
     }
     for (node, _) in client_nodes.iter_mut() {
-        assert_eq!(
-            node.check_finalized().await.unwrap().unwrap().block_hash,
-            block_hash
-        );
+        let finalization = node.check_finalized().await.unwrap().unwrap();
+        assert_eq!(finalization.block_hash, block_hash);
+        assert_eq!(finalization.header, block_header);
     }
     serve_task.await.unwrap();
 }
 
+/// Three of the four validators veto the first round,
+/// and the other one skips it by their skip votes received through the DMS.
+#[tokio::test]
+async fn skip_round_by_remote_votes() {
+    setup_test();
+
+    let network_id = "consensus".to_string();
+    let ((server_network_config, server_private_key), client_network_configs_and_keys, members, fi) =
+        setup_server_client_nodes(network_id.clone(), 4).await;
+    let server_dms = Arc::new(RwLock::new(
+        create_test_dms::<ConsensusMessage>(
+            network_id.clone(),
+            members.clone(),
+            server_private_key,
+        )
+        .await,
+    ));
+    let serve_task = tokio::spawn(Dms::serve(server_dms, server_network_config));
+
+    let mut client_nodes = Vec::new();
+    for (network_config, private_key) in client_network_configs_and_keys {
+        let path = create_temp_dir();
+        StorageImpl::create(&path).await.unwrap();
+        let storage = StorageImpl::open(&path).await.unwrap();
+        client_nodes.push((
+            Consensus::new(
+                Arc::new(RwLock::new(
+                    create_test_dms(network_id.clone(), members.clone(), private_key.clone()).await,
+                )),
+                storage,
+                fi.clone(),
+                ConsensusParams {
+                    timeout_ms: 6000,
+                    // So that nil prevotes alone don't fast-forward the round.
+                    repeat_round_for_first_leader: 1,
+                },
+                Some(private_key),
+            )
+            .await
+            .unwrap(),
+            network_config,
+        ));
+    }
+    for (node, _) in client_nodes.iter_mut() {
+        node.progress(0).await.unwrap();
+    }
+
+    let (last_node, vetoing_nodes) = client_nodes.split_last_mut().unwrap();
+    for (node, network_config) in vetoing_nodes.iter_mut() {
+        node.veto_round(0, 0).await.unwrap();
+        node.progress(0).await.unwrap();
+        node.flush().await.unwrap();
+        dms::DistributedMessageSet::broadcast(node.get_dms(), network_config)
+            .await
+            .unwrap();
+    }
+
+    let (node, network_config) = last_node;
+    dms::DistributedMessageSet::fetch(node.get_dms(), network_config)
+        .await
+        .unwrap();
+    assert_eq!(node.get_round().await.unwrap(), 0);
+    node.update().await.unwrap();
+    node.progress(0).await.unwrap();
+    assert_eq!(node.get_round().await.unwrap(), 1);
+    assert!(node.get_vetoed_rounds().await.unwrap().is_empty());
+
+    serve_task.abort();
+    let _ = serve_task.await;
+}
+
 /// Same as `basic_1` but all the nodes (including the 'server node') participate in consensus.
 #[ignore]
 #[tokio::test]
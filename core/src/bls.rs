@@ -0,0 +1,370 @@
+//! BLS aggregate signatures (BLS12-381, with public keys in G1) for compact finalization proofs.
+//!
+//! This is enabled by the `bls` feature, and is an alternative to `FinalizationProof`
+//! which carries a signature per validator.
+//!
+//! Note that the BLS key of each validator must come with a proof of possession
+//! (see `BlsPublicKey::verify_possession()`) before being trusted,
+//! to prevent rogue-key attacks on the aggregation.
+use crate::verify::Error as VerifyError;
+use crate::*;
+use blst::min_pk;
+use blst::BLST_ERROR;
+use serde::{Deserialize, Serialize};
+
+/// The domain separation tag for the signatures, of the proof-of-possession scheme.
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// The domain separation tag for the proofs of possession.
+const POSSESSION_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// A BLS public key, in the compressed form.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BlsPublicKey {
+    key: HexSerializedBytes<48>,
+}
+
+/// A BLS private key.
+///
+/// It is validated when deserialized, so it always holds a valid scalar.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
+#[serde(try_from = "HexSerializedBytes<32>", into = "HexSerializedBytes<32>")]
+pub struct BlsPrivateKey {
+    key: HexSerializedBytes<32>,
+}
+
+impl TryFrom<HexSerializedBytes<32>> for BlsPrivateKey {
+    type Error = CryptoError;
+
+    fn try_from(key: HexSerializedBytes<32>) -> Result<Self, CryptoError> {
+        min_pk::SecretKey::from_bytes(&key.data)
+            .map_err(|e| CryptoError::InvalidFormat(format!("BLS private key: {e:?}")))?;
+        Ok(Self { key })
+    }
+}
+
+impl From<BlsPrivateKey> for HexSerializedBytes<32> {
+    fn from(private_key: BlsPrivateKey) -> Self {
+        private_key.key
+    }
+}
+
+/// A BLS signature (possibly aggregated), in the compressed form.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BlsSignature {
+    signature: HexSerializedBytes<96>,
+}
+
+impl BlsPublicKey {
+    fn to_blst(&self) -> Result<min_pk::PublicKey, CryptoError> {
+        min_pk::PublicKey::key_validate(&self.key.data)
+            .map_err(|e| CryptoError::InvalidFormat(format!("BLS public key: {e:?}")))
+    }
+
+    /// Verifies that the owner of this key holds the private key of it.
+    pub fn verify_possession(&self, proof: &BlsSignature) -> Result<(), CryptoError> {
+        let public_key = self.to_blst()?;
+        check(proof.to_blst()?.verify(
+            true,
+            &self.key.data,
+            POSSESSION_DST,
+            &[],
+            &public_key,
+            false,
+        ))
+    }
+}
+
+impl BlsPrivateKey {
+    /// Derives a private key from the seed.
+    pub fn generate(seed: impl AsRef<[u8]>) -> Self {
+        let private_key = min_pk::SecretKey::key_gen(Hash256::hash(seed).as_ref(), &[])
+            .expect("the key material is 32 bytes");
+        Self {
+            key: HexSerializedBytes {
+                data: private_key.to_bytes(),
+            },
+        }
+    }
+
+    fn to_blst(&self) -> min_pk::SecretKey {
+        min_pk::SecretKey::from_bytes(&self.key.data).expect("validated on construction")
+    }
+
+    pub fn public_key(&self) -> BlsPublicKey {
+        BlsPublicKey {
+            key: HexSerializedBytes {
+                data: self.to_blst().sk_to_pk().compress(),
+            },
+        }
+    }
+
+    /// Signs the hash of the given data.
+    pub fn sign<T: ToHash256>(&self, data: &T) -> BlsSignature {
+        BlsSignature::from_blst(
+            self.to_blst()
+                .sign(data.to_hash256().as_ref(), SIGNATURE_DST, &[]),
+        )
+    }
+
+    /// Creates the proof of possession of this key.
+    pub fn prove_possession(&self) -> BlsSignature {
+        let public_key = self.public_key();
+        BlsSignature::from_blst(
+            self.to_blst()
+                .sign(&public_key.key.data, POSSESSION_DST, &[]),
+        )
+    }
+}
+
+impl BlsSignature {
+    fn from_blst(signature: min_pk::Signature) -> Self {
+        Self {
+            signature: HexSerializedBytes {
+                data: signature.compress(),
+            },
+        }
+    }
+
+    fn to_blst(&self) -> Result<min_pk::Signature, CryptoError> {
+        min_pk::Signature::from_bytes(&self.signature.data)
+            .map_err(|e| CryptoError::InvalidFormat(format!("BLS signature: {e:?}")))
+    }
+
+    /// Aggregates the signatures into one.
+    pub fn aggregate(signatures: &[BlsSignature]) -> Result<Self, CryptoError> {
+        let signatures = signatures
+            .iter()
+            .map(BlsSignature::to_blst)
+            .collect::<Result<Vec<_>, _>>()?;
+        let aggregate =
+            min_pk::AggregateSignature::aggregate(&signatures.iter().collect::<Vec<_>>(), true)
+                .map_err(|e| CryptoError::InvalidFormat(format!("BLS aggregation: {e:?}")))?;
+        Ok(Self::from_blst(aggregate.to_signature()))
+    }
+}
+
+fn check(result: BLST_ERROR) -> Result<(), CryptoError> {
+    if result == BLST_ERROR::BLST_SUCCESS {
+        Ok(())
+    } else {
+        Err(CryptoError::VerificationFailed)
+    }
+}
+
+/// A finalization proof with a single aggregate signature of the signers.
+///
+/// The signers are marked in `signers`, a bitmap over the validator set of the block header:
+/// the `i`-th validator signed if the `i % 8`-th bit of the `i / 8`-th byte is set.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct AggregateFinalizationProof {
+    pub round: ConsensusRound,
+    pub signers: HexSerializedVec,
+    pub signature: BlsSignature,
+}
+
+impl AggregateFinalizationProof {
+    /// Aggregates the signatures of the given validators, indexed by their position in the validator set.
+    ///
+    /// Each validator must sign at most once.
+    pub fn aggregate(
+        round: ConsensusRound,
+        validator_set_size: usize,
+        signatures: &[(usize, BlsSignature)],
+    ) -> Result<Self, CryptoError> {
+        let mut signers = vec![0; (validator_set_size + 7) / 8];
+        for (index, _) in signatures {
+            if *index >= validator_set_size {
+                return Err(CryptoError::InvalidFormat(format!(
+                    "signer index {index} is out of the validator set"
+                )));
+            }
+            if signers[index / 8] & (1 << (index % 8)) != 0 {
+                return Err(CryptoError::InvalidFormat(format!(
+                    "signer index {index} is duplicated"
+                )));
+            }
+            signers[index / 8] |= 1 << (index % 8);
+        }
+        let signatures = signatures
+            .iter()
+            .map(|(_, signature)| signature.clone())
+            .collect::<Vec<_>>();
+        Ok(Self {
+            round,
+            signers: signers.into(),
+            signature: BlsSignature::aggregate(&signatures)?,
+        })
+    }
+
+    /// Checks whether the `index`-th validator is marked as a signer.
+    pub fn is_signer(&self, index: usize) -> bool {
+        self.signers
+            .data
+            .get(index / 8)
+            .map_or(false, |byte| byte & (1 << (index % 8)) != 0)
+    }
+}
+
+/// Verifies the aggregate finalization proof of the given block header.
+///
+/// `bls_keys[i]` must be the (possession-verified) BLS key of `header.validator_set[i]`.
+pub fn verify_aggregate_finalization_proof(
+    header: &BlockHeader,
+    bls_keys: &[BlsPublicKey],
+    proof: &AggregateFinalizationProof,
+) -> Result<(), VerifyError> {
+    let validator_set_size = header.validator_set.len();
+    if bls_keys.len() != validator_set_size {
+        return Err(VerifyError::InvalidArgument(format!(
+            "expected {validator_set_size} BLS keys, got {}",
+            bls_keys.len()
+        )));
+    }
+    if proof.signers.data.len() != (validator_set_size + 7) / 8
+        || (validator_set_size..proof.signers.data.len() * 8).any(|i| proof.is_signer(i))
+    {
+        return Err(VerifyError::InvalidProof(
+            "invalid finalization proof - the signer bitmap doesn't match the validator set"
+                .to_owned(),
+        ));
+    }
+    let signers = (0..validator_set_size)
+        .filter(|i| proof.is_signer(*i))
+        .collect::<Vec<_>>();
+    let total_voting_power: VotingPower = header.validator_set.iter().map(|(_, v)| v).sum();
+    let voted_voting_power: VotingPower = signers.iter().map(|i| header.validator_set[*i].1).sum();
+    if voted_voting_power * 3 <= total_voting_power * 2 {
+        return Err(VerifyError::InvalidProof(format!(
+            "invalid finalization proof - voted voting power is too low: {voted_voting_power} / {total_voting_power}"
+        )));
+    }
+    let public_keys = signers
+        .iter()
+        .map(|i| bls_keys[*i].to_blst())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| VerifyError::CryptoError("invalid BLS key".to_owned(), e))?;
+    let target = FinalizationSignTarget {
+        block_hash: header.to_hash256(),
+        round: proof.round,
+    };
+    let signature = proof
+        .signature
+        .to_blst()
+        .map_err(|e| VerifyError::CryptoError("invalid finalization proof".to_owned(), e))?;
+    check(signature.fast_aggregate_verify(
+        true,
+        target.to_hash256().as_ref(),
+        SIGNATURE_DST,
+        &public_keys.iter().collect::<Vec<_>>(),
+    ))
+    .map_err(|e| VerifyError::CryptoError("invalid finalization proof".to_owned(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(n: usize) -> (BlockHeader, Vec<BlsPrivateKey>) {
+        let (reserved_state, _) = test_utils::generate_standard_genesis(n);
+        let header = reserved_state.genesis_info.header;
+        let keys = (0..n)
+            .map(|i| BlsPrivateKey::generate(format!("{i}")))
+            .collect();
+        (header, keys)
+    }
+
+    fn sign(
+        header: &BlockHeader,
+        keys: &[BlsPrivateKey],
+        signers: &[usize],
+    ) -> Vec<(usize, BlsSignature)> {
+        let target = FinalizationSignTarget {
+            block_hash: header.to_hash256(),
+            round: 1,
+        };
+        signers
+            .iter()
+            .map(|i| (*i, keys[*i].sign(&target)))
+            .collect()
+    }
+
+    #[test]
+    fn aggregate_finalization_proof() {
+        let (header, keys) = setup(10);
+        let public_keys = keys.iter().map(|k| k.public_key()).collect::<Vec<_>>();
+        for (private_key, public_key) in keys.iter().zip(public_keys.iter()) {
+            public_key
+                .verify_possession(&private_key.prove_possession())
+                .unwrap();
+        }
+        let signatures = sign(&header, &keys, &[0, 2, 3, 4, 5, 6, 8, 9]);
+        let proof = AggregateFinalizationProof::aggregate(1, 10, &signatures).unwrap();
+        assert_eq!(proof.signers.data, vec![0b0111_1101, 0b0000_0011]);
+        verify_aggregate_finalization_proof(&header, &public_keys, &proof).unwrap();
+
+        // It survives the serialization.
+        let decoded = serde_spb::from_str(&serde_spb::to_string(&proof).unwrap()).unwrap();
+        assert_eq!(proof, decoded);
+        verify_aggregate_finalization_proof(&header, &public_keys, &decoded).unwrap();
+    }
+
+    #[test]
+    fn aggregate_finalization_proof_with_wrong_bitmap() {
+        let (header, keys) = setup(4);
+        let public_keys = keys.iter().map(|k| k.public_key()).collect::<Vec<_>>();
+        let signatures = sign(&header, &keys, &[0, 1, 2, 3]);
+        let proof = AggregateFinalizationProof::aggregate(1, 4, &signatures).unwrap();
+        verify_aggregate_finalization_proof(&header, &public_keys, &proof).unwrap();
+
+        // A signer missing from the bitmap breaks the aggregate signature.
+        let mut missing_signer = proof.clone();
+        missing_signer.signers.data[0] = 0b0000_0111;
+        assert!(
+            verify_aggregate_finalization_proof(&header, &public_keys, &missing_signer).is_err()
+        );
+        // So does a signer who didn't sign.
+        let signatures = sign(&header, &keys, &[0, 1, 2]);
+        let mut extra_signer = AggregateFinalizationProof::aggregate(1, 4, &signatures).unwrap();
+        verify_aggregate_finalization_proof(&header, &public_keys, &extra_signer).unwrap();
+        extra_signer.signers.data[0] = 0b0000_1111;
+        assert!(verify_aggregate_finalization_proof(&header, &public_keys, &extra_signer).is_err());
+        // A signer out of the validator set.
+        let mut out_of_range = proof.clone();
+        out_of_range.signers.data[0] = 0b0001_1111;
+        assert!(matches!(
+            verify_aggregate_finalization_proof(&header, &public_keys, &out_of_range),
+            Err(VerifyError::InvalidProof(_))
+        ));
+        // Too few signers.
+        let signatures = sign(&header, &keys, &[0, 1]);
+        let too_few = AggregateFinalizationProof::aggregate(1, 4, &signatures).unwrap();
+        assert!(matches!(
+            verify_aggregate_finalization_proof(&header, &public_keys, &too_few),
+            Err(VerifyError::InvalidProof(_))
+        ));
+        // A signer can't be counted twice.
+        let signatures = sign(&header, &keys, &[0, 1, 1]);
+        assert!(matches!(
+            AggregateFinalizationProof::aggregate(1, 4, &signatures),
+            Err(CryptoError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_private_key() {
+        let private_key = BlsPrivateKey::generate("seed");
+        let encoded = serde_spb::to_string(&private_key).unwrap();
+        assert_eq!(
+            serde_spb::from_str::<BlsPrivateKey>(&encoded).unwrap(),
+            private_key
+        );
+        // Zero is not a valid scalar, and neither is one that is not smaller than the group order.
+        for byte in ["00", "ff"] {
+            let encoded = serde_spb::to_string(&byte.repeat(32)).unwrap();
+            serde_spb::from_str::<HexSerializedBytes<32>>(&encoded).unwrap();
+            assert!(serde_spb::from_str::<BlsPrivateKey>(&encoded).is_err());
+        }
+    }
+}
@@ -25,6 +25,15 @@ pub trait ToHash256 {
     fn to_hash256(&self) -> Hash256;
 }
 
+/// A type whose values are signed with [`TypedSignature`].
+///
+/// The domain is signed along with the hash of the data, so that a signature on a value
+/// of one type is never valid for a value of another type, even if their hashes coincide.
+pub trait SignatureDomain: ToHash256 {
+    /// The tag of the type, which must be unique among the signed types.
+    const SIGNATURE_DOMAIN: &'static str;
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Copy)]
 pub struct HexSerializedBytes<const N: usize> {
     pub data: [u8; N],
@@ -205,7 +214,7 @@ impl Hash256 {
         }
     }
 
-    pub fn from_array(data: [u8; 32]) -> Self {
+    pub const fn from_array(data: [u8; 32]) -> Self {
         Hash256 {
             hash: HexSerializedBytes { data },
         }
@@ -214,6 +223,13 @@ impl Hash256 {
     pub fn aggregate(&self, other: &Self) -> Self {
         Self::hash([self.hash.data, other.hash.data].concat())
     }
+
+    /// Returns the first `digits` hexadecimal digits of the hash (or all of them if fewer).
+    pub fn short(&self, digits: usize) -> String {
+        let mut short = self.to_string();
+        short.truncate(digits);
+        short
+    }
 }
 
 impl std::convert::AsRef<[u8]> for Hash256 {
@@ -306,7 +322,8 @@ impl Signature {
 
 /// A signature that is explicitly marked with the type of the signed data.
 ///
-/// This implies that the signature is created on `Hash256::hash(serde_spb::to_vec(T).unwrap())`.
+/// This implies that the signature is created on the hash of the data,
+/// separated by the domain of the type (see [`SignatureDomain`]).
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
 pub struct TypedSignature<T> {
     signature: Signature,
@@ -315,11 +332,16 @@ pub struct TypedSignature<T> {
     _mark: std::marker::PhantomData<T>,
 }
 
-impl<T: ToHash256> TypedSignature<T> {
+impl<T: SignatureDomain> TypedSignature<T> {
     /// Creates a new signature from the given data and keys.
     pub fn sign(data: &T, private_key: &PrivateKey) -> Result<Self, Error> {
-        let data = data.to_hash256();
-        Signature::sign(data, private_key).map(|signature| TypedSignature {
+        Self::sign_hash(data.to_hash256(), private_key)
+    }
+
+    /// Creates a new signature on the data of the given hash,
+    /// for when only the hash of the data is at hand.
+    pub fn sign_hash(data_hash: Hash256, private_key: &PrivateKey) -> Result<Self, Error> {
+        Signature::sign(Self::signed_hash(data_hash), private_key).map(|signature| TypedSignature {
             signature,
             signer: private_key.public_key(),
             _mark: std::marker::PhantomData,
@@ -340,13 +362,23 @@ impl<T: ToHash256> TypedSignature<T> {
 
     /// Verifies the signature against the given data and public key.
     pub fn verify(&self, data: &T) -> Result<(), Error> {
-        let data = data.to_hash256();
-        self.signature.verify(data, &self.signer)
+        self.verify_hash(data.to_hash256())
+    }
+
+    /// Verifies the signature against the data of the given hash and public key.
+    pub fn verify_hash(&self, data_hash: Hash256) -> Result<(), Error> {
+        self.signature
+            .verify(Self::signed_hash(data_hash), &self.signer)
     }
 
     pub fn get_raw_signature(&self) -> Signature {
         self.signature.clone()
     }
+
+    /// The hash that is actually signed, which binds the hash of the data to its domain.
+    fn signed_hash(data_hash: Hash256) -> Hash256 {
+        Hash256::hash(T::SIGNATURE_DOMAIN).aggregate(&data_hash)
+    }
 }
 
 impl std::convert::AsRef<[u8]> for Signature {
@@ -483,6 +515,21 @@ mod tests {
     use super::*;
     use crate::serde_spb;
 
+    #[test]
+    fn short_format() {
+        let hash = Hash256::hash("hello world");
+        let full = hash.to_string();
+        assert_eq!(hash.short(8), full[..8]);
+        assert_eq!(hash.short(100), full);
+        // The full form decodes back to the hash, and the short form is the prefix of it.
+        let decoded: Hash256 = serde_spb::from_str(&format!("\"{full}\"")).unwrap();
+        assert_eq!(decoded, hash);
+        assert_eq!(decoded.short(8), hash.short(8));
+        // A different hash has a different short form (unless they collide in the prefix).
+        let other = Hash256::hash("hello rustaceans");
+        assert_ne!(other.short(8), hash.short(8));
+    }
+
     #[test]
     fn pretty_format() {
         let hash = Hash256::hash("hello world");
@@ -595,4 +642,44 @@ mod tests {
             hex::encode(recovered.as_ref())
         );
     }
+
+    #[test]
+    fn typed_signature_domain_separation() {
+        use crate::types::Agenda;
+
+        /// A vote for an agenda, whose hash coincides with the agenda's.
+        struct Vote(Agenda);
+
+        impl ToHash256 for Vote {
+            fn to_hash256(&self) -> Hash256 {
+                self.0.to_hash256()
+            }
+        }
+
+        impl SignatureDomain for Vote {
+            const SIGNATURE_DOMAIN: &'static str = "test-vote";
+        }
+
+        let (_, private_key) = generate_keypair("hello world");
+        let agenda = Agenda {
+            height: 1,
+            author: "member-0000".to_owned(),
+            timestamp: 0,
+            transactions_hash: Hash256::hash("transactions"),
+            previous_block_hash: Hash256::hash("previous block"),
+        };
+        let vote = Vote(agenda.clone());
+        assert_eq!(vote.to_hash256(), agenda.to_hash256());
+
+        let signature = TypedSignature::sign(&vote, &private_key).unwrap();
+        signature.verify(&vote).unwrap();
+        TypedSignature::<Agenda>::new(signature.get_raw_signature(), signature.signer().clone())
+            .verify(&agenda)
+            .unwrap_err();
+        // Nor is it a valid signature on the raw hash.
+        signature
+            .get_raw_signature()
+            .verify(agenda.to_hash256(), signature.signer())
+            .unwrap_err();
+    }
 }
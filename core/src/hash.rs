@@ -60,6 +60,12 @@ impl ToHash256 for BlockHeader {
     }
 }
 
+impl ToHash256 for ReservedState {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
 impl ToHash256 for Diff {
     fn to_hash256(&self) -> Hash256 {
         Hash256::hash(serde_spb::to_vec(self).unwrap())
@@ -128,6 +134,20 @@ impl ToHash256 for Commit {
 }
 
 impl Transaction {
+    /// Returns the transaction that the author signs, which is this one without the signature.
+    pub fn to_sign_target(&self) -> Transaction {
+        Transaction {
+            author_signature: None,
+            ..self.clone()
+        }
+    }
+
+    /// Signs the transaction as its author, replacing the existing signature if any.
+    pub fn sign(&mut self, private_key: &PrivateKey) -> Result<(), CryptoError> {
+        self.author_signature = Some(TypedSignature::sign(&self.to_sign_target(), private_key)?);
+        Ok(())
+    }
+
     /// Returns the alternative hash of the transaction, which is for the Merkle tree.
     pub fn merkle_hash(&self) -> Hash256 {
         Hash256::hash(self.body.as_bytes())
@@ -160,6 +180,26 @@ impl BlockHeader {
     // note that `repository_merkle_root` is calculated from `simperby-repository`.
 }
 
+impl SignatureDomain for FinalizationSignTarget {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-finalization";
+}
+
+impl SignatureDomain for Agenda {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-agenda";
+}
+
+impl SignatureDomain for Transaction {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-transaction";
+}
+
+impl SignatureDomain for DelegationTransactionData {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-delegation";
+}
+
+impl SignatureDomain for UndelegationTransactionData {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-undelegation";
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
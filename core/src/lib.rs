@@ -1,3 +1,5 @@
+#[cfg(feature = "bls")]
+pub mod bls;
 pub mod crypto;
 pub mod hash;
 pub mod light_client;
@@ -3,12 +3,20 @@ use merkle_tree::*;
 use serde::{Deserialize, Serialize};
 
 /// A light client state machine.
+///
+/// It can be persisted with `to_bytes()` and resumed with `from_bytes()`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LightClient {
     pub repository_roots: Vec<Hash256>,
     pub commit_roots: Vec<Hash256>,
+    /// The height of the first roots.
     pub height_offset: u64,
     pub last_header: BlockHeader,
+    /// If set, only the roots of the last `max_roots` heights are kept.
+    ///
+    /// The light clients saved before it was introduced keep all the roots.
+    #[serde(default)]
+    pub max_roots: Option<usize>,
 }
 
 impl LightClient {
@@ -19,9 +27,28 @@ impl LightClient {
             commit_roots: vec![initial_header.commit_merkle_root],
             height_offset: initial_header.height,
             last_header: initial_header,
+            max_roots: None,
         }
     }
 
+    /// Initializes a new light client that keeps only the roots of the last `max_roots` heights.
+    pub fn with_max_roots(initial_header: BlockHeader, max_roots: usize) -> Self {
+        Self {
+            max_roots: Some(max_roots.max(1)),
+            ..Self::new(initial_header)
+        }
+    }
+
+    /// Serializes the light client to be saved in a storage.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_spb::to_vec(self).unwrap()
+    }
+
+    /// Loads the light client saved by `to_bytes()`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        serde_spb::from_slice(bytes).map_err(|e| e.to_string())
+    }
+
     /// Updates the header by providing the next block and the proof of it.
     pub fn update(&mut self, header: BlockHeader, proof: FinalizationProof) -> Result<(), String> {
         verify::verify_header_to_header(&self.last_header, &header).map_err(|e| e.to_string())?;
@@ -29,9 +56,45 @@ impl LightClient {
         self.repository_roots.push(header.repository_merkle_root);
         self.commit_roots.push(header.commit_merkle_root);
         self.last_header = header;
+        if let Some(max_roots) = self.max_roots {
+            let pruned = self.commit_roots.len().saturating_sub(max_roots);
+            self.repository_roots.drain(..pruned);
+            self.commit_roots.drain(..pruned);
+            self.height_offset += pruned as u64;
+        }
         Ok(())
     }
 
+    /// Returns the index of the roots for the given height,
+    /// failing if the height has been pruned or is not reached yet.
+    fn root_index(&self, block_height: u64) -> Result<usize, String> {
+        if block_height < self.height_offset {
+            return Err(format!(
+                "height {block_height} has been pruned (the oldest kept height is {})",
+                self.height_offset
+            ));
+        }
+        if block_height > self.last_header.height {
+            return Err(format!(
+                "height {block_height} is beyond the last header (height {})",
+                self.last_header.height
+            ));
+        }
+        Ok((block_height - self.height_offset) as usize)
+    }
+
+    /// Verifies the given transaction with its proof, returning the reason if it fails.
+    pub fn check_transaction_commitment(
+        &self,
+        transaction: &Transaction,
+        block_height: u64,
+        proof: MerkleProof,
+    ) -> Result<(), String> {
+        let root = self.commit_roots[self.root_index(block_height)?];
+        let message = serde_spb::to_vec(transaction).unwrap();
+        proof.verify(root, &message).map_err(|e| e.to_string())
+    }
+
     /// Verifies the given transaction with its proof.
     pub fn verify_transaction_commitment(
         &self,
@@ -39,17 +102,32 @@ impl LightClient {
         block_height: u64,
         proof: MerkleProof,
     ) -> bool {
-        let message = serde_spb::to_vec(transaction).unwrap();
-        if block_height < self.height_offset
-            || block_height >= self.height_offset + self.commit_roots.len() as u64
-        {
-            return false;
-        }
-        proof
-            .verify(
-                self.commit_roots[(block_height - self.height_offset) as usize],
-                &message,
-            )
+        self.check_transaction_commitment(transaction, block_height, proof)
+            .is_ok()
+    }
+
+    /// Verifies that the given key is absent from the state at the height,
+    /// returning the reason if it fails.
+    ///
+    /// The state is committed as a `SortedMerkleTree` by the repository Merkle root.
+    pub fn check_state_non_membership(
+        &self,
+        key: Hash256,
+        block_height: u64,
+        proof: NonMembershipProof,
+    ) -> Result<(), String> {
+        let root = self.repository_roots[self.root_index(block_height)?];
+        proof.verify(root, key).map_err(|e| e.to_string())
+    }
+
+    /// Verifies that the given key is absent from the state at the height.
+    pub fn verify_state_non_membership(
+        &self,
+        key: Hash256,
+        block_height: u64,
+        proof: NonMembershipProof,
+    ) -> bool {
+        self.check_state_non_membership(key, block_height, proof)
             .is_ok()
     }
 
@@ -63,3 +141,43 @@ impl LightClient {
         todo!()
     }
 }
+
+/// A self-contained proof that a transaction is committed in a finalized block.
+///
+/// This is what a settlement relayer delivers with `SettlementChain::execute()`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CommitmentBundle {
+    pub header: BlockHeader,
+    pub proof: FinalizationProof,
+    pub transaction: Transaction,
+    pub merkle_proof: MerkleProof,
+}
+
+impl CommitmentBundle {
+    /// Builds a bundle for the transaction included in the finalized block.
+    pub fn build(
+        finalization_info: &FinalizationInfo,
+        transaction: Transaction,
+        merkle_proof: MerkleProof,
+    ) -> Self {
+        Self {
+            header: finalization_info.header.clone(),
+            proof: finalization_info.proof.clone(),
+            transaction,
+            merkle_proof,
+        }
+    }
+
+    /// Verifies the finalization proof of the header and the inclusion of the transaction.
+    ///
+    /// Note that whether the validator set of the header is trusted
+    /// must be checked separately (e.g., by `LightClient`).
+    pub fn verify(&self) -> bool {
+        let message = serde_spb::to_vec(&self.transaction).unwrap();
+        verify::verify_finalization_proof(&self.header, &self.proof).is_ok()
+            && self
+                .merkle_proof
+                .verify(self.header.commit_merkle_root, &message)
+                .is_ok()
+    }
+}
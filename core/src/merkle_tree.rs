@@ -121,6 +121,59 @@ impl OneshotMerkleTree {
     }
 }
 
+/// A Merkle tree of keys sorted in ascending order, which can also prove that a key is absent.
+///
+/// Each leaf is the hash of a key, so the Merkle proof of a key is verified
+/// with `MerkleProof::verify(root, key.as_ref())`.
+/// `MIN_KEY` and `MAX_KEY` are always included as sentinels,
+/// so that any other absent key lies between two adjacent keys.
+pub struct SortedMerkleTree {
+    keys: Vec<Hash256>,
+    tree: OneshotMerkleTree,
+}
+
+impl SortedMerkleTree {
+    pub const MIN_KEY: Hash256 = Hash256::zero();
+    pub const MAX_KEY: Hash256 = Hash256::from_array([0xff; 32]);
+
+    /// Creates a new SortedMerkleTree from the given keys, ignoring duplicates.
+    pub fn create(mut keys: Vec<Hash256>) -> Self {
+        keys.push(Self::MIN_KEY);
+        keys.push(Self::MAX_KEY);
+        keys.sort();
+        keys.dedup();
+        let tree = OneshotMerkleTree::create(keys.iter().map(Hash256::hash).collect());
+        SortedMerkleTree { keys, tree }
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> Hash256 {
+        self.tree.root()
+    }
+
+    /// Creates a Merkle proof for the given key.
+    ///
+    /// Returns `None` if the key is not in the tree.
+    pub fn create_merkle_proof(&self, key: Hash256) -> Option<MerkleProof> {
+        self.keys.binary_search(&key).ok()?;
+        self.tree.create_merkle_proof(Hash256::hash(key))
+    }
+
+    /// Creates a proof that the given key is not in the tree.
+    ///
+    /// Returns `None` if the key is in the tree.
+    pub fn create_non_membership_proof(&self, key: Hash256) -> Option<NonMembershipProof> {
+        // The index is never `0` nor `self.keys.len()`, thanks to the sentinels.
+        let index = self.keys.binary_search(&key).err()?;
+        let left = self.keys[index - 1];
+        let right = self.keys[index];
+        Some(NonMembershipProof {
+            left: (left, self.create_merkle_proof(left)?),
+            right: (right, self.create_merkle_proof(right)?),
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct MerkleProof {
     pub proof: Vec<MerkleProofEntry>,
@@ -144,6 +197,15 @@ pub enum MerkleProofError {
 }
 
 impl MerkleProof {
+    /// Returns the index of the leaf that this proof starts from,
+    /// or `None` if the proof is too long to have one.
+    fn leaf_index(&self) -> Option<u64> {
+        self.proof.iter().rev().try_fold(0u64, |index, node| {
+            let bit = matches!(node, MerkleProofEntry::LeftChild(_)) as u64;
+            index.checked_mul(2).map(|index| index + bit)
+        })
+    }
+
     /// Verifies whether the given data is in the block.
     pub fn verify(&self, root: Hash256, data: &[u8]) -> Result<(), MerkleProofError> {
         let mut calculated_root: Hash256 = Hash256::hash(data);
@@ -169,6 +231,42 @@ impl MerkleProof {
     }
 }
 
+/// A proof that a key is not in a `SortedMerkleTree`, made of the two adjacent keys around it.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct NonMembershipProof {
+    /// The greatest key in the tree that is less than the key, with its Merkle proof.
+    pub left: (Hash256, MerkleProof),
+    /// The least key in the tree that is greater than the key, with its Merkle proof.
+    pub right: (Hash256, MerkleProof),
+}
+
+impl NonMembershipProof {
+    /// Verifies whether the given key is absent from the `SortedMerkleTree` of the root.
+    pub fn verify(&self, root: Hash256, key: Hash256) -> Result<(), MerkleProofError> {
+        let (left, left_proof) = &self.left;
+        let (right, right_proof) = &self.right;
+        if !(*left < key && key < *right) {
+            return Err(MerkleProofError::MalformedProof(format!(
+                "key {key} is not between {left} and {right}"
+            )));
+        }
+        left_proof.verify(root, left.as_ref())?;
+        right_proof.verify(root, right.as_ref())?;
+        // Both must be leaves (of the same depth) next to each other.
+        let adjacent = left_proof.proof.len() == right_proof.proof.len()
+            && matches!(
+                (left_proof.leaf_index(), right_proof.leaf_index()),
+                (Some(left_index), Some(right_index)) if left_index.checked_add(1) == Some(right_index)
+            );
+        if !adjacent {
+            return Err(MerkleProofError::MalformedProof(format!(
+                "{left} and {right} are not adjacent"
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -276,4 +374,66 @@ mod test {
         assert!(root_hash != OneshotMerkleTree::EMPTY_HASH);
         assert!(MerkleProof::verify(&merkle_proof.unwrap(), root_hash, &[10]).is_ok());
     }
+
+    #[test]
+    /// Test if a non-membership proof is created and verified for absent keys,
+    /// including ones around the first and the last keys.
+    fn non_membership_proof() {
+        let hash_list: Vec<Hash256> = create_hash_list(11);
+        let merkle_tree: SortedMerkleTree = SortedMerkleTree::create(hash_list.clone());
+        let root_hash: Hash256 = merkle_tree.root();
+        let absent_keys = [
+            Hash256::hash([42]),
+            Hash256::from_array([0x01; 32]),
+            Hash256::from_array([0xfe; 32]),
+        ];
+
+        for key in absent_keys {
+            let proof = merkle_tree.create_non_membership_proof(key).unwrap();
+            assert!(proof.verify(root_hash, key).is_ok());
+        }
+        for key in hash_list {
+            assert!(merkle_tree.create_non_membership_proof(key).is_none());
+            assert!(MerkleProof::verify(
+                &merkle_tree.create_merkle_proof(key).unwrap(),
+                root_hash,
+                key.as_ref()
+            )
+            .is_ok());
+        }
+        let proof = merkle_tree
+            .create_non_membership_proof(SortedMerkleTree::MIN_KEY)
+            .or_else(|| merkle_tree.create_non_membership_proof(SortedMerkleTree::MAX_KEY));
+        assert!(proof.is_none());
+    }
+
+    #[test]
+    /// Test if a non-membership proof fails for a key that is in the tree.
+    fn non_membership_proof_for_present_key() {
+        let hash_list: Vec<Hash256> = create_hash_list(16);
+        let merkle_tree: SortedMerkleTree = SortedMerkleTree::create(hash_list.clone());
+        let root_hash: Hash256 = merkle_tree.root();
+        let mut sorted_list = hash_list;
+        sorted_list.sort();
+        let (present, next) = (sorted_list[3], sorted_list[4]);
+        let absent = Hash256::from_array({
+            let mut data = present.hash.data;
+            data[31] = data[31].wrapping_add(1);
+            data
+        });
+        assert!(present < absent && absent < next);
+
+        let proof = merkle_tree.create_non_membership_proof(absent).unwrap();
+        assert!(proof.verify(root_hash, absent).is_ok());
+        assert!(proof.verify(root_hash, present).is_err());
+        // Claiming that the present key lies between its neighbours doesn't work either.
+        let forged = NonMembershipProof {
+            left: (
+                sorted_list[2],
+                merkle_tree.create_merkle_proof(sorted_list[2]).unwrap(),
+            ),
+            right: proof.right,
+        };
+        assert!(forged.verify(root_hash, present).is_err());
+    }
 }
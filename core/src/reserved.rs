@@ -1,6 +1,6 @@
 use crate::*;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 
 /// The partial set of the blockchain state which is reserved and protected.
 ///
@@ -18,38 +18,56 @@ pub struct ReservedState {
     pub consensus_leader_order: Vec<MemberName>,
     /// The semantic version of Simperby protocol for this network.
     pub version: String,
+    /// The thresholds that the governance votes for an agenda must exceed.
+    #[serde(default)]
+    pub governance_policy: GovernancePolicy,
 }
 
 impl ReservedState {
+    /// Returns the consensus validator set, which is the same as `effective_consensus_set()`.
     pub fn get_validator_set(&self) -> Result<Vec<(PublicKey, VotingPower)>, String> {
-        let validator_set = self
-            .members
-            .iter()
-            .filter(|member| !member.expelled)
-            .map(|member| {
-                let name = member
-                    .consensus_delegatee
-                    .as_ref()
-                    .map_or_else(|| member.name.as_str(), |name| name.as_str());
-                let public_key = self.query_public_key(&name.to_string()).ok_or_else(|| {
-                    format!("the public key of {name} is not found in the reserved state.")
-                })?;
-                Ok((public_key, member.consensus_voting_power))
-            })
-            .try_fold(
-                BTreeMap::new(),
-                |mut map, result: Result<(crypto::PublicKey, u64), String>| {
-                    let (public_key, voting_power) = result?;
-                    map.entry(public_key)
-                        .and_modify(|v| *v += voting_power)
-                        .or_insert(voting_power);
-                    Ok::<BTreeMap<crypto::PublicKey, u64>, String>(map)
-                },
-            )?
-            .into_iter()
-            .collect::<Vec<_>>();
+        self.effective_consensus_set()
+    }
 
-        Ok(validator_set)
+    /// Returns the voting power of each validator, sorted by the public key,
+    /// with the consensus delegations resolved.
+    ///
+    /// The voting power of a member is counted for the end of its `consensus_delegatee` chain,
+    /// so a member who delegated its consensus power is not in the set unless someone else
+    /// delegates to it. Expelled members have no voting power.
+    pub fn effective_consensus_set(&self) -> Result<Vec<(PublicKey, VotingPower)>, String> {
+        let mut validator_set = BTreeMap::new();
+        for member in self.members.iter().filter(|member| !member.expelled) {
+            let name = self.resolve_consensus_delegatee(&member.name)?;
+            let public_key = self.query_public_key(&name).ok_or_else(|| {
+                format!("the public key of {name} is not found in the reserved state.")
+            })?;
+            *validator_set.entry(public_key).or_insert(0) += member.consensus_voting_power;
+        }
+        Ok(validator_set.into_iter().collect())
+    }
+
+    /// Follows the `consensus_delegatee` chain from the given member
+    /// and returns the member who finally exercises its consensus power.
+    ///
+    /// It fails if the chain is broken or forms a cycle.
+    pub fn resolve_consensus_delegatee(&self, name: &MemberName) -> Result<MemberName, String> {
+        let mut visited = HashSet::new();
+        let mut name = name.clone();
+        loop {
+            if !visited.insert(name.clone()) {
+                return Err(format!("the consensus delegation of {name} forms a cycle."));
+            }
+            let member = self
+                .members
+                .iter()
+                .find(|member| member.name == name)
+                .ok_or_else(|| format!("{name} is not a member."))?;
+            match &member.consensus_delegatee {
+                Some(delegatee) => name = delegatee.clone(),
+                None => return Ok(name),
+            }
+        }
     }
 
     pub fn get_governance_set(&self) -> Result<Vec<(PublicKey, VotingPower)>, String> {
@@ -112,6 +130,12 @@ impl ReservedState {
         if tx.proof.verify(&tx.data).is_err() {
             return Err("delegation proof verification failed".to_string());
         }
+        if self.resolve_consensus_delegatee(&tx.data.delegatee)? == tx.data.delegator {
+            return Err(format!(
+                "the delegation from {} to {} would form a cycle",
+                tx.data.delegator, tx.data.delegatee
+            ));
+        }
         for delegator in &mut self.members {
             if delegator.name == tx.data.delegator {
                 if delegator.consensus_delegatee.is_some() {
@@ -179,6 +203,93 @@ impl ReservedState {
         None
     }
 
+    /// Sets the leader order of the consensus rounds,
+    /// if it is valid for the current members.
+    pub fn set_leader_order(&mut self, order: Vec<MemberName>) -> Result<(), verify::Error> {
+        self.verify_consensus_leader_order(&order)?;
+        self.consensus_leader_order = order;
+        Ok(())
+    }
+
+    /// Rotates the leader order by one, moving the first leader to the end.
+    pub fn rotate_leaders(&mut self) -> Result<(), verify::Error> {
+        let mut order = self.consensus_leader_order.clone();
+        if !order.is_empty() {
+            order.rotate_left(1);
+        }
+        self.set_leader_order(order)
+    }
+
+    /// Checks whether the given leader order is valid for the current members.
+    pub fn verify_consensus_leader_order(&self, order: &[MemberName]) -> Result<(), verify::Error> {
+        // 1. consensus_leader_order should be the subset of members.
+        // 2. every consensus leader should not be expelled.
+        // 3. consensus_leader_order should consist of more than 1 unique members to avoid a SPoF.
+        let valid_leader_candidates: HashSet<&MemberName> = self
+            .members
+            .iter()
+            .filter(|m| !m.expelled)
+            .map(|m| &m.name)
+            .collect();
+        if !order.iter().all(|m| valid_leader_candidates.contains(m)) {
+            return Err(verify::Error::InvalidArgument(
+                "Some consensus leaders are not valid candidates".to_string(),
+            ));
+        }
+        if order.iter().collect::<HashSet<&MemberName>>().len() <= 1 {
+            return Err(verify::Error::InvalidArgument(
+                "consensus_leader_order should consist of more than 1 unique members".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the invariants that every reserved state must hold by itself,
+    /// regardless of the previous one.
+    pub fn verify_structure(&self) -> Result<(), verify::Error> {
+        // Check that the number of members is at least 4.
+        if self.members.len() < 4 {
+            return Err(verify::Error::InvalidArgument(
+                "the number of members is less than 4".to_string(),
+            ));
+        }
+        // Check that `consensus_leader_order` is correct.
+        self.verify_consensus_leader_order(&self.consensus_leader_order)?;
+        // Check that every governance threshold is a fraction in `[0, 1)`.
+        for threshold in std::iter::once(&self.governance_policy.default_threshold)
+            .chain(self.governance_policy.thresholds.values())
+            .chain(self.governance_policy.veto_thresholds.values())
+        {
+            if threshold.numerator >= threshold.denominator {
+                return Err(verify::Error::InvalidArgument(format!(
+                    "invalid governance threshold {}/{}",
+                    threshold.numerator, threshold.denominator
+                )));
+            }
+        }
+        // Check that `Member::name` and `Member::public_key` are unique.
+        let mut member_names = HashSet::new();
+        let mut public_keys = HashSet::new();
+        for member in &self.members {
+            if !member_names.insert(&member.name) {
+                return Err(verify::Error::InvalidArgument(format!(
+                    "member name '{}' already exists",
+                    member.name
+                )));
+            }
+            if !public_keys.insert(&member.public_key) {
+                return Err(verify::Error::InvalidArgument(format!(
+                    "the public key of '{}' already exists",
+                    member.name
+                )));
+            }
+        }
+        // Check that every consensus delegation chain is resolvable and acyclic.
+        self.effective_consensus_set()
+            .map_err(verify::Error::InvalidArgument)?;
+        Ok(())
+    }
+
     pub fn is_expelled(&self, name: &MemberName) -> Option<bool> {
         for member in &self.members {
             if &member.name == name {
@@ -335,6 +446,7 @@ mod tests {
             members,
             consensus_leader_order: vec!["member-0003".to_string()],
             version: "0.1.0".to_string(),
+            governance_policy: GovernancePolicy::default(),
         };
         assert_eq!(
             reserved_state.get_validator_set().unwrap(),
@@ -394,6 +506,7 @@ mod tests {
             members,
             consensus_leader_order: vec!["member-0001".to_string(), "member-0003".to_string()],
             version: "0.1.0".to_string(),
+            governance_policy: GovernancePolicy::default(),
         };
         assert_eq!(
             reserved_state.get_validator_set().unwrap(),
@@ -452,6 +565,7 @@ mod tests {
             members,
             consensus_leader_order: (0..4).map(|i| format!("member-{i:04}")).collect::<Vec<_>>(),
             version: "0.1.0".to_string(),
+            governance_policy: GovernancePolicy::default(),
         };
         assert_eq!(
             reserved_state.get_governance_set().unwrap(),
@@ -510,6 +624,7 @@ mod tests {
             members,
             consensus_leader_order: (0..4).map(|i| format!("member-{i:04}")).collect::<Vec<_>>(),
             version: "0.1.0".to_string(),
+            governance_policy: GovernancePolicy::default(),
         };
         assert_eq!(
             reserved_state
@@ -575,6 +690,7 @@ mod tests {
             members,
             consensus_leader_order: vec!["member-0001".to_string(), "member-0003".to_string()],
             version: "0.1.0".to_string(),
+            governance_policy: GovernancePolicy::default(),
         };
         assert_eq!(
             reserved_state
@@ -640,6 +756,7 @@ mod tests {
             members,
             consensus_leader_order: (0..5).map(|i| format!("member-{i:04}")).collect::<Vec<_>>(),
             version: "0.1.0".to_string(),
+            governance_policy: GovernancePolicy::default(),
         };
         assert_eq!(
             reserved_state
@@ -947,6 +1064,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_apply_delegate_on_consensus_failure3() {
+        let (mut state, keys) = generate_standard_genesis(3);
+
+        // member-0001 -> member-0002 -> member-0000
+        state.members[1] = create_member_with_consensus_delegation(keys.clone(), 1, 2);
+        state.members[2] = create_member_with_consensus_delegation(keys.clone(), 2, 0);
+
+        let data = DelegationTransactionData {
+            // member-0000 -> member-0001 would close the cycle
+            delegator: state.members[0].name.clone(),
+            delegatee: state.members[1].name.clone(),
+            governance: false,
+            block_height: 0,
+            timestamp: 0,
+            chain_name: state.genesis_info.chain_name.clone(),
+        };
+        let proof = TypedSignature::sign(&data, &keys[0].1).unwrap();
+
+        let tx = TxDelegate { data, proof };
+
+        let new_state = state.apply_delegate(&tx);
+
+        if new_state.is_ok() {
+            panic!("it must fail when the delegation forms a cycle");
+        }
+        assert!(state.effective_consensus_set().is_ok());
+    }
+
     #[test]
     fn test_apply_undelegate_on_governance_and_consensus_success() {
         // given
@@ -1082,4 +1228,103 @@ mod tests {
             panic!("it must fail when the delegator is expelled");
         }
     }
+
+    #[test]
+    fn set_leader_order_with_expelled_member() {
+        let (mut state, keys) = generate_standard_genesis(4);
+        state.members[1] = create_expelled_member(keys, 1);
+
+        let order = vec!["member-0000".to_string(), "member-0001".to_string()];
+        assert!(matches!(
+            state.set_leader_order(order),
+            Err(verify::Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            state.set_leader_order(vec!["member-0000".to_string()]),
+            Err(verify::Error::InvalidArgument(_))
+        ));
+        assert_eq!(
+            state.consensus_leader_order,
+            (0..4).map(|i| format!("member-{i:04}")).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rotate_leaders() {
+        let (mut state, _) = generate_standard_genesis(4);
+        state.rotate_leaders().unwrap();
+        assert_eq!(
+            state.consensus_leader_order,
+            vec!["member-0001", "member-0002", "member-0003", "member-0000"]
+        );
+
+        let order = vec!["member-0002".to_string(), "member-0000".to_string()];
+        state.set_leader_order(order.clone()).unwrap();
+        assert_eq!(state.consensus_leader_order, order);
+    }
+
+    #[test]
+    fn effective_consensus_set_with_delegation() {
+        let (mut state, keys) = generate_standard_genesis(4);
+        let power = |state: &ReservedState, i: usize| {
+            state
+                .effective_consensus_set()
+                .unwrap()
+                .into_iter()
+                .find(|(public_key, _)| *public_key == keys[i].0)
+                .map_or(0, |(_, power)| power)
+        };
+        let total = |state: &ReservedState| {
+            state
+                .effective_consensus_set()
+                .unwrap()
+                .iter()
+                .map(|(_, power)| power)
+                .sum::<VotingPower>()
+        };
+        assert_eq!(total(&state), 4);
+
+        // Member 0 delegates to member 1.
+        state.members[0] = create_member_with_consensus_delegation(keys.clone(), 0, 1);
+        assert_eq!(power(&state, 0), 0);
+        assert_eq!(power(&state, 1), 2);
+        assert_eq!(total(&state), 4);
+
+        // Member 1 delegates to member 2, carrying the power of member 0 along.
+        state.members[1] = create_member_with_consensus_delegation(keys.clone(), 1, 2);
+        assert_eq!(
+            state
+                .resolve_consensus_delegatee(&"member-0000".to_string())
+                .unwrap(),
+            "member-0002"
+        );
+        assert_eq!(power(&state, 1), 0);
+        assert_eq!(power(&state, 2), 3);
+        assert_eq!(total(&state), 4);
+        assert_eq!(state.get_validator_set(), state.effective_consensus_set());
+
+        // An expelled delegator has no power to delegate.
+        state.members[0] = create_expelled_member(keys.clone(), 0);
+        assert_eq!(power(&state, 2), 2);
+        assert_eq!(total(&state), 3);
+    }
+
+    #[test]
+    fn effective_consensus_set_with_delegation_cycle() {
+        let (mut state, keys) = generate_standard_genesis(4);
+        state.members[0] = create_member_with_consensus_delegation(keys.clone(), 0, 1);
+        state.members[1] = create_member_with_consensus_delegation(keys.clone(), 1, 2);
+        state.members[2] = create_member_with_consensus_delegation(keys.clone(), 2, 0);
+        assert!(state
+            .resolve_consensus_delegatee(&"member-0000".to_string())
+            .is_err());
+        assert!(state.effective_consensus_set().is_err());
+        // The member outside of the cycle is still resolved.
+        assert_eq!(
+            state
+                .resolve_consensus_delegatee(&"member-0003".to_string())
+                .unwrap(),
+            "member-0003"
+        );
+    }
 }
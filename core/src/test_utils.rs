@@ -1,7 +1,17 @@
 use crate::*;
 
 pub fn generate_fi(member_number: usize) -> (FinalizationInfo, Vec<(PublicKey, PrivateKey)>) {
-    let (rs, keys) = generate_standard_genesis(member_number);
+    generate_fi_at(member_number, 0)
+}
+
+/// Same as `generate_fi()`, but with the genesis block created at the given timestamp.
+///
+/// This is useful for running the consensus, whose round 0 begins at the genesis timestamp.
+pub fn generate_fi_at(
+    member_number: usize,
+    timestamp: Timestamp,
+) -> (FinalizationInfo, Vec<(PublicKey, PrivateKey)>) {
+    let (rs, keys) = generate_standard_genesis_at(member_number, timestamp);
     let fi = FinalizationInfo {
         header: rs.genesis_info.header.clone(),
         commit_hash: CommitHash::zero(),
@@ -15,6 +25,14 @@ pub fn generate_fi(member_number: usize) -> (FinalizationInfo, Vec<(PublicKey, P
 /// and the associated key pairs of the members.
 pub fn generate_standard_genesis(
     member_number: usize,
+) -> (ReservedState, Vec<(PublicKey, PrivateKey)>) {
+    generate_standard_genesis_at(member_number, 0)
+}
+
+/// Same as `generate_standard_genesis()`, but with the genesis block created at the given timestamp.
+pub fn generate_standard_genesis_at(
+    member_number: usize,
+    timestamp: Timestamp,
 ) -> (ReservedState, Vec<(PublicKey, PrivateKey)>) {
     let keys = (0..member_number)
         .map(|i| generate_keypair(format!("{i}")))
@@ -38,7 +56,7 @@ pub fn generate_standard_genesis(
         prev_block_finalization_proof: FinalizationProof::genesis(),
         previous_hash: Hash256::zero(),
         height: 0,
-        timestamp: 0,
+        timestamp,
         commit_merkle_root: Hash256::zero(),
         repository_merkle_root: Hash256::zero(),
         validator_set: members
@@ -75,6 +93,7 @@ pub fn generate_standard_genesis(
                 .map(|i| format!("member-{i:04}"))
                 .collect::<Vec<_>>(),
             version: SIMPERBY_CORE_PROTOCOL_VERSION.to_string(),
+            governance_policy: GovernancePolicy::default(),
         },
         keys,
     )
@@ -157,6 +176,7 @@ pub fn generate_delegated_genesis(
                 .map(|i| format!("member-{i:04}"))
                 .collect::<Vec<_>>(),
             version: "0.1.0".to_string(),
+            governance_policy: GovernancePolicy::default(),
         },
         keys,
     )
@@ -1,5 +1,6 @@
 use crate::{crypto::*, reserved::ReservedState};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
 pub type VotingPower = u64;
@@ -91,6 +92,96 @@ pub struct Agenda {
     pub previous_block_hash: Hash256,
 }
 
+/// The kind of an agenda, which decides the governance threshold it has to meet.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
+pub enum AgendaType {
+    /// An agenda that leaves the reserved state untouched.
+    General,
+    /// An agenda that changes the reserved state (e.g., adding or expelling a member).
+    ReservedStateChange,
+}
+
+impl AgendaType {
+    /// Classifies an agenda by the transactions it contains.
+    pub fn from_transactions<'a>(transactions: impl IntoIterator<Item = &'a Transaction>) -> Self {
+        if transactions
+            .into_iter()
+            .any(|tx| matches!(tx.diff, Diff::Reserved(..) | Diff::General(..)))
+        {
+            AgendaType::ReservedStateChange
+        } else {
+            AgendaType::General
+        }
+    }
+}
+
+/// A fraction of the total governance voting power that an agenda's votes must exceed.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct GovernanceThreshold {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl GovernanceThreshold {
+    /// More than a half of the voting power.
+    pub const SIMPLE_MAJORITY: Self = Self {
+        numerator: 1,
+        denominator: 2,
+    };
+    /// More than two thirds of the voting power, as required for the finalization proof.
+    pub const SUPER_MAJORITY: Self = Self {
+        numerator: 2,
+        denominator: 3,
+    };
+
+    /// Checks whether `voted_power` strictly exceeds this fraction of `total_voting_power`.
+    pub fn is_met(&self, voted_power: VotingPower, total_voting_power: VotingPower) -> bool {
+        voted_power as u128 * self.denominator as u128
+            > total_voting_power as u128 * self.numerator as u128
+    }
+}
+
+/// The governance thresholds that agendas have to meet, as recorded in the reserved state.
+///
+/// An agenda is approved once its votes exceed the threshold of its type.
+/// If its type also has a veto threshold, the agenda is withheld as long as its vetoes exceed
+/// that threshold, however many votes it has; this lets a minority block such agendas.
+/// The votes and the vetoes are tallied independently.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct GovernancePolicy {
+    /// The threshold for the agendas of a type without an override.
+    pub default_threshold: GovernanceThreshold,
+    /// The per-agenda-type overrides of `default_threshold`.
+    pub thresholds: BTreeMap<AgendaType, GovernanceThreshold>,
+    /// The veto thresholds of the agenda types that can be vetoed.
+    #[serde(default)]
+    pub veto_thresholds: BTreeMap<AgendaType, GovernanceThreshold>,
+}
+
+impl Default for GovernancePolicy {
+    fn default() -> Self {
+        Self {
+            default_threshold: GovernanceThreshold::SIMPLE_MAJORITY,
+            thresholds: BTreeMap::new(),
+            veto_thresholds: BTreeMap::new(),
+        }
+    }
+}
+
+impl GovernancePolicy {
+    pub fn get_threshold(&self, agenda_type: AgendaType) -> GovernanceThreshold {
+        self.thresholds
+            .get(&agenda_type)
+            .copied()
+            .unwrap_or(self.default_threshold)
+    }
+
+    /// Returns the veto threshold of the given agenda type, or `None` if it can't be vetoed.
+    pub fn get_veto_threshold(&self, agenda_type: AgendaType) -> Option<GovernanceThreshold> {
+        self.veto_thresholds.get(&agenda_type).copied()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct ChatLog {
     // TODO
@@ -115,13 +206,18 @@ pub enum Diff {
     /// Nothing changed in the repository; an empty commit.
     None,
     /// Changes the reserved area ONLY.
-    Reserved(Box<ReservedState>),
+    ///
+    /// It contains the new reserved state and the hash of the reserved state it is based on.
+    Reserved(Box<ReservedState>, Hash256),
     /// Changes the non-reserved area ONLY.
     ///
     /// It contains the hash of the diff.
     NonReserved(Hash256),
     /// General diff that may change both the reserved state and the non-reserved state.
-    General(Box<ReservedState>, Hash256),
+    ///
+    /// It contains the new reserved state, the hash of the diff
+    /// and the hash of the reserved state it is based on.
+    General(Box<ReservedState>, Hash256, Hash256),
 }
 
 /// A general transaction to be included in the agenda.
@@ -134,6 +230,7 @@ pub enum Diff {
 /// (if not, it will be rejected by the node)
 /// - `head` and `body` might be used for the trustless message delivery.
 /// Please refer to the *simperby-settlement* crate.
+/// - `author_signature` is optional; if present, it is verified against the `author`.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct Transaction {
     pub author: MemberName,
@@ -141,6 +238,9 @@ pub struct Transaction {
     pub head: String,
     pub body: String,
     pub diff: Diff,
+    /// The signature of the author on `to_sign_target()` of this transaction.
+    #[serde(default)]
+    pub author_signature: Option<TypedSignature<Transaction>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -162,9 +262,29 @@ pub struct TxUndelegate {
     pub proof: TypedSignature<UndelegationTransactionData>,
 }
 
+/// A report of a validator that misbehaved during the consensus of a height.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct TxReport {
-    // TODO
+    /// The height of the block that the consensus was performed for.
+    pub height: BlockHeight,
+    /// The round in which the misbehavior is committed.
+    pub round: ConsensusRound,
+    /// The misbehaving validator.
+    pub byzantine_node: PublicKey,
+    pub misbehavior: ReportedMisbehavior,
+}
+
+/// The misbehavior of a validator, with the block hashes involved.
+///
+/// `None` stands for a nil vote.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum ReportedMisbehavior {
+    DoubleProposal(Hash256, Hash256),
+    DoublePrevote(Option<Hash256>, Option<Hash256>),
+    DoublePrecommit(Option<Hash256>, Option<Hash256>),
+    InvalidProposal(Hash256),
+    InvalidPrevote(Hash256),
+    InvalidPrecommit(Hash256),
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -203,6 +323,30 @@ pub enum Commit {
     ChatLog(ChatLog),
 }
 
+/// The kind of a `Commit`, regardless of its content.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum CommitKind {
+    Block,
+    Transaction,
+    Agenda,
+    AgendaProof,
+    ExtraAgendaTransaction,
+    ChatLog,
+}
+
+impl Commit {
+    pub fn kind(&self) -> CommitKind {
+        match self {
+            Commit::Block(_) => CommitKind::Block,
+            Commit::Transaction(_) => CommitKind::Transaction,
+            Commit::Agenda(_) => CommitKind::Agenda,
+            Commit::AgendaProof(_) => CommitKind::AgendaProof,
+            Commit::ExtraAgendaTransaction(_) => CommitKind::ExtraAgendaTransaction,
+            Commit::ChatLog(_) => CommitKind::ChatLog,
+        }
+    }
+}
+
 /// The special finalization proof commit in the `fp` branch.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct LastFinalizationProof {
@@ -219,6 +363,13 @@ impl CommitHash {
     pub fn zero() -> Self {
         Self { hash: [0; 20] }
     }
+
+    /// Returns the first `digits` hexadecimal digits of the hash (or all of them if fewer).
+    pub fn short(&self, digits: usize) -> String {
+        let mut short = self.to_string();
+        short.truncate(digits);
+        short
+    }
 }
 
 impl ToHash256 for CommitHash {
@@ -268,9 +419,26 @@ pub struct FinalizationInfo {
 
 #[cfg(test)]
 mod tests {
-    use super::CommitHash;
+    use super::*;
     use serde_json::{from_str, to_string};
 
+    #[test]
+    fn short_commit_hash() {
+        let commit_hash = CommitHash { hash: [0xab; 20] };
+        assert_eq!(commit_hash.short(8), "abababab");
+        assert_eq!(commit_hash.short(100), commit_hash.to_string());
+        // The full form decodes back to the hash, and the short form is the prefix of it.
+        let mut hash = [0; 20];
+        hash[..4].copy_from_slice(&[0x12, 0x34, 0x56, 0x78]);
+        hash[19] = 0x9a;
+        let commit_hash = CommitHash { hash };
+        let full = commit_hash.to_string();
+        assert_eq!(commit_hash.short(8), "12345678");
+        assert!(full.starts_with(&commit_hash.short(8)));
+        let decoded: CommitHash = from_str(&format!("\"{full}\"")).unwrap();
+        assert_eq!(decoded, commit_hash);
+    }
+
     #[test]
     fn en_decode_commit_hash() {
         let commit_hash = CommitHash { hash: [1; 20] };
@@ -279,4 +447,53 @@ mod tests {
         let deserialized: CommitHash = from_str(&serialized).unwrap();
         assert_eq!(deserialized, commit_hash);
     }
+
+    #[test]
+    fn commit_kind() {
+        let (fi, _) = crate::test_utils::generate_fi(4);
+        let agenda = Agenda {
+            height: 1,
+            author: "member-0000".to_owned(),
+            timestamp: 0,
+            transactions_hash: Hash256::zero(),
+            previous_block_hash: fi.header.to_hash256(),
+        };
+        let commits = vec![
+            (Commit::Block(fi.header.clone()), CommitKind::Block),
+            (
+                Commit::Transaction(Transaction {
+                    author: "member-0000".to_owned(),
+                    timestamp: 0,
+                    head: "abc".to_owned(),
+                    body: "def".to_owned(),
+                    diff: Diff::None,
+                    author_signature: None,
+                }),
+                CommitKind::Transaction,
+            ),
+            (Commit::Agenda(agenda.clone()), CommitKind::Agenda),
+            (
+                Commit::AgendaProof(AgendaProof {
+                    height: 1,
+                    agenda_hash: agenda.to_hash256(),
+                    proof: Vec::new(),
+                    timestamp: 0,
+                }),
+                CommitKind::AgendaProof,
+            ),
+            (
+                Commit::ExtraAgendaTransaction(ExtraAgendaTransaction::Report(TxReport {
+                    height: 1,
+                    round: 0,
+                    byzantine_node: PublicKey::zero(),
+                    misbehavior: ReportedMisbehavior::InvalidProposal(Hash256::zero()),
+                })),
+                CommitKind::ExtraAgendaTransaction,
+            ),
+            (Commit::ChatLog(ChatLog {}), CommitKind::ChatLog),
+        ];
+        for (commit, kind) in commits {
+            assert_eq!(commit.kind(), kind);
+        }
+    }
 }
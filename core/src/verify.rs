@@ -20,9 +20,9 @@ pub enum Error {
 ///
 /// Note that you still need to verify
 /// 1. block body (other commits)
-/// 2. finalization proof
-/// 3. protocol version of the node binary.
+/// 2. finalization proof.
 pub fn verify_header_to_header(h1: &BlockHeader, h2: &BlockHeader) -> Result<(), Error> {
+    verify_protocol_version(&h2.version)?;
     if h2.height != h1.height + 1 {
         return Err(Error::InvalidArgument(format!(
             "invalid height: expected {}, got {}",
@@ -57,7 +57,69 @@ pub fn verify_header_to_header(h1: &BlockHeader, h2: &BlockHeader) -> Result<(),
     Ok(())
 }
 
+/// Verifies whether the given protocol version is compatible with
+/// `SIMPERBY_CORE_PROTOCOL_VERSION` of this node.
+///
+/// Two versions are compatible if their leftmost non-zero components
+/// (major, or minor/patch for `0.x` versions) are the same, following the semver convention.
+pub fn verify_protocol_version(version: &str) -> Result<(), Error> {
+    let ours = semver::Version::parse(SIMPERBY_CORE_PROTOCOL_VERSION)
+        .expect("the protocol version of the node must be a valid semver");
+    let theirs = semver::Version::parse(version)
+        .map_err(|e| Error::InvalidArgument(format!("invalid protocol version {version}: {e}")))?;
+    let compatible = match (ours.major, ours.minor) {
+        (0, 0) => theirs.major == 0 && theirs.minor == 0 && theirs.patch == ours.patch,
+        (0, minor) => theirs.major == 0 && theirs.minor == minor,
+        (major, _) => theirs.major == major,
+    };
+    if !compatible {
+        return Err(Error::InvalidArgument(format!(
+            "incompatible protocol version: expected compatible with {}, got {}",
+            SIMPERBY_CORE_PROTOCOL_VERSION, version
+        )));
+    }
+    Ok(())
+}
+
+/// Assigns the indices (as the consensus state machine refers to the validators)
+/// to the validators of the given block, in the order of `validator_set`.
+///
+/// The consensus follows the leader order, which may differ from the order of
+/// `header.validator_set`, so it fails unless `validator_set` has exactly
+/// the same validators and voting powers as the header.
+/// This makes sure that an index always refers to a validator who can sign
+/// the finalization proof of the block.
+pub fn validator_index_map(
+    header: &BlockHeader,
+    validator_set: &[(PublicKey, VotingPower)],
+) -> Result<(Vec<PublicKey>, HashMap<PublicKey, usize>), Error> {
+    let mut indices = HashMap::new();
+    for (index, (public_key, _)) in validator_set.iter().enumerate() {
+        if indices.insert(public_key.clone(), index).is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "duplicate validator: {public_key}"
+            )));
+        }
+    }
+    let mut expected = header.validator_set.clone();
+    let mut actual = validator_set.to_vec();
+    expected.sort();
+    actual.sort();
+    if expected != actual {
+        return Err(Error::InvalidArgument(
+            "the validator set doesn't match the one of the block header".to_owned(),
+        ));
+    }
+    let public_keys = validator_set
+        .iter()
+        .map(|(public_key, _)| public_key.clone())
+        .collect();
+    Ok((public_keys, indices))
+}
+
 /// Verifies the finalization proof of the given block header.
+///
+/// The proof is rejected if it contains a signer twice or a signer not in the validator set.
 pub fn verify_finalization_proof(
     header: &BlockHeader,
     block_finalization_proof: &FinalizationProof,
@@ -71,7 +133,22 @@ pub fn verify_finalization_proof(
                 round: block_finalization_proof.round,
             })
             .map_err(|e| Error::CryptoError("invalid finalization proof".to_string(), e))?;
-        voted_validators.insert(signature.signer());
+        if !header
+            .validator_set
+            .iter()
+            .any(|(v, _)| v == signature.signer())
+        {
+            return Err(Error::InvalidProof(format!(
+                "invalid finalization proof - {} is not in the validator set",
+                signature.signer()
+            )));
+        }
+        if !voted_validators.insert(signature.signer()) {
+            return Err(Error::InvalidProof(format!(
+                "invalid finalization proof - duplicate signer {}",
+                signature.signer()
+            )));
+        }
     }
     let voted_voting_power: VotingPower = header
         .validator_set
@@ -101,6 +178,7 @@ enum Phase {
     // The agenda phase.
     Agenda {
         agenda: Agenda,
+        agenda_type: AgendaType,
     },
     // The agenda proof phase.
     AgendaProof {
@@ -116,6 +194,17 @@ enum Phase {
     Block,
 }
 
+/// The verification status of a block commit applied by `CommitSequenceVerifier::verify_block_range()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockVerification {
+    pub header: BlockHeader,
+    /// The index of the block commit in the given sequence.
+    pub index: usize,
+    /// Whether the finalization of the block has been verified
+    /// by the finalization proof in the following block.
+    pub finalized: bool,
+}
+
 /// Verifies whether the given sequence of commits can be a partial sequence of a valid finalized chain.
 ///
 /// It may accept sequences that contain more than one `BlockHeader`.
@@ -124,6 +213,9 @@ pub struct CommitSequenceVerifier {
     header: BlockHeader,
     phase: Phase,
     reserved_state: ReservedState,
+    /// The reserved state as of the last block header, which decides the governance set
+    /// for the agenda of the next block (regardless of the reserved-diff transactions before it).
+    reserved_state_at_header: ReservedState,
     commits_for_next_block: Vec<Commit>,
     total_commits: Vec<Commit>,
 }
@@ -134,6 +226,7 @@ impl CommitSequenceVerifier {
         Ok(Self {
             header: start_header.clone(),
             phase: Phase::Block,
+            reserved_state_at_header: reserved_state.clone(),
             reserved_state,
             commits_for_next_block: vec![],
             total_commits: vec![Commit::Block(start_header)],
@@ -153,6 +246,15 @@ impl CommitSequenceVerifier {
         &self.reserved_state
     }
 
+    /// Returns whether an `ExtraAgendaTransaction` can be applied in the current phase,
+    /// which is after an agenda proof.
+    pub fn is_extra_agenda_transaction_allowed(&self) -> bool {
+        matches!(
+            self.phase,
+            Phase::AgendaProof { .. } | Phase::ExtraAgendaTransaction { .. }
+        )
+    }
+
     /// Returns the block headers received so far, with the index of the commit.
     ///
     /// It returns `[start_header]` if no block header has been received.
@@ -177,63 +279,11 @@ impl CommitSequenceVerifier {
 
     /// Verifies whether the given reserved state is valid from the current state.
     pub fn verify_reserved_state(&self, rs: &ReservedState) -> Result<(), Error> {
-        // Check that the number of members is at least 4.
-        if rs.members.len() < 4 {
-            return Err(Error::InvalidArgument(
-                "the number of members is less than 4".to_string(),
-            ));
-        }
-        // Check that `consensus_leader_order` is correct.
-        // 1. consensus_leader_order should be the subset of members.
-        // 2. every consensus leader should not be expelled.
-        // 3. consensus_leader_order should consist of more than 1 unique members to avoid a SPoF.
-        let valid_leader_candidates: HashSet<&MemberName> = rs
-            .members
-            .iter()
-            .filter(|m| !m.expelled)
-            .map(|m| &m.name)
-            .collect();
-        if !rs
-            .consensus_leader_order
-            .iter()
-            .all(|m| valid_leader_candidates.contains(m))
-        {
-            return Err(Error::InvalidArgument(
-                "Some consensus leaders are not valid candidates".to_string(),
-            ));
-        }
-        if rs
-            .consensus_leader_order
-            .iter()
-            .collect::<HashSet<&MemberName>>()
-            .len()
-            <= 1
-        {
-            return Err(Error::InvalidArgument(
-                "consensus_leader_order should consist of more than 1 unique members".to_string(),
-            ));
-        }
+        rs.verify_structure()?;
         // Check that `genesis_info` stays the same.
         if rs.genesis_info != self.reserved_state.genesis_info {
             return Err(Error::InvalidArgument("genesis_info changes".to_string()));
         }
-        // Check that `Member::name` and `Member::public_key` are unique.
-        let mut member_names = HashSet::new();
-        let mut public_keys = HashSet::new();
-        for member in &rs.members {
-            if !member_names.insert(&member.name) {
-                return Err(Error::InvalidArgument(format!(
-                    "member name '{}' already exists",
-                    member.name
-                )));
-            }
-            if !public_keys.insert(&member.public_key) {
-                return Err(Error::InvalidArgument(format!(
-                    "the public key of '{}' already exists",
-                    member.name
-                )));
-            }
-        }
         // Check that `member` monotonically increases (refer to `Member::expelled`).
         // Once a member is added, it cannot be removed, even if it is expelled.
         let member_names: HashSet<String> = rs.members.iter().map(|m| m.name.clone()).collect();
@@ -248,6 +298,78 @@ impl CommitSequenceVerifier {
         Ok(())
     }
 
+    /// Verifies the author signature of the transaction, if it is signed.
+    fn verify_transaction_signature(&self, tx: &Transaction) -> Result<(), Error> {
+        if let Some(signature) = &tx.author_signature {
+            let author = self
+                .reserved_state
+                .query_public_key(&tx.author)
+                .ok_or_else(|| {
+                    Error::InvalidArgument(format!(
+                        "the author of the signed transaction is not a member: {}",
+                        tx.author
+                    ))
+                })?;
+            if *signature.signer() != author {
+                return Err(Error::InvalidArgument(format!(
+                    "the transaction is signed by {}, not by its author {}",
+                    signature.signer(),
+                    tx.author
+                )));
+            }
+            signature.verify(&tx.to_sign_target()).map_err(|e| {
+                Error::CryptoError("invalid transaction author signature".to_string(), e)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Verifies the reserved state of the given diff, if any, returning it.
+    ///
+    /// The diff must be based on the current reserved state,
+    /// which may have been modified by the preceding transactions of the same block.
+    fn verify_reserved_diff(&self, diff: &Diff) -> Result<Option<ReservedState>, Error> {
+        let (rs, base) = match diff {
+            Diff::Reserved(rs, base) | Diff::General(rs, _, base) => (rs, base),
+            Diff::None | Diff::NonReserved(_) => return Ok(None),
+        };
+        let current = self.reserved_state.to_hash256();
+        if *base != current {
+            return Err(Error::InvalidArgument(format!(
+                "reserved-diff is based on a stale reserved state: expected {current}, got {base}"
+            )));
+        }
+        self.verify_reserved_state(rs)?;
+        Ok(Some(*rs.clone()))
+    }
+
+    /// Applies the given sequence of commits that may span several heights,
+    /// and returns the verification status of each block commit in the sequence.
+    ///
+    /// Every block but the last one is fully verified, since its finalization proof
+    /// is included in the following block.
+    /// The last one can be verified with `verify_last_header_finalization()`.
+    pub fn verify_block_range(
+        &mut self,
+        commits: &[Commit],
+    ) -> Result<Vec<BlockVerification>, Error> {
+        let mut result: Vec<BlockVerification> = Vec::new();
+        for (index, commit) in commits.iter().enumerate() {
+            self.apply_commit(commit)?;
+            if let Commit::Block(header) = commit {
+                if let Some(last) = result.last_mut() {
+                    last.finalized = true;
+                }
+                result.push(BlockVerification {
+                    header: header.clone(),
+                    index,
+                    finalized: false,
+                });
+            }
+        }
+        Ok(result)
+    }
+
     /// Verifies the given commit and updates the internal reserved_state of CommitSequenceVerifier.
     pub fn apply_commit(&mut self, commit: &Commit) -> Result<(), Error> {
         match (commit, &self.phase) {
@@ -264,6 +386,7 @@ impl CommitSequenceVerifier {
                 };
                 self.header = block_header.clone();
                 self.phase = Phase::Block;
+                self.reserved_state_at_header = self.reserved_state.clone();
                 self.commits_for_next_block = vec![];
             }
             (
@@ -291,16 +414,14 @@ impl CommitSequenceVerifier {
                 };
                 self.header = block_header.clone();
                 self.phase = Phase::Block;
+                self.reserved_state_at_header = self.reserved_state.clone();
                 self.commits_for_next_block = vec![];
             }
             (Commit::Transaction(tx), Phase::Block) => {
+                self.verify_transaction_signature(tx)?;
                 // Update reserved_state for reserved-diff transactions.
-                if let Diff::Reserved(rs) = &tx.diff {
-                    self.verify_reserved_state(rs)?;
-                    self.reserved_state = *rs.clone();
-                } else if let Diff::General(rs, _) = &tx.diff {
-                    self.verify_reserved_state(rs)?;
-                    self.reserved_state = *rs.clone();
+                if let Some(rs) = self.verify_reserved_diff(&tx.diff)? {
+                    self.reserved_state = rs;
                 }
                 self.phase = Phase::Transaction {
                     last_transaction: tx.clone(),
@@ -321,13 +442,10 @@ impl CommitSequenceVerifier {
                         last_transaction.timestamp, tx.timestamp
                     )));
                 }
+                self.verify_transaction_signature(tx)?;
                 // Update reserved_state for reserved-diff transactions.
-                if let Diff::Reserved(rs) = &tx.diff {
-                    self.verify_reserved_state(rs)?;
-                    self.reserved_state = *rs.clone();
-                } else if let Diff::General(rs, _) = &tx.diff {
-                    self.verify_reserved_state(rs)?;
-                    self.reserved_state = *rs.clone();
+                if let Some(rs) = self.verify_reserved_diff(&tx.diff)? {
+                    self.reserved_state = rs;
                 }
                 let mut preceding_transactions = preceding_transactions.clone();
                 preceding_transactions.push(last_transaction.clone());
@@ -363,6 +481,7 @@ impl CommitSequenceVerifier {
                 }
                 self.phase = Phase::Agenda {
                     agenda: agenda.clone(),
+                    agenda_type: AgendaType::General,
                 };
             }
             (
@@ -409,9 +528,16 @@ impl CommitSequenceVerifier {
                 }
                 self.phase = Phase::Agenda {
                     agenda: agenda.clone(),
+                    agenda_type: AgendaType::from_transactions(&transactions),
                 };
             }
-            (Commit::AgendaProof(agenda_proof), Phase::Agenda { agenda }) => {
+            (
+                Commit::AgendaProof(agenda_proof),
+                Phase::Agenda {
+                    agenda,
+                    agenda_type,
+                },
+            ) => {
                 // Check if agenda proof is associated with the current block sequence.
                 if agenda_proof.height != self.header.height + 1 {
                     return Err(Error::InvalidArgument(format!(
@@ -434,9 +560,10 @@ impl CommitSequenceVerifier {
                         Error::CryptoError("invalid agenda proof: invalid signature".to_string(), e)
                     })?;
                 }
-                // Check if the agenda proof is signed by the majority of the governance participants
+                // Check if the agenda proof meets the governance threshold for its agenda type,
+                // as of the last block; members added by the agenda's own transactions don't count.
                 let governance_set = self
-                    .reserved_state
+                    .reserved_state_at_header
                     .get_governance_set()
                     .unwrap()
                     .into_iter()
@@ -458,7 +585,12 @@ impl CommitSequenceVerifier {
                     .collect::<Result<Vec<_>, Error>>()?
                     .iter()
                     .sum::<u64>();
-                if signed_weight * 2 <= total_weight {
+                if !self
+                    .reserved_state_at_header
+                    .governance_policy
+                    .get_threshold(*agenda_type)
+                    .is_met(signed_weight, total_weight)
+                {
                     return Err(Error::InvalidArgument(
                         "invalid agenda proof: insufficient signed weight".to_string(),
                     ));
@@ -538,7 +670,10 @@ impl CommitSequenceVerifier {
                 ));
             }
         }
-        self.commits_for_next_block.push(commit.clone());
+        // A block commit starts a new block body, so it is not a part of the next block.
+        if !matches!(commit, Commit::Block(_)) {
+            self.commits_for_next_block.push(commit.clone());
+        }
         self.total_commits.push(commit.clone());
         Ok(())
     }
@@ -636,6 +771,7 @@ mod test {
             members, // TODO: fix to not use genesis header
             consensus_leader_order,
             version: SIMPERBY_CORE_PROTOCOL_VERSION.to_string(),
+            governance_policy: GovernancePolicy::default(),
         }
     }
 
@@ -646,6 +782,7 @@ mod test {
             head: "Test empty commit".to_string(),
             body: "This is important!".to_string(),
             diff: Diff::None,
+            author_signature: None,
         })
     }
 
@@ -662,6 +799,7 @@ mod test {
             }))
             .unwrap(),
             diff: Diff::NonReserved(Hash256::hash("The actual content of the diff".as_bytes())),
+            author_signature: None,
         })
     }
 
@@ -671,6 +809,7 @@ mod test {
         seed: u8,
         time: Timestamp,
     ) -> Commit {
+        let base = reserved_state.to_hash256();
         // Update reserved reserved_state
         validator_keypair.push(generate_keypair([seed]));
         let new_member_name = format!("member{}", validator_keypair.len() - 1);
@@ -690,7 +829,8 @@ mod test {
             timestamp: time,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state.clone())),
+            diff: Diff::Reserved(Box::new(reserved_state.clone()), base),
+            author_signature: None,
         })
     }
 
@@ -700,6 +840,7 @@ mod test {
         seed: u8,
         time: Timestamp,
     ) -> Commit {
+        let base = reserved_state.to_hash256();
         // Update reserved reserved_state
         validator_keypair.push(generate_keypair([seed]));
         let new_member_name = format!("member{}", validator_keypair.len() - 1);
@@ -728,7 +869,9 @@ mod test {
             diff: Diff::General(
                 Box::new(reserved_state.clone()),
                 Hash256::hash("The actual content of the diff".as_bytes()),
+                base,
             ),
+            author_signature: None,
         })
     }
 
@@ -900,9 +1043,118 @@ mod test {
             previous_block_hash: csv.header.to_hash256(),
         };
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
-        // Apply agenda-proof commit
+        // Apply agenda-proof commit, signed by the members as of the last block
         csv.apply_commit(&generate_agenda_proof_commit(
-            &validator_keypair,
+            &validator_keypair[..4],
+            &agenda,
+            agenda.to_hash256(),
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    /// Test the case where members are added by the transactions of an agenda.
+    /// The agenda proof must meet the threshold of the governance set before the change.
+    fn agenda_proof_with_governance_set_before_change() {
+        let (mut validator_keypair, mut reserved_state, mut csv) = setup_test(4);
+        // Add two members, making the set of 6 members
+        csv.apply_commit(&generate_reserved_diff_transaction_commit(
+            &mut validator_keypair,
+            &mut reserved_state,
+            4,
+            1,
+        ))
+        .unwrap();
+        csv.apply_commit(&generate_reserved_diff_transaction_commit(
+            &mut validator_keypair,
+            &mut reserved_state,
+            5,
+            2,
+        ))
+        .unwrap();
+        let agenda_transactions_hash = calculate_agenda_transactions_hash(csv.phase.clone());
+        let agenda: Agenda = Agenda {
+            author: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+            timestamp: 3,
+            transactions_hash: agenda_transactions_hash,
+            height: csv.header.height + 1,
+            previous_block_hash: csv.header.to_hash256(),
+        };
+        csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
+        // The new members are not in the governance set for this agenda.
+        csv.clone()
+            .apply_commit(&generate_agenda_proof_commit(
+                &validator_keypair[2..],
+                &agenda,
+                agenda.to_hash256(),
+            ))
+            .unwrap_err();
+        // 3 of the 4 previous members is a majority, while it wouldn't be of the 6 members.
+        csv.apply_commit(&generate_agenda_proof_commit(
+            &validator_keypair[..3],
+            &agenda,
+            agenda.to_hash256(),
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    /// Test the case where the agenda proof meets a simple majority
+    /// but not the threshold of the governance policy for its agenda type.
+    fn invalid_agenda_proof_with_insufficient_threshold_for_agenda_type() {
+        let (mut validator_keypair, mut reserved_state, csv) = setup_test(5);
+        reserved_state.governance_policy.thresholds.insert(
+            AgendaType::ReservedStateChange,
+            GovernanceThreshold::SUPER_MAJORITY,
+        );
+        let mut csv =
+            CommitSequenceVerifier::new(csv.get_header().clone(), reserved_state.clone()).unwrap();
+
+        // A general agenda only needs a simple majority, which 3 of 5 is.
+        let agenda = Agenda {
+            author: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+            timestamp: 1,
+            transactions_hash: Agenda::calculate_transactions_hash(&[]),
+            height: csv.header.height + 1,
+            previous_block_hash: csv.header.to_hash256(),
+        };
+        let mut general_csv = csv.clone();
+        general_csv
+            .apply_commit(&generate_agenda_commit(&agenda))
+            .unwrap();
+        general_csv
+            .apply_commit(&generate_agenda_proof_commit(
+                &validator_keypair[..3],
+                &agenda,
+                agenda.to_hash256(),
+            ))
+            .unwrap();
+
+        // An agenda changing the reserved state needs a supermajority, which 3 of 5 isn't.
+        csv.apply_commit(&generate_reserved_diff_transaction_commit(
+            &mut validator_keypair,
+            &mut reserved_state,
+            10,
+            1,
+        ))
+        .unwrap();
+        let agenda = Agenda {
+            author: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+            timestamp: 2,
+            transactions_hash: calculate_agenda_transactions_hash(csv.phase.clone()),
+            height: csv.header.height + 1,
+            previous_block_hash: csv.header.to_hash256(),
+        };
+        csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
+        csv.clone()
+            .apply_commit(&generate_agenda_proof_commit(
+                &validator_keypair[..3],
+                &agenda,
+                agenda.to_hash256(),
+            ))
+            .unwrap_err();
+        csv.apply_commit(&generate_agenda_proof_commit(
+            &validator_keypair[..4],
             &agenda,
             agenda.to_hash256(),
         ))
@@ -932,6 +1184,119 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    /// Test the case where the commit sequence spans two blocks.
+    fn correct_commit_sequence_with_two_blocks() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        let mut commits = Vec::new();
+        let mut previous_header = csv.header.clone();
+        for height in 1..=2 {
+            let agenda = Agenda {
+                author: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+                timestamp: height as Timestamp,
+                transactions_hash: Agenda::calculate_transactions_hash(&[]),
+                height,
+                previous_block_hash: previous_header.to_hash256(),
+            };
+            let body = vec![
+                generate_agenda_commit(&agenda),
+                generate_agenda_proof_commit(&validator_keypair, &agenda, agenda.to_hash256()),
+            ];
+            let block = generate_block_commit(
+                &validator_keypair,
+                0,
+                previous_header.clone(),
+                height as Timestamp,
+                BlockHeader::calculate_commit_merkle_root(&body),
+                Hash256::zero(),
+            );
+            previous_header = match &block {
+                Commit::Block(header) => header.clone(),
+                _ => unreachable!(),
+            };
+            commits.extend(body);
+            commits.push(block);
+        }
+
+        let result = csv.verify_block_range(&commits).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            result
+                .iter()
+                .map(|x| (x.header.height, x.index, x.finalized))
+                .collect::<Vec<_>>(),
+            vec![(1, 2, true), (2, 5, false)]
+        );
+        assert_eq!(csv.get_header(), &previous_header);
+        assert_eq!(csv.get_block_headers().len(), 3);
+        csv.verify_last_header_finalization(&generate_unanimous_finalization_proof(
+            &validator_keypair,
+            &previous_header,
+            0,
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn validator_index_map_in_leader_order() {
+        let (validator_keypair, _, csv) = setup_test(4);
+        let header = csv.get_header();
+        let mut validator_set = header.validator_set.clone();
+        validator_set.reverse();
+        let (public_keys, indices) = validator_index_map(header, &validator_set).unwrap();
+        for (index, (public_key, _)) in validator_set.iter().enumerate() {
+            assert_eq!(&public_keys[index], public_key);
+            assert_eq!(indices[public_key], index);
+        }
+        assert_eq!(public_keys[0], validator_keypair[3].0);
+
+        // The validator set must be the one of the header.
+        let mut duplicated = validator_set.clone();
+        duplicated[0] = duplicated[1].clone();
+        assert!(validator_index_map(header, &duplicated).is_err());
+        let mut reweighted = validator_set.clone();
+        reweighted[0].1 += 1;
+        assert!(validator_index_map(header, &reweighted).is_err());
+        assert!(validator_index_map(header, &validator_set[1..]).is_err());
+    }
+
+    #[test]
+    /// Test the case where the finalization proof contains the same signer twice.
+    fn invalid_finalization_proof_with_duplicate_signer() {
+        let (validator_keypair, _, csv) = setup_test(4);
+        let mut proof =
+            generate_unanimous_finalization_proof(&validator_keypair, csv.get_header(), 0);
+        csv.verify_last_header_finalization(&proof).unwrap();
+        proof.signatures.push(proof.signatures[0].clone());
+        assert!(matches!(
+            csv.verify_last_header_finalization(&proof).unwrap_err(),
+            Error::InvalidProof(_)
+        ));
+    }
+
+    #[test]
+    /// Test the case where the finalization proof contains a signer not in the validator set.
+    fn invalid_finalization_proof_with_non_validator_signer() {
+        let (validator_keypair, _, csv) = setup_test(4);
+        let mut proof =
+            generate_unanimous_finalization_proof(&validator_keypair, csv.get_header(), 0);
+        let (_, non_validator_key) = generate_keypair([100]);
+        proof.signatures.push(
+            TypedSignature::sign(
+                &FinalizationSignTarget {
+                    round: 0,
+                    block_hash: csv.get_header().to_hash256(),
+                },
+                &non_validator_key,
+            )
+            .unwrap(),
+        );
+        assert!(matches!(
+            csv.verify_last_header_finalization(&proof).unwrap_err(),
+            Error::InvalidProof(_)
+        ));
+    }
+
     #[test]
     /// Test the case where the block commit is invalid because the block height is invalid.
     fn invalid_block_commit_with_invalid_height() {
@@ -977,6 +1342,57 @@ mod test {
         .unwrap_err();
     }
 
+    #[test]
+    /// Test the case where the block commit is invalid because of an incompatible protocol version,
+    /// while a compatible patch version is accepted.
+    fn block_commit_with_protocol_version() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        // Apply agenda commit
+        let agenda_transactions_hash = calculate_agenda_transactions_hash(csv.phase.clone());
+        let agenda: Agenda = Agenda {
+            author: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+            timestamp: 1,
+            transactions_hash: agenda_transactions_hash,
+            height: csv.header.height + 1,
+            previous_block_hash: csv.header.to_hash256(),
+        };
+        csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
+        // Apply agenda-proof commit
+        csv.apply_commit(&generate_agenda_proof_commit(
+            &validator_keypair,
+            &agenda,
+            agenda.to_hash256(),
+        ))
+        .unwrap();
+        let header = match generate_block_commit(
+            &validator_keypair,
+            0,
+            csv.header.clone(),
+            2,
+            BlockHeader::calculate_commit_merkle_root(&csv.commits_for_next_block),
+            Hash256::zero(),
+        ) {
+            Commit::Block(header) => header,
+            _ => unreachable!(),
+        };
+        let block_with_version = |version: semver::Version| {
+            Commit::Block(BlockHeader {
+                version: version.to_string(),
+                ..header.clone()
+            })
+        };
+        let ours = semver::Version::parse(SIMPERBY_CORE_PROTOCOL_VERSION).unwrap();
+        // Apply block commit with an incompatible major version
+        let mut incompatible = ours.clone();
+        incompatible.major += 1;
+        csv.apply_commit(&block_with_version(incompatible))
+            .unwrap_err();
+        // Apply block commit with a compatible patch version
+        let mut compatible = ours;
+        compatible.patch += 1;
+        csv.apply_commit(&block_with_version(compatible)).unwrap();
+    }
+
     #[test]
     /// Test the case where the block commit is invalid because the previous hash is invalid.
     fn invalid_block_commit_with_invalid_previous_hash() {
@@ -1750,7 +2166,11 @@ mod test {
             timestamp: 3,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state)),
+            diff: Diff::Reserved(
+                Box::new(reserved_state),
+                csv.get_reserved_state().to_hash256(),
+            ),
+            author_signature: None,
         }))
         .unwrap_err();
     }
@@ -1769,7 +2189,11 @@ mod test {
             timestamp: 3,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state.clone())),
+            diff: Diff::Reserved(
+                Box::new(reserved_state.clone()),
+                csv.get_reserved_state().to_hash256(),
+            ),
+            author_signature: None,
         }))
         .unwrap_err();
     }
@@ -1786,7 +2210,11 @@ mod test {
             timestamp: 3,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state)),
+            diff: Diff::Reserved(
+                Box::new(reserved_state),
+                csv.get_reserved_state().to_hash256(),
+            ),
+            author_signature: None,
         }))
         .unwrap_err();
     }
@@ -1827,7 +2255,11 @@ mod test {
             timestamp: 3,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state)),
+            diff: Diff::Reserved(
+                Box::new(reserved_state),
+                csv.get_reserved_state().to_hash256(),
+            ),
+            author_signature: None,
         }))
         .unwrap_err();
     }
@@ -1853,7 +2285,11 @@ mod test {
             timestamp: 3,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state.clone())),
+            diff: Diff::Reserved(
+                Box::new(reserved_state.clone()),
+                csv.get_reserved_state().to_hash256(),
+            ),
+            author_signature: None,
         }))
         .unwrap_err();
     }
@@ -1878,11 +2314,79 @@ mod test {
             timestamp: 3,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state.clone())),
+            diff: Diff::Reserved(
+                Box::new(reserved_state.clone()),
+                csv.get_reserved_state().to_hash256(),
+            ),
+            author_signature: None,
         }))
         .unwrap_err();
     }
 
+    #[test]
+    /// Test the case where the consensus delegations form a cycle.
+    fn invalid_reserved_state_with_consensus_delegation_cycle() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        // Make member0 and member1 delegate to each other
+        reserved_state.members[0].consensus_delegatee =
+            Some(reserved_state.members[1].name.clone());
+        reserved_state.members[1].consensus_delegatee =
+            Some(reserved_state.members[0].name.clone());
+        // Apply reserved-diff commit to verify the reserved state
+        let error = csv
+            .apply_commit(&Commit::Transaction(Transaction {
+                author: "doesn't matter".to_owned(),
+                timestamp: 3,
+                head: "Test reserved-diff commit".to_string(),
+                body: String::new(),
+                diff: Diff::Reserved(
+                    Box::new(reserved_state),
+                    csv.get_reserved_state().to_hash256(),
+                ),
+                author_signature: None,
+            }))
+            .unwrap_err();
+        assert!(error.to_string().contains("cycle"), "{error}");
+    }
+
+    #[test]
+    /// Test the case where a reserved-diff transaction is based on a stale reserved state.
+    fn invalid_reserved_diff_with_stale_base() {
+        let (mut validator_keypair, mut reserved_state, mut csv) = setup_test(4);
+        let base = reserved_state.clone();
+        csv.apply_commit(&generate_reserved_diff_transaction_commit(
+            &mut validator_keypair,
+            &mut reserved_state,
+            4,
+            3,
+        ))
+        .unwrap();
+        // The second one keeps the member added by the first one, but is based on the original state.
+        let mut new_state = reserved_state.clone();
+        new_state.members[0].governance_voting_power = 2;
+        let error = csv
+            .apply_commit(&Commit::Transaction(Transaction {
+                author: "doesn't matter".to_owned(),
+                timestamp: 4,
+                head: "Test reserved-diff commit".to_string(),
+                body: String::new(),
+                diff: Diff::Reserved(Box::new(new_state.clone()), base.to_hash256()),
+                author_signature: None,
+            }))
+            .unwrap_err();
+        assert!(error.to_string().contains("stale"), "{error}");
+        // The same state is accepted once it is based on the current one.
+        csv.apply_commit(&Commit::Transaction(Transaction {
+            author: "doesn't matter".to_owned(),
+            timestamp: 4,
+            head: "Test reserved-diff commit".to_string(),
+            body: String::new(),
+            diff: Diff::Reserved(Box::new(new_state), reserved_state.to_hash256()),
+            author_signature: None,
+        }))
+        .unwrap();
+    }
+
     #[test]
     /// Test the case where the member names don't monotonically increase.
     fn invalid_reserved_state_with_non_monotonic_increased_member_names() {
@@ -1896,7 +2400,11 @@ mod test {
             timestamp: 3,
             head: "Test reserved-diff commit".to_string(),
             body: String::new(),
-            diff: Diff::Reserved(Box::new(reserved_state.clone())),
+            diff: Diff::Reserved(
+                Box::new(reserved_state.clone()),
+                csv.get_reserved_state().to_hash256(),
+            ),
+            author_signature: None,
         }))
         .unwrap_err();
     }
@@ -2353,6 +2861,38 @@ mod test {
         todo!("Implement this test")
     }
 
+    #[test]
+    /// Test the transactions signed by their authors.
+    fn signed_transaction() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        let mut transaction = Transaction {
+            author: reserved_state.members[0].name.clone(),
+            timestamp: 1,
+            head: "Test signed commit".to_string(),
+            body: "Signed by the author".to_string(),
+            diff: Diff::None,
+            author_signature: None,
+        };
+        transaction.sign(&validator_keypair[0].1).unwrap();
+        csv.apply_commit(&Commit::Transaction(transaction.clone()))
+            .unwrap();
+
+        // Signed by someone other than the author
+        let mut forged = transaction.clone();
+        forged.author = reserved_state.members[1].name.clone();
+        forged.timestamp = 2;
+        forged.sign(&validator_keypair[0].1).unwrap();
+        csv.apply_commit(&Commit::Transaction(forged)).unwrap_err();
+
+        // Modified after being signed
+        let mut tampered = transaction;
+        tampered.timestamp = 2;
+        tampered.sign(&validator_keypair[0].1).unwrap();
+        tampered.body = "Not signed by the author".to_string();
+        csv.apply_commit(&Commit::Transaction(tampered))
+            .unwrap_err();
+    }
+
     // TODO: add test cases where the `Report` extra-agenda transactions are invalid.
     // These test cases are TODO because the `Report` extra-agenda transaction is not implemented yet.
 }
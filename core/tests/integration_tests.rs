@@ -19,6 +19,7 @@ fn basic1() {
         head: "commit 1".to_owned(),
         body: "".to_owned(),
         diff: Diff::None,
+        author_signature: None,
     };
     csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
     let agenda = Agenda {
@@ -103,6 +104,7 @@ fn basic2() {
         head: "commit 1".to_owned(),
         body: "".to_owned(),
         diff: Diff::None,
+        author_signature: None,
     };
     csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
     let agenda = Agenda {
@@ -138,8 +140,15 @@ fn basic2() {
     };
     csv.apply_commit(&Commit::Block(block_header.clone()))
         .unwrap();
+    // The delegators are not in the validator set, so only the validators sign.
     let signatures = keys
         .iter()
+        .filter(|(public_key, _)| {
+            block_header
+                .validator_set
+                .iter()
+                .any(|(validator, _)| validator == public_key)
+        })
         .map(|(_, private_key)| {
             TypedSignature::sign(
                 &FinalizationSignTarget {
@@ -185,6 +194,7 @@ fn basic3() {
         head: "commit 1".to_owned(),
         body: "".to_owned(),
         diff: Diff::None,
+        author_signature: None,
     };
     csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
 
@@ -339,8 +349,9 @@ fn basic3() {
         previous_hash: block_header.to_hash256(),
         height,
         timestamp,
+        // The previous block commit (at index 5) is not a part of this block.
         commit_merkle_root: BlockHeader::calculate_commit_merkle_root(
-            &csv.get_total_commits()[5..],
+            &csv.get_total_commits()[6..],
         ),
         repository_merkle_root: Hash256::zero(),
         validator_set: rs.get_validator_set().unwrap(),
@@ -377,3 +388,236 @@ fn basic3() {
         Some(&(keys[0].0.clone(), 1))
     );
 }
+
+#[test]
+fn commitment_bundle() {
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let genesis_info = rs.genesis_info.clone();
+
+    let mut csv = CommitSequenceVerifier::new(genesis_info.header.clone(), rs.clone()).unwrap();
+    let tx = Transaction {
+        author: "doesn't matter".to_owned(),
+        timestamp: 0,
+        head: "commit 1".to_owned(),
+        body: "".to_owned(),
+        diff: Diff::None,
+        author_signature: None,
+    };
+    csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
+    let agenda = Agenda {
+        height: 1,
+        author: rs.query_name(&keys[0].0).unwrap(),
+        timestamp: 0,
+        transactions_hash: Agenda::calculate_transactions_hash(std::slice::from_ref(&tx)),
+        previous_block_hash: csv.get_header().to_hash256(),
+    };
+    csv.apply_commit(&Commit::Agenda(agenda.clone())).unwrap();
+    csv.apply_commit(&Commit::AgendaProof(AgendaProof {
+        height: 1,
+        agenda_hash: agenda.to_hash256(),
+        proof: keys
+            .iter()
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+            .collect::<Vec<_>>(),
+        timestamp: 0,
+    }))
+    .unwrap();
+    let block_header = BlockHeader {
+        author: keys[0].0.clone(),
+        prev_block_finalization_proof: genesis_info.genesis_proof,
+        previous_hash: csv.get_header().to_hash256(),
+        height: 1,
+        timestamp: 0,
+        commit_merkle_root: BlockHeader::calculate_commit_merkle_root(
+            &csv.get_total_commits()[1..],
+        ),
+        repository_merkle_root: Hash256::zero(),
+        validator_set: genesis_info.header.validator_set.clone(),
+        version: genesis_info.header.version,
+    };
+    csv.apply_commit(&Commit::Block(block_header.clone()))
+        .unwrap();
+    let signatures = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(
+                &FinalizationSignTarget {
+                    block_hash: block_header.to_hash256(),
+                    round: 0,
+                },
+                private_key,
+            )
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let finalization_info = FinalizationInfo {
+        header: block_header,
+        commit_hash: CommitHash::zero(),
+        reserved_state: csv.get_reserved_state().clone(),
+        proof: FinalizationProof {
+            round: 0,
+            signatures,
+        },
+    };
+
+    let commits = csv.get_total_commits();
+    let merkle_tree = OneshotMerkleTree::create(
+        commits[1..=(commits.len() - 2)]
+            .iter()
+            .map(|c| c.to_hash256())
+            .collect(),
+    );
+    let merkle_proof = merkle_tree.create_merkle_proof(tx.to_hash256()).unwrap();
+    let bundle = CommitmentBundle::build(&finalization_info, tx, merkle_proof);
+    assert!(bundle.verify());
+
+    // A bundle with a different transaction must be rejected.
+    let mut tampered = bundle.clone();
+    tampered.transaction.head = "commit 2".to_owned();
+    assert!(!tampered.verify());
+
+    // So is a bundle without enough finalization signatures.
+    let mut tampered = bundle;
+    tampered.proof.signatures.truncate(2);
+    assert!(!tampered.verify());
+}
+
+#[test]
+fn light_client_persistence_and_pruning() {
+    let member_number = 10;
+    let (rs, keys) = test_utils::generate_standard_genesis(member_number);
+    let genesis_info = rs.genesis_info.clone();
+    let genesis_header = rs.genesis_info.header.clone();
+
+    let mut csv = CommitSequenceVerifier::new(genesis_header.clone(), rs.clone()).unwrap();
+    let mut light_client = LightClient::with_max_roots(genesis_header, 2);
+    let mut fp = genesis_info.genesis_proof.clone();
+    let mut block_start = 1;
+    let mut proofs = Vec::new();
+
+    for height in 1..=3 {
+        let tx = Transaction {
+            author: "doesn't matter".to_owned(),
+            timestamp: 0,
+            head: format!("commit {height}"),
+            body: "".to_owned(),
+            diff: Diff::None,
+            author_signature: None,
+        };
+        csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
+        let agenda = Agenda {
+            height,
+            author: rs.query_name(&keys[0].0).unwrap(),
+            timestamp: 0,
+            transactions_hash: Agenda::calculate_transactions_hash(&[tx.clone()]),
+            previous_block_hash: csv.get_header().to_hash256(),
+        };
+        csv.apply_commit(&Commit::Agenda(agenda.clone())).unwrap();
+        csv.apply_commit(&Commit::AgendaProof(AgendaProof {
+            height,
+            agenda_hash: agenda.to_hash256(),
+            proof: keys
+                .iter()
+                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .collect::<Vec<_>>(),
+            timestamp: 0,
+        }))
+        .unwrap();
+        let block_header = BlockHeader {
+            author: keys[0].0.clone(),
+            prev_block_finalization_proof: fp,
+            previous_hash: csv.get_header().to_hash256(),
+            height,
+            timestamp: 0,
+            commit_merkle_root: BlockHeader::calculate_commit_merkle_root(
+                &csv.get_total_commits()[block_start..],
+            ),
+            repository_merkle_root: Hash256::zero(),
+            validator_set: genesis_info.header.validator_set.clone(),
+            version: genesis_info.header.version.clone(),
+        };
+        csv.apply_commit(&Commit::Block(block_header.clone()))
+            .unwrap();
+        let signatures = keys
+            .iter()
+            .map(|(_, private_key)| {
+                TypedSignature::sign(
+                    &FinalizationSignTarget {
+                        block_hash: block_header.to_hash256(),
+                        round: 0,
+                    },
+                    private_key,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        fp = FinalizationProof {
+            round: 0,
+            signatures,
+        };
+        csv.verify_last_header_finalization(&fp).unwrap();
+
+        // Resume from the saved state before every update.
+        light_client = LightClient::from_bytes(&light_client.to_bytes()).unwrap();
+        light_client.update(block_header, fp.clone()).unwrap();
+
+        let commits = csv.get_total_commits();
+        let merkle_tree = OneshotMerkleTree::create(
+            commits[block_start..(commits.len() - 1)]
+                .iter()
+                .map(|c| c.to_hash256())
+                .collect(),
+        );
+        proofs.push((
+            tx.clone(),
+            merkle_tree.create_merkle_proof(tx.to_hash256()).unwrap(),
+        ));
+        block_start = commits.len();
+    }
+
+    assert_eq!(light_client.last_header.height, 3);
+    assert_eq!(light_client.height_offset, 2);
+    assert_eq!(light_client.commit_roots.len(), 2);
+    let (tx, proof) = proofs[0].clone();
+    let error = light_client
+        .check_transaction_commitment(&tx, 1, proof)
+        .unwrap_err();
+    assert!(error.contains("pruned"), "{error}");
+    for (height, (tx, proof)) in proofs.into_iter().enumerate().skip(1) {
+        assert!(light_client.verify_transaction_commitment(&tx, height as u64 + 1, proof));
+    }
+}
+
+#[test]
+fn light_client_without_max_roots() {
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let light_client = LightClient::new(rs.genesis_info.header);
+    // A light client saved before `max_roots` was introduced.
+    let mut saved = serde_json::to_value(&light_client).unwrap();
+    saved.as_object_mut().unwrap().remove("max_roots");
+    let loaded: LightClient = serde_json::from_value(saved).unwrap();
+    assert_eq!(loaded.max_roots, None);
+    assert_eq!(loaded.commit_roots, light_client.commit_roots);
+}
+
+#[test]
+fn light_client_state_non_membership() {
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let used_nonces = (0..10u64)
+        .map(|nonce| Hash256::hash(nonce.to_be_bytes()))
+        .collect::<Vec<_>>();
+    let state = SortedMerkleTree::create(used_nonces.clone());
+    let mut header = rs.genesis_info.header;
+    header.repository_merkle_root = state.root();
+    let light_client = LightClient::new(header.clone());
+
+    let fresh_nonce = Hash256::hash(10u64.to_be_bytes());
+    let proof = state.create_non_membership_proof(fresh_nonce).unwrap();
+    light_client
+        .check_state_non_membership(fresh_nonce, header.height, proof.clone())
+        .unwrap();
+    assert!(!light_client.verify_state_non_membership(fresh_nonce, header.height + 1, proof));
+    for nonce in used_nonces {
+        assert!(state.create_non_membership_proof(nonce).is_none());
+    }
+}
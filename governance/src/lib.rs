@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use simperby_core::utils::get_timestamp;
 use simperby_core::*;
 use simperby_network::*;
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -12,6 +12,34 @@ pub type Error = eyre::Error;
 pub struct GovernanceStatus {
     /// Agenda hashes and their voters.
     pub votes: BTreeMap<Hash256, BTreeMap<PublicKey, Signature>>,
+    /// Agenda hashes and their vetoers.
+    #[serde(default)]
+    pub vetoes: BTreeMap<Hash256, BTreeMap<PublicKey, Signature>>,
+    /// The governance set of this height, with the voting power of each member.
+    pub governance_set: BTreeMap<PublicKey, VotingPower>,
+}
+
+impl GovernanceStatus {
+    /// Returns `(agenda hash, voted power, total power)` for each voted agenda,
+    /// in the order of agenda hashes and without the signatures.
+    ///
+    /// Votes from outside the governance set are not counted.
+    pub fn summary(&self) -> Vec<(Hash256, VotingPower, VotingPower)> {
+        let total_voting_power = self.governance_set.values().sum();
+        self.votes
+            .iter()
+            .map(|(agenda, votes)| {
+                (
+                    *agenda,
+                    votes
+                        .keys()
+                        .filter_map(|voter| self.governance_set.get(voter))
+                        .sum(),
+                    total_voting_power,
+                )
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -26,7 +54,44 @@ impl ToHash256 for Vote {
     }
 }
 
-impl DmsMessage for Vote {
+/// A veto against an agenda, which is tallied separately from the votes
+/// (see `GovernancePolicy` for how it blocks an agenda).
+///
+/// This is distinct from a consensus veto, which is against a block proposal.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct GovernanceVeto {
+    pub agenda_hash: Hash256,
+}
+
+impl ToHash256 for GovernanceVeto {
+    /// Differs from the hash of the vote for the same agenda,
+    /// so that the two are distinct messages in the DMS.
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash("governance-veto").aggregate(&self.agenda_hash)
+    }
+}
+
+impl SignatureDomain for GovernanceVeto {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-governance-veto";
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum GovernanceMessage {
+    Vote(Vote),
+    Veto(GovernanceVeto),
+}
+
+impl ToHash256 for GovernanceMessage {
+    fn to_hash256(&self) -> Hash256 {
+        match self {
+            GovernanceMessage::Vote(vote) => vote.to_hash256(),
+            GovernanceMessage::Veto(veto) => veto.to_hash256(),
+        }
+    }
+}
+
+impl DmsMessage for GovernanceMessage {
     const DMS_TAG: &'static str = "governance";
 
     fn check(&self) -> Result<(), Error> {
@@ -34,6 +99,9 @@ impl DmsMessage for Vote {
     }
 
     /// Agenda hash cryptographically contains the information of height. It's safe to ignore `dms_key`.
+    ///
+    /// The signature of a vote is a signature on the agenda, so that it can be used as is
+    /// for the agenda proof.
     fn commit(
         &self,
         _dms_key: &DmsKey,
@@ -42,7 +110,16 @@ impl DmsMessage for Vote {
     where
         Self: Sized,
     {
-        Signature::sign(self.to_hash256(), private_key).map(|signature| MessageCommitmentProof {
+        let signature = match self {
+            GovernanceMessage::Vote(vote) => {
+                TypedSignature::<Agenda>::sign_hash(vote.agenda_hash, private_key)?
+                    .get_raw_signature()
+            }
+            GovernanceMessage::Veto(veto) => {
+                TypedSignature::sign(veto, private_key)?.get_raw_signature()
+            }
+        };
+        Ok(MessageCommitmentProof {
             committer: private_key.public_key(),
             signature,
         })
@@ -53,23 +130,37 @@ impl DmsMessage for Vote {
         proof: &MessageCommitmentProof,
         _dms_key: &DmsKey,
     ) -> Result<(), CryptoError> {
-        proof.signature.verify(self.to_hash256(), &proof.committer)
+        match self {
+            GovernanceMessage::Vote(vote) => {
+                TypedSignature::<Agenda>::new(proof.signature.clone(), proof.committer.clone())
+                    .verify_hash(vote.agenda_hash)
+            }
+            GovernanceMessage::Veto(veto) => TypedSignature::<GovernanceVeto>::new(
+                proof.signature.clone(),
+                proof.committer.clone(),
+            )
+            .verify(veto),
+        }
     }
 }
 
 pub struct Governance {
-    dms: Arc<RwLock<Dms<Vote>>>,
+    dms: Arc<RwLock<Dms<GovernanceMessage>>>,
+    /// The last finalized block, whose reserved state gives the governance set
+    /// that the agendas of the next height are tallied with.
     fi: FinalizationInfo,
     /// Note that this is not stored in the storage.
     /// That's because the set of all verified agendas can be derived from repository.
-    verified_agendas: BTreeSet<Hash256>,
+    ///
+    /// Each agenda is kept with its type, which decides the threshold it has to meet.
+    verified_agendas: BTreeMap<Hash256, AgendaType>,
 }
 
 impl Governance {
     pub async fn new(
-        dms: Arc<RwLock<Dms<Vote>>>,
+        dms: Arc<RwLock<Dms<GovernanceMessage>>>,
         fi: FinalizationInfo,
-        verified_agendas: BTreeSet<Hash256>,
+        verified_agendas: BTreeMap<Hash256, AgendaType>,
     ) -> Result<Self, Error> {
         // TODO: this must set the DMS to accept messages only from
         // the eligible governance set for this height.
@@ -81,56 +172,93 @@ impl Governance {
     }
 
     pub async fn read(&self) -> Result<GovernanceStatus, Error> {
-        let votes = self.dms.read().await.read_messages().await?;
-        let mut result = BTreeMap::<Hash256, BTreeMap<PublicKey, Signature>>::default();
-        for vote in votes {
-            for committers in vote.committers {
-                result
-                    .entry(vote.message.to_hash256())
+        let messages = self.dms.read().await.read_messages().await?;
+        let mut votes = BTreeMap::<Hash256, BTreeMap<PublicKey, Signature>>::default();
+        let mut vetoes = BTreeMap::<Hash256, BTreeMap<PublicKey, Signature>>::default();
+        for message in messages {
+            let (tally, agenda_hash) = match &message.message {
+                GovernanceMessage::Vote(vote) => (&mut votes, vote.agenda_hash),
+                GovernanceMessage::Veto(veto) => (&mut vetoes, veto.agenda_hash),
+            };
+            for committers in message.committers {
+                tally
+                    .entry(agenda_hash)
                     .or_default()
                     .insert(committers.committer, committers.signature);
             }
         }
-        let status = GovernanceStatus { votes: result };
+        let governance_set = self
+            .fi
+            .reserved_state
+            .get_governance_set()
+            .map_err(|e| eyre::eyre!("invalid governance set: {e}"))?
+            .into_iter()
+            .collect();
+        let status = GovernanceStatus {
+            votes,
+            vetoes,
+            governance_set,
+        };
         Ok(status)
     }
 
     pub async fn register_verified_agenda_hash(
         &mut self,
         agenda_hash: Hash256,
+        agenda_type: AgendaType,
     ) -> Result<(), Error> {
-        self.verified_agendas.insert(agenda_hash);
+        self.verified_agendas.insert(agenda_hash, agenda_type);
         Ok(())
     }
 
     pub async fn get_eligible_agendas(&self) -> Result<Vec<(Hash256, AgendaProof)>, Error> {
-        let governance_set = self
-            .fi
-            .reserved_state
-            .get_governance_set()
-            // TODO: handle integrity error
-            .unwrap()
-            .into_iter()
-            .collect::<BTreeMap<_, _>>();
         let governance_state = self.read().await?;
-        let votes: Vec<(Hash256, VotingPower)> = governance_state
+        let governance_set = &governance_state.governance_set;
+        // Only the votes for the agendas verified for this height are counted,
+        // so that a vote replayed from another height is discarded.
+        let votes: Vec<(Hash256, AgendaType, VotingPower)> = governance_state
             .votes
             .iter()
-            .map(|(agenda, votes)| {
+            .filter_map(|(agenda, votes)| {
+                self.verified_agendas
+                    .get(agenda)
+                    .map(|agenda_type| (agenda, *agenda_type, votes))
+            })
+            .map(|(agenda, agenda_type, votes)| {
                 (
                     *agenda,
+                    agenda_type,
                     votes
                         .keys()
-                        .map(|voter| governance_set.get(voter).unwrap())
+                        .filter_map(|voter| governance_set.get(voter))
                         .sum(),
                 )
             })
-            .filter(|(agenda, _)| self.verified_agendas.contains(agenda))
             .collect();
         let mut result = Vec::new();
         let total_voting_power = governance_set.values().sum::<VotingPower>();
-        for (agenda, voted_power) in votes {
-            if voted_power * 2 > total_voting_power {
+        let policy = &self.fi.reserved_state.governance_policy;
+        for (agenda, agenda_type, voted_power) in votes {
+            let vetoed_power = governance_state
+                .vetoes
+                .get(&agenda)
+                .map(|vetoes| {
+                    vetoes
+                        .keys()
+                        .filter_map(|vetoer| governance_set.get(vetoer))
+                        .sum()
+                })
+                .unwrap_or(0);
+            let vetoed = policy
+                .get_veto_threshold(agenda_type)
+                .map_or(false, |threshold| {
+                    threshold.is_met(vetoed_power, total_voting_power)
+                });
+            if !vetoed
+                && policy
+                    .get_threshold(agenda_type)
+                    .is_met(voted_power, total_voting_power)
+            {
                 let proof: Vec<_> = governance_state.votes[&agenda]
                     .iter()
                     .map(|(k, s)| TypedSignature::<Agenda>::new(s.clone(), k.clone()))
@@ -149,15 +277,44 @@ impl Governance {
         Ok(result)
     }
 
+    /// Votes for the given agenda.
+    ///
+    /// The agenda must have been registered by `register_verified_agenda_hash()` for this height.
     pub async fn vote(&mut self, agenda_hash: Hash256) -> Result<(), Error> {
+        self.check_verified_agenda(agenda_hash)?;
         self.dms
             .write()
             .await
-            .commit_message(&Vote { agenda_hash })
+            .commit_message(&GovernanceMessage::Vote(Vote { agenda_hash }))
             .await?;
         Ok(())
     }
 
+    /// Vetoes the given agenda.
+    ///
+    /// The agenda must have been registered by `register_verified_agenda_hash()` for this height.
+    /// Note that the veto has no effect if the type of the agenda can't be vetoed.
+    pub async fn veto(&mut self, agenda_hash: Hash256) -> Result<(), Error> {
+        self.check_verified_agenda(agenda_hash)?;
+        self.dms
+            .write()
+            .await
+            .commit_message(&GovernanceMessage::Veto(GovernanceVeto { agenda_hash }))
+            .await?;
+        Ok(())
+    }
+
+    fn check_verified_agenda(&self, agenda_hash: Hash256) -> Result<(), Error> {
+        if !self.verified_agendas.contains_key(&agenda_hash) {
+            return Err(eyre::eyre!(
+                "agenda {} is not a verified agenda for height {}",
+                agenda_hash,
+                self.fi.header.height + 1
+            ));
+        }
+        Ok(())
+    }
+
     pub async fn flush(&self) -> Result<(), Error> {
         Ok(())
     }
@@ -166,7 +323,7 @@ impl Governance {
         Ok(())
     }
 
-    pub fn get_dms(&self) -> Arc<RwLock<Dms<Vote>>> {
+    pub fn get_dms(&self) -> Arc<RwLock<Dms<GovernanceMessage>>> {
         Arc::clone(&self.dms)
     }
 }
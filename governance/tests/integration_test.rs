@@ -2,6 +2,7 @@ use simperby_core::*;
 use simperby_governance::*;
 use simperby_network::*;
 use simperby_test_suite::*;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -18,7 +19,9 @@ async fn basic_1() {
             create_test_dms(network_id.clone(), members.clone(), server_private_key).await,
         )),
         fi.clone(),
-        vec![agenda_hash].into_iter().collect(),
+        vec![(agenda_hash, AgendaType::General)]
+            .into_iter()
+            .collect(),
     )
     .await
     .unwrap();
@@ -31,7 +34,9 @@ async fn basic_1() {
                     create_test_dms(network_id.clone(), members.clone(), private_key.clone()).await,
                 )),
                 fi.clone(),
-                vec![agenda_hash].into_iter().collect(),
+                vec![(agenda_hash, AgendaType::General)]
+                    .into_iter()
+                    .collect(),
             )
             .await
             .unwrap(),
@@ -69,3 +74,294 @@ async fn basic_1() {
     }
     serve_task.await.unwrap();
 }
+
+#[tokio::test]
+async fn vote_for_unregistered_agenda() {
+    setup_test();
+    let agenda_hash = Hash256::hash("agenda");
+    let old_agenda_hash = Hash256::hash("old agenda");
+    let network_id = "governance".to_string();
+    let ((server_network_config, server_private_key), client_network_configs_and_keys, members, fi) =
+        setup_server_client_nodes(network_id.clone(), 4).await;
+
+    let server_node = Governance::new(
+        Arc::new(RwLock::new(
+            create_test_dms(network_id.clone(), members.clone(), server_private_key).await,
+        )),
+        fi.clone(),
+        vec![(agenda_hash, AgendaType::General)]
+            .into_iter()
+            .collect(),
+    )
+    .await
+    .unwrap();
+    let serve_task = tokio::spawn(Dms::serve(server_node.get_dms(), server_network_config));
+
+    let mut client_nodes = Vec::new();
+    for (network_config, private_key) in client_network_configs_and_keys.iter() {
+        client_nodes.push((
+            Governance::new(
+                Arc::new(RwLock::new(
+                    create_test_dms(network_id.clone(), members.clone(), private_key.clone()).await,
+                )),
+                fi.clone(),
+                vec![(agenda_hash, AgendaType::General)]
+                    .into_iter()
+                    .collect(),
+            )
+            .await
+            .unwrap(),
+            network_config,
+        ));
+    }
+
+    sleep_ms(500).await;
+    for (node, network_config) in client_nodes.iter_mut() {
+        // A node never votes for an agenda that is not verified for this height.
+        assert!(node.vote(old_agenda_hash).await.is_err());
+        // Simulate a vote replayed from another height.
+        node.get_dms()
+            .write()
+            .await
+            .commit_message(&GovernanceMessage::Vote(Vote {
+                agenda_hash: old_agenda_hash,
+            }))
+            .await
+            .unwrap();
+        node.vote(agenda_hash).await.unwrap();
+        node.flush().await.unwrap();
+        dms::DistributedMessageSet::broadcast(node.get_dms(), network_config)
+            .await
+            .unwrap();
+    }
+    for (node, network_config) in client_nodes.iter_mut() {
+        // Poll the server until the votes of all the nodes arrive.
+        for _ in 0..50 {
+            dms::DistributedMessageSet::fetch(node.get_dms(), network_config)
+                .await
+                .unwrap();
+            node.update().await.unwrap();
+            if node.read().await.unwrap().votes[&old_agenda_hash].len() == 4 {
+                break;
+            }
+            sleep_ms(100).await;
+        }
+        assert_eq!(node.read().await.unwrap().votes[&old_agenda_hash].len(), 4);
+        let eligible_agendas = node
+            .get_eligible_agendas()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(agenda_hash, _)| agenda_hash)
+            .collect::<Vec<_>>();
+        assert_eq!(eligible_agendas, vec![agenda_hash]);
+    }
+    serve_task.abort();
+    let _ = serve_task.await;
+}
+
+#[tokio::test]
+async fn vetoed_agenda_is_not_eligible() {
+    setup_test();
+    let vetoed_agenda = Hash256::hash("vetoed agenda");
+    let agenda = Hash256::hash("agenda");
+    let network_id = "governance".to_string();
+    let (
+        (server_network_config, server_private_key),
+        client_network_configs_and_keys,
+        members,
+        mut fi,
+    ) = setup_server_client_nodes(network_id.clone(), 4).await;
+    // More than a fifth of the voting power can veto a general agenda.
+    fi.reserved_state.governance_policy.veto_thresholds.insert(
+        AgendaType::General,
+        GovernanceThreshold {
+            numerator: 1,
+            denominator: 5,
+        },
+    );
+    let agendas: BTreeMap<_, _> = vec![
+        (vetoed_agenda, AgendaType::General),
+        (agenda, AgendaType::General),
+    ]
+    .into_iter()
+    .collect();
+
+    let mut server_node = Governance::new(
+        Arc::new(RwLock::new(
+            create_test_dms(network_id.clone(), members.clone(), server_private_key).await,
+        )),
+        fi.clone(),
+        agendas.clone(),
+    )
+    .await
+    .unwrap();
+    server_node.vote(vetoed_agenda).await.unwrap();
+    server_node.vote(agenda).await.unwrap();
+    let server_dms = server_node.get_dms();
+    let serve_task = tokio::spawn(async move {
+        let task = tokio::spawn(Dms::serve(server_dms, server_network_config));
+        sleep_ms(3000).await;
+        task.abort();
+        let _ = task.await;
+    });
+
+    let mut client_nodes = Vec::new();
+    for (network_config, private_key) in client_network_configs_and_keys.iter() {
+        client_nodes.push((
+            Governance::new(
+                Arc::new(RwLock::new(
+                    create_test_dms(network_id.clone(), members.clone(), private_key.clone()).await,
+                )),
+                fi.clone(),
+                agendas.clone(),
+            )
+            .await
+            .unwrap(),
+            network_config,
+        ));
+    }
+
+    sleep_ms(500).await;
+    // Three out of four members vote for both agendas, and the last one vetoes one of them.
+    for (i, (node, network_config)) in client_nodes.iter_mut().enumerate() {
+        if i == 0 {
+            node.veto(vetoed_agenda).await.unwrap();
+        } else {
+            node.vote(vetoed_agenda).await.unwrap();
+        }
+        node.vote(agenda).await.unwrap();
+        node.flush().await.unwrap();
+        dms::DistributedMessageSet::broadcast(node.get_dms(), network_config)
+            .await
+            .unwrap();
+    }
+    sleep_ms(500).await;
+    for (node, network_config) in client_nodes.iter_mut() {
+        dms::DistributedMessageSet::fetch(node.get_dms(), network_config)
+            .await
+            .unwrap();
+        node.update().await.unwrap();
+        let status = node.read().await.unwrap();
+        assert_eq!(status.votes[&vetoed_agenda].len(), 3);
+        assert_eq!(status.vetoes[&vetoed_agenda].len(), 1);
+        let eligible_agendas = node
+            .get_eligible_agendas()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(agenda_hash, _)| agenda_hash)
+            .collect::<Vec<_>>();
+        assert_eq!(eligible_agendas, vec![agenda]);
+    }
+    serve_task.await.unwrap();
+}
+
+/// Returns the agendas that become eligible when only the first member,
+/// holding `voting_power` out of 100, votes for all of them.
+async fn eligible_agendas_with_single_voter(
+    voting_power: VotingPower,
+    agendas: Vec<(Hash256, AgendaType)>,
+) -> Vec<Hash256> {
+    let (mut fi, keys) = test_utils::generate_fi(3);
+    fi.reserved_state.governance_policy.thresholds.insert(
+        AgendaType::ReservedStateChange,
+        GovernanceThreshold::SUPER_MAJORITY,
+    );
+    let powers = [voting_power, 100 - voting_power - 33, 33];
+    for (member, power) in fi.reserved_state.members.iter_mut().zip(powers) {
+        member.governance_voting_power = power;
+        member.consensus_voting_power = power;
+    }
+    let members = keys
+        .iter()
+        .map(|(public_key, _)| public_key.clone())
+        .collect();
+    let mut node = Governance::new(
+        Arc::new(RwLock::new(
+            create_test_dms("governance".to_string(), members, keys[0].1.clone()).await,
+        )),
+        fi,
+        agendas.iter().cloned().collect(),
+    )
+    .await
+    .unwrap();
+    for (agenda_hash, _) in agendas {
+        node.vote(agenda_hash).await.unwrap();
+    }
+    node.get_eligible_agendas()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(agenda_hash, _)| agenda_hash)
+        .collect()
+}
+
+#[tokio::test]
+async fn simple_majority_threshold() {
+    setup_test();
+    let general_agenda = Hash256::hash("general agenda");
+    let membership_agenda = Hash256::hash("membership agenda");
+    let eligible_agendas = eligible_agendas_with_single_voter(
+        51,
+        vec![
+            (general_agenda, AgendaType::General),
+            (membership_agenda, AgendaType::ReservedStateChange),
+        ],
+    )
+    .await;
+    // 51% is enough for a general agenda, but not for a membership change.
+    assert_eq!(eligible_agendas, vec![general_agenda]);
+}
+
+#[tokio::test]
+async fn super_majority_threshold() {
+    setup_test();
+    let membership_agenda = Hash256::hash("membership agenda");
+    let agendas = vec![(membership_agenda, AgendaType::ReservedStateChange)];
+    assert!(eligible_agendas_with_single_voter(66, agendas.clone())
+        .await
+        .is_empty());
+    assert_eq!(
+        eligible_agendas_with_single_voter(67, agendas).await,
+        vec![membership_agenda]
+    );
+}
+
+#[tokio::test]
+async fn summary_serialization_round_trip() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let agendas = [Hash256::hash("agenda 1"), Hash256::hash("agenda 2")];
+    let members = keys
+        .iter()
+        .map(|(public_key, _)| public_key.clone())
+        .collect();
+    let mut node = Governance::new(
+        Arc::new(RwLock::new(
+            create_test_dms("governance".to_string(), members, keys[0].1.clone()).await,
+        )),
+        fi,
+        agendas
+            .iter()
+            .map(|agenda_hash| (*agenda_hash, AgendaType::General))
+            .collect(),
+    )
+    .await
+    .unwrap();
+    for agenda_hash in agendas {
+        node.vote(agenda_hash).await.unwrap();
+    }
+
+    let status = node.read().await.unwrap();
+    let serialized = serde_spb::to_string(&status).unwrap();
+    let deserialized: GovernanceStatus = serde_spb::from_str(&serialized).unwrap();
+    assert_eq!(serde_spb::to_string(&deserialized).unwrap(), serialized);
+    assert_eq!(deserialized.summary(), status.summary());
+    let mut expected: Vec<_> = agendas
+        .iter()
+        .map(|agenda_hash| (*agenda_hash, 1, 4))
+        .collect();
+    expected.sort();
+    assert_eq!(status.summary(), expected);
+}
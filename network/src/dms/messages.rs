@@ -64,6 +64,27 @@ pub struct Message<T: DmsMessage> {
     pub committers: Vec<MessageCommitmentProof>,
 }
 
+/// A message with the commitment proof of each of its committers.
+///
+/// Unlike `Message`, it can be handed to a party that doesn't trust the node serving it;
+/// the party verifies the proofs against the DMS key it knows by itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "T: DmsMessage")]
+pub struct MessageWithProofs<T: DmsMessage> {
+    pub message: T,
+    pub proofs: Vec<MessageCommitmentProof>,
+}
+
+impl<T: DmsMessage> MessageWithProofs<T> {
+    /// Verifies that every proof is a valid commitment to the message under `dms_key`.
+    pub fn verify(&self, dms_key: &DmsKey) -> Result<(), CryptoError> {
+        for proof in &self.proofs {
+            self.message.verify_commitment(proof, dms_key)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MessageCommitmentProof {
     pub committer: PublicKey,
@@ -15,6 +15,7 @@ use rpc::*;
 use serde_tc::http::*;
 use serde_tc::{serde_tc_full, StubCall};
 use simperby_core::*;
+use std::collections::{BTreeSet, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -23,8 +24,8 @@ const STATE_FILE_PATH: &str = "state.json";
 
 pub type Error = eyre::Error;
 
-pub use messages::{DmsKey, DmsMessage, Message, MessageCommitmentProof};
-pub use rpc::PeerStatus;
+pub use messages::{DmsKey, DmsMessage, Message, MessageCommitmentProof, MessageWithProofs};
+pub use rpc::{DmsRequestSignTarget, PeerStatus, RequestCredential, REQUEST_CREDENTIAL_VALIDITY};
 pub use server::*;
 
 #[derive(thiserror::Error, Debug)]
@@ -50,7 +51,8 @@ pub struct Config {
 pub struct DistributedMessageSet<S, M> {
     storage: Arc<RwLock<S>>,
     config: Config,
-    private_key: PrivateKey,
+    /// `None` for a read-only instance.
+    private_key: Option<PrivateKey>,
     _marker: std::marker::PhantomData<M>,
 }
 
@@ -76,44 +78,54 @@ impl<S, M> std::fmt::Debug for DistributedMessageSet<S, M> {
 ///   it will `await` until the lock is released.
 /// - It takes 'Arc<RwLock<Self>>' instead of `self` if network clients are used.
 ///
-/// TODO: add read only type that does not require the private key.
+/// An instance created by [`new_readonly()`](Self::new_readonly) has no private key;
+/// it can store and serve the messages received from the members,
+/// but can't commit a message or make a request to the other nodes.
 impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
     /// Creates a message set instance.
     ///
     /// If the storage is empty, it creates a new one.
     /// If not, check the `dms_key` with the stored one.
-    /// It loads the storage if the `dms_key` is the same.
-    /// It clears all and initializes a new one if not.
+    /// It loads the storage if the `dms_key` is the same,
+    /// failing if the rest of the config (i.e., the members) differs.
+    /// It clears all and initializes a new one if not,
+    /// since a new `dms_key` is for a new height which may have a new member set.
     ///
     /// - `private_key`: The private key for signing messages.
-    pub async fn new(
-        mut storage: S,
-        config: Config,
-        private_key: PrivateKey,
-    ) -> Result<Self, Error> {
+    pub async fn new(storage: S, config: Config, private_key: PrivateKey) -> Result<Self, Error> {
         if !config.members.contains(&private_key.public_key()) {
             return Err(eyre!("given private key is not in the member list"));
         }
+        Self::open(storage, config, Some(private_key)).await
+    }
 
-        match storage.read_file(STATE_FILE_PATH).await {
-            Ok(x) => {
-                let config2: Config = serde_spb::from_str(&x)?;
-                if config2 != config {
-                    return Err(eyre!("config mismatch: {:?}", config2));
-                }
+    /// Creates a read-only message set instance that does not require the private key.
+    ///
+    /// It is for the nodes that observe and relay the messages without signing them.
+    pub async fn new_readonly(storage: S, config: Config) -> Result<Self, Error> {
+        Self::open(storage, config, None).await
+    }
+
+    async fn open(
+        mut storage: S,
+        config: Config,
+        private_key: Option<PrivateKey>,
+    ) -> Result<Self, Error> {
+        match Self::read_stored_config(&storage).await? {
+            Some(stored_config) if stored_config == config => (),
+            // The same DMS key means the same height, whose members can't change.
+            Some(stored_config) if stored_config.dms_key == config.dms_key => {
+                return Err(eyre!(
+                    "config mismatch for the same DMS key: {:?}",
+                    stored_config
+                ));
             }
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    storage.remove_all_files().await?;
-                    storage
-                        .add_or_overwrite_file(
-                            STATE_FILE_PATH,
-                            serde_spb::to_string(&config).unwrap(),
-                        )
-                        .await?;
-                } else {
-                    return Err(e.into());
-                }
+            // The storage is empty or left from a previous height (e.g., with other members).
+            _ => {
+                storage.remove_all_files().await?;
+                storage
+                    .add_or_overwrite_file(STATE_FILE_PATH, serde_spb::to_string(&config).unwrap())
+                    .await?;
             }
         }
 
@@ -130,7 +142,8 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
     /// This is useful for when you want to store some additional data
     /// under the same file lock that this DMS uses.
     ///
-    /// Note that you MUST NOT create or access files that start with `message-`.
+    /// Note that you MUST NOT create or access files that start with
+    /// `message-`, `metadata-` or `delivered-`.
     pub fn get_storage(&self) -> Arc<RwLock<S>> {
         Arc::clone(&self.storage)
     }
@@ -139,6 +152,29 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
         self.config.clone()
     }
 
+    /// Returns whether this instance has no private key.
+    pub fn is_readonly(&self) -> bool {
+        self.private_key.is_none()
+    }
+
+    fn private_key(&self) -> Result<&PrivateKey, Error> {
+        self.private_key
+            .as_ref()
+            .ok_or_else(|| eyre!("read-only DMS does not have a private key"))
+    }
+
+    /// Reads the config stored in the given storage, or `None` if the storage is empty.
+    ///
+    /// This is useful for checking whether the storage is for the expected DMS key
+    /// (e.g., not for an outdated height) before calling `new()`.
+    pub async fn read_stored_config(storage: &S) -> Result<Option<Config>, Error> {
+        match storage.read_file(STATE_FILE_PATH).await {
+            Ok(x) => Ok(Some(serde_spb::from_str(&x)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub async fn clear(&mut self) -> Result<(), Error> {
         self.storage.write().await.remove_all_files().await?;
         self.storage
@@ -172,10 +208,27 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
             }))
     }
 
+    /// Queries the message along with the commitment proofs of its committers,
+    /// so that the result can be verified without trusting this node.
+    ///
+    /// See [`MessageWithProofs::verify()`].
+    pub async fn query_message_with_proofs(
+        &self,
+        message_hash: Hash256,
+    ) -> Result<Option<MessageWithProofs<M>>, Error> {
+        Ok(self
+            .read_raw_message(message_hash)
+            .await?
+            .map(|(message, metadata)| MessageWithProofs {
+                message,
+                proofs: metadata.committers,
+            }))
+    }
+
     /// Signs the given message and adds it to the storage.
     pub async fn commit_message(&mut self, message: &M) -> Result<(), Error> {
         message.check()?;
-        let commitment = message.commit(&self.config.dms_key, &self.private_key)?;
+        let commitment = message.commit(&self.config.dms_key, self.private_key()?)?;
         self.store_message(message, commitment).await?;
         Ok(())
     }
@@ -274,6 +327,33 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
         self.config.members.contains(member)
     }
 
+    /// Receives the packets, skipping the ones that have been already received
+    /// in the same session (identified by the hash of the encoded message and the committer).
+    ///
+    /// This avoids decoding and verifying the same message repeatedly
+    /// when it is served by many peers.
+    /// Returns the number of packets that have been actually processed.
+    async fn receive_packets(
+        &mut self,
+        packets: Vec<Packet>,
+        seen: &mut HashSet<(Hash256, PublicKey)>,
+    ) -> Result<usize, Error> {
+        let mut processed = 0;
+        for packet in packets {
+            let key = (
+                Hash256::hash(&packet.message),
+                packet.commitment.committer.clone(),
+            );
+            if seen.contains(&key) {
+                continue;
+            }
+            self.receive_packet(packet).await?;
+            seen.insert(key);
+            processed += 1;
+        }
+        Ok(processed)
+    }
+
     async fn receive_packet(&mut self, packet: Packet) -> Result<(), Error> {
         let message = serde_spb::from_slice::<M>(&packet.message)?;
         message.verify_commitment(&packet.commitment, &self.config.dms_key)?;
@@ -326,6 +406,54 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
         Ok(())
     }
 
+    /// Reads the hashes of the packets that have been delivered to the given peer.
+    async fn read_delivered_packets(&self, peer: &PublicKey) -> Result<BTreeSet<Hash256>, Error> {
+        let data = match self
+            .storage
+            .read()
+            .await
+            .read_file(&format!("delivered-{}.json", Hash256::hash(peer)))
+            .await
+        {
+            Ok(x) => x,
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    return Ok(BTreeSet::new());
+                } else {
+                    return Err(e.into());
+                }
+            }
+        };
+        let delivered = serde_spb::from_str::<BTreeSet<Hash256>>(&data)
+            .map_err(|e| IntegrityError::new(format!("can't decode stored data: {e}")))?;
+        Ok(delivered)
+    }
+
+    /// Records that the given packets have been delivered to the peer.
+    ///
+    /// The records are kept only for the packets in `current` (i.e., those that this DMS still has),
+    /// so that they don't outgrow the message set.
+    /// They are cleared along with the messages when the DMS key changes (see `new()`).
+    async fn add_delivered_packets(
+        &mut self,
+        peer: &PublicKey,
+        packets: Vec<Hash256>,
+        current: &BTreeSet<Hash256>,
+    ) -> Result<(), Error> {
+        let mut delivered = self.read_delivered_packets(peer).await?;
+        delivered.extend(packets);
+        delivered.retain(|hash| current.contains(hash));
+        self.storage
+            .write()
+            .await
+            .add_or_overwrite_file(
+                &format!("delivered-{}.json", Hash256::hash(peer)),
+                serde_spb::to_string(&delivered).unwrap(),
+            )
+            .await?;
+        Ok(())
+    }
+
     async fn retrieve_packets(&self) -> Result<Vec<Packet>, Error> {
         let messages = self.read_raw_messages().await?;
         let mut result = Vec::new();
@@ -1,10 +1,16 @@
 use super::*;
 use crate::keys;
 use simperby_core::utils::get_timestamp;
+use std::collections::HashMap;
+
+/// The maximum difference (in milliseconds) between the timestamp of a `RequestCredential`
+/// and the clock of the server that admits it.
+pub const REQUEST_CREDENTIAL_VALIDITY: Timestamp = 60_000;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct PingResponse {
-    pub public_key: PublicKey,
+    /// The public key of the server, or `None` if it serves a read-only DMS.
+    pub public_key: Option<PublicKey>,
     pub timestamp: Timestamp,
     pub msg: String,
 }
@@ -19,16 +25,100 @@ pub struct PeerStatus {
     pub last_msg: String,
 }
 
+/// What a `RequestCredential` signs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct DmsRequestSignTarget {
+    pub dms_key: DmsKey,
+    /// The name of the RPC method that the credential is made for.
+    pub method: String,
+    pub timestamp: Timestamp,
+    pub nonce: u64,
+}
+
+impl ToHash256 for DmsRequestSignTarget {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
+impl SignatureDomain for DmsRequestSignTarget {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-dms-request";
+}
+
+/// Proves which member is making an RPC request.
+///
+/// The server uses it to identify the peer for rate limiting.
+/// A read-only instance, which has no key to sign it, makes requests without it.
+/// It is admitted only for the RPC method it is signed for, within `REQUEST_CREDENTIAL_VALIDITY`
+/// of its timestamp, and only once, so it can't be replayed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct RequestCredential {
+    pub timestamp: Timestamp,
+    /// A random number to tell apart the credentials created at the same time.
+    pub nonce: u64,
+    pub signature: TypedSignature<DmsRequestSignTarget>,
+}
+
+impl RequestCredential {
+    pub fn new(
+        dms_key: &DmsKey,
+        method: &str,
+        private_key: &PrivateKey,
+    ) -> Result<Self, CryptoError> {
+        let timestamp = get_timestamp();
+        let nonce = rand::random();
+        Ok(Self {
+            timestamp,
+            nonce,
+            signature: TypedSignature::sign(
+                &DmsRequestSignTarget {
+                    dms_key: dms_key.clone(),
+                    method: method.to_owned(),
+                    timestamp,
+                    nonce,
+                },
+                private_key,
+            )?,
+        })
+    }
+
+    pub fn verify(&self, dms_key: &DmsKey, method: &str) -> Result<(), CryptoError> {
+        self.signature.verify(&self.sign_target(dms_key, method))
+    }
+
+    pub fn requester(&self) -> &PublicKey {
+        self.signature.signer()
+    }
+
+    fn sign_target(&self, dms_key: &DmsKey, method: &str) -> DmsRequestSignTarget {
+        DmsRequestSignTarget {
+            dms_key: dms_key.clone(),
+            method: method.to_owned(),
+            timestamp: self.timestamp,
+            nonce: self.nonce,
+        }
+    }
+}
+
 /// The interface that will be wrapped into an HTTP RPC server for the peers.
+///
+/// The credential is `None` for the requests from a read-only instance.
 #[serde_tc_full]
 pub(super) trait DistributedMessageSetRpcInterface: Send + Sync + 'static {
     /// Requests to response some packets.
-    async fn request_packets(&self) -> Result<Vec<Packet>, String>;
+    async fn request_packets(
+        &self,
+        credential: Option<RequestCredential>,
+    ) -> Result<Vec<Packet>, String>;
 
     /// Sends packets to the peer.
-    async fn send_packets(&self, packets: Vec<Packet>) -> Result<(), String>;
+    async fn send_packets(
+        &self,
+        credential: Option<RequestCredential>,
+        packets: Vec<Packet>,
+    ) -> Result<(), String>;
 
-    async fn ping(&self) -> Result<PingResponse, String>;
+    async fn ping(&self, credential: Option<RequestCredential>) -> Result<PingResponse, String>;
 }
 
 pub(super) struct DmsWrapper<S: Storage, M: DmsMessage> {
@@ -36,99 +126,242 @@ pub(super) struct DmsWrapper<S: Storage, M: DmsMessage> {
     /// This is an `Option` because we have to explicitly drop the server
     /// (it could live forever in the RPC server (`axum`) otherwise)
     pub(super) dms: Arc<parking_lot::RwLock<Option<Arc<RwLock<DistributedMessageSet<S, M>>>>>>,
+    pub(super) rate_limiter: RateLimiter,
+    /// The credentials admitted within the freshness window, with their timestamps.
+    pub(super) seen_credentials: parking_lot::Mutex<HashMap<(PublicKey, Hash256), Timestamp>>,
 }
 
-/// Server-side implementation of the RPC interface.
-#[async_trait]
-impl<S: Storage, M: DmsMessage> DistributedMessageSetRpcInterface for DmsWrapper<S, M> {
-    async fn request_packets(&self) -> Result<Vec<Packet>, String> {
-        let dms = Arc::clone(
+impl<S: Storage, M: DmsMessage> DmsWrapper<S, M> {
+    /// Checks that the request comes from a member with a fresh credential for `method`,
+    /// and that the member is not over its rate.
+    ///
+    /// A request without a credential is admitted as long as the anonymous requests,
+    /// which share one rate, are not over it. This lets the read-only instances relay
+    /// the messages, which are verified by the signatures of their committers anyway.
+    async fn admit(
+        &self,
+        dms: &RwLock<DistributedMessageSet<S, M>>,
+        credential: Option<&RequestCredential>,
+        method: &str,
+    ) -> Result<(), String> {
+        let Some(credential) = credential else {
+            return self.acquire(None);
+        };
+        let dms = dms.read().await;
+        credential
+            .verify(&dms.config.dms_key, method)
+            .map_err(|e| format!("invalid request credential: {e}"))?;
+        if !dms.test_membership(credential.requester()) {
+            return Err("requester is not a member".to_owned());
+        }
+        if (get_timestamp() - credential.timestamp).abs() > REQUEST_CREDENTIAL_VALIDITY {
+            return Err(format!(
+                "request credential expired: timestamp {}",
+                credential.timestamp
+            ));
+        }
+        {
+            let mut seen_credentials = self.seen_credentials.lock();
+            // The credentials out of the window would be rejected anyway.
+            let now = get_timestamp();
+            seen_credentials.retain(|_, timestamp| now - *timestamp <= REQUEST_CREDENTIAL_VALIDITY);
+            let id = (
+                credential.requester().clone(),
+                credential
+                    .sign_target(&dms.config.dms_key, method)
+                    .to_hash256(),
+            );
+            if seen_credentials.insert(id, credential.timestamp).is_some() {
+                return Err(format!(
+                    "request credential replayed: timestamp {}",
+                    credential.timestamp
+                ));
+            }
+        }
+        self.acquire(Some(credential.requester()))
+    }
+
+    fn acquire(&self, requester: Option<&PublicKey>) -> Result<(), String> {
+        self.rate_limiter
+            .acquire(requester)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl<S: Storage, M: DmsMessage> DmsWrapper<S, M> {
+    fn dms(&self) -> Result<Arc<RwLock<DistributedMessageSet<S, M>>>, String> {
+        Ok(Arc::clone(
             self.dms
                 .read()
                 .as_ref()
                 .ok_or_else(|| "server terminated".to_owned())?,
-        );
-        let packets = dms
-            .read()
-            .await
-            .retrieve_packets()
-            .await
-            .map_err(|e| e.to_string())?;
-        Ok(packets)
+        ))
     }
 
-    async fn send_packets(&self, packets: Vec<Packet>) -> Result<(), String> {
-        let dms = Arc::clone(
-            self.dms
+    /// Logs the outcome of a request, with the number of messages it carried if succeeded.
+    ///
+    /// Failures are logged as warnings since they are returned to the peer only.
+    fn log_request(
+        operation: &str,
+        credential: Option<&RequestCredential>,
+        outcome: &Result<usize, String>,
+    ) {
+        let dms = keys::port_key_dms::<M>();
+        let peer = requester_name(credential.map(RequestCredential::requester));
+        match outcome {
+            Ok(count) => log::debug!("[{dms}] {operation} from {peer}: {count} messages"),
+            Err(e) => log::warn!("[{dms}] {operation} from {peer} failed: {e}"),
+        }
+    }
+}
+
+/// Server-side implementation of the RPC interface.
+#[async_trait]
+impl<S: Storage, M: DmsMessage> DistributedMessageSetRpcInterface for DmsWrapper<S, M> {
+    async fn request_packets(
+        &self,
+        credential: Option<RequestCredential>,
+    ) -> Result<Vec<Packet>, String> {
+        let result = async {
+            let dms = self.dms()?;
+            self.admit(&dms, credential.as_ref(), "request_packets")
+                .await?;
+            let packets = dms
                 .read()
-                .as_ref()
-                .ok_or_else(|| "server terminated".to_owned())?,
+                .await
+                .retrieve_packets()
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(packets)
+        }
+        .await;
+        Self::log_request(
+            "request_packets",
+            credential.as_ref(),
+            &result.as_ref().map(Vec::len).map_err(Clone::clone),
         );
-        for packet in packets {
-            dms.write()
+        result
+    }
+
+    async fn send_packets(
+        &self,
+        credential: Option<RequestCredential>,
+        packets: Vec<Packet>,
+    ) -> Result<(), String> {
+        let result = async {
+            let dms = self.dms()?;
+            self.admit(&dms, credential.as_ref(), "send_packets")
+                .await?;
+            let received = dms
+                .write()
                 .await
-                .receive_packet(packet)
+                .receive_packets(packets, &mut HashSet::new())
                 .await
                 .map_err(|e| e.to_string())?;
+            Ok(received)
         }
-        Ok(())
+        .await;
+        Self::log_request("send_packets", credential.as_ref(), &result);
+        result.map(|_| ())
     }
 
-    async fn ping(&self) -> Result<PingResponse, String> {
-        let dms = Arc::clone(
-            self.dms
+    async fn ping(&self, credential: Option<RequestCredential>) -> Result<PingResponse, String> {
+        let result = async {
+            let dms = self.dms()?;
+            self.admit(&dms, credential.as_ref(), "ping").await?;
+            let public_key = dms
                 .read()
+                .await
+                .private_key
                 .as_ref()
-                .ok_or_else(|| "server terminated".to_owned())?,
+                .map(PrivateKey::public_key);
+            Ok(PingResponse {
+                public_key,
+                timestamp: get_timestamp(),
+                msg: "hello?".to_string(),
+            })
+        }
+        .await;
+        Self::log_request(
+            "ping",
+            credential.as_ref(),
+            &result.as_ref().map(|_| 0).map_err(Clone::clone),
         );
-        let public_key = dms.read().await.private_key.public_key();
-        Ok(PingResponse {
-            public_key,
-            timestamp: get_timestamp(),
-            msg: "hello?".to_string(),
-        })
+        result
     }
 }
 
+/// Describes the requester for the logs and errors.
+pub(super) fn requester_name(requester: Option<&PublicKey>) -> String {
+    requester
+        .map(ToString::to_string)
+        .unwrap_or_else(|| "anonymous requester".to_owned())
+}
+
 impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
+    /// Creates a credential for an RPC request, or `None` if this is a read-only instance.
+    fn request_credential(&self, method: &str) -> Result<Option<RequestCredential>, Error> {
+        Ok(match &self.private_key {
+            Some(private_key) => Some(RequestCredential::new(
+                &self.config.dms_key,
+                method,
+                private_key,
+            )?),
+            None => None,
+        })
+    }
+
     /// Fetches unknown messages from the peers using an RPC protocol,
     /// and adds them to the local storage.
+    ///
+    /// Only the peers that serve this DMS are contacted.
     pub async fn fetch(
         this: Arc<RwLock<Self>>,
         network_config: &ClientNetworkConfig,
     ) -> Result<(), Error> {
+        let peers = network_config.peers_serving(&keys::port_key_dms::<M>());
+        // The packets already received from one peer are skipped for the others.
+        let seen = Arc::new(tokio::sync::Mutex::new(HashSet::new()));
         let mut tasks = Vec::new();
-        for peer in &network_config.peers {
+        for peer in peers.iter().copied() {
             let this_ = Arc::clone(&this);
+            let seen = Arc::clone(&seen);
             let task = async move {
                 let this_read = this_.read().await;
                 let port_key = keys::port_key_dms::<M>();
                 let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
                     format!(
-                        "{}:{}/dms",
-                        peer.address.ip(),
-                        peer.ports
-                            .get(&port_key)
+                        "{}/dms",
+                        peer.service_address(&port_key)
                             .ok_or_else(|| eyre!("can't find port key: {}", port_key))?
                     ),
                     reqwest::Client::new(),
                 )));
+                let credential = this_read.request_credential("request_packets")?;
                 let packets = stub
-                    .request_packets()
+                    .request_packets(credential)
                     .await
                     .map_err(|e| eyre!("{}", e))?
                     .map_err(|e| eyre!(e))?;
                 // Important: drop the lock before `write()`
                 drop(this_read);
-                for packet in packets {
-                    this_.write().await.receive_packet(packet).await?;
-                }
+                let fetched = packets.len();
+                let mut seen = seen.lock().await;
+                let received = this_
+                    .write()
+                    .await
+                    .receive_packets(packets, &mut seen)
+                    .await?;
+                log::debug!(
+                    "[{port_key}] fetched {fetched} messages from {} ({received} new)",
+                    peer.name
+                );
                 Result::<(), Error>::Ok(())
             };
             tasks.push(task);
         }
         let results = future::join_all(tasks).await;
-        for (result, peer) in results.into_iter().zip(network_config.peers.iter()) {
+        for (result, peer) in results.into_iter().zip(peers) {
             if let Err(e) = result {
                 log::warn!("failed to fetch from client {:?}: {}", peer, e);
             }
@@ -138,6 +371,10 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
 
     /// Tries to broadcast all the message that this DMS instance has.
     ///
+    /// Each peer receives only the packets that haven't been delivered to it yet,
+    /// so the packets that failed to reach a peer are retried on the next broadcast.
+    /// The peers that don't serve this DMS are skipped.
+    ///
     /// Note: this function may take just `&self` due to its simple implementation,
     /// but keeps `Arc<RwLock<Self>>` to make sure the interface to indicate
     /// that this is a network-involved method (unlike others)
@@ -151,25 +388,45 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
         if packets.is_empty() {
             return Ok(());
         }
-        for peer in &network_config.peers {
+        let packets = packets
+            .into_iter()
+            .map(|packet| (packet.to_hash256(), packet))
+            .collect::<Vec<_>>();
+        let current = packets
+            .iter()
+            .map(|(hash, _)| *hash)
+            .collect::<BTreeSet<_>>();
+        for peer in network_config.peers_serving(&keys::port_key_dms::<M>()) {
             let port_key = keys::port_key_dms::<M>();
-            let packets_ = packets.clone();
+            let delivered = this
+                .read()
+                .await
+                .read_delivered_packets(&peer.public_key)
+                .await?;
+            let pending = packets
+                .iter()
+                .filter(|(hash, _)| !delivered.contains(hash))
+                .cloned()
+                .collect::<Vec<_>>();
+            if pending.is_empty() {
+                continue;
+            }
+            let credential = this.read().await.request_credential("send_packets")?;
             let task = async move {
                 let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
                     format!(
-                        "{}:{}/dms",
-                        peer.address.ip(),
-                        peer.ports
-                            .get(&port_key)
+                        "{}/dms",
+                        peer.service_address(&port_key)
                             .ok_or_else(|| eyre!("can't find port key: {}", port_key))?
                     ),
                     reqwest::Client::new(),
                 )));
-                stub.send_packets(packets_.clone())
+                let (hashes, packets) = pending.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+                stub.send_packets(credential, packets)
                     .await
                     .map_err(|e| eyre!(e))?
                     .map_err(|e| eyre!(e))?;
-                Result::<(), Error>::Ok(())
+                Result::<_, Error>::Ok((peer, hashes))
             };
             tasks_and_messages.push((task, format!("RPC message add to {}", peer.public_key)));
         }
@@ -179,8 +436,14 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
 
         let results = future::join_all(tasks).await;
         for (result, msg) in results.into_iter().zip(messages.iter()) {
-            if let Err(e) = result {
-                log::warn!("failure in {}: {}", msg, e);
+            match result {
+                Ok((peer, hashes)) => {
+                    this.write()
+                        .await
+                        .add_delivered_packets(&peer.public_key, hashes, &current)
+                        .await?;
+                }
+                Err(e) => log::warn!("failure in {}: {}", msg, e),
             }
         }
         Ok(())
@@ -198,28 +461,30 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
                 let port_key = keys::port_key_dms::<M>();
                 let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
                     format!(
-                        "{}:{}/dms",
-                        peer.address.ip(),
-                        peer.ports
-                            .get(&port_key)
+                        "{}/dms",
+                        peer.service_address(&port_key)
                             .ok_or_else(|| eyre!("can't find port key: {}", port_key))?
                     ),
                     reqwest::Client::new(),
                 )));
+                let credential = this_read.request_credential("ping")?;
                 let ping_response = stub
-                    .ping()
+                    .ping(credential)
                     .await
                     .map_err(|e| eyre!("{}", e))?
                     .map_err(|e| eyre!(e))?;
                 // Important: drop the lock before `write()`
                 drop(this_read);
 
-                if peer.public_key != ping_response.public_key {
-                    return Err(eyre!(
-                        "peer public key mismatch: expected {}, got {}",
-                        peer.public_key,
-                        ping_response.public_key
-                    ));
+                // A read-only server has no key to be checked.
+                if let Some(public_key) = ping_response.public_key {
+                    if peer.public_key != public_key {
+                        return Err(eyre!(
+                            "peer public key mismatch: expected {}, got {}",
+                            peer.public_key,
+                            public_key
+                        ));
+                    }
                 }
                 Result::<(), Error>::Ok(())
             };
@@ -237,16 +502,13 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
                 "success".to_owned()
             };
 
-            let port = peer
-                .ports
-                .get(&port_key)
+            let address = peer
+                .service_address(&port_key)
                 .ok_or_else(|| eyre!("can't find port key: {}", port_key))?;
 
             final_results.push(PeerStatus {
                 public_key: peer.public_key.clone(),
-                address: format!("{}:{}", peer.address.ip(), port)
-                    .parse()
-                    .expect("valid address"),
+                address,
                 last_ping: ping,
                 last_observed_timestamp: 0,      // TODO
                 last_claimed_local_timestamp: 0, // TODO
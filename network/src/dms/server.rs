@@ -1,7 +1,73 @@
 use super::*;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Returned to a peer that has exceeded its request rate.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("request throttled: {} exceeded the rate limit", requester_name(.peer.as_ref()))]
+pub struct ThrottledError {
+    /// `None` for the anonymous requests.
+    pub peer: Option<PublicKey>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Keeps a token bucket for each peer, and one for all the anonymous requests.
+pub(super) struct RateLimiter {
+    /// `None` if the members are not throttled.
+    member_rate_limit: Option<RateLimit>,
+    anonymous_rate_limit: RateLimit,
+    buckets: parking_lot::Mutex<HashMap<Option<PublicKey>, TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Throttles each member with the given rate, if any, and the anonymous requests
+    /// together with the same rate or `DEFAULT_ANONYMOUS_RATE_LIMIT`.
+    pub(super) fn new(rate_limit: Option<RateLimit>) -> Self {
+        Self {
+            anonymous_rate_limit: rate_limit.clone().unwrap_or(DEFAULT_ANONYMOUS_RATE_LIMIT),
+            member_rate_limit: rate_limit,
+            buckets: Default::default(),
+        }
+    }
+
+    /// Takes a token from the bucket of the given peer, failing if it is empty.
+    pub(super) fn acquire(&self, peer: Option<&PublicKey>) -> Result<(), ThrottledError> {
+        let rate_limit = match (peer, &self.member_rate_limit) {
+            (Some(_), Some(rate_limit)) => rate_limit,
+            (Some(_), None) => return Ok(()),
+            (None, _) => &self.anonymous_rate_limit,
+        };
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry(peer.cloned()).or_insert(TokenBucket {
+            tokens: rate_limit.burst as f64,
+            last_refill: now,
+        });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens =
+            (bucket.tokens + elapsed * rate_limit.requests_per_second).min(rate_limit.burst as f64);
+        bucket.last_refill = now;
+        if bucket.tokens < 1.0 {
+            return Err(ThrottledError {
+                peer: peer.cloned(),
+            });
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
 
 impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
     /// Runs a DMS server. This function will block the current thread.
+    ///
+    /// Every request must come from a member or, without a credential, from a read-only peer.
+    /// If `network_config.rate_limit` is set, the requests are throttled per member,
+    /// and all the anonymous ones together; otherwise only the anonymous ones are,
+    /// with `DEFAULT_ANONYMOUS_RATE_LIMIT`.
     pub async fn serve(
         dms: Arc<RwLock<DistributedMessageSet<S, M>>>,
         network_config: ServerNetworkConfig,
@@ -19,20 +85,24 @@ impl<S: Storage, M: DmsMessage> DistributedMessageSet<S, M> {
             let _drop_helper = DropHelper {
                 wrapped_dms: Arc::clone(&wrapped_dms),
             };
-            run_server(
+            crate::rpc_server::run_server(
                 network_config.port,
                 [(
                     "dms".to_owned(),
-                    create_http_object(Arc::new(DmsWrapper { dms: wrapped_dms })
+                    create_http_object(Arc::new(DmsWrapper {
+                        dms: wrapped_dms,
+                        rate_limiter: RateLimiter::new(network_config.rate_limit),
+                        seen_credentials: Default::default(),
+                    })
                         as Arc<dyn DistributedMessageSetRpcInterface>),
                 )]
                 .iter()
                 .cloned()
                 .collect(),
             )
-            .await;
+            .await
         };
-        rpc_task.await;
+        rpc_task.await?;
         Ok(())
     }
 
@@ -63,6 +63,106 @@ async fn single_1() {
     );
 }
 
+#[tokio::test]
+async fn member_change_for_new_height() {
+    let ((_, private_key), _, _) = setup_server_client_nodes(1).await;
+    let (_, other_member) = generate_keypair_random();
+    let path = create_temp_dir();
+    StorageImpl::create(&path).await.unwrap();
+    let config = Config {
+        dms_key: generate_random_string(),
+        members: vec![private_key.public_key()],
+    };
+    let mut dms = Dms::new(
+        StorageImpl::open(&path).await.unwrap(),
+        config.clone(),
+        private_key.clone(),
+    )
+    .await
+    .unwrap();
+    dms.commit_message(&"message".to_owned()).await.unwrap();
+    drop(dms);
+
+    // A different member set for the same height is rejected, keeping the messages.
+    let new_members = vec![private_key.public_key(), other_member.public_key()];
+    assert!(Dms::new(
+        StorageImpl::open(&path).await.unwrap(),
+        Config {
+            dms_key: config.dms_key.clone(),
+            members: new_members.clone(),
+        },
+        private_key.clone(),
+    )
+    .await
+    .is_err());
+    let dms = Dms::new(
+        StorageImpl::open(&path).await.unwrap(),
+        config,
+        private_key.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(dms.read_messages().await.unwrap().len(), 1);
+    drop(dms);
+
+    // The one for a new height reinitializes the storage.
+    let new_config = Config {
+        dms_key: generate_random_string(),
+        members: new_members,
+    };
+    let dms = Dms::new(
+        StorageImpl::open(&path).await.unwrap(),
+        new_config.clone(),
+        private_key,
+    )
+    .await
+    .unwrap();
+    assert_eq!(dms.get_config(), new_config);
+    assert!(dms.read_messages().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn query_message_with_proofs() {
+    let key = generate_random_string();
+    let ((_, private_key), _, _) = setup_server_client_nodes(1).await;
+    let mut dms = create_dms(
+        Config {
+            dms_key: key.clone(),
+            members: vec![private_key.public_key()],
+        },
+        private_key,
+    )
+    .await;
+    let message = "hello".to_owned();
+    dms.commit_message(&message).await.unwrap();
+
+    let queried = dms
+        .query_message_with_proofs(message.to_hash256())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(queried.message, message);
+    assert_eq!(queried.proofs.len(), 1);
+    queried.verify(&key).unwrap();
+    // The proofs are bound to the DMS key.
+    assert!(queried.verify(&generate_random_string()).is_err());
+
+    // A proof claimed by another committer is detected.
+    let mut tampered = queried.clone();
+    tampered.proofs[0].committer = generate_keypair_random().0;
+    assert!(tampered.verify(&key).is_err());
+    // So is a proof moved onto another message.
+    let mut tampered = queried;
+    tampered.message = "bye".to_owned();
+    assert!(tampered.verify(&key).is_err());
+
+    assert!(dms
+        .query_message_with_proofs(Hash256::hash("unknown"))
+        .await
+        .unwrap()
+        .is_none());
+}
+
 pub async fn setup_server_client_nodes(
     client_n: usize,
 ) -> (
@@ -73,6 +173,7 @@ pub async fn setup_server_client_nodes(
     let (_, server_private_key) = generate_keypair_random();
     let server = ServerNetworkConfig {
         port: dispense_port(),
+        rate_limit: None,
     };
     let mut clients = Vec::new();
     for _ in 0..client_n {
@@ -85,7 +186,7 @@ pub async fn setup_server_client_nodes(
                 ports: vec![("dms-test_dms_message".to_owned(), server.port)]
                     .into_iter()
                     .collect(),
-                message: "".to_owned(),
+                announcement: None,
                 recently_seen_timestamp: 0,
             }],
         };
@@ -210,7 +311,7 @@ async fn setup_client_nodes_with_specific_ports(
                 ports: vec![("dms-test_dms_message".to_owned(), *port)]
                     .into_iter()
                     .collect::<std::collections::BTreeMap<String, u16>>(),
-                message: "".to_owned(),
+                announcement: None,
                 recently_seen_timestamp: 0,
             });
         }
@@ -350,3 +451,721 @@ async fn multi_3() {
     // TODO: test with the server turing off and on repeatedly.
     // clients must be able to sync with each other even if the server is not available 100% of the time.
 }
+
+#[tokio::test]
+async fn broadcast_retry() {
+    let key = "broadcast_retry".to_owned();
+    let ((server_network_config, server_private_key), client_network_config_and_keys, members) =
+        setup_server_client_nodes(1).await;
+    let (client_network_config, client_private_key) =
+        client_network_config_and_keys.into_iter().next().unwrap();
+    let config = Config {
+        dms_key: key,
+        members,
+    };
+    let server_dms = Arc::new(RwLock::new(
+        create_dms(config.clone(), server_private_key).await,
+    ));
+    let client_path = create_temp_dir();
+    StorageImpl::create(&client_path).await.unwrap();
+    let client_dms = Arc::new(RwLock::new(
+        Dms::new(
+            StorageImpl::open(&client_path).await.unwrap(),
+            config.clone(),
+            client_private_key.clone(),
+        )
+        .await
+        .unwrap(),
+    ));
+    client_dms
+        .write()
+        .await
+        .commit_message(&"hello".to_owned())
+        .await
+        .unwrap();
+
+    // The server is down, so the message stays undelivered.
+    Dms::broadcast(Arc::clone(&client_dms), &client_network_config)
+        .await
+        .unwrap();
+    let server_task = tokio::spawn(Dms::serve(Arc::clone(&server_dms), server_network_config));
+    sleep_ms(500).await;
+    assert!(server_dms
+        .read()
+        .await
+        .read_messages()
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The next broadcast retries it.
+    Dms::broadcast(Arc::clone(&client_dms), &client_network_config)
+        .await
+        .unwrap();
+    let messages = server_dms.read().await.read_messages().await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].message, "hello");
+
+    // Once delivered, the message is not sent to the server again.
+    server_dms
+        .write()
+        .await
+        .remove_message(messages[0].message.to_hash256(), None)
+        .await
+        .unwrap();
+    Dms::broadcast(Arc::clone(&client_dms), &client_network_config)
+        .await
+        .unwrap();
+    assert!(server_dms
+        .read()
+        .await
+        .read_messages()
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The record of a packet goes away with the message.
+    let server_public_key = client_network_config.peers[0].public_key.clone();
+    client_dms
+        .write()
+        .await
+        .remove_message("hello".to_owned().to_hash256(), None)
+        .await
+        .unwrap();
+    client_dms
+        .write()
+        .await
+        .commit_message(&"world".to_owned())
+        .await
+        .unwrap();
+    Dms::broadcast(Arc::clone(&client_dms), &client_network_config)
+        .await
+        .unwrap();
+    assert_eq!(
+        client_dms
+            .read()
+            .await
+            .read_delivered_packets(&server_public_key)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    server_task.abort();
+
+    // The records are cleared for a new DMS key.
+    drop(client_dms);
+    let client_dms = Dms::new(
+        StorageImpl::open(&client_path).await.unwrap(),
+        Config {
+            dms_key: "broadcast_retry_next".to_owned(),
+            ..config
+        },
+        client_private_key,
+    )
+    .await
+    .unwrap();
+    assert!(client_dms
+        .read_delivered_packets(&server_public_key)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn broadcast_skips_peer_not_serving_dms() {
+    let key = "broadcast_skips_peer_not_serving_dms".to_owned();
+    let ((server_network_config, server_private_key), client_network_config_and_keys, members) =
+        setup_server_client_nodes(1).await;
+    let (mut client_network_config, client_private_key) =
+        client_network_config_and_keys.into_iter().next().unwrap();
+    // A peer that serves only the governance DMS, on the same port.
+    let (governance_peer_key, _) = generate_keypair_random();
+    client_network_config.peers.insert(
+        0,
+        Peer {
+            public_key: governance_peer_key.clone(),
+            name: "governance-only".to_owned(),
+            address: "127.0.0.1:1".parse().unwrap(),
+            ports: vec![("dms-governance".to_owned(), server_network_config.port)]
+                .into_iter()
+                .collect(),
+            announcement: None,
+            recently_seen_timestamp: 0,
+        },
+    );
+    assert_eq!(
+        client_network_config
+            .peers_serving("dms-test_dms_message")
+            .into_iter()
+            .map(|peer| peer.name.clone())
+            .collect::<Vec<_>>(),
+        vec!["server".to_owned()]
+    );
+
+    let config = Config {
+        dms_key: key,
+        members,
+    };
+    let server_dms = Arc::new(RwLock::new(
+        create_dms(config.clone(), server_private_key).await,
+    ));
+    let client_dms = Arc::new(RwLock::new(create_dms(config, client_private_key).await));
+    let server_task = tokio::spawn(Dms::serve(Arc::clone(&server_dms), server_network_config));
+    sleep_ms(500).await;
+    client_dms
+        .write()
+        .await
+        .commit_message(&"hello".to_owned())
+        .await
+        .unwrap();
+    Dms::broadcast(Arc::clone(&client_dms), &client_network_config)
+        .await
+        .unwrap();
+
+    let messages = server_dms.read().await.read_messages().await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(
+        client_dms
+            .read()
+            .await
+            .read_delivered_packets(&client_network_config.peers[1].public_key)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    // The governance-only peer is never contacted.
+    assert!(client_dms
+        .read()
+        .await
+        .read_delivered_packets(&governance_peer_key)
+        .await
+        .unwrap()
+        .is_empty());
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn serve_fails_on_bound_port() {
+    let key = generate_random_string();
+    let ((server_network_config, server_private_key), _, members) =
+        setup_server_client_nodes(1).await;
+    let _listener = std::net::TcpListener::bind(("0.0.0.0", server_network_config.port)).unwrap();
+    let dms = Arc::new(RwLock::new(
+        create_dms(
+            Config {
+                dms_key: key,
+                members,
+            },
+            server_private_key,
+        )
+        .await,
+    ));
+    let error = Dms::serve(dms, server_network_config).await.unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<std::io::Error>().unwrap().kind(),
+        std::io::ErrorKind::AddrInUse
+    );
+}
+
+#[tokio::test]
+async fn fetch_from_ipv6_peer() {
+    let key = "fetch_from_ipv6_peer".to_owned();
+    let ((server_network_config, server_private_key), client_network_config_and_keys, members) =
+        setup_server_client_nodes(1).await;
+    let (mut client_network_config, client_private_key) =
+        client_network_config_and_keys.into_iter().next().unwrap();
+    let port = server_network_config.port;
+    let peer = &mut client_network_config.peers[0];
+    peer.address = "[::1]:1".parse().unwrap();
+    assert_eq!(
+        peer.service_address("dms-test_dms_message")
+            .unwrap()
+            .to_string(),
+        format!("[::1]:{port}")
+    );
+
+    let config = Config {
+        dms_key: key,
+        members,
+    };
+    let server_dms = Arc::new(RwLock::new(
+        create_dms(config.clone(), server_private_key).await,
+    ));
+    server_dms
+        .write()
+        .await
+        .commit_message(&"hello".to_owned())
+        .await
+        .unwrap();
+    let client_dms = Arc::new(RwLock::new(create_dms(config, client_private_key).await));
+    let server_task = tokio::spawn(Dms::serve(Arc::clone(&server_dms), server_network_config));
+    sleep_ms(500).await;
+
+    Dms::fetch(Arc::clone(&client_dms), &client_network_config)
+        .await
+        .unwrap();
+    let messages = client_dms.read().await.read_messages().await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].message, "hello");
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn readonly_relay() {
+    let key = "readonly_relay".to_owned();
+    let ((server_network_config, _), client_network_config_and_keys, members) =
+        setup_server_client_nodes(2).await;
+    let config = Config {
+        dms_key: key,
+        members,
+    };
+    let path = create_temp_dir();
+    StorageImpl::create(&path).await.unwrap();
+    let storage = StorageImpl::open(&path).await.unwrap();
+    let server_dms = Arc::new(RwLock::new(
+        Dms::new_readonly(storage, config.clone()).await.unwrap(),
+    ));
+    assert!(server_dms.read().await.is_readonly());
+    assert!(server_dms
+        .write()
+        .await
+        .commit_message(&"hello".to_owned())
+        .await
+        .is_err());
+    let server_task = tokio::spawn(Dms::serve(Arc::clone(&server_dms), server_network_config));
+    sleep_ms(500).await;
+
+    let mut client_dmses = Vec::new();
+    for (_, private_key) in &client_network_config_and_keys {
+        client_dmses.push(Arc::new(RwLock::new(
+            create_dms(config.clone(), private_key.clone()).await,
+        )));
+    }
+    client_dmses[0]
+        .write()
+        .await
+        .commit_message(&"hello".to_owned())
+        .await
+        .unwrap();
+    Dms::broadcast(
+        Arc::clone(&client_dmses[0]),
+        &client_network_config_and_keys[0].0,
+    )
+    .await
+    .unwrap();
+    // The read-only server has verified and stored the commitment of the member.
+    let messages = server_dms.read().await.read_messages().await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(
+        messages[0].committers[0].committer,
+        client_network_config_and_keys[0].1.public_key()
+    );
+
+    // And serves it to the other member.
+    Dms::fetch(
+        Arc::clone(&client_dmses[1]),
+        &client_network_config_and_keys[1].0,
+    )
+    .await
+    .unwrap();
+    let messages = client_dmses[1].read().await.read_messages().await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].message, "hello");
+
+    // It answers the ping even without a private key.
+    let status = Dms::get_peer_status(
+        Arc::clone(&client_dmses[1]),
+        &client_network_config_and_keys[1].0,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status[0].last_ping, "success");
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn readonly_broadcast_and_fetch() {
+    let key = "readonly_broadcast_and_fetch".to_owned();
+    let ((server_network_config, server_private_key), client_network_config_and_keys, members) =
+        setup_server_client_nodes(1).await;
+    let (client_network_config, member_private_key) =
+        client_network_config_and_keys.into_iter().next().unwrap();
+    let config = Config {
+        dms_key: key,
+        members,
+    };
+    let server_dms = Arc::new(RwLock::new(
+        create_dms(config.clone(), server_private_key).await,
+    ));
+    server_dms
+        .write()
+        .await
+        .commit_message(&"world".to_owned())
+        .await
+        .unwrap();
+    let server_task = tokio::spawn(Dms::serve(Arc::clone(&server_dms), server_network_config));
+    sleep_ms(500).await;
+
+    // The read-only node has stored a message committed by a member.
+    let mut member_dms = create_dms(config.clone(), member_private_key).await;
+    member_dms
+        .commit_message(&"hello".to_owned())
+        .await
+        .unwrap();
+    let path = create_temp_dir();
+    StorageImpl::create(&path).await.unwrap();
+    let storage = StorageImpl::open(&path).await.unwrap();
+    let readonly_dms = Arc::new(RwLock::new(
+        Dms::new_readonly(storage, config).await.unwrap(),
+    ));
+    let packets = member_dms.retrieve_packets().await.unwrap();
+    readonly_dms
+        .write()
+        .await
+        .receive_packets(packets, &mut HashSet::new())
+        .await
+        .unwrap();
+
+    // It relays the message to the peer, and fetches the one of the peer.
+    Dms::broadcast(Arc::clone(&readonly_dms), &client_network_config)
+        .await
+        .unwrap();
+    Dms::fetch(Arc::clone(&readonly_dms), &client_network_config)
+        .await
+        .unwrap();
+    let messages = |messages: Vec<Message<String>>| {
+        let mut messages = messages
+            .into_iter()
+            .map(|message| message.message)
+            .collect::<Vec<_>>();
+        messages.sort();
+        messages
+    };
+    let expected = vec!["hello".to_owned(), "world".to_owned()];
+    assert_eq!(
+        messages(server_dms.read().await.read_messages().await.unwrap()),
+        expected
+    );
+    assert_eq!(
+        messages(readonly_dms.read().await.read_messages().await.unwrap()),
+        expected
+    );
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn rate_limit_per_peer() {
+    let key = "rate_limit_per_peer".to_owned();
+    let ((mut server_network_config, server_private_key), clients, members) =
+        setup_server_client_nodes(2).await;
+    server_network_config.rate_limit = Some(RateLimit {
+        burst: 3,
+        requests_per_second: 0.001,
+    });
+    let port = server_network_config.port;
+    let server_dms = Arc::new(RwLock::new(
+        create_dms(
+            Config {
+                dms_key: key.clone(),
+                members,
+            },
+            server_private_key,
+        )
+        .await,
+    ));
+    let server_task = tokio::spawn(Dms::serve(server_dms, server_network_config));
+    sleep_ms(500).await;
+
+    let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
+        format!("127.0.0.1:{port}/dms"),
+        reqwest::Client::new(),
+    )));
+    let (_, abusive_key) = &clients[0];
+    let (_, compliant_key) = &clients[1];
+    for _ in 0..3 {
+        let credential = RequestCredential::new(&key, "request_packets", abusive_key).unwrap();
+        stub.request_packets(Some(credential))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    let credential = RequestCredential::new(&key, "request_packets", abusive_key).unwrap();
+    let error = stub
+        .request_packets(Some(credential))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert!(error.contains("throttled"), "{error}");
+
+    for _ in 0..3 {
+        let credential = RequestCredential::new(&key, "request_packets", compliant_key).unwrap();
+        stub.request_packets(Some(credential))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    // The anonymous requests share one rate.
+    for _ in 0..3 {
+        stub.request_packets(None).await.unwrap().unwrap();
+    }
+    let error = stub.request_packets(None).await.unwrap().unwrap_err();
+    assert!(error.contains("anonymous requester"), "{error}");
+
+    // A credential signed for another DMS or another method is rejected.
+    let credential =
+        RequestCredential::new(&"other".to_owned(), "request_packets", compliant_key).unwrap();
+    assert!(stub
+        .request_packets(Some(credential))
+        .await
+        .unwrap()
+        .is_err());
+    let credential = RequestCredential::new(&key, "ping", compliant_key).unwrap();
+    assert!(stub
+        .request_packets(Some(credential))
+        .await
+        .unwrap()
+        .is_err());
+    server_task.abort();
+}
+
+#[test]
+fn default_rate_limit_for_anonymous_requests() {
+    let rate_limiter = RateLimiter::new(None);
+    // The members are not throttled without a configured rate limit.
+    let (member, _) = generate_keypair_random();
+    for _ in 0..DEFAULT_ANONYMOUS_RATE_LIMIT.burst * 2 {
+        rate_limiter.acquire(Some(&member)).unwrap();
+    }
+    // But the anonymous requests still are.
+    for _ in 0..DEFAULT_ANONYMOUS_RATE_LIMIT.burst {
+        rate_limiter.acquire(None).unwrap();
+    }
+    assert_eq!(
+        rate_limiter.acquire(None),
+        Err(ThrottledError { peer: None })
+    );
+}
+
+#[tokio::test]
+async fn replayed_credential_is_rejected() {
+    let key = "replayed_credential_is_rejected".to_owned();
+    let ((server_network_config, server_private_key), clients, members) =
+        setup_server_client_nodes(1).await;
+    let port = server_network_config.port;
+    let server_dms = Arc::new(RwLock::new(
+        create_dms(
+            Config {
+                dms_key: key.clone(),
+                members,
+            },
+            server_private_key,
+        )
+        .await,
+    ));
+    let server_task = tokio::spawn(Dms::serve(server_dms, server_network_config));
+    sleep_ms(500).await;
+
+    let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
+        format!("127.0.0.1:{port}/dms"),
+        reqwest::Client::new(),
+    )));
+    let (_, client_key) = &clients[0];
+    let credential = RequestCredential::new(&key, "request_packets", client_key).unwrap();
+    stub.request_packets(Some(credential.clone()))
+        .await
+        .unwrap()
+        .unwrap();
+    let error = stub
+        .request_packets(Some(credential))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert!(error.contains("replayed"), "{error}");
+
+    // Credentials arriving out of the order of their creation are all admitted.
+    let credentials = (0..3)
+        .map(|_| RequestCredential::new(&key, "request_packets", client_key).unwrap())
+        .collect::<Vec<_>>();
+    for credential in credentials.into_iter().rev() {
+        stub.request_packets(Some(credential))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    // A validly signed credential out of the freshness window is rejected.
+    let timestamp = simperby_core::utils::get_timestamp() - 2 * REQUEST_CREDENTIAL_VALIDITY;
+    let stale = RequestCredential {
+        timestamp,
+        nonce: 0,
+        signature: TypedSignature::sign(
+            &DmsRequestSignTarget {
+                dms_key: key.clone(),
+                method: "request_packets".to_owned(),
+                timestamp,
+                nonce: 0,
+            },
+            client_key,
+        )
+        .unwrap(),
+    };
+    let error = stub
+        .request_packets(Some(stale))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert!(error.contains("expired"), "{error}");
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn duplicate_packets() {
+    let key = generate_random_string();
+    let ((_, private_key), client_network_config_and_keys, members) =
+        setup_server_client_nodes(1).await;
+    let config = Config {
+        dms_key: key,
+        members,
+    };
+    let mut source = create_dms(config.clone(), private_key).await;
+    source.commit_message(&"hello".to_owned()).await.unwrap();
+    let packets = source.retrieve_packets().await.unwrap();
+    assert_eq!(packets.len(), 1);
+
+    let mut dms = create_dms(config, client_network_config_and_keys[0].1.clone()).await;
+    let duplicates = std::iter::repeat(packets[0].clone())
+        .take(1000)
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    assert_eq!(
+        dms.receive_packets(duplicates.clone(), &mut seen)
+            .await
+            .unwrap(),
+        1
+    );
+    // The same session skips them entirely.
+    assert_eq!(dms.receive_packets(duplicates, &mut seen).await.unwrap(), 0);
+    let messages = dms.read_messages().await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].committers.len(), 1);
+}
+
+/// Captures the log records of the DMS (by their target) while a `LogCapture` is alive.
+///
+/// The logger is global, so it is installed only once,
+/// and it keeps nothing when no capture is alive.
+struct CapturedLogs;
+
+static CAPTURED_LOGS: parking_lot::Mutex<Option<Vec<String>>> = parking_lot::const_mutex(None);
+
+impl log::Log for CapturedLogs {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata
+            .target()
+            .starts_with(module_path!().trim_end_matches("::tests"))
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Some(logs) = CAPTURED_LOGS.lock().as_mut() {
+            logs.push(format!("{} {}", record.level(), record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+struct LogCapture;
+
+impl LogCapture {
+    /// Starts capturing, returning `None` if another logger has been installed.
+    fn start() -> Option<Self> {
+        static INSTALLED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+        let installed = *INSTALLED.get_or_init(|| {
+            let installed = log::set_logger(&CapturedLogs).is_ok();
+            if installed {
+                log::set_max_level(log::LevelFilter::Debug);
+            }
+            installed
+        });
+        if !installed {
+            return None;
+        }
+        *CAPTURED_LOGS.lock() = Some(Vec::new());
+        Some(Self)
+    }
+
+    fn logs(&self) -> Vec<String> {
+        CAPTURED_LOGS.lock().clone().unwrap_or_default()
+    }
+}
+
+impl Drop for LogCapture {
+    fn drop(&mut self) {
+        *CAPTURED_LOGS.lock() = None;
+    }
+}
+
+#[tokio::test]
+async fn requests_are_logged() {
+    let capture = LogCapture::start().expect("the capturing logger must be installed");
+    let key = "requests_are_logged".to_owned();
+    let ((server_network_config, server_private_key), client_network_config_and_keys, members) =
+        setup_server_client_nodes(1).await;
+    let (client_network_config, client_private_key) =
+        client_network_config_and_keys.into_iter().next().unwrap();
+    let port = server_network_config.port;
+    let config = Config {
+        dms_key: key.clone(),
+        members,
+    };
+    let server_dms = Arc::new(RwLock::new(
+        create_dms(config.clone(), server_private_key).await,
+    ));
+    for message in ["hello", "world"] {
+        server_dms
+            .write()
+            .await
+            .commit_message(&message.to_owned())
+            .await
+            .unwrap();
+    }
+    let client_public_key = client_private_key.public_key();
+    let client_dms = Arc::new(RwLock::new(create_dms(config, client_private_key).await));
+    let server_task = tokio::spawn(Dms::serve(Arc::clone(&server_dms), server_network_config));
+    sleep_ms(500).await;
+
+    Dms::fetch(Arc::clone(&client_dms), &client_network_config)
+        .await
+        .unwrap();
+    // A request from a non-member fails, but only the peer would know it without the log.
+    let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
+        format!("127.0.0.1:{port}/dms"),
+        reqwest::Client::new(),
+    )));
+    let (stranger, stranger_key) = generate_keypair_random();
+    let credential = RequestCredential::new(&key, "request_packets", &stranger_key).unwrap();
+    assert!(stub
+        .request_packets(Some(credential))
+        .await
+        .unwrap()
+        .is_err());
+    server_task.abort();
+
+    let logs = capture.logs();
+    let has_log = |line: String| logs.iter().any(|log| *log == line);
+    assert!(has_log(format!(
+        "DEBUG [dms-test_dms_message] request_packets from {client_public_key}: 2 messages"
+    )));
+    assert!(has_log(
+        "DEBUG [dms-test_dms_message] fetched 2 messages from server (2 new)".to_owned()
+    ));
+    assert!(has_log(format!(
+        "WARN [dms-test_dms_message] request_packets from {stranger} failed: requester is not a member"
+    )));
+}
@@ -4,12 +4,13 @@ pub mod peers;
 
 #[cfg(never)]
 mod peer_discovery;
+mod rpc_server;
 pub mod storage;
 
 use serde::{Deserialize, Serialize};
-use simperby_core::{crypto::*, MemberName, Timestamp};
+use simperby_core::{crypto::*, serde_spb, MemberName, Timestamp};
 use std::collections::BTreeMap;
-use std::net::SocketAddrV4;
+use std::net::SocketAddr;
 
 pub type Error = eyre::Error;
 pub type Dms<T> = dms::DistributedMessageSet<storage::StorageImpl, T>;
@@ -23,25 +24,138 @@ pub struct Peer {
     pub public_key: PublicKey,
     pub name: MemberName,
     /// The address used for the discovery protocol
-    pub address: SocketAddrV4,
+    pub address: SocketAddr,
     /// For the other network services like gossip or RPC,
     /// it provides a map of `identifier->port`.
     pub ports: BTreeMap<String, u16>,
-    pub message: String,
+    /// The self-announcement of the peer, which `peers::Peers` requires and verifies.
+    pub announcement: Option<SignedPeerAnnouncement>,
     pub recently_seen_timestamp: Timestamp,
 }
 
+impl Peer {
+    /// Returns the address of the given network service of the peer, if it provides one.
+    ///
+    /// Note that its `Display` wraps an IPv6 address in brackets (e.g., `[::1]:8080`),
+    /// so it can be put in a URL as is.
+    pub fn service_address(&self, identifier: &str) -> Option<SocketAddr> {
+        self.ports
+            .get(identifier)
+            .map(|port| SocketAddr::new(self.address.ip(), *port))
+    }
+
+    /// Verifies that the peer has an announcement signed by itself for its address.
+    pub fn verify_announcement(&self) -> Result<(), Error> {
+        let announcement = self
+            .announcement
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("no announcement"))?;
+        announcement.verify(&self.public_key)?;
+        if announcement.announcement.address != self.address {
+            return Err(eyre::eyre!(
+                "announced address {} differs from {}",
+                announcement.announcement.address,
+                self.address
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The maximum difference (in milliseconds) between the timestamp of a `PeerAnnouncement`
+/// and the clock of the node that adds the peer with it.
+pub const PEER_ANNOUNCEMENT_MAX_AGE: Timestamp = 600_000;
+
+/// The information that a peer advertises about itself.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct PeerAnnouncement {
+    /// The advertised address used for the discovery protocol.
+    pub address: SocketAddr,
+    /// The advertised map of `identifier->port` for the other network services.
+    pub ports: BTreeMap<String, u16>,
+    pub timestamp: Timestamp,
+}
+
+impl ToHash256 for PeerAnnouncement {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
+impl SignatureDomain for PeerAnnouncement {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-peer-announcement";
+}
+
+/// A `PeerAnnouncement` signed by the announcing peer.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SignedPeerAnnouncement {
+    pub announcement: PeerAnnouncement,
+    pub signature: TypedSignature<PeerAnnouncement>,
+}
+
+impl SignedPeerAnnouncement {
+    pub fn sign(announcement: PeerAnnouncement, private_key: &PrivateKey) -> Result<Self, Error> {
+        let signature = TypedSignature::sign(&announcement, private_key)?;
+        Ok(Self {
+            announcement,
+            signature,
+        })
+    }
+
+    /// Verifies that the announcement is signed by the given public key.
+    pub fn verify(&self, public_key: &PublicKey) -> Result<(), Error> {
+        if self.signature.signer() != public_key {
+            return Err(eyre::eyre!(
+                "announcement is signed by {}, not by the peer {}",
+                self.signature.signer(),
+                public_key
+            ));
+        }
+        self.signature.verify(&self.announcement)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientNetworkConfig {
     /// The peer nodes to broadcast the message.
     pub peers: Vec<Peer>,
 }
 
+impl ClientNetworkConfig {
+    /// Returns the peers that provide the given network service (see `Peer::service_address()`).
+    pub fn peers_serving(&self, identifier: &str) -> Vec<&Peer> {
+        self.peers
+            .iter()
+            .filter(|peer| peer.ports.contains_key(identifier))
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerNetworkConfig {
     pub port: u16, // TODO: add various configurations for NAT traversal
+    /// The request rate allowed for each peer. `None` means unlimited for the members,
+    /// while the anonymous requests are still limited by `DEFAULT_ANONYMOUS_RATE_LIMIT`.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
 }
 
+/// A per-peer request rate, enforced with a token bucket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// The number of requests that a peer can make at once.
+    pub burst: u32,
+    /// The number of requests regained by a peer every second.
+    pub requests_per_second: f64,
+}
+
+/// The rate shared by all the anonymous requests to a server without a configured rate limit.
+pub const DEFAULT_ANONYMOUS_RATE_LIMIT: RateLimit = RateLimit {
+    burst: 100,
+    requests_per_second: 20.0,
+};
+
 pub mod keys {
     use simperby_core::*;
 
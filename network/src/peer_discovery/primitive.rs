@@ -1,6 +1,6 @@
 use super::behaviour::{DiscoveryBehaviour, DiscoveryEvent};
 use super::utils::{
-    convert_keypair, convert_multiaddr_into_sockv4, convert_public_key, get_peer_id,
+    convert_keypair, convert_multiaddr_into_sockaddr, convert_public_key, get_peer_id,
 };
 use crate::{primitives::PeerDiscoveryPrimitive, *};
 use async_trait::async_trait;
@@ -153,7 +153,7 @@ impl PeerDiscoveryPrimitiveImpl {
         let public_ip_addr = info
             .listen_addrs
             .iter()
-            .filter_map(|multiaddr| convert_multiaddr_into_sockv4(multiaddr.to_owned()).ok())
+            .filter_map(|multiaddr| convert_multiaddr_into_sockaddr(multiaddr.to_owned()).ok())
             .find(|address| global_v4(address.ip()) || address.ip().is_loopback())
             .ok_or_else(|| eyre!("no public ip address found"))?;
         let (message, ports) = serde_spb::from_str(&info.agent_version)?;
@@ -5,6 +5,7 @@ use libp2p::{
     multiaddr::{Multiaddr, Protocol},
     PeerId,
 };
+use std::net::IpAddr;
 
 /// Converts a simperby keypair into a libp2p keypair.
 pub(crate) fn convert_keypair(
@@ -35,10 +36,10 @@ pub(crate) fn get_peer_id(peer: &Peer) -> Result<PeerId, Error> {
     }
 }
 
-/// Converts libp2p Multiaddr into SocketAddrV4.
-pub(crate) fn convert_multiaddr_into_sockv4(
+/// Converts libp2p Multiaddr into SocketAddr.
+pub(crate) fn convert_multiaddr_into_sockaddr(
     mut multiaddr: Multiaddr,
-) -> Result<SocketAddrV4, Error> {
+) -> Result<SocketAddr, Error> {
     let port = loop {
         if let Protocol::Tcp(port) = multiaddr
             .pop()
@@ -47,15 +48,17 @@ pub(crate) fn convert_multiaddr_into_sockv4(
             break port;
         }
     };
-    let ip = loop {
-        if let Protocol::Ip4(ipv4_addr) = multiaddr
+    let ip: IpAddr = loop {
+        match multiaddr
             .pop()
-            .ok_or_else(|| eyre!("multiaddr does not contain an ipv4 address"))?
+            .ok_or_else(|| eyre!("multiaddr does not contain an ip address"))?
         {
-            break ipv4_addr;
+            Protocol::Ip4(ipv4_addr) => break ipv4_addr.into(),
+            Protocol::Ip6(ipv6_addr) => break ipv6_addr.into(),
+            _ => (),
         }
     };
-    Ok(SocketAddrV4::new(ip, port))
+    Ok(SocketAddr::new(ip, port))
 }
 
 #[cfg(test)]
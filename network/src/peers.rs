@@ -57,12 +57,14 @@ pub struct PingResponse {
 pub(super) trait PeerRpcInterface: Send + Sync + 'static {
     /// Requests to response some packets.
     async fn ping(&self) -> Result<PingResponse, String>;
-    /// Requests to response the port map of this node.
-    async fn port_map(&self) -> Result<BTreeMap<String, u16>, String>;
+    /// Requests to announce this node at its configured address,
+    /// along with the port map of this node.
+    async fn announce(&self) -> Result<SignedPeerAnnouncement, String>;
 }
 
 pub struct PeerRpcImpl {
     peers: Arc<RwLock<Peers>>,
+    address: SocketAddr,
     port_map: BTreeMap<String, u16>,
 }
 
@@ -79,8 +81,17 @@ impl PeerRpcInterface for PeerRpcImpl {
         })
     }
 
-    async fn port_map(&self) -> Result<BTreeMap<String, u16>, String> {
-        Ok(self.port_map.clone())
+    async fn announce(&self) -> Result<SignedPeerAnnouncement, String> {
+        let peers = self.peers.read().await;
+        SignedPeerAnnouncement::sign(
+            PeerAnnouncement {
+                address: self.address,
+                ports: self.port_map.clone(),
+                timestamp: simperby_core::utils::get_timestamp(),
+            },
+            &peers.private_key,
+        )
+        .map_err(|e| e.to_string())
     }
 }
 
@@ -101,21 +112,71 @@ impl Peers {
         })
     }
 
+    /// Updates the last finalized block, verifying the peers again against its reserved state.
+    ///
+    /// The peers that fail the verification (e.g., no longer members) are dropped with a log.
     pub async fn update_block(&mut self, lfi: FinalizationInfo) -> Result<()> {
         let peers = self.storage.read().await?;
-        self.storage.write(vec![]).await?;
+        self.lfi = lfi;
+        let mut new_peers = Vec::new();
         for peer in peers {
-            self.add_peer(peer.name, peer.address).await?;
+            let Some(announcement) = peer.announcement else {
+                log::warn!("dropping peer {}: no announcement", peer.name);
+                continue;
+            };
+            match self.verify_peer(peer.name.clone(), peer.address, announcement) {
+                Ok(peer) => new_peers.push(peer),
+                Err(e) => log::warn!("dropping peer {}: {}", peer.name, e),
+            }
         }
-        self.lfi = lfi;
+        self.storage.write(new_peers).await?;
         Ok(())
     }
 
+    /// Requests the self-announcement of the peer at the given address.
+    ///
+    /// `address` is the one of the peer discovery RPC. The returned announcement is not verified;
+    /// `add_peer()` verifies it against the public key of the peer.
+    pub async fn fetch_announcement(address: SocketAddr) -> Result<SignedPeerAnnouncement> {
+        let stub = PeerRpcInterfaceStub::new(Box::new(HttpClient::new(
+            format!("{address}/peer"),
+            reqwest::Client::new(),
+        )));
+        stub.announce()
+            .await
+            .map_err(|e| eyre!("failed to get announcement from {}: {}", address, e))?
+            .map_err(|e| eyre!("failed to get announcement from {}: {}", address, e))
+    }
+
     /// Adds a peer to the list of known peers. This will try to connect to the peer and ask information.
     ///
     /// - `name` - the name of the peer as it is known in the reserved state.
     /// - `addr` - the address of the peer. The port must be the one of the peer discovery RPC.
-    pub async fn add_peer(&mut self, name: MemberName, addr: SocketAddrV4) -> Result<()> {
+    /// - `announcement` - the self-announcement of the peer (see `fetch_announcement()`).
+    ///   It is rejected if not signed by the peer's public key, if it announces another address,
+    ///   or if its timestamp is off by more than `PEER_ANNOUNCEMENT_MAX_AGE`.
+    pub async fn add_peer(
+        &mut self,
+        name: MemberName,
+        addr: SocketAddr,
+        announcement: SignedPeerAnnouncement,
+    ) -> Result<()> {
+        check_announcement_age(&announcement)
+            .map_err(|e| eyre!("invalid announcement of peer {}: {}", name, e))?;
+        let peer = self.verify_peer(name, addr, announcement)?;
+        let mut peers = self.storage.read().await?;
+        peers.push(peer);
+        self.storage.write(peers).await?;
+        Ok(())
+    }
+
+    /// Builds a peer of the given member, checking that it has announced itself at `addr`.
+    fn verify_peer(
+        &self,
+        name: MemberName,
+        addr: SocketAddr,
+        announcement: SignedPeerAnnouncement,
+    ) -> Result<Peer> {
         let peer = Peer {
             public_key: self
                 .lfi
@@ -124,14 +185,13 @@ impl Peers {
                 .ok_or_else(|| eyre!("peer does not exist: {}", name))?,
             name,
             address: addr,
-            ports: Default::default(),
-            message: "".to_owned(),
+            ports: announcement.announcement.ports.clone(),
+            announcement: Some(announcement),
             recently_seen_timestamp: 0,
         };
-        let mut peers = self.storage.read().await?;
-        peers.push(peer);
-        self.storage.write(peers).await?;
-        Ok(())
+        peer.verify_announcement()
+            .map_err(|e| eyre!("invalid announcement of peer {}: {}", peer.name, e))?;
+        Ok(peer)
     }
 
     /// Removes a peer in the list of known peers.
@@ -153,42 +213,86 @@ impl Peers {
 
         for peer in peers {
             let stub = PeerRpcInterfaceStub::new(Box::new(HttpClient::new(
-                format!("{}:{}/peer", peer.address.ip(), peer.address.port()),
+                format!("{}/peer", peer.address),
                 reqwest::Client::new(),
             )));
             stub.ping()
                 .await
                 .map_err(|e| eyre!("failed to ping peer {}: {}", peer.name, e))?
                 .map_err(|e| eyre!("failed to ping peer {}: {}", peer.name, e))?;
-            let ports = stub
-                .port_map()
+            let announcement = stub
+                .announce()
                 .await
-                .map_err(|e| eyre!("failed to get port map {}: {}", peer.name, e))?
-                .map_err(|e| eyre!("failed to get port map {}: {}", peer.name, e))?;
+                .map_err(|e| eyre!("failed to get announcement {}: {}", peer.name, e))?
+                .map_err(|e| eyre!("failed to get announcement {}: {}", peer.name, e))?;
+            check_announcement_age(&announcement)
+                .map_err(|e| eyre!("invalid announcement of peer {}: {}", peer.name, e))?;
 
             let mut new_peer = peer.clone();
-            new_peer.ports = ports;
+            new_peer.ports = announcement.announcement.ports.clone();
+            new_peer.announcement = Some(announcement);
+            new_peer
+                .verify_announcement()
+                .map_err(|e| eyre!("invalid announcement of peer {}: {}", peer.name, e))?;
             new_peers.push(new_peer);
         }
         self.storage.write(new_peers).await?;
         Ok(())
     }
 
+    /// Asks each peer for the header of its last finalized block.
+    ///
+    /// The peers that can't be reached or that answer with a different public key are skipped.
+    pub async fn query_last_finalized_headers(&self) -> Result<Vec<(Peer, BlockHeader)>> {
+        let mut headers = Vec::new();
+        for peer in self.storage.read().await? {
+            let stub = PeerRpcInterfaceStub::new(Box::new(HttpClient::new(
+                format!("{}/peer", peer.address),
+                reqwest::Client::new(),
+            )));
+            let response = match stub.ping().await {
+                Ok(Ok(response)) => response,
+                Ok(Err(e)) => {
+                    log::warn!("failed to ping peer {}: {}", peer.name, e);
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("failed to ping peer {}: {}", peer.name, e);
+                    continue;
+                }
+            };
+            if response.public_key != peer.public_key {
+                log::warn!(
+                    "peer {} answered with a different public key: {}",
+                    peer.name,
+                    response.public_key
+                );
+                continue;
+            }
+            headers.push((peer, response.last_finalized_block_header));
+        }
+        Ok(headers)
+    }
+
     pub async fn list_peers(&self) -> Result<Vec<Peer>> {
         self.storage.read().await
     }
 
+    /// Runs the peer discovery RPC server, announcing this node at `address`
+    /// (the one that the peers dial, with the port of `server_network_config`).
     pub async fn serve(
         this: Arc<RwLock<Peers>>,
+        address: SocketAddr,
         port_map: BTreeMap<String, u16>,
         server_network_config: ServerNetworkConfig,
     ) -> Result<(), Error> {
-        run_server(
+        crate::rpc_server::run_server(
             server_network_config.port,
             [(
                 "peer".to_owned(),
                 create_http_object(Arc::new(PeerRpcImpl {
                     peers: Arc::clone(&this),
+                    address,
                     port_map,
                 }) as Arc<dyn PeerRpcInterface>),
             )]
@@ -196,8 +300,143 @@ impl Peers {
             .cloned()
             .collect(),
         )
-        .await;
-        std::future::pending::<()>().await;
+        .await?;
         Ok(())
     }
 }
+
+/// Checks that the announcement is neither too old nor from the future.
+fn check_announcement_age(announcement: &SignedPeerAnnouncement) -> Result<()> {
+    let timestamp = announcement.announcement.timestamp;
+    if (simperby_core::utils::get_timestamp() - timestamp).abs() > PEER_ANNOUNCEMENT_MAX_AGE {
+        return Err(eyre!("announcement expired: timestamp {}", timestamp));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simperby_test_suite::*;
+
+    async fn setup_peers() -> (Peers, Vec<(PublicKey, PrivateKey)>) {
+        let (fi, keys) = simperby_core::test_utils::generate_fi(4);
+        let path = format!("{}/peers.json", create_temp_dir());
+        let mut peers = Peers::new(&path, fi, keys[0].1.clone()).await.unwrap();
+        peers.storage.write(vec![]).await.unwrap();
+        (peers, keys)
+    }
+
+    fn announcement(address: SocketAddr) -> PeerAnnouncement {
+        PeerAnnouncement {
+            address,
+            ports: vec![("dms-governance".to_owned(), 1234)]
+                .into_iter()
+                .collect(),
+            timestamp: simperby_core::utils::get_timestamp(),
+        }
+    }
+
+    #[tokio::test]
+    async fn add_peer_with_announcement() {
+        let (mut peers, keys) = setup_peers().await;
+        let address: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let signed = SignedPeerAnnouncement::sign(announcement(address), &keys[1].1).unwrap();
+        peers
+            .add_peer("member-0001".to_owned(), address, signed.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            peers.list_peers().await.unwrap()[0].announcement,
+            Some(signed)
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_forged_announcement() {
+        let (mut peers, keys) = setup_peers().await;
+        let address: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+        // Signed by another member.
+        let forged = SignedPeerAnnouncement::sign(announcement(address), &keys[2].1).unwrap();
+        assert!(peers
+            .add_peer("member-0001".to_owned(), address, forged)
+            .await
+            .is_err());
+
+        // Tampered after being signed.
+        let mut tampered = SignedPeerAnnouncement::sign(announcement(address), &keys[1].1).unwrap();
+        tampered.announcement.address = "127.0.0.2:1".parse().unwrap();
+        assert!(peers
+            .add_peer("member-0001".to_owned(), address, tampered)
+            .await
+            .is_err());
+
+        // Announced too long ago.
+        let mut stale = announcement(address);
+        stale.timestamp -= 2 * PEER_ANNOUNCEMENT_MAX_AGE;
+        let stale = SignedPeerAnnouncement::sign(stale, &keys[1].1).unwrap();
+        assert!(peers
+            .add_peer("member-0001".to_owned(), address, stale)
+            .await
+            .is_err());
+
+        // Announced for another address.
+        let other =
+            SignedPeerAnnouncement::sign(announcement("127.0.0.2:1".parse().unwrap()), &keys[1].1)
+                .unwrap();
+        assert!(peers
+            .add_peer("member-0001".to_owned(), address, other)
+            .await
+            .is_err());
+
+        assert!(peers.list_peers().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_block_drops_unverified_peers() {
+        let (mut peers, keys) = setup_peers().await;
+        let address: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let signed = SignedPeerAnnouncement::sign(announcement(address), &keys[1].1).unwrap();
+        peers
+            .add_peer("member-0001".to_owned(), address, signed)
+            .await
+            .unwrap();
+        let mut stored = peers.list_peers().await.unwrap();
+        let mut without_announcement = stored[0].clone();
+        without_announcement.name = "member-0002".to_owned();
+        without_announcement.public_key = keys[2].0.clone();
+        without_announcement.announcement = None;
+        stored.insert(0, without_announcement);
+        peers.storage.write(stored).await.unwrap();
+
+        let lfi = peers.lfi.clone();
+        peers.update_block(lfi).await.unwrap();
+        let names = peers
+            .list_peers()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|peer| peer.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["member-0001".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn ipv6_repository_url() {
+        let (mut peers, keys) = setup_peers().await;
+        let address: SocketAddr = "[::1]:1".parse().unwrap();
+        let signed = SignedPeerAnnouncement::sign(announcement(address), &keys[1].1).unwrap();
+        peers
+            .add_peer("member-0001".to_owned(), address, signed)
+            .await
+            .unwrap();
+        let mut peer = peers.list_peers().await.unwrap().remove(0);
+        assert_eq!(peer.address, address);
+        peer.ports.insert("repository".to_owned(), 9418);
+        assert_eq!(
+            format!("git://{}/", peer.service_address("repository").unwrap()),
+            "git://[::1]:9418/"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! The HTTP server for the RPC interfaces of the network services.
+
+use axum::{extract::Path, http::StatusCode, routing::post, Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use serde_tc::http::HttpInterface;
+use serde_tc::{DispatchStringDictAsync, DispatchStringTupleAsync};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
+use std::sync::Arc;
+
+type Objects = HashMap<String, Arc<dyn HttpInterface>>;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawArg {
+    method: String,
+    params: Value,
+}
+
+/// Runs an RPC server for the given objects, each of which is served at `/<key>`.
+///
+/// Unlike `serde_tc::http::run_server()`, which listens on IPv4 only,
+/// it listens on both IPv4 and IPv6 (or on IPv4 only if IPv6 is not available),
+/// so that the peers can reach it with either address.
+///
+/// It fails if it can't bind the port or the server stops with an error.
+pub(crate) async fn run_server(port: u16, objects: Objects) -> std::io::Result<()> {
+    let listener = bind(port)?;
+    let app = Router::new()
+        .route("/:key", post(dispatch))
+        .layer(Extension(Arc::new(objects)));
+    axum::Server::from_tcp(listener)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+        .serve(app.into_make_service())
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+/// Binds a dual-stack socket, falling back to an IPv4 one.
+fn bind(port: u16) -> std::io::Result<TcpListener> {
+    let listener = match bind_dual_stack(port) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::warn!("failed to listen on IPv6 at port {port}, falling back to IPv4: {e}");
+            TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))?
+        }
+    };
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+fn bind_dual_stack(port: u16) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    // Accepts the IPv4 connections as well, regardless of the default of the platform.
+    socket.set_only_v6(false)?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+async fn dispatch(
+    Path(key): Path<String>,
+    Json(args): Json<RawArg>,
+    Extension(objects): Extension<Arc<Objects>>,
+) -> (StatusCode, Json<Value>) {
+    let Some(object) = objects.get(&key) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "object not found", "object": key })),
+        );
+    };
+    let arguments = args.params.to_string();
+    let result = if args.params.is_array() {
+        DispatchStringTupleAsync::dispatch(object.as_ref(), &args.method, &arguments).await
+    } else if args.params.is_object() {
+        DispatchStringDictAsync::dispatch(object.as_ref(), &args.method, &arguments).await
+    } else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("invalid argument type: {}", args.params) })),
+        );
+    };
+    match result.map(|x| serde_json::from_str(&x)) {
+        Ok(Ok(value)) => (StatusCode::OK, Json(value)),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("invalid response: {e}") })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "error": "invalid http request",
+                "error_message": e.to_string(),
+                "request": args,
+            })),
+        ),
+    }
+}
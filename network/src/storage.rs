@@ -37,6 +37,9 @@ pub trait Storage: Send + Sync + 'static {
     async fn remove_all_files(&mut self) -> Result<(), StorageError>;
 }
 
+/// The suffix of the temporary files used for atomic writes.
+const TEMP_FILE_SUFFIX: &str = ".tmp";
+
 pub struct StorageImpl {
     lock_file: Option<std::fs::File>,
     path: String,
@@ -80,7 +83,7 @@ impl Storage for StorageImpl {
         Ok(files
             .into_iter()
             .map(|file| file.file_name().into_string().unwrap())
-            .filter(|file| file != "lock")
+            .filter(|file| file != "lock" && !file.ends_with(TEMP_FILE_SUFFIX))
             .collect())
     }
 
@@ -89,11 +92,10 @@ impl Storage for StorageImpl {
         name: &str,
         content: String,
     ) -> Result<(), StorageError> {
-        let mut file = fs::File::create(format!("{}/{}", self.path, name)).await?;
-        file.write_all(content.as_bytes()).await?;
-        // IMPORTANT!
-        file.flush().await?;
-        Ok(())
+        // Write to a temporary file first and then rename it over the target,
+        // so that a crash in the middle never leaves a partially written file.
+        let temp_path = self.write_temp_file(name, content).await?;
+        fs::rename(temp_path, format!("{}/{}", self.path, name)).await
     }
 
     async fn read_file(&self, name: &str) -> Result<String, StorageError> {
@@ -113,6 +115,19 @@ impl Storage for StorageImpl {
     }
 }
 
+impl StorageImpl {
+    /// Writes the content to the temporary file of `name` and returns its path.
+    async fn write_temp_file(&self, name: &str, content: String) -> Result<String, StorageError> {
+        let temp_path = format!("{}/{}{}", self.path, name, TEMP_FILE_SUFFIX);
+        let mut file = fs::File::create(&temp_path).await?;
+        file.write_all(content.as_bytes()).await?;
+        // IMPORTANT!
+        file.flush().await?;
+        file.sync_all().await?;
+        Ok(temp_path)
+    }
+}
+
 impl Drop for StorageImpl {
     fn drop(&mut self) {
         let lock_file = self.lock_file.take().unwrap();
@@ -211,6 +226,39 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn crash_before_rename() {
+        let dir = gerenate_random_storage_directory();
+        StorageImpl::create(&dir).await.unwrap();
+        let mut storage = StorageImpl::open(&dir).await.unwrap();
+
+        let name = generate_random_string();
+        let old_content = generate_random_string();
+        storage
+            .add_or_overwrite_file(&name, old_content.clone())
+            .await
+            .unwrap();
+
+        // Simulate a crash right after writing the temporary file.
+        storage
+            .write_temp_file(&name, generate_random_string())
+            .await
+            .unwrap();
+        drop(storage);
+
+        let mut storage = StorageImpl::open(&dir).await.unwrap();
+        assert_eq!(storage.read_file(&name).await.unwrap(), old_content);
+        assert_eq!(storage.list_files().await.unwrap(), vec![name.clone()]);
+
+        // A later write must replace the stale temporary file.
+        let new_content = generate_random_string();
+        storage
+            .add_or_overwrite_file(&name, new_content.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.read_file(&name).await.unwrap(), new_content);
+    }
+
     #[tokio::test]
     async fn never_interrupted() {
         let dir = gerenate_random_storage_directory();
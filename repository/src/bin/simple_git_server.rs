@@ -75,7 +75,7 @@ async fn main() {
             let commit_hash = CommitHash {
                 hash: hex::decode(&commit).unwrap().try_into().unwrap(),
             };
-            // TODO: handle inner Result<(), String>.
+            // TODO: handle `SyncOutcome::Rejected`.
             let result = drepo.sync(commit_hash).await;
             match result {
                 Ok(_) => std::process::exit(0),
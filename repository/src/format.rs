@@ -1,8 +1,67 @@
 use crate::{raw::RawCommit, raw::SemanticCommit, UNKNOWN_COMMIT_AUTHOR};
 use eyre::{eyre, Error};
 use regex::Regex;
+use serde::Serialize;
 use simperby_core::{reserved::ReservedState, *};
 
+/// The version of the encoding of the commits that Simperby creates.
+///
+/// It is tagged at the end of the body of every such commit, so it is a part of the commit hash.
+/// Any change to the encoding must come with a new version,
+/// so that nodes of different versions never silently disagree on the commit hashes.
+pub const SEMANTIC_COMMIT_FORMAT_VERSION: u32 = 1;
+
+/// The prefix of the last paragraph of the body, which holds the format version.
+const FORMAT_VERSION_TAG: &str = "format-version: ";
+
+/// Encodes the data into the body of a commit, tagged with the format version.
+fn encode_body<T: Serialize>(data: &T) -> String {
+    format!(
+        "{}\n\n{FORMAT_VERSION_TAG}{SEMANTIC_COMMIT_FORMAT_VERSION}",
+        serde_spb::to_string(data).unwrap()
+    )
+}
+
+/// Splits the body of a commit into the encoded data and the format version.
+fn decode_body(body: &str) -> Option<(&str, u32)> {
+    let (data, tag) = body.rsplit_once("\n\n")?;
+    let version = tag.strip_prefix(FORMAT_VERSION_TAG)?.parse().ok()?;
+    Some((data, version))
+}
+
+/// The prefix of the last paragraph of the body of a signed transaction, which holds the signature.
+const AUTHOR_SIGNATURE_TAG: &str = "author-signature: ";
+
+/// Appends the author signature of the transaction, if any, to its body.
+fn encode_transaction_body(transaction: &Transaction) -> String {
+    match &transaction.author_signature {
+        Some(signature) => {
+            let tag = format!(
+                "{AUTHOR_SIGNATURE_TAG}{}",
+                serde_spb::to_string(signature).unwrap()
+            );
+            if transaction.body.is_empty() {
+                tag
+            } else {
+                format!("{}\n\n{tag}", transaction.body)
+            }
+        }
+        None => transaction.body.clone(),
+    }
+}
+
+/// Splits the body of a transaction into the original body and the author signature, if any.
+fn decode_transaction_body(body: &str) -> (&str, Option<TypedSignature<Transaction>>) {
+    let (data, tag) = body.rsplit_once("\n\n").unwrap_or(("", body));
+    match tag
+        .strip_prefix(AUTHOR_SIGNATURE_TAG)
+        .and_then(|signature| serde_spb::from_str(signature).ok())
+    {
+        Some(signature) => (data, Some(signature)),
+        None => (body, None),
+    }
+}
+
 /// Converts a commit to a semantic commit.
 pub fn to_semantic_commit(
     commit: &Commit,
@@ -11,7 +70,7 @@ pub fn to_semantic_commit(
     match commit {
         Commit::Agenda(agenda) => {
             let title = format!(">agenda: {}", agenda.height);
-            let body = serde_spb::to_string(agenda).unwrap();
+            let body = encode_body(agenda);
             Ok(SemanticCommit {
                 title,
                 body,
@@ -22,7 +81,7 @@ pub fn to_semantic_commit(
         }
         Commit::Block(block_header) => {
             let title = format!(">block: {}", block_header.height);
-            let body = serde_spb::to_string(block_header).unwrap();
+            let body = encode_body(block_header);
             Ok(SemanticCommit {
                 title,
                 body,
@@ -44,14 +103,14 @@ pub fn to_semantic_commit(
         }
         Commit::Transaction(transaction) => Ok(SemanticCommit {
             title: transaction.head.clone(),
-            body: transaction.body.clone(),
+            body: encode_transaction_body(transaction),
             diff: transaction.diff.clone(),
             author: transaction.author.clone(),
             timestamp: transaction.timestamp,
         }),
         Commit::AgendaProof(agenda_proof) => {
             let title = format!(">agenda-proof: {}", agenda_proof.height);
-            let body = serde_spb::to_string(agenda_proof).unwrap();
+            let body = encode_body(agenda_proof);
             Ok(SemanticCommit {
                 title,
                 body,
@@ -61,14 +120,16 @@ pub fn to_semantic_commit(
             })
         }
         Commit::ExtraAgendaTransaction(tx) => {
-            let body = serde_spb::to_string(tx).unwrap();
+            let body = encode_body(tx);
             match tx {
                 ExtraAgendaTransaction::Delegate(tx) => {
                     let title = format!(
                         ">tx-delegate: {} to {}",
                         tx.data.delegator, tx.data.delegatee
                     );
-                    let diff = Diff::Reserved(Box::new(reserved_state.apply_delegate(tx).unwrap()));
+                    let base = reserved_state.to_hash256();
+                    let diff =
+                        Diff::Reserved(Box::new(reserved_state.apply_delegate(tx).unwrap()), base);
                     Ok(SemanticCommit {
                         title,
                         body,
@@ -79,8 +140,11 @@ pub fn to_semantic_commit(
                 }
                 ExtraAgendaTransaction::Undelegate(tx) => {
                     let title = format!(">tx-undelegate: {}", tx.data.delegator);
-                    let diff =
-                        Diff::Reserved(Box::new(reserved_state.apply_undelegate(tx).unwrap()));
+                    let base = reserved_state.to_hash256();
+                    let diff = Diff::Reserved(
+                        Box::new(reserved_state.apply_undelegate(tx).unwrap()),
+                        base,
+                    );
                     Ok(SemanticCommit {
                         title,
                         body,
@@ -98,159 +162,192 @@ pub fn to_semantic_commit(
     }
 }
 
+/// The prefix of the title of every commit that Simperby creates, except general transactions.
+pub const SIMPERBY_COMMIT_TITLE_PREFIX: &str = ">";
+
+/// An error from parsing a semantic commit into a `Commit`.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum CommitParseError {
+    /// The title is reserved for Simperby, but of no known commit kind.
+    #[error("unrecognized commit: {0}")]
+    Unrecognized(String),
+    /// The title names a known commit kind, but the commit is ill-formed.
+    #[error("malformed commit ({0}): {1}")]
+    Malformed(String, String),
+    /// The commit is encoded in a format version other than `SEMANTIC_COMMIT_FORMAT_VERSION`.
+    #[error("unsupported format version of commit ({0}): {1}")]
+    UnsupportedVersion(String, u32),
+}
+
 /// Converts a semantic commit to a commit.
 ///
+/// A commit whose title doesn't start with `SIMPERBY_COMMIT_TITLE_PREFIX` is a general transaction.
+///
 /// TODO: retrieve author and timestamp from the commit metadata.
-pub fn from_semantic_commit(semantic_commit: SemanticCommit) -> Result<Commit, Error> {
+pub fn from_semantic_commit(semantic_commit: SemanticCommit) -> Result<Commit, CommitParseError> {
     let pattern = Regex::new(
         r"^>(((agenda)|(block)|(agenda-proof)): (\d+))|((tx-delegate): ((\D+)-(\d+)) to ((\D+)-(\d+)))|((tx-undelegate): ((\D+)-(\d+)))$"
     )
     .unwrap();
-    let captures = pattern.captures(&semantic_commit.title);
-    if let Some(captures) = captures {
-        let commit_type = captures
-            .get(2)
-            .or_else(|| captures.get(8))
-            .or_else(|| captures.get(16))
-            .map(|m| m.as_str())
-            .ok_or_else(|| {
-                eyre!(
-                    "failed to parse commit type from the commit title: {}",
-                    semantic_commit.title
-                )
-            })?;
-        match commit_type {
-            "agenda" => {
-                let agenda: Agenda = serde_spb::from_str(&semantic_commit.body)?;
-                let height = captures.get(6).map(|m| m.as_str()).ok_or_else(|| {
-                    eyre!(
-                        "failed to parse height from the commit title: {}",
-                        semantic_commit.title
-                    )
-                })?;
-                let height = height.parse::<u64>()?;
-                if height != agenda.height {
-                    return Err(eyre!(
-                        "agenda height mismatch: expected {}, got {}",
-                        agenda.height,
-                        height
-                    ));
-                }
-                Ok(Commit::Agenda(agenda))
+    if let Some(captures) = pattern.captures(&semantic_commit.title) {
+        let (body, version) = decode_body(&semantic_commit.body).ok_or_else(|| {
+            CommitParseError::Malformed(
+                semantic_commit.title.clone(),
+                "missing format version".to_owned(),
+            )
+        })?;
+        if version != SEMANTIC_COMMIT_FORMAT_VERSION {
+            return Err(CommitParseError::UnsupportedVersion(
+                semantic_commit.title,
+                version,
+            ));
+        }
+        parse_simperby_commit(&captures, &semantic_commit.title, body)
+            .map_err(|e| CommitParseError::Malformed(semantic_commit.title.clone(), e.to_string()))
+    } else if semantic_commit
+        .title
+        .starts_with(SIMPERBY_COMMIT_TITLE_PREFIX)
+    {
+        Err(CommitParseError::Unrecognized(semantic_commit.title))
+    } else {
+        let (body, author_signature) = decode_transaction_body(&semantic_commit.body);
+        Ok(Commit::Transaction(Transaction {
+            author: semantic_commit.author,
+            timestamp: semantic_commit.timestamp,
+            head: semantic_commit.title,
+            body: body.to_owned(),
+            diff: semantic_commit.diff,
+            author_signature,
+        }))
+    }
+}
+
+/// Parses a semantic commit whose title matched one of the Simperby commit kinds,
+/// given the body without the format version.
+fn parse_simperby_commit(
+    captures: &regex::Captures,
+    title: &str,
+    body: &str,
+) -> Result<Commit, Error> {
+    let commit_type = captures
+        .get(2)
+        .or_else(|| captures.get(8))
+        .or_else(|| captures.get(16))
+        .map(|m| m.as_str())
+        .ok_or_else(|| {
+            eyre!(
+                "failed to parse commit type from the commit title: {}",
+                title
+            )
+        })?;
+    match commit_type {
+        "agenda" => {
+            let agenda: Agenda = serde_spb::from_str(body)?;
+            let height = captures
+                .get(6)
+                .map(|m| m.as_str())
+                .ok_or_else(|| eyre!("failed to parse height from the commit title: {}", title))?;
+            let height = height.parse::<u64>()?;
+            if height != agenda.height {
+                return Err(eyre!(
+                    "agenda height mismatch: expected {}, got {}",
+                    agenda.height,
+                    height
+                ));
             }
-            "block" => {
-                let block_header: BlockHeader = serde_spb::from_str(&semantic_commit.body)?;
-                let height = captures.get(6).map(|m| m.as_str()).ok_or_else(|| {
-                    eyre!(
-                        "failed to parse height from the commit title: {}",
-                        semantic_commit.title
-                    )
-                })?;
-                let height = height.parse::<u64>()?;
-                if height != block_header.height {
-                    return Err(eyre!(
-                        "block height mismatch: expected {}, got {}",
-                        block_header.height,
-                        height
-                    ));
-                }
-                Ok(Commit::Block(block_header))
+            Ok(Commit::Agenda(agenda))
+        }
+        "block" => {
+            let block_header: BlockHeader = serde_spb::from_str(body)?;
+            let height = captures
+                .get(6)
+                .map(|m| m.as_str())
+                .ok_or_else(|| eyre!("failed to parse height from the commit title: {}", title))?;
+            let height = height.parse::<u64>()?;
+            if height != block_header.height {
+                return Err(eyre!(
+                    "block height mismatch: expected {}, got {}",
+                    block_header.height,
+                    height
+                ));
             }
-            "agenda-proof" => {
-                let agenda_proof: AgendaProof = serde_spb::from_str(&semantic_commit.body)?;
-                let height = captures.get(6).map(|m| m.as_str()).ok_or_else(|| {
-                    eyre!(
-                        "failed to parse height from the commit title: {}",
-                        semantic_commit.title
-                    )
-                })?;
-                let height = height.parse::<u64>()?;
-                if height != agenda_proof.height {
-                    return Err(eyre!(
-                        "agenda-proof height mismatch: expected {}, got {}",
-                        agenda_proof.height,
-                        height
-                    ));
-                }
-                Ok(Commit::AgendaProof(agenda_proof))
+            Ok(Commit::Block(block_header))
+        }
+        "agenda-proof" => {
+            let agenda_proof: AgendaProof = serde_spb::from_str(body)?;
+            let height = captures
+                .get(6)
+                .map(|m| m.as_str())
+                .ok_or_else(|| eyre!("failed to parse height from the commit title: {}", title))?;
+            let height = height.parse::<u64>()?;
+            if height != agenda_proof.height {
+                return Err(eyre!(
+                    "agenda-proof height mismatch: expected {}, got {}",
+                    agenda_proof.height,
+                    height
+                ));
             }
-            "tx-delegate" => {
-                let tx: ExtraAgendaTransaction = serde_spb::from_str(&semantic_commit.body)?;
-                match tx {
-                    ExtraAgendaTransaction::Delegate(ref tx) => {
-                        let delegator = captures.get(9).map(|m| m.as_str()).ok_or_else(|| {
-                            eyre!(
-                                "failed to parse delegator from the commit title: {}",
-                                semantic_commit.title
-                            )
-                        })?;
-                        if delegator != tx.data.delegator {
-                            return Err(eyre!(
-                                "delegator mismatch: expected {}, got {}",
-                                delegator,
-                                tx.data.delegator
-                            ));
-                        }
-                        let delegatee = captures.get(12).map(|m| m.as_str()).ok_or_else(|| {
-                            eyre!(
-                                "failed to parse delegatee from the commit title: {}",
-                                semantic_commit.title
-                            )
-                        })?;
-                        if delegatee != tx.data.delegatee {
-                            return Err(eyre!(
-                                "delegatee mismatch: expected {}, got {}",
-                                delegatee,
-                                tx.data.delegatee
-                            ));
-                        }
-                        Ok(Commit::ExtraAgendaTransaction(
-                            ExtraAgendaTransaction::Delegate(tx.clone()),
-                        ))
+            Ok(Commit::AgendaProof(agenda_proof))
+        }
+        "tx-delegate" => {
+            let tx: ExtraAgendaTransaction = serde_spb::from_str(body)?;
+            match tx {
+                ExtraAgendaTransaction::Delegate(ref tx) => {
+                    let delegator = captures.get(9).map(|m| m.as_str()).ok_or_else(|| {
+                        eyre!("failed to parse delegator from the commit title: {}", title)
+                    })?;
+                    if delegator != tx.data.delegator {
+                        return Err(eyre!(
+                            "delegator mismatch: expected {}, got {}",
+                            delegator,
+                            tx.data.delegator
+                        ));
+                    }
+                    let delegatee = captures.get(12).map(|m| m.as_str()).ok_or_else(|| {
+                        eyre!("failed to parse delegatee from the commit title: {}", title)
+                    })?;
+                    if delegatee != tx.data.delegatee {
+                        return Err(eyre!(
+                            "delegatee mismatch: expected {}, got {}",
+                            delegatee,
+                            tx.data.delegatee
+                        ));
                     }
-                    _ => Err(eyre!("expected delegation transaction, got {:?}", tx)),
+                    Ok(Commit::ExtraAgendaTransaction(
+                        ExtraAgendaTransaction::Delegate(tx.clone()),
+                    ))
                 }
+                _ => Err(eyre!("expected delegation transaction, got {:?}", tx)),
             }
-            "tx-undelegate" => {
-                let tx: ExtraAgendaTransaction = serde_spb::from_str(&semantic_commit.body)?;
-                match tx {
-                    ExtraAgendaTransaction::Undelegate(ref tx) => {
-                        let delegator = captures.get(17).map(|m| m.as_str()).ok_or_else(|| {
-                            eyre!(
-                                "failed to parse delegator from the commit title: {}",
-                                semantic_commit.title
-                            )
-                        })?;
-                        if delegator != tx.data.delegator {
-                            return Err(eyre!(
-                                "delegator mismatch: expected {}, got {}",
-                                delegator,
-                                tx.data.delegator
-                            ));
-                        }
-                        Ok(Commit::ExtraAgendaTransaction(
-                            ExtraAgendaTransaction::Undelegate(tx.clone()),
-                        ))
+        }
+        "tx-undelegate" => {
+            let tx: ExtraAgendaTransaction = serde_spb::from_str(body)?;
+            match tx {
+                ExtraAgendaTransaction::Undelegate(ref tx) => {
+                    let delegator = captures.get(17).map(|m| m.as_str()).ok_or_else(|| {
+                        eyre!("failed to parse delegator from the commit title: {}", title)
+                    })?;
+                    if delegator != tx.data.delegator {
+                        return Err(eyre!(
+                            "delegator mismatch: expected {}, got {}",
+                            delegator,
+                            tx.data.delegator
+                        ));
                     }
-                    _ => Err(eyre!("expected undelegation transaction, got {:?}", tx)),
+                    Ok(Commit::ExtraAgendaTransaction(
+                        ExtraAgendaTransaction::Undelegate(tx.clone()),
+                    ))
                 }
+                _ => Err(eyre!("expected undelegation transaction, got {:?}", tx)),
             }
-            _ => Err(eyre!("unknown commit type: {}", commit_type)),
         }
-    } else {
-        Ok(Commit::Transaction(Transaction {
-            author: semantic_commit.author,
-            timestamp: semantic_commit.timestamp,
-            head: semantic_commit.title,
-            body: semantic_commit.body,
-            diff: semantic_commit.diff,
-        }))
+        _ => Err(eyre!("unknown commit type: {}", commit_type)),
     }
 }
 
 pub fn fp_to_semantic_commit(fp: &LastFinalizationProof) -> SemanticCommit {
     let title = format!(">fp: {}", fp.height);
-    let body = serde_spb::to_string(&fp).unwrap();
+    let body = encode_body(fp);
     SemanticCommit {
         title,
         body,
@@ -273,7 +370,16 @@ pub fn fp_from_semantic_commit(
             )
         })?;
         let height = height.parse::<u64>()?;
-        let proof: LastFinalizationProof = serde_spb::from_str(&semantic_commit.body)?;
+        let (body, version) = decode_body(&semantic_commit.body)
+            .ok_or_else(|| eyre!("missing format version: {}", semantic_commit.title))?;
+        if version != SEMANTIC_COMMIT_FORMAT_VERSION {
+            return Err(eyre!(
+                "unsupported format version {}: {}",
+                version,
+                semantic_commit.title
+            ));
+        }
+        let proof: LastFinalizationProof = serde_spb::from_str(body)?;
         if height != proof.height {
             return Err(eyre!(
                 "proof height mismatch: expected {}, got {}",
@@ -287,24 +393,58 @@ pub fn fp_from_semantic_commit(
     }
 }
 
-pub fn raw_commit_to_semantic_commit(raw_commit: RawCommit) -> SemanticCommit {
+/// Converts a raw commit to a semantic commit.
+///
+/// The message is split into the title and the body by the first empty line,
+/// and the timestamp is converted from seconds (git) to milliseconds (Simperby).
+/// A raw commit with a diff is rejected since the diff of a semantic commit is the hash of
+/// the whole commit shown by git (see `RawRepository::read_semantic_commit()`), not of the patch.
+pub fn raw_commit_to_semantic_commit(raw_commit: RawCommit) -> Result<SemanticCommit, Error> {
+    if raw_commit.diff.is_some() {
+        // TODO: should handle cases, `Reserved`, `NonReserved, `General`.
+        return Err(eyre!(
+            "a raw commit with a diff can't be converted to a semantic commit: {}",
+            raw_commit.message
+        ));
+    }
     let (title, body) = if let Some((title, body)) = raw_commit.message.split_once("\n\n") {
         (title.to_string(), body.to_string())
     } else {
-        (String::new(), String::new())
+        (raw_commit.message, String::new())
     };
-    SemanticCommit {
+    Ok(SemanticCommit {
         title,
         body,
-        diff: if raw_commit.diff.is_none() {
-            Diff::None
-        } else {
-            // TODO: should handle cases, `Reserved`, `NonReserved, `General`.
-            unimplemented!()
-        },
+        diff: Diff::None,
         author: raw_commit.author,
-        timestamp: raw_commit.timestamp / 1000,
+        timestamp: raw_commit.timestamp * 1000,
+    })
+}
+
+/// Converts a semantic commit without a diff to a raw commit
+/// authored by `semantic_commit.author` with the given email.
+///
+/// The timestamp is converted from milliseconds (Simperby) to seconds (git),
+/// so `raw_commit_to_semantic_commit()` followed by this restores the same raw commit
+/// (and thus the same commit hash).
+/// A semantic commit with a diff is rejected since it carries only the hash of the diff.
+pub fn semantic_commit_to_raw_commit(
+    semantic_commit: &SemanticCommit,
+    email: &str,
+) -> Result<RawCommit, Error> {
+    if semantic_commit.diff != Diff::None {
+        return Err(eyre!(
+            "the patch of the diff can't be restored from the semantic commit: {}",
+            semantic_commit.title
+        ));
     }
+    Ok(RawCommit {
+        message: format!("{}\n\n{}", semantic_commit.title, semantic_commit.body),
+        diff: None,
+        author: semantic_commit.author.clone(),
+        email: email.to_string(),
+        timestamp: semantic_commit.timestamp / 1000,
+    })
 }
 
 #[cfg(test)]
@@ -321,6 +461,7 @@ mod tests {
             head: "abc".to_string(),
             body: "def".to_string(),
             diff: Diff::None,
+            author_signature: None,
         });
         assert_eq!(
             transaction,
@@ -329,6 +470,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn format_signed_transaction_commit() {
+        let (reserved_state, keys) = generate_standard_genesis(4);
+        for body in ["", "def", "def\n\nghi"] {
+            let mut transaction = Transaction {
+                author: reserved_state.members[0].name.clone(),
+                timestamp: 0,
+                head: "abc".to_string(),
+                body: body.to_string(),
+                diff: Diff::None,
+                author_signature: None,
+            };
+            transaction.sign(&keys[0].1).unwrap();
+            let transaction = Commit::Transaction(transaction);
+            assert_eq!(
+                transaction,
+                from_semantic_commit(
+                    to_semantic_commit(&transaction, reserved_state.clone()).unwrap()
+                )
+                .unwrap()
+            );
+        }
+    }
+
     #[test]
     fn format_agenda_commit() {
         let (reserved_state, _) = generate_standard_genesis(4);
@@ -451,4 +616,79 @@ mod tests {
             fp_from_semantic_commit(fp_to_semantic_commit(&fp)).unwrap()
         );
     }
+
+    #[test]
+    fn format_version_tagged_commit() {
+        simperby_test_suite::setup_test();
+        let (reserved_state, _) = generate_standard_genesis(4);
+        let agenda = Commit::Agenda(Agenda {
+            height: 3,
+            author: "member-0000".to_owned(),
+            timestamp: 123,
+            transactions_hash: Hash256::zero(),
+            previous_block_hash: Hash256::zero(),
+        });
+        let semantic_commit = to_semantic_commit(&agenda, reserved_state).unwrap();
+        // Any change of this encoding changes the commit hash, so it must bump the version.
+        assert_eq!(semantic_commit.title, ">agenda: 3");
+        assert_eq!(
+            semantic_commit.body,
+            r#"{
+  "height": 3,
+  "author": "member-0000",
+  "timestamp": 123,
+  "transactions_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+  "previous_block_hash": "0000000000000000000000000000000000000000000000000000000000000000"
+}
+
+format-version: 1"#
+        );
+        assert_eq!(
+            from_semantic_commit(semantic_commit.clone()).unwrap(),
+            agenda
+        );
+
+        let mut unknown_version = semantic_commit.clone();
+        unknown_version.body = unknown_version
+            .body
+            .replace("format-version: 1", "format-version: 2");
+        assert_eq!(
+            from_semantic_commit(unknown_version),
+            Err(CommitParseError::UnsupportedVersion(
+                ">agenda: 3".to_owned(),
+                2
+            ))
+        );
+        let mut untagged = semantic_commit;
+        untagged.body = untagged.body.replace("\n\nformat-version: 1", "");
+        assert!(matches!(
+            from_semantic_commit(untagged),
+            Err(CommitParseError::Malformed(..))
+        ));
+    }
+
+    #[test]
+    fn unrecognized_commit() {
+        // A malformed commit creates an `eyre` report, which must come after the hook is installed.
+        simperby_test_suite::setup_test();
+        let fp = LastFinalizationProof {
+            height: 3,
+            proof: FinalizationProof::genesis(),
+        };
+        assert_eq!(
+            from_semantic_commit(fp_to_semantic_commit(&fp)),
+            Err(CommitParseError::Unrecognized(">fp: 3".to_owned()))
+        );
+        let semantic_commit = SemanticCommit {
+            title: ">agenda: 1".to_owned(),
+            body: "not an agenda".to_owned(),
+            diff: Diff::None,
+            author: UNKNOWN_COMMIT_AUTHOR.to_owned(),
+            timestamp: 0,
+        };
+        assert!(matches!(
+            from_semantic_commit(semantic_commit),
+            Err(CommitParseError::Malformed(..))
+        ));
+    }
 }
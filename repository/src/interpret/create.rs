@@ -25,7 +25,7 @@ pub async fn approve(
 
     // Check if the agenda branch is rebased on top of the `finalized` branch.
     let last_header_commit = raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
-    let agenda_branch_name = format!("a-{}", &agenda_hash.to_string()[0..BRANCH_NAME_HASH_DIGITS]);
+    let agenda_branch_name = format!("a-{}", agenda_hash.short(BRANCH_NAME_HASH_DIGITS));
     let agenda_commit_hash = raw.locate_branch(agenda_branch_name.clone()).await?;
     let find_merge_base_result = raw
         .find_merge_base(last_header_commit, agenda_commit_hash)
@@ -81,7 +81,9 @@ pub async fn approve(
         format::to_semantic_commit(&agenda_proof_commit, reserved_state)?;
     let agenda_proof_branch_name = format!(
         "a-{}",
-        &agenda_proof_commit.to_hash256().to_string()[0..BRANCH_NAME_HASH_DIGITS]
+        agenda_proof_commit
+            .to_hash256()
+            .short(BRANCH_NAME_HASH_DIGITS)
     );
     // Check if it is already approved.
     if raw
@@ -93,7 +95,8 @@ pub async fn approve(
     }
     raw.create_branch(agenda_proof_branch_name.clone(), agenda_commit_hash)
         .await?;
-    raw.checkout(agenda_proof_branch_name).await?;
+    raw.checkout(agenda_proof_branch_name.clone()).await?;
+    raw.check_head_on_branch(agenda_proof_branch_name).await?;
     let agenda_proof_commit_hash = raw
         .create_semantic_commit(agenda_proof_semantic_commit, true)
         .await?;
@@ -117,6 +120,7 @@ pub async fn create_transaction(
 pub async fn create_agenda(
     raw: &mut RawRepository,
     author: MemberName,
+    force: bool,
 ) -> Result<(Agenda, CommitHash), Error> {
     let last_header = read_last_finalized_block_header(raw).await?;
     raw.check_clean()
@@ -147,10 +151,27 @@ pub async fn create_agenda(
             transactions.push(t.clone());
         }
     }
+    let transactions_hash = Agenda::calculate_transactions_hash(&transactions);
+
+    // Check if the same agenda (except the timestamp) already exists.
+    if !force {
+        for (commit_hash, _) in read_agendas(raw).await? {
+            if let Commit::Agenda(agenda) = read_commit(raw, commit_hash).await? {
+                if agenda.author == author
+                    && agenda.transactions_hash == transactions_hash
+                    && agenda.height == last_header.height + 1
+                    && agenda.previous_block_hash == last_header.to_hash256()
+                {
+                    return Ok((agenda, commit_hash));
+                }
+            }
+        }
+    }
+
     let agenda = Agenda {
         author,
         timestamp: get_timestamp(),
-        transactions_hash: Agenda::calculate_transactions_hash(&transactions),
+        transactions_hash,
         height: last_header.height + 1,
         previous_block_hash: last_header.to_hash256(),
     };
@@ -163,8 +184,7 @@ pub async fn create_agenda(
 
     raw.checkout_clean().await?;
     let result = raw.create_semantic_commit(semantic_commit, true).await?;
-    let mut agenda_branch_name = agenda_commit.to_hash256().to_string();
-    agenda_branch_name.truncate(BRANCH_NAME_HASH_DIGITS);
+    let agenda_branch_name = agenda_commit.to_hash256().short(BRANCH_NAME_HASH_DIGITS);
     let agenda_branch_name = format!("a-{agenda_branch_name}");
     raw.create_branch(agenda_branch_name, result).await?;
     Ok((agenda, result))
@@ -229,8 +249,7 @@ pub async fn create_block(
     raw.checkout_clean().await?;
     raw.checkout_detach(head).await?;
     let result = raw.create_semantic_commit(semantic_commit, true).await?;
-    let mut block_branch_name = block_commit.to_hash256().to_string();
-    block_branch_name.truncate(BRANCH_NAME_HASH_DIGITS);
+    let block_branch_name = block_commit.to_hash256().short(BRANCH_NAME_HASH_DIGITS);
     let block_branch_name = format!("b-{block_branch_name}");
     raw.create_branch(block_branch_name.clone(), result).await?;
     raw.checkout(block_branch_name).await?;
@@ -264,10 +283,17 @@ pub async fn create_extra_agenda_transaction(
             .map_err(|e| eyre!("verification error on commit {}: {}", hash, e))?;
     }
 
+    if !verifier.is_extra_agenda_transaction_allowed() {
+        return Err(eyre!(
+            "extra-agenda transactions can be created only after an agenda proof: HEAD {} is in another phase",
+            head
+        ));
+    }
     let extra_agenda_tx_commit = Commit::ExtraAgendaTransaction(transaction.clone());
-    verifier.apply_commit(&extra_agenda_tx_commit).map_err(|_| {
+    verifier.apply_commit(&extra_agenda_tx_commit).map_err(|e| {
             eyre!(
-                "extra-agenda transaction commit cannot be created on top of the current commit sequence"
+                "extra-agenda transaction commit cannot be created on top of the current commit sequence: {}",
+                e
             )
         })?;
 
@@ -290,40 +316,15 @@ pub async fn finalize(
         .expect("there must be at least one commit in CSV")
     {
         csv.verify_last_header_finalization(&proof)?;
-        raw.checkout_clean().await?;
-        raw.move_branch(FP_BRANCH_NAME.to_string(), block_commit_hash)
-            .await
-            .map_err(|e| match e {
-                raw::Error::NotFound(_) => {
-                    eyre!(IntegrityError::new(format!(
-                        "failed to find fp branch: {e}"
-                    )))
-                }
-                _ => eyre!(e),
-            })?;
-        raw.checkout(FP_BRANCH_NAME.into())
-            .await
-            .map_err(|e| match e {
-                raw::Error::NotFound(_) => {
-                    eyre!(IntegrityError::new(format!(
-                        "failed to find the fp branch: {e}"
-                    )))
-                }
-                _ => eyre!(e),
-            })?;
-        let commit_hash = raw
-            .create_semantic_commit(
-                format::fp_to_semantic_commit(&LastFinalizationProof {
-                    height: block.height,
-                    proof,
-                }),
-                true,
-            )
-            .await?;
-        sync(raw, commit_hash)
-            .await?
-            .expect("already checked by CSV");
-        Ok(commit_hash)
+        works::advance_finalized_branch(
+            raw,
+            block_commit_hash,
+            LastFinalizationProof {
+                height: block.height,
+                proof,
+            },
+        )
+        .await
     } else {
         Err(eyre!("commit {} is not a block commit", block_commit_hash))
     }
@@ -346,8 +347,8 @@ pub async fn commit_gitignore(raw: &mut RawRepository) -> Result<(), Error> {
     let commit = RawCommit {
         message: "Add `.simperby/` entry to .gitignore".to_string(),
         diff: None,
-        author: "Simperby".to_string(),
-        email: "hi@simperby.net".to_string(),
+        author: SIMPERBY_COMMIT_AUTHOR.to_string(),
+        email: SIMPERBY_COMMIT_EMAIL.to_string(),
         timestamp: get_timestamp() / 1000,
     };
     raw.create_commit_all(commit).await?;
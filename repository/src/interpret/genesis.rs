@@ -20,6 +20,8 @@ pub async fn genesis(raw: &mut RawRepository) -> Result<(), Error> {
             }
             _ => eyre!(e),
         })?;
+    raw.check_head_on_branch(FINALIZED_BRANCH_NAME.into())
+        .await?;
     let result = raw.create_semantic_commit(semantic_commit, true).await?;
     // TODO: ignore only if the error is 'already exists'. Otherwise, propagate the error.
     let _ = raw.create_branch(FP_BRANCH_NAME.into(), result).await;
@@ -33,6 +35,7 @@ pub async fn genesis(raw: &mut RawRepository) -> Result<(), Error> {
             }
             _ => eyre!(e),
         })?;
+    raw.check_head_on_branch(FP_BRANCH_NAME.into()).await?;
     raw.create_semantic_commit(
         fp_to_semantic_commit(&LastFinalizationProof {
             height: 0,
@@ -1,11 +1,30 @@
 use super::*;
 
+/// What a member signs to request a push of a branch to a repository server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PushSignTarget {
+    /// The tip of the branch to push.
+    pub commit_hash: CommitHash,
+    pub branch: String,
+    pub timestamp: Timestamp,
+}
+
+impl ToHash256 for PushSignTarget {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
+impl SignatureDomain for PushSignTarget {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-push-request";
+}
+
 pub async fn test_push_eligibility(
     raw: &RawRepository,
     commit_hash: CommitHash,
     branch_name: String,
     timestamp: Timestamp,
-    signature: TypedSignature<(CommitHash, String, Timestamp)>,
+    signature: TypedSignature<PushSignTarget>,
     _timestamp_to_test: Timestamp,
 ) -> Result<bool, Error> {
     let reserved_state = raw.read_reserved_state().await?;
@@ -17,13 +36,13 @@ pub async fn test_push_eligibility(
         .map(|member| !member.expelled)
         .unwrap_or(false);
     let is_valid_signature = signature
-        .verify(&(commit_hash, branch_name.clone(), timestamp))
+        .verify(&PushSignTarget {
+            commit_hash,
+            branch: branch_name,
+            timestamp,
+        })
         .is_ok();
     let is_eligible = is_member && is_valid_signature;
-    let is_eligible = is_eligible
-        && signature
-            .verify(&(commit_hash, branch_name, timestamp))
-            .is_ok();
     // TODO: put the threshold in the config.
     // let is_eligible = is_eligible && (timestamp_to_test - timestamp).abs() <= 1000;
     Ok(is_eligible)
@@ -47,28 +66,32 @@ pub async fn broadcast(
     for (remote_name, _) in remotes {
         for &commit_hash in &commit_hashes {
             let timestamp = get_timestamp();
-            let branch = &commit_hash
+            let branch = commit_hash
                 .to_hash256()
                 .aggregate(&timestamp.to_hash256())
-                .to_string()[0..BRANCH_NAME_HASH_DIGITS];
+                .short(BRANCH_NAME_HASH_DIGITS);
             let signature = TypedSignature::sign(
-                &(commit_hash, branch.to_owned(), timestamp as u64),
+                &PushSignTarget {
+                    commit_hash,
+                    branch: branch.clone(),
+                    timestamp,
+                },
                 private_key.as_ref().unwrap(),
             )?;
             let signer = serde_spb::to_string(signature.signer())?.replace('\"', "\\\"");
             let signature =
                 serde_spb::to_string(&signature.get_raw_signature())?.replace('\"', "\\\"");
 
-            raw.create_branch(branch.into(), commit_hash).await?;
+            raw.create_branch(branch.clone(), commit_hash).await?;
             raw.push_option(
                 remote_name.clone(),
-                branch.into(),
+                branch.clone(),
                 Some(format!(
                     "{commit_hash} {branch} {timestamp} {signature} {signer}"
                 )),
             )
             .await?;
-            raw.delete_branch(branch.into()).await?;
+            raw.delete_branch(branch).await?;
         }
     }
     Ok(())
@@ -6,7 +6,7 @@ pub enum CommitError {
     #[error("raw repo error: {0}")]
     Raw(#[from] raw::Error),
     #[error("failed to parse commit ({1}): {0}")]
-    Commit(eyre::Error, CommitHash),
+    Commit(format::CommitParseError, CommitHash),
     #[error("reserved state error: {0}")]
     ReservedState(#[from] super::Error),
 }
@@ -115,8 +115,17 @@ pub async fn read_finalization_info(
             })
             .collect::<Vec<_>>();
 
-        let header: BlockHeader = serde_spb::from_str(&commits[0].0.body)?;
-        let next_header: BlockHeader = serde_spb::from_str(&commits[1].0.body)?;
+        let read_header =
+            |semantic_commit: &raw::SemanticCommit| match format::from_semantic_commit(
+                semantic_commit.clone(),
+            )
+            .map_err(|e| eyre!(e))?
+            {
+                Commit::Block(header) => Ok::<_, Error>(header),
+                _ => Err(eyre!("not a block commit: {}", semantic_commit.title)),
+            };
+        let header = read_header(&commits[0].0)?;
+        let next_header = read_header(&commits[1].0)?;
         let commit_hash = commits[0].1;
         let reserved_state = raw.read_reserved_state_at_commit(commit_hash).await?;
         let proof = next_header.prev_block_finalization_proof;
@@ -232,14 +241,43 @@ pub async fn read_commit(raw: &RawRepository, commit_hash: CommitHash) -> Result
     format::from_semantic_commit(semantic_commit).map_err(|e| eyre!(e))
 }
 
+pub async fn read_payload_branch(
+    raw: &RawRepository,
+) -> Result<Option<(CommitHash, Vec<Commit>)>, Error> {
+    let tip = match raw.locate_branch(PAYLOAD_BRANCH_NAME.into()).await {
+        Ok(commit_hash) => commit_hash,
+        Err(raw::Error::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(eyre!(e)),
+    };
+    let last_header_commit_hash = get_last_finalized_block_commit_hash(raw).await?;
+    if tip == last_header_commit_hash {
+        return Ok(Some((tip, Vec::new())));
+    }
+    if raw.find_merge_base(last_header_commit_hash, tip).await? != last_header_commit_hash {
+        return Err(eyre!(
+            "branch {} should be rebased on top of the {} branch",
+            PAYLOAD_BRANCH_NAME,
+            FINALIZED_BRANCH_NAME
+        ));
+    }
+    let commits = read_commits(raw, last_header_commit_hash, tip)
+        .await?
+        .into_iter()
+        .map(|(commit, _)| commit)
+        .collect();
+    Ok(Some((tip, commits)))
+}
+
 pub async fn read_agendas(raw: &RawRepository) -> Result<Vec<(CommitHash, Hash256)>, Error> {
     let mut agendas: Vec<(CommitHash, Hash256)> = vec![];
     let branches = read_local_branches(raw).await?;
     let last_header_commit_hash = raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
+    let last_header = read_last_finalized_block_header(raw).await?;
     for (branch, branch_commit_hash) in branches {
         // Check if the branch is an agenda branch
         if branch.as_str().starts_with("a-") {
-            // Check if the agenda branch is rebased on top of the `finalized` branch
+            // Check if the agenda branch is rebased on top of the `finalized` branch;
+            // a branch left from before the last finalization forks from an older block.
             let find_merge_base_result = raw
                 .find_merge_base(last_header_commit_hash, branch_commit_hash)
                 .await
@@ -263,11 +301,17 @@ pub async fn read_agendas(raw: &RawRepository) -> Result<Vec<(CommitHash, Hash25
 
             // Push currently valid and height-acceptable agendas to the list
             let commits = read_commits(raw, last_header_commit_hash, branch_commit_hash).await?;
-            let last_header = read_last_finalized_block_header(raw).await?;
             for (commit, hash) in commits {
                 if let Commit::Agenda(agenda) = commit {
                     if agenda.height == last_header.height + 1 {
                         agendas.push((hash, agenda.to_hash256()));
+                    } else {
+                        log::warn!(
+                            "agenda {} in branch {} is for height {}, not the next one",
+                            hash,
+                            branch,
+                            agenda.height
+                        );
                     }
                 }
             }
@@ -276,6 +320,25 @@ pub async fn read_agendas(raw: &RawRepository) -> Result<Vec<(CommitHash, Hash25
     Ok(agendas)
 }
 
+/// Classifies the agenda at `agenda_commit` by the transactions it contains,
+/// i.e., the ones from the last finalized block to the agenda commit.
+pub async fn read_agenda_type(
+    raw: &RawRepository,
+    agenda_commit: CommitHash,
+) -> Result<AgendaType, Error> {
+    let last_header_commit_hash = raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
+    let commits = read_commits(raw, last_header_commit_hash, agenda_commit).await?;
+    if !matches!(commits.last(), Some((Commit::Agenda(_), _))) {
+        return Err(eyre!("commit {} is not an agenda", agenda_commit));
+    }
+    Ok(AgendaType::from_transactions(commits.iter().filter_map(
+        |(commit, _)| match commit {
+            Commit::Transaction(tx) => Some(tx),
+            _ => None,
+        },
+    )))
+}
+
 pub async fn read_governance_approved_agendas(
     raw: &RawRepository,
 ) -> Result<Vec<(CommitHash, Hash256)>, Error> {
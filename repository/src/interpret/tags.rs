@@ -5,8 +5,7 @@ pub async fn vote(raw: &mut RawRepository, commit_hash: CommitHash) -> Result<()
     let commit = format::from_semantic_commit(semantic_commit).map_err(|e| eyre!(e))?;
     // Check if the commit is an agenda commit.
     if let Commit::Agenda(_) = commit {
-        let mut vote_tag_name = commit.to_hash256().to_string();
-        vote_tag_name.truncate(TAG_NAME_HASH_DIGITS);
+        let vote_tag_name = commit.to_hash256().short(TAG_NAME_HASH_DIGITS);
         let vote_tag_name = format!("vote-{vote_tag_name}");
         raw.create_tag(vote_tag_name, commit_hash).await?;
         Ok(())
@@ -16,15 +15,32 @@ pub async fn vote(raw: &mut RawRepository, commit_hash: CommitHash) -> Result<()
 }
 
 pub async fn veto(raw: &mut RawRepository, commit_hash: CommitHash) -> Result<(), Error> {
+    let veto_tag_name = veto_tag_name(raw, commit_hash).await?;
+    raw.create_tag(veto_tag_name, commit_hash).await?;
+    Ok(())
+}
+
+pub async fn unveto(raw: &mut RawRepository, commit_hash: CommitHash) -> Result<(), Error> {
+    let veto_tag_name = veto_tag_name(raw, commit_hash).await?;
+    if !raw.list_tags().await?.contains(&veto_tag_name) {
+        log::info!(
+            "no veto tag {} on commit {}; nothing to remove",
+            veto_tag_name,
+            commit_hash
+        );
+        return Ok(());
+    }
+    raw.remove_tag(veto_tag_name).await?;
+    Ok(())
+}
+
+async fn veto_tag_name(raw: &RawRepository, commit_hash: CommitHash) -> Result<Tag, Error> {
     let semantic_commit = raw.read_semantic_commit(commit_hash).await?;
     let commit = format::from_semantic_commit(semantic_commit).map_err(|e| eyre!(e))?;
     // Check if the commit is a block commit.
     if let Commit::Block(_) = commit {
-        let mut veto_tag_name = commit.to_hash256().to_string();
-        veto_tag_name.truncate(TAG_NAME_HASH_DIGITS);
-        let veto_tag_name = format!("veto-{veto_tag_name}");
-        raw.create_tag(veto_tag_name, commit_hash).await?;
-        Ok(())
+        let veto_tag_name = commit.to_hash256().short(TAG_NAME_HASH_DIGITS);
+        Ok(format!("veto-{veto_tag_name}"))
     } else {
         Err(eyre!("commit {} is not a block commit", commit_hash))
     }
@@ -1,31 +1,169 @@
 use super::*;
 use read::*;
 
+/// Moves the `finalized` branch to the given block commit and the `fp` branch to
+/// a new finalization proof commit on top of it, returning the hash of the latter.
+///
+/// The finalization proof commit is created before any branch is moved, and `fp` is moved
+/// before `finalized`. A crash in between thus leaves `fp` ahead of `finalized` with the proof,
+/// which `recover_finalization()` detects and completes on the next open.
 pub(crate) async fn advance_finalized_branch(
     raw: &mut RawRepository,
     to_be_finalized_block_commit_hash: CommitHash,
     finalization_proof: LastFinalizationProof,
-) -> Result<(), Error> {
+) -> Result<CommitHash, Error> {
     raw.checkout_clean().await?;
+    raw.checkout_detach(to_be_finalized_block_commit_hash)
+        .await?;
+    let fp_commit_hash = raw
+        .create_semantic_commit(format::fp_to_semantic_commit(&finalization_proof), true)
+        .await?;
+    raw.move_branch(FP_BRANCH_NAME.into(), fp_commit_hash)
+        .await?;
     raw.move_branch(
         FINALIZED_BRANCH_NAME.into(),
         to_be_finalized_block_commit_hash,
     )
     .await?;
-    raw.move_branch(FP_BRANCH_NAME.into(), to_be_finalized_block_commit_hash)
-        .await?;
-    raw.checkout(FP_BRANCH_NAME.into()).await?;
-    raw.create_semantic_commit(format::fp_to_semantic_commit(&finalization_proof), true)
-        .await?;
     raw.checkout_detach(to_be_finalized_block_commit_hash)
         .await?;
+    Ok(fp_commit_hash)
+}
+
+/// Completes a finalization that was interrupted after moving the `fp` branch
+/// but before moving the `finalized` branch (see `advance_finalized_branch()`).
+///
+/// If `fp` is ahead of `finalized`, the commits in between are verified
+/// along with the finalization proof, and `finalized` is moved to the block under `fp`.
+/// Returns whether the repository has been repaired.
+pub async fn recover_finalization(raw: &mut RawRepository) -> Result<bool, Error> {
+    let (finalized, fp) = match (
+        raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await,
+        raw.locate_branch(FP_BRANCH_NAME.into()).await,
+    ) {
+        (Ok(finalized), Ok(fp)) => (finalized, fp),
+        // Not initialized yet; `check()` is responsible for a missing branch.
+        _ => return Ok(false),
+    };
+    let block_commit_hash = match raw.list_ancestors(fp, Some(1)).await?.first() {
+        Some(commit_hash) => *commit_hash,
+        None => return Ok(false),
+    };
+    if block_commit_hash == finalized {
+        return Ok(false);
+    }
+    if raw.find_merge_base(finalized, block_commit_hash).await? != finalized {
+        return Err(eyre!(IntegrityError::new(
+            "`fp` branch is neither on nor ahead of `finalized` branch".to_owned()
+        )));
+    }
+    let proof = format::fp_from_semantic_commit(raw.read_semantic_commit(fp).await?)
+        .map_err(|e| eyre!(IntegrityError::new(format!("invalid `fp` commit: {e}"))))?;
+
+    let mut csv = CommitSequenceVerifier::new(
+        read_last_finalized_block_header(raw).await?,
+        read_last_finalized_reserved_state(raw).await?,
+    )
+    .map_err(|e| {
+        eyre!(IntegrityError::new(format!(
+            "invalid `finalized` branch: {e}"
+        )))
+    })?;
+    let commits = read_commits(raw, finalized, block_commit_hash).await?;
+    for (commit, commit_hash) in &commits {
+        csv.apply_commit(commit).map_err(|e| {
+            eyre!(IntegrityError::new(format!(
+                "invalid commit {commit_hash} under `fp` branch: {e}"
+            )))
+        })?;
+    }
+    match commits.last() {
+        Some((Commit::Block(header), _)) if header.height == proof.height => (),
+        _ => {
+            return Err(eyre!(IntegrityError::new(
+                "`fp` commit is not on top of the block it finalizes".to_owned()
+            )))
+        }
+    }
+    csv.verify_last_header_finalization(&proof.proof)
+        .map_err(|e| eyre!(IntegrityError::new(format!("invalid `fp` commit: {e}"))))?;
+
+    log::warn!("completing the interrupted finalization of {block_commit_hash}");
+    raw.checkout_clean().await?;
+    raw.checkout_detach(block_commit_hash).await?;
+    raw.move_branch(FINALIZED_BRANCH_NAME.into(), block_commit_hash)
+        .await?;
+    Ok(true)
+}
+
+/// `HEAD` and the local branches, to which a rejected `sync()` restores the repository.
+type Checkpoint = (raw::HeadState, HashSet<(Branch, CommitHash)>);
+
+async fn take_checkpoint(raw: &RawRepository) -> Result<Checkpoint, Error> {
+    Ok((raw.get_head_state().await?, read_local_branches(raw).await?))
+}
+
+/// Restores `HEAD` and the local branches to the given checkpoint of a rejected `sync()`.
+///
+/// Does nothing (especially, keeps the working tree) if the state has not been changed.
+async fn restore_head_and_branches(
+    raw: &mut RawRepository,
+    (head, branches): Checkpoint,
+) -> Result<(), Error> {
+    let current_branches = read_local_branches(raw).await?;
+    if raw.get_head_state().await? == head && current_branches == branches {
+        return Ok(());
+    }
+    let (head_branch, head_commit_hash) = match head {
+        raw::HeadState::Attached(branch, commit_hash) => (Some(branch), commit_hash),
+        raw::HeadState::Detached(commit_hash) => (None, commit_hash),
+    };
+    // Detach first so that any branch can be moved or deleted.
+    raw.checkout_clean().await?;
+    raw.checkout_detach(head_commit_hash).await?;
+    for (branch, commit_hash) in &current_branches {
+        match branches.iter().find(|(b, _)| b == branch) {
+            Some((_, previous)) if previous == commit_hash => (),
+            Some((_, previous)) => raw.move_branch(branch.clone(), *previous).await?,
+            None => raw.delete_branch(branch.clone()).await?,
+        }
+    }
+    for (branch, commit_hash) in branches {
+        if !current_branches.iter().any(|(b, _)| *b == branch) {
+            raw.create_branch(branch, commit_hash).await?;
+        }
+    }
+    if let Some(branch) = head_branch {
+        raw.checkout(branch).await?;
+    }
     Ok(())
 }
 
+/// Syncs the given branch tip (see `DistributedRepository::sync()`).
+///
+/// If the branch is rejected or the operation fails partway,
+/// `HEAD` and the local branches are restored to the state before the call,
+/// or to the one right after advancing the `finalized` branch if it has been advanced,
+/// since the finalization is already verified.
 pub async fn sync(
     raw: &mut RawRepository,
     tip_commit_hash: CommitHash,
-) -> Result<Result<(), String>, Error> {
+) -> Result<SyncOutcome, Error> {
+    let mut checkpoint = take_checkpoint(raw).await?;
+    let result = sync_unrecovered(raw, tip_commit_hash, &mut checkpoint).await;
+    if matches!(result, Ok(SyncOutcome::Rejected(_)) | Err(_)) {
+        restore_head_and_branches(raw, checkpoint)
+            .await
+            .map_err(|e| eyre!("failed to restore the repository after a rejected sync: {e}"))?;
+    }
+    result
+}
+
+async fn sync_unrecovered(
+    raw: &mut RawRepository,
+    tip_commit_hash: CommitHash,
+    checkpoint: &mut Checkpoint,
+) -> Result<SyncOutcome, Error> {
     let lfi = read_last_finalization_info(raw).await?;
     let mut csv = CommitSequenceVerifier::new(lfi.header.clone(), lfi.reserved_state.clone())
         .map_err(|e| {
@@ -37,7 +175,7 @@ pub async fn sync(
         .await?
         != lfi.commit_hash
     {
-        return Ok(Err(
+        return Ok(SyncOutcome::Rejected(
             "the received branch tip commit is not a descendant of the last finalized block."
                 .to_owned(),
         ));
@@ -51,20 +189,22 @@ pub async fn sync(
         // (fp is not treated in the CSV)
         let commit_hash = raw.list_ancestors(tip_commit_hash, Some(1)).await?[0];
         if commit_hash == lfi.commit_hash {
-            return Ok(Err("the received commit is already finalized.".to_owned()));
+            return Ok(SyncOutcome::NoChange);
         }
 
         // Read the commits in the branch and verify them
         let commits = match read_commits(raw, lfi.commit_hash, commit_hash).await {
             Ok(x) => x,
             Err(CommitError::Commit(error, commit)) => {
-                return Ok(Err(format!("failed to parse commit {commit}: {error}")));
+                return Ok(SyncOutcome::Rejected(format!(
+                    "failed to parse commit {commit}: {error}"
+                )));
             }
             Err(e) => return Err(e.into()),
         };
         for (commit, commit_hash) in &commits {
             if let Err(e) = csv.apply_commit(commit) {
-                return Ok(Err(format!(
+                return Ok(SyncOutcome::Rejected(format!(
                     "commit sequence verification failed: {e} at {commit_hash}",
                 )));
             }
@@ -73,37 +213,43 @@ pub async fn sync(
         let (last_commit, last_commit_hash) = commits.last().expect(
             "already checked that the received commit is not same as the last finalized block",
         );
-        if let Commit::Block(_) = last_commit {
+        if let Commit::Block(header) = last_commit {
             if csv
                 .verify_last_header_finalization(&last_finalization_proof.proof)
                 .is_err()
             {
-                return Ok(Err(
-                    "finalization proof is invalid for the last block.".to_owned()
+                return Ok(SyncOutcome::Rejected(
+                    "finalization proof is invalid for the last block.".to_owned(),
                 ));
             }
+            let height = header.height;
             advance_finalized_branch(raw, *last_commit_hash, last_finalization_proof).await?;
+            Ok(SyncOutcome::Finalized(height))
         } else {
-            return Ok(Err("fp commit must be on top of a block commit.".to_owned()));
+            Ok(SyncOutcome::Rejected(
+                "fp commit must be on top of a block commit.".to_owned(),
+            ))
         }
     }
     // If the branch ends with a block, agenda, or agenda proof commit
     else {
         if tip_commit_hash == lfi.commit_hash {
-            return Ok(Err("the received commit is already finalized.".to_owned()));
+            return Ok(SyncOutcome::NoChange);
         }
 
         // Read the commits in the branch and verify them
         let commits = match read_commits(raw, lfi.commit_hash, tip_commit_hash).await {
             Ok(x) => x,
             Err(CommitError::Commit(error, commit)) => {
-                return Ok(Err(format!("failed to parse commit {commit}: {error}",)));
+                return Ok(SyncOutcome::Rejected(format!(
+                    "failed to parse commit {commit}: {error}",
+                )));
             }
             Err(e) => return Err(e.into()),
         };
         for (commit, commit_hash) in &commits {
             if let Err(e) = csv.apply_commit(commit) {
-                return Ok(Err(format!(
+                return Ok(SyncOutcome::Rejected(format!(
                     "commit sequence verification failed: {e} at {commit_hash}",
                 )));
             }
@@ -111,24 +257,28 @@ pub async fn sync(
 
         // If the commit sequence contains block commit(s) that can be finalized
         let headers = csv.get_block_headers();
+        let mut finalized = None;
         if headers.len() > 2 {
             let (last_header, _) = headers.last().expect(
                 "already checked that the received commit is not same as the last finalized block",
             );
             let (second_to_last_header, index) = headers[headers.len() - 2].clone();
+            // `index` counts the starting header of the CSV, which is not in `commits`.
             advance_finalized_branch(
                 raw,
-                commits[index].1,
+                commits[index - 1].1,
                 LastFinalizationProof {
                     height: second_to_last_header.height,
                     proof: last_header.prev_block_finalization_proof.clone(),
                 },
             )
             .await?;
+            *checkpoint = take_checkpoint(raw).await?;
+            finalized = Some(SyncOutcome::Finalized(second_to_last_header.height));
         }
 
         // Create a branch associated to the last commit
-        let branch_name = match &commits
+        let (branch_name, observed) = match &commits
             .last()
             .expect("already checked that the received commit is not a finalization proof commit")
             .0
@@ -140,7 +290,7 @@ pub async fn sync(
                         read::read_commit(raw, commit_hash).await?
                     {
                         if agenda_proof.agenda_hash == agenda.to_hash256() {
-                            return Ok(Err("agenda proof already exists.".to_owned()));
+                            return Ok(SyncOutcome::NoChange);
                         }
                     } else {
                         return Err(eyre!(IntegrityError::new(format!(
@@ -150,18 +300,15 @@ pub async fn sync(
                     }
                 }
 
-                format!(
-                    "a-{}",
-                    &agenda.to_hash256().to_string()[0..BRANCH_NAME_HASH_DIGITS]
+                (
+                    format!("a-{}", agenda.to_hash256().short(BRANCH_NAME_HASH_DIGITS)),
+                    SyncOutcome::AgendaObserved(tip_commit_hash),
                 )
             }
             Commit::AgendaProof(agenda_proof) => {
                 let agenda_name = match &commits[commits.len() - 2].0 {
                     Commit::Agenda(agenda) => {
-                        format!(
-                            "a-{}",
-                            &agenda.to_hash256().to_string()[0..BRANCH_NAME_HASH_DIGITS]
-                        )
+                        format!("a-{}", agenda.to_hash256().short(BRANCH_NAME_HASH_DIGITS))
                     }
                     _ => {
                         return Err(eyre!(IntegrityError::new(
@@ -173,28 +320,134 @@ pub async fn sync(
                     raw.delete_branch(agenda_name).await?;
                 }
 
-                format!(
-                    "a-{}",
-                    &agenda_proof.to_hash256().to_string()[0..BRANCH_NAME_HASH_DIGITS]
+                (
+                    format!(
+                        "a-{}",
+                        agenda_proof.to_hash256().short(BRANCH_NAME_HASH_DIGITS)
+                    ),
+                    SyncOutcome::AgendaObserved(tip_commit_hash),
                 )
             }
-            Commit::Block(block) => {
-                format!(
-                    "b-{}",
-                    &block.to_hash256().to_string()[0..BRANCH_NAME_HASH_DIGITS]
-                )
+            Commit::Block(block) => (
+                format!("b-{}", block.to_hash256().short(BRANCH_NAME_HASH_DIGITS)),
+                SyncOutcome::BlockObserved(tip_commit_hash),
+            ),
+            x => {
+                return Ok(SyncOutcome::Rejected(format!(
+                    "commit sequence ends with: {x:?}"
+                )))
             }
-            x => return Ok(Err(format!("commit sequence ends with: {x:?}"))),
         };
         if raw.locate_branch(branch_name.clone()).await.is_ok() {
-            return Ok(Err(format!("branch already exists: {branch_name}",)));
+            return Ok(finalized.unwrap_or(SyncOutcome::NoChange));
         }
         raw.create_branch(branch_name, tip_commit_hash).await?;
-    };
-    Ok(Ok(()))
+        Ok(finalized.unwrap_or(observed))
+    }
+}
+
+/// Syncs the given branch tip like `sync()`, but finalizes at most `max_heights` heights.
+///
+/// If the branch goes further, only its prefix up to the block that carries the finalization proof
+/// of the `max_heights`-th next height is synced, so that no more than that many heights of commits
+/// are loaded and verified at once. The branch created for that prefix is removed afterwards,
+/// since the rest of the branch will be synced later.
+pub async fn sync_window(
+    raw: &mut RawRepository,
+    tip_commit_hash: CommitHash,
+    max_heights: usize,
+) -> Result<SyncOutcome, Error> {
+    let lfi = read_last_finalization_info(raw).await?;
+    let (window_tip, header) =
+        match locate_window_tip(raw, &lfi, tip_commit_hash, max_heights).await? {
+            Some(x) => x,
+            None => return sync(raw, tip_commit_hash).await,
+        };
+    let branch_name = format!("b-{}", header.to_hash256().short(BRANCH_NAME_HASH_DIGITS));
+    let existed = raw.locate_branch(branch_name.clone()).await.is_ok();
+    let result = sync(raw, window_tip).await?;
+    if !existed && raw.locate_branch(branch_name.clone()).await.is_ok() {
+        raw.delete_branch(branch_name).await?;
+    }
+    Ok(result)
+}
+
+/// Finds the block commit, on the way from the last finalized block to the given tip,
+/// whose `prev_block_finalization_proof` finalizes the `max_heights`-th next height.
+///
+/// Returns `None` if the branch doesn't go that far or is not a valid descendant of the
+/// last finalized block, in which case it is left to `sync()` to handle.
+async fn locate_window_tip(
+    raw: &RawRepository,
+    lfi: &FinalizationInfo,
+    tip_commit_hash: CommitHash,
+    max_heights: usize,
+) -> Result<Option<(CommitHash, BlockHeader)>, Error> {
+    if tip_commit_hash == lfi.commit_hash
+        || raw
+            .find_merge_base(lfi.commit_hash, tip_commit_hash)
+            .await?
+            != lfi.commit_hash
+    {
+        return Ok(None);
+    }
+    let target_height = lfi.header.height + max_heights as BlockHeight + 1;
+    // Only the hashes are kept; the commits are read one by one.
+    for commit_hash in raw
+        .query_commit_path(lfi.commit_hash, tip_commit_hash)
+        .await?
+    {
+        match from_semantic_commit(raw.read_semantic_commit(commit_hash).await?) {
+            Ok(Commit::Block(header)) if header.height == target_height => {
+                return Ok(Some((commit_hash, header)))
+            }
+            Ok(_) => (),
+            Err(_) => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+pub async fn sync_all(raw: &mut RawRepository) -> Result<Vec<(String, SyncOutcome)>, Error> {
+    sync_all_ranked(raw, |_| 0).await
+}
+
+/// Performs `sync_all()`, syncing the remote tracking branches in the order of
+/// the rank of their remotes (the lower, the earlier).
+pub async fn sync_all_ranked(
+    raw: &mut RawRepository,
+    remote_rank: impl Fn(&str) -> usize,
+) -> Result<Vec<(String, SyncOutcome)>, Error> {
+    sync_branches(raw, remote_rank, None).await
+}
+
+/// Performs `sync_all_ranked()`, but finalizes at most `max_heights` heights in total
+/// (see `sync_window()`), skipping the remaining branches once that many have been finalized.
+///
+/// The agenda and block branches left behind by the window are removed afterwards,
+/// so that they don't pile up while a long history is synced window by window.
+pub async fn sync_all_windowed(
+    raw: &mut RawRepository,
+    remote_rank: impl Fn(&str) -> usize,
+    max_heights: usize,
+) -> Result<Vec<(String, SyncOutcome)>, Error> {
+    let result = sync_branches(raw, remote_rank, Some(max_heights)).await?;
+    let finalized = raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
+    for (branch, commit_hash) in read_local_branches(raw).await? {
+        if (branch.starts_with("a-") || branch.starts_with("b-"))
+            && raw.find_merge_base(commit_hash, finalized).await? != finalized
+        {
+            raw.delete_branch(branch).await?;
+        }
+    }
+    Ok(result)
 }
 
-pub async fn sync_all(raw: &mut RawRepository) -> Result<Vec<(String, Result<(), String>)>, Error> {
+async fn sync_branches(
+    raw: &mut RawRepository,
+    remote_rank: impl Fn(&str) -> usize,
+    max_heights: Option<usize>,
+) -> Result<Vec<(String, SyncOutcome)>, Error> {
     let local_branches: Vec<String> = raw
         .list_branches()
         .await?
@@ -204,20 +457,39 @@ pub async fn sync_all(raw: &mut RawRepository) -> Result<Vec<(String, Result<(),
                 && s.as_str() != FP_BRANCH_NAME
                 && !s.starts_with("a-")
                 && !s.starts_with("b-")
-                && s.as_str() != "p"
+                && s.as_str() != PAYLOAD_BRANCH_NAME
         })
         .collect();
-    let remote_tracking_branches = raw.list_remote_tracking_branches().await?;
+    let mut remote_tracking_branches = raw.list_remote_tracking_branches().await?;
+    remote_tracking_branches.sort_by_key(|(remote, _, _)| remote_rank(remote));
 
-    let mut result = Vec::new();
+    let target_height = match max_heights {
+        Some(max_heights) => {
+            Some(read_last_finalized_block_header(raw).await?.height + max_heights as BlockHeight)
+        }
+        None => None,
+    };
+    let mut branches = Vec::new();
     for branch in local_branches {
-        result.push((
-            branch.to_owned(),
-            sync(raw, raw.locate_branch(branch.to_owned()).await?).await?,
-        ));
+        let commit_hash = raw.locate_branch(branch.to_owned()).await?;
+        branches.push((branch, commit_hash));
     }
     for (remote, branch, commit_hash) in remote_tracking_branches {
-        result.push((format!("{remote}/{branch}"), sync(raw, commit_hash).await?));
+        branches.push((format!("{remote}/{branch}"), commit_hash));
+    }
+
+    let mut result = Vec::new();
+    for (branch, commit_hash) in branches {
+        let outcome = if let Some(target_height) = target_height {
+            let height = read_last_finalized_block_header(raw).await?.height;
+            if height >= target_height {
+                break;
+            }
+            sync_window(raw, commit_hash, (target_height - height) as usize).await?
+        } else {
+            sync(raw, commit_hash).await?
+        };
+        result.push((branch, outcome));
     }
     Ok(result)
 }
@@ -1,5 +1,6 @@
 pub mod format;
 pub mod interpret;
+mod lock;
 pub mod raw;
 // TODO: integrate the server feature with `DistributedRepository`
 mod network;
@@ -9,6 +10,7 @@ use eyre::eyre;
 use format::*;
 use futures::prelude::*;
 use interpret::*;
+use lru::LruCache;
 use raw::{RawCommit, RawRepository};
 use serde::{Deserialize, Serialize};
 use simperby_core::reserved::ReservedState;
@@ -16,11 +18,16 @@ use simperby_core::utils::get_timestamp;
 use simperby_core::verify::CommitSequenceVerifier;
 use simperby_core::*;
 use simperby_network::*;
-use std::sync::Arc;
-use std::{collections::HashSet, fmt};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 use tokio::sync::RwLock;
 
+pub use lock::{RawGuard, RawReadGuard, RawWriteGuard};
 pub use network::RepositoryMessage;
 
 pub type Branch = String;
@@ -28,10 +35,30 @@ pub type Tag = String;
 
 pub const FINALIZED_BRANCH_NAME: &str = "finalized";
 pub const FP_BRANCH_NAME: &str = "fp";
+/// The staging area for transactions that are not yet included in any agenda.
+///
+/// It is expected to be branched from the `finalized` branch,
+/// and a new agenda is composed of the transactions staged on this branch.
+pub const PAYLOAD_BRANCH_NAME: &str = "p";
+/// The number of hash digits (see `Hash256::short()`) shown in a commit title.
 pub const COMMIT_TITLE_HASH_DIGITS: usize = 8;
+/// The number of hash digits (see `Hash256::short()`) in a `vote-` or `veto-` tag name.
 pub const TAG_NAME_HASH_DIGITS: usize = 8;
+/// The number of hash digits (see `Hash256::short()`) in an `a-` or `b-` branch name.
 pub const BRANCH_NAME_HASH_DIGITS: usize = 8;
 pub const UNKNOWN_COMMIT_AUTHOR: &str = "unknown";
+/// The number of finalized commits that `DistributedRepository::read_commit()` keeps in memory.
+pub const COMMIT_CACHE_SIZE: usize = 1024;
+/// The number of reserved states at finalized commits that
+/// `RawRepository::read_reserved_state_at_commit()` keeps in memory.
+pub const RESERVED_STATE_CACHE_SIZE: usize = 64;
+/// The number of loose objects over which `Client::clean` runs the garbage collection,
+/// which is the default of `gc.auto` of git.
+pub const GC_LOOSE_OBJECTS_THRESHOLD: usize = 6700;
+/// The git author name of the commits created by Simperby itself.
+pub const SIMPERBY_COMMIT_AUTHOR: &str = "Simperby";
+/// The git author email of the commits created by Simperby itself.
+pub const SIMPERBY_COMMIT_EMAIL: &str = "hi@simperby.net";
 
 pub type Error = eyre::Error;
 
@@ -57,6 +84,22 @@ pub struct Config {
     pub long_range_attack_distance: usize,
 }
 
+/// What `DistributedRepository::sync()` observed from the received branch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncOutcome {
+    /// The `finalized` branch advanced to the block of the given height.
+    Finalized(BlockHeight),
+    /// A new block candidate is received and a `b-#` branch is created on the given commit.
+    BlockObserved(CommitHash),
+    /// A new agenda (either governance-approved or not) is received
+    /// and an `a-#` branch is created on the given commit.
+    AgendaObserved(CommitHash),
+    /// The branch has nothing new (e.g., it is already finalized or already has its branch).
+    NoChange,
+    /// The branch is invalid and thus rejected, with a reason.
+    Rejected(String),
+}
+
 /// The local Simperby blockchain data repository.
 ///
 /// It automatically locks the repository once created.
@@ -67,30 +110,64 @@ pub struct DistributedRepository {
     dms: Option<Arc<RwLock<Dms<RepositoryMessage>>>>,
     /// We keep the `RawRepository` in a `RwLock` for possible concurrent accesses in some operations.
     raw: Arc<RwLock<RawRepository>>,
+    raw_holders: lock::RawLockHolders,
     _config: Config,
     private_key: Option<PrivateKey>,
+    /// The cache of the read commits, which never change.
+    commit_cache: Mutex<LruCache<CommitHash, Commit>>,
+    /// The branch messages committed to the DMS by `flush()`,
+    /// as `(last finalized commit, tip commit) -> message hash`.
+    flushed_branches: Mutex<BTreeMap<(CommitHash, CommitHash), Hash256>>,
 }
 
 impl DistributedRepository {
+    /// Returns the lock of the raw repository.
+    ///
+    /// Note that the guards acquired from it directly are not tracked by
+    /// the re-entrance check of `read_raw()` and `write_raw()`.
     pub fn get_raw(&self) -> Arc<RwLock<RawRepository>> {
         Arc::clone(&self.raw)
     }
 
+    /// Locks the raw repository for reading.
+    ///
+    /// In debug builds, it panics if the current task already holds
+    /// the lock for writing, which would deadlock otherwise.
+    pub async fn read_raw(&self) -> RawReadGuard<'_> {
+        self.raw_holders.read(&self.raw).await
+    }
+
+    /// Locks the raw repository for writing.
+    ///
+    /// In debug builds, it panics if the current task already holds the lock,
+    /// which would deadlock otherwise.
+    pub async fn write_raw(&self) -> RawWriteGuard<'_> {
+        self.raw_holders.write(&self.raw).await
+    }
+
     pub fn get_dms(&self) -> Option<Arc<RwLock<Dms<RepositoryMessage>>>> {
         self.dms.as_ref().map(Arc::clone)
     }
 
+    /// Opens the repository, completing the last finalization if it has been interrupted
+    /// (see `finalize()`).
     pub async fn new(
         dms: Option<Arc<RwLock<Dms<RepositoryMessage>>>>,
         raw: Arc<RwLock<RawRepository>>,
         config: Config,
         private_key: Option<PrivateKey>,
     ) -> Result<Self, Error> {
+        recover_finalization(&mut *raw.write().await).await?;
         Ok(Self {
             dms,
             raw,
+            raw_holders: Default::default(),
             _config: config,
             private_key,
+            commit_cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(COMMIT_CACHE_SIZE).expect("nonzero cache size"),
+            )),
+            flushed_branches: Default::default(),
         })
     }
 
@@ -111,7 +188,7 @@ impl DistributedRepository {
 
     /// Reads the last finalization information from the repository.
     pub async fn read_last_finalization_info(&self) -> Result<FinalizationInfo, Error> {
-        read_last_finalization_info(&*self.raw.read().await).await
+        read_last_finalization_info(&*self.read_raw().await).await
     }
 
     /// Reads the finalization information at specific height.
@@ -119,17 +196,47 @@ impl DistributedRepository {
         &self,
         height: BlockHeight,
     ) -> Result<FinalizationInfo, Error> {
-        read_finalization_info(&*self.raw.read().await, height).await
+        read_finalization_info(&*self.read_raw().await, height).await
     }
 
     /// Reads the given commit.
+    ///
+    /// Commits are cached, so reading them again doesn't touch the raw repository.
     pub async fn read_commit(&self, commit_hash: CommitHash) -> Result<Commit, Error> {
-        read_commit(&*self.raw.read().await, commit_hash).await
+        if let Some(commit) = self.commit_cache.lock().unwrap().get(&commit_hash) {
+            return Ok(commit.clone());
+        }
+        let commit = read_commit(&*self.read_raw().await, commit_hash).await?;
+        // A git commit is addressed by its content, so the cached one never gets stale
+        // even if the commit is discarded (e.g., by `clean()`) and created again.
+        self.commit_cache
+            .lock()
+            .unwrap()
+            .put(commit_hash, commit.clone());
+        Ok(commit)
+    }
+
+    /// Reads the `p` (payload) branch, which is the staging area for the transactions
+    /// to be included in the next agenda.
+    ///
+    /// Returns the tip of the branch and the commits from the last finalized block (exclusive)
+    /// to the tip (inclusive), or `None` if there is no `p` branch.
+    pub async fn read_payload_branch(&self) -> Result<Option<(CommitHash, Vec<Commit>)>, Error> {
+        read_payload_branch(&*self.read_raw().await).await
     }
 
     /// Returns the currently valid and height-acceptable agendas in the repository.
+    ///
+    /// Only the agenda branches forking from the current `finalized` tip are read,
+    /// and only the agendas for the next height are returned;
+    /// branches left from before the last finalization are ignored.
     pub async fn read_agendas(&self) -> Result<Vec<(CommitHash, Hash256)>, Error> {
-        read_agendas(&*self.raw.read().await).await
+        read_agendas(&*self.read_raw().await).await
+    }
+
+    /// Returns the type of the given agenda, which decides the governance threshold it requires.
+    pub async fn read_agenda_type(&self, agenda_commit: CommitHash) -> Result<AgendaType, Error> {
+        read_agenda_type(&*self.read_raw().await, agenda_commit).await
     }
 
     /// Returns governance-approved agendas in the repository.
@@ -137,12 +244,12 @@ impl DistributedRepository {
     pub async fn read_governance_approved_agendas(
         &self,
     ) -> Result<Vec<(CommitHash, Hash256)>, Error> {
-        read_governance_approved_agendas(&*self.raw.read().await).await
+        read_governance_approved_agendas(&*self.read_raw().await).await
     }
 
     /// Returns the currently valid and height-acceptable blocks in the repository.
     pub async fn read_blocks(&self) -> Result<Vec<(CommitHash, Hash256)>, Error> {
-        read_blocks(&*self.raw.read().await).await
+        read_blocks(&*self.read_raw().await).await
     }
 
     /// Checks the validity of the repository, starting from the given height.
@@ -161,7 +268,7 @@ impl DistributedRepository {
     /// Checks the existence of `.gitignore` file and `.simperby/` entry in `.gitignore`.
     /// This returns true if both exist.
     pub async fn check_gitignore(&self) -> Result<bool, Error> {
-        check_gitignore(&*self.raw.read().await).await
+        check_gitignore(&*self.read_raw().await).await
     }
 
     // ---------------
@@ -169,27 +276,70 @@ impl DistributedRepository {
     // (manually added commits or remote tracking branches)
     // ---------------
 
-    /// Synchronizes the repository with the given commit (interpreted as a branch tip).
-    /// - Returns `Ok(Ok(()))` if the branch is successfully received.
-    /// - Returns `Ok(Err(_))` if the branch is invalid and thus rejected, with a reason.
+    /// Synchronizes the repository with the given commit (interpreted as a branch tip),
+    /// returning what it observed.
     /// - Returns `Err(_)` if an error occurs.
     ///
     /// 1. Finalization: move the `finalized` and `fp` branch to the last finalized block commit.
     /// 2. Block observed: add a `b-#` branch on the block candidate.
     /// 3. Agenda observed (either governance-approved or not): add an `a-#` branch on the agenda candidate.
     ///
+    /// If the branch both finalizes a block and carries a new candidate on top of it,
+    /// `SyncOutcome::Finalized` is returned (the candidate branch is still created).
+    ///
     /// This will verify every commit along the way.
     /// If the given commit is not a descendant of the
     /// current `finalized` (i.e., cannot be fast-forwarded), it fails.
-    pub async fn sync(&mut self, commit_hash: CommitHash) -> Result<Result<(), String>, Error> {
-        sync(&mut *self.raw.write().await, commit_hash).await
+    ///
+    /// A rejected (or failed) sync leaves `HEAD` and the local branches as they were.
+    ///
+    /// A branch may arrive ahead of the finalization it builds on
+    /// (e.g., an agenda for height `N+2` before the block of `N+1` is finalized).
+    /// It is not rejected: its branch is created right away, and it is read as an agenda
+    /// (or a block) once the preceding height is finalized, without being received again.
+    /// If the branch itself carries the next block, the preceding one is finalized by it.
+    pub async fn sync(&mut self, commit_hash: CommitHash) -> Result<SyncOutcome, Error> {
+        sync(&mut *self.write_raw().await, commit_hash).await
     }
 
     /// Performs `sync()` on all local branches and remote tracking branches on the repository.
     ///
     /// Returns the list of `(branch name, result of sync())`.
-    pub async fn sync_all(&mut self) -> Result<Vec<(String, Result<(), String>)>, Error> {
-        sync_all(&mut *self.raw.write().await).await
+    pub async fn sync_all(&mut self) -> Result<Vec<(String, SyncOutcome)>, Error> {
+        sync_all(&mut *self.write_raw().await).await
+    }
+
+    /// Performs `sync_all()`, syncing the remote tracking branches in the order of
+    /// the rank of their remotes (the lower, the earlier).
+    ///
+    /// Since the first branch that finalizes a block takes the height,
+    /// this lets the branches of the preferred remotes take precedence.
+    pub async fn sync_all_ranked(
+        &mut self,
+        remote_rank: impl Fn(&str) -> usize,
+    ) -> Result<Vec<(String, SyncOutcome)>, Error> {
+        sync_all_ranked(&mut *self.write_raw().await, remote_rank).await
+    }
+
+    /// Performs `sync()`, finalizing at most `max_heights` heights,
+    /// so that only a bounded number of commits are verified at once.
+    ///
+    /// The rest of the branch can be synced by calling it again.
+    pub async fn sync_window(
+        &mut self,
+        commit_hash: CommitHash,
+        max_heights: usize,
+    ) -> Result<SyncOutcome, Error> {
+        sync_window(&mut *self.write_raw().await, commit_hash, max_heights).await
+    }
+
+    /// Performs `sync_all_ranked()`, finalizing at most `max_heights` heights in total.
+    pub async fn sync_all_windowed(
+        &mut self,
+        remote_rank: impl Fn(&str) -> usize,
+        max_heights: usize,
+    ) -> Result<Vec<(String, SyncOutcome)>, Error> {
+        sync_all_windowed(&mut *self.write_raw().await, remote_rank, max_heights).await
     }
 
     /// Tests if the given push request is acceptable.
@@ -198,11 +348,11 @@ impl DistributedRepository {
         commit_hash: CommitHash,
         branch_name: String,
         timestamp: Timestamp,
-        signature: TypedSignature<(CommitHash, String, Timestamp)>,
+        signature: TypedSignature<PushSignTarget>,
         _timestamp_to_test: Timestamp,
     ) -> Result<bool, Error> {
         test_push_eligibility(
-            &*self.raw.read().await,
+            &*self.read_raw().await,
             commit_hash,
             branch_name,
             timestamp,
@@ -220,25 +370,29 @@ impl DistributedRepository {
     /// when `hard` is `true`,
     ///
     /// and when `hard` is `false`,
-    /// - the `p` branch
+    /// - the `p` branch (the staging area for the transactions of the next agenda)
     /// - the `a-#` branches
     /// - the `b-#` branches
     /// will be left as well
     /// if only the branches have valid commit sequences
     /// and are not outdated (branched from the last finalized commit).
     pub async fn clean(&mut self, hard: bool) -> Result<(), Error> {
-        clean(&mut *self.raw.write().await, hard).await
+        clean(&mut *self.write_raw().await, hard).await
     }
 
     /// Broadcasts all the local messages.
     pub async fn broadcast(&mut self) -> Result<(), Error> {
-        broadcast(&mut *self.raw.write().await, self.private_key.clone()).await
+        broadcast(&mut *self.write_raw().await, self.private_key.clone()).await
     }
 
     // ---------------
     // DMS-related operations
     // ---------------
 
+    /// Commits the local branches and the recent finalization proofs to the DMS.
+    ///
+    /// A branch that has already been committed since the last finalization is skipped,
+    /// unless its message has been removed from the DMS.
     pub async fn flush(&mut self) -> Result<(), Error> {
         self.flush_().await
     }
@@ -267,7 +421,7 @@ impl DistributedRepository {
         proof: Vec<TypedSignature<Agenda>>,
         timestamp: Timestamp,
     ) -> Result<CommitHash, Error> {
-        approve(&mut *self.raw.write().await, agenda_hash, proof, timestamp).await
+        approve(&mut *self.write_raw().await, agenda_hash, proof, timestamp).await
     }
 
     /// Creates a transaction commit on top of the HEAD.
@@ -275,15 +429,19 @@ impl DistributedRepository {
         &mut self,
         transaction: Transaction,
     ) -> Result<CommitHash, Error> {
-        create_transaction(&mut *self.raw.write().await, transaction).await
+        create_transaction(&mut *self.write_raw().await, transaction).await
     }
 
     /// Creates an agenda commit on top of the HEAD.
+    ///
+    /// If there is already an agenda for the next height with the same author and transactions,
+    /// it returns the existing one instead of creating a new one, unless `force` is set.
     pub async fn create_agenda(
         &mut self,
         author: MemberName,
+        force: bool,
     ) -> Result<(Agenda, CommitHash), Error> {
-        create_agenda(&mut *self.raw.write().await, author).await
+        create_agenda(&mut *self.write_raw().await, author, force).await
     }
 
     /// Creates a block commit on top of the HEAD.
@@ -291,7 +449,7 @@ impl DistributedRepository {
         &mut self,
         author: PublicKey,
     ) -> Result<(BlockHeader, CommitHash), Error> {
-        create_block(&mut *self.raw.write().await, author).await
+        create_block(&mut *self.write_raw().await, author).await
     }
 
     /// Creates an extra-agenda transaction commit on top of the HEAD.
@@ -299,22 +457,25 @@ impl DistributedRepository {
         &mut self,
         transaction: &ExtraAgendaTransaction,
     ) -> Result<CommitHash, Error> {
-        create_extra_agenda_transaction(&mut *self.raw.write().await, transaction).await
+        create_extra_agenda_transaction(&mut *self.write_raw().await, transaction).await
     }
 
     /// Finalizes the block with the given proof. Returns the commit hash of the updated `fp` branch.
+    ///
+    /// The `fp` branch is moved before the `finalized` branch, so if the process crashes
+    /// in between, the finalization is completed when the repository is opened next time.
     pub async fn finalize(
         &mut self,
         block_commit_hash: CommitHash,
         proof: FinalizationProof,
     ) -> Result<CommitHash, Error> {
-        finalize(&mut *self.raw.write().await, block_commit_hash, proof).await
+        finalize(&mut *self.write_raw().await, block_commit_hash, proof).await
     }
 
     /// Creates a commit that adds `.simperby/` entry to `.gitignore`.
     /// It fails if it exists normally.
     pub async fn commit_gitignore(&mut self) -> Result<(), Error> {
-        commit_gitignore(&mut *self.raw.write().await).await
+        commit_gitignore(&mut *self.write_raw().await).await
     }
 
     // ---------------
@@ -323,11 +484,18 @@ impl DistributedRepository {
 
     /// Puts a 'vote' tag on the commit.
     pub async fn vote(&mut self, commit_hash: CommitHash) -> Result<(), Error> {
-        vote(&mut *self.raw.write().await, commit_hash).await
+        vote(&mut *self.write_raw().await, commit_hash).await
     }
 
     /// Puts a 'veto' tag on the commit.
     pub async fn veto(&mut self, commit_hash: CommitHash) -> Result<(), Error> {
-        veto(&mut *self.raw.write().await, commit_hash).await
+        veto(&mut *self.write_raw().await, commit_hash).await
+    }
+
+    /// Removes the 'veto' tag from the commit.
+    ///
+    /// It does nothing if there is no such tag.
+    pub async fn unveto(&mut self, commit_hash: CommitHash) -> Result<(), Error> {
+        unveto(&mut *self.write_raw().await, commit_hash).await
     }
 }
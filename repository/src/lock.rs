@@ -0,0 +1,133 @@
+use super::*;
+use std::ops::{Deref, DerefMut};
+use tokio::sync::{RwLockReadGuard, RwLockWriteGuard};
+
+/// A guard of the raw repository, acquired by `DistributedRepository::read_raw()`
+/// or `DistributedRepository::write_raw()`.
+///
+/// In debug builds, it records the task holding it so that a re-entrant acquisition
+/// by the same task panics instead of deadlocking.
+pub struct RawGuard<G> {
+    guard: G,
+    #[cfg(debug_assertions)]
+    _holding: debug::Holding,
+}
+
+pub type RawReadGuard<'a> = RawGuard<RwLockReadGuard<'a, RawRepository>>;
+pub type RawWriteGuard<'a> = RawGuard<RwLockWriteGuard<'a, RawRepository>>;
+
+impl<G: Deref> Deref for RawGuard<G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for RawGuard<G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// The holders of the raw repository guards, which are tracked only in debug builds.
+#[derive(Default)]
+pub(crate) struct RawLockHolders {
+    #[cfg(debug_assertions)]
+    holders: Arc<Mutex<Vec<(debug::Holder, bool)>>>,
+}
+
+impl RawLockHolders {
+    pub(crate) async fn read<'a>(&self, lock: &'a RwLock<RawRepository>) -> RawReadGuard<'a> {
+        #[cfg(debug_assertions)]
+        debug::check_reentrance(&self.holders, false);
+        RawGuard {
+            guard: lock.read().await,
+            #[cfg(debug_assertions)]
+            _holding: debug::Holding::new(&self.holders, false),
+        }
+    }
+
+    pub(crate) async fn write<'a>(&self, lock: &'a RwLock<RawRepository>) -> RawWriteGuard<'a> {
+        #[cfg(debug_assertions)]
+        debug::check_reentrance(&self.holders, true);
+        RawGuard {
+            guard: lock.write().await,
+            #[cfg(debug_assertions)]
+            _holding: debug::Holding::new(&self.holders, true),
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+mod debug {
+    use super::*;
+
+    /// Who holds a guard: the current tokio task,
+    /// or the current thread outside of a task (e.g., `block_on()`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(super) enum Holder {
+        Task(tokio::task::Id),
+        Thread(std::thread::ThreadId),
+    }
+
+    impl Holder {
+        fn current() -> Self {
+            tokio::task::try_id()
+                .map(Holder::Task)
+                .unwrap_or_else(|| Holder::Thread(std::thread::current().id()))
+        }
+    }
+
+    /// Panics if acquiring a guard would wait for a guard held by the current task.
+    ///
+    /// Shared guards may be held together (e.g., two reads in a `join!`),
+    /// but an exclusive one can't be held together with any other.
+    pub(super) fn check_reentrance(holders: &Mutex<Vec<(Holder, bool)>>, exclusive: bool) {
+        let current = Holder::current();
+        let reentrant = lock_holders(holders)
+            .iter()
+            .any(|(holder, held_exclusive)| *holder == current && (exclusive || *held_exclusive));
+        if reentrant {
+            panic!(
+                "re-entrant lock of the raw repository: the current task already holds it, \
+                so this would deadlock"
+            );
+        }
+    }
+
+    /// Locks the holders, ignoring the poison since the list is always left consistent.
+    fn lock_holders(
+        holders: &Mutex<Vec<(Holder, bool)>>,
+    ) -> std::sync::MutexGuard<'_, Vec<(Holder, bool)>> {
+        holders
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Removes its holder from the holders when dropped.
+    pub(super) struct Holding {
+        holders: Arc<Mutex<Vec<(Holder, bool)>>>,
+        entry: (Holder, bool),
+    }
+
+    impl Holding {
+        pub(super) fn new(holders: &Arc<Mutex<Vec<(Holder, bool)>>>, exclusive: bool) -> Self {
+            let entry = (Holder::current(), exclusive);
+            lock_holders(holders).push(entry);
+            Self {
+                holders: Arc::clone(holders),
+                entry,
+            }
+        }
+    }
+
+    impl Drop for Holding {
+        fn drop(&mut self) {
+            let mut holders = lock_holders(&self.holders);
+            if let Some(index) = holders.iter().position(|entry| *entry == self.entry) {
+                holders.swap_remove(index);
+            }
+        }
+    }
+}
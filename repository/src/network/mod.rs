@@ -1,5 +1,5 @@
 use super::*;
-use raw::RawCommit;
+use raw::{RawCommit, SemanticCommit};
 use simperby_network::Error;
 use simperby_network::*;
 
@@ -64,9 +64,10 @@ impl DmsMessage for RepositoryMessage {
 fn update_single_branch(branch: &PayloadBranch, lfi: FinalizationInfo) -> Result<(), String> {
     let mut commits = Vec::new();
     for raw_commit in &branch.commits {
+        let semantic_commit = format::raw_commit_to_semantic_commit(raw_commit.clone())
+            .map_err(|error| error.to_string())?;
         let commit =
-            format::from_semantic_commit(format::raw_commit_to_semantic_commit(raw_commit.clone()))
-                .map_err(|error| error.to_string())?;
+            format::from_semantic_commit(semantic_commit).map_err(|error| error.to_string())?;
         commits.push(commit);
     }
     let mut csv = CommitSequenceVerifier::new(lfi.header, lfi.reserved_state)
@@ -75,11 +76,7 @@ fn update_single_branch(branch: &PayloadBranch, lfi: FinalizationInfo) -> Result
         csv.apply_commit(&commit)
             .map_err(|err| format!("failed to apply commit: {}", err))?;
     }
-    let tip_commit = match branch.tip_commit.clone() {
-        TipCommit::Block(block) => Commit::Block(block),
-        TipCommit::Agenda(ag) => Commit::Agenda(ag),
-        TipCommit::AgendaProof(ap) => Commit::AgendaProof(ap),
-    };
+    let tip_commit = branch.tip_commit.clone().into_commit();
     csv.apply_commit(&tip_commit)
         .map_err(|err| format!("failed to apply commit: {}", err))?;
     Ok(())
@@ -91,14 +88,13 @@ impl DistributedRepository {
         branch: &PayloadBranch,
         lfi: &FinalizationInfo,
     ) -> Result<(), Error> {
-        let branch_prefix = match branch.tip_commit {
-            TipCommit::Block(_) => "b",
-            TipCommit::Agenda(_) => "a",
-            TipCommit::AgendaProof(_) => "a",
-        };
         let tip_commit = branch.tip_commit.clone().into_commit();
-        let mut branch_name = tip_commit.to_hash256().to_string();
-        branch_name.truncate(BRANCH_NAME_HASH_DIGITS);
+        let branch_prefix = match tip_commit.kind() {
+            CommitKind::Block => "b",
+            CommitKind::Agenda | CommitKind::AgendaProof => "a",
+            kind => unreachable!("{kind:?} can't be a tip commit"),
+        };
+        let branch_name = tip_commit.to_hash256().short(BRANCH_NAME_HASH_DIGITS);
         let branch_name = format!("{branch_prefix}-{branch_name}");
         let result = self
             .raw
@@ -107,26 +103,32 @@ impl DistributedRepository {
             .locate_branch(branch_name.clone())
             .await;
         match result {
-            Err(raw::Error::NotFound(_)) => {
-                let mut raw_repo = self.raw.write().await;
+            Err(err @ (raw::Error::NotFound(_) | raw::Error::CorruptReference(_))) => {
+                let mut raw_repo = self.write_raw().await;
                 raw_repo.checkout_clean().await?;
                 let lfb_commit_hash = raw_repo
                     .locate_branch(FINALIZED_BRANCH_NAME.to_owned())
                     .await?;
                 raw_repo.checkout_detach(lfb_commit_hash).await?;
+                // A corrupt branch is recreated from the received commits.
+                if let raw::Error::CorruptReference(reason) = err {
+                    log::warn!("recreating the corrupt branch {branch_name}: {reason}");
+                    raw_repo.delete_branch(branch_name.clone()).await?;
+                }
                 for commit in branch.commits.clone() {
                     raw_repo.create_commit(commit).await?;
                 }
-                let semantic_commit =
-                    format::to_semantic_commit(&tip_commit, lfi.reserved_state.clone())?;
-                let raw_commit = RawCommit {
-                    message: format!("{}\n\n{}", semantic_commit.title, semantic_commit.body),
-                    diff: None,
-                    author: "Simperby".to_string(),
-                    email: "hi@simperby.net".to_string(),
-                    timestamp: semantic_commit.timestamp / 1000,
+                // The tip is authored by Simperby, as `create_semantic_commit()` does.
+                let semantic_commit = SemanticCommit {
+                    author: SIMPERBY_COMMIT_AUTHOR.to_string(),
+                    ..format::to_semantic_commit(&tip_commit, lfi.reserved_state.clone())?
                 };
-                raw_repo.create_commit(raw_commit).await?;
+                raw_repo
+                    .create_commit(format::semantic_commit_to_raw_commit(
+                        &semantic_commit,
+                        SIMPERBY_COMMIT_EMAIL,
+                    )?)
+                    .await?;
                 let head = raw_repo.get_head().await?;
                 raw_repo.create_branch(branch_name, head).await?;
                 Ok(())
@@ -194,7 +196,7 @@ impl DistributedRepository {
                     match verify::verify_finalization_proof(&block_commit, &proof) {
                         Ok(_) => {
                             crate::works::advance_finalized_branch(
-                                &mut *self.raw.write().await,
+                                &mut *self.write_raw().await,
                                 block_commit_hash,
                                 LastFinalizationProof {
                                     height: block_commit.height,
@@ -217,10 +219,35 @@ impl DistributedRepository {
 
     pub async fn flush_(&self) -> Result<(), Error> {
         let lfi = self.read_last_finalization_info().await?;
+        let dms_ = self
+            .dms
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("dms is not initialized yet"))?
+            .clone();
 
-        let blocks = self.read_blocks().await?;
-        let agendas = self.read_agendas().await?;
-        let agenda_proofs = self.read_governance_approved_agendas().await?;
+        // The branches of the previous heights are never flushed again.
+        let flushed_branches = {
+            let mut flushed_branches = self.flushed_branches.lock().unwrap();
+            flushed_branches.retain(|(base, _), _| *base == lfi.commit_hash);
+            flushed_branches.clone()
+        };
+        let mut already_flushed = HashSet::new();
+        {
+            let dms = dms_.read().await;
+            for ((_, tip), message_hash) in flushed_branches {
+                if dms.query_message(message_hash).await?.is_some() {
+                    already_flushed.insert(tip);
+                }
+            }
+        }
+        let not_flushed = |tips: Vec<(CommitHash, Hash256)>| {
+            tips.into_iter()
+                .filter(|(commit_hash, _)| !already_flushed.contains(commit_hash))
+                .collect::<Vec<_>>()
+        };
+        let blocks = not_flushed(self.read_blocks().await?);
+        let agendas = not_flushed(self.read_agendas().await?);
+        let agenda_proofs = not_flushed(self.read_governance_approved_agendas().await?);
 
         /// A behaivor of `create_branch` abstracted over the types of branches.
         trait BranchType {
@@ -289,12 +316,12 @@ impl DistributedRepository {
             this: &DistributedRepository,
             tip_commits: Vec<(CommitHash, Hash256)>,
             lfi: &FinalizationInfo,
-        ) -> Result<Vec<PayloadBranch>, Error> {
-            let raw = this.raw.read().await;
+        ) -> Result<Vec<(CommitHash, PayloadBranch)>, Error> {
+            let raw = this.read_raw().await;
             let mut branches = Vec::new();
             for (commit_hash, _) in tip_commits {
                 let commits = read::read_raw_commits(&raw, lfi.commit_hash, commit_hash).await?;
-                let commit = T::commit(this.read_commit(commit_hash).await?)?;
+                let commit = T::commit(read::read_commit(&raw, commit_hash).await?)?;
                 let len = commits.len();
                 if len == 0 {
                     return Err(eyre::Error::from(IntegrityError::new(format!(
@@ -302,10 +329,13 @@ impl DistributedRepository {
                         T::name()
                     ))));
                 }
-                branches.push(PayloadBranch {
-                    commits: commits.into_iter().take(len - 1).map(|(x, _)| x).collect(),
-                    tip_commit: T::tip_commit(commit),
-                });
+                branches.push((
+                    commit_hash,
+                    PayloadBranch {
+                        commits: commits.into_iter().take(len - 1).map(|(x, _)| x).collect(),
+                        tip_commit: T::tip_commit(commit),
+                    },
+                ));
             }
             Ok(branches)
         }
@@ -325,16 +355,14 @@ impl DistributedRepository {
             });
         }
 
-        let dms_ = self
-            .dms
-            .as_ref()
-            .ok_or_else(|| eyre::eyre!("dms is not initialized yet"))?
-            .clone();
         let mut dms = dms_.write().await;
-
-        for branch in branches {
-            dms.commit_message(&RepositoryMessage::Branch(branch))
-                .await?;
+        for (tip, branch) in branches {
+            let message = RepositoryMessage::Branch(branch);
+            dms.commit_message(&message).await?;
+            self.flushed_branches
+                .lock()
+                .unwrap()
+                .insert((lfi.commit_hash, tip), message.to_hash256());
         }
         for fp in fps {
             dms.commit_message(&RepositoryMessage::FinalizationProof(fp))
@@ -1,7 +1,7 @@
 #!/bin/sh
 value="$GIT_PUSH_OPTION_0"
-simperby_executable_path="$SIMPERBY_EXECUTABLE_PATH"
-simperby_root_path="$SIMPERBY_ROOT_PATH"
+simperby_executable_path="$(git config --get simperby.executablePath)"
+simperby_root_path="$(git config --get simperby.rootPath)"
 branch_name="$(echo "$value" | awk '{print $2}')"
 
 eval "$simperby_executable_path $simperby_root_path after-push $branch_name"
@@ -1,8 +1,8 @@
 #!/bin/sh
 push_option_count="$GIT_PUSH_OPTION_COUNT"
 value="$GIT_PUSH_OPTION_0"
-simperby_executable_path="$SIMPERBY_EXECUTABLE_PATH"
-simperby_root_path="$SIMPERBY_ROOT_PATH"
+simperby_executable_path="$(git config --get simperby.executablePath)"
+simperby_root_path="$(git config --get simperby.rootPath)"
 
 if [ "$push_option_count" -ne 1 ]; then
 	echo "The number of push options is not 1"
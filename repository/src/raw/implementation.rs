@@ -18,6 +18,7 @@ impl RawRepositoryInner {
         directory: &str,
         init_commit_message: &str,
         init_commit_branch: &Branch,
+        author: Option<CommitAuthor>,
     ) -> Result<Self, Error>
     where
         Self: Sized,
@@ -34,12 +35,17 @@ impl RawRepositoryInner {
                     // Set base configs.
                     let mut config = repo.config()?;
 
-                    // Set user configs if they are not set.
+                    // Set user configs if they are given or not set.
+                    let default_author = CommitAuthor::default();
+                    if let Some(author) = &author {
+                        config.set_str("user.name", &author.name)?;
+                        config.set_str("user.email", &author.email)?;
+                    }
                     if config.get_string("user.name").is_err() {
-                        config.set_str("user.name", "user")?;
+                        config.set_str("user.name", &default_author.name)?;
                     }
                     if config.get_string("user.email").is_err() {
-                        config.set_str("user.email", "user@simperby.net")?;
+                        config.set_str("user.email", &default_author.email)?;
                     }
 
                     config.set_str("receive.advertisePushOptions", "true")?;
@@ -65,14 +71,77 @@ impl RawRepositoryInner {
         Ok(Self { repo })
     }
 
-    pub(crate) fn clone(directory: &str, url: &str) -> Result<Self, Error>
+    /// Clones the repository branch by branch, so that the branches fetched before a failure
+    /// are not fetched again on a retry (or on a later clone into the same directory).
+    pub(crate) fn clone(directory: &str, url: &str, options: &CloneOptions) -> Result<Self, Error>
     where
         Self: Sized,
     {
-        let repo = Repository::clone(url, directory)?;
-        let mut config = repo.config()?;
-        config.set_str("receive.advertisePushOptions", "true")?;
-        config.set_str("sendpack.sideband", "false")?;
+        // Resume the clone left in the directory, if any.
+        let resumable = Repository::open(directory).ok().filter(|repo| {
+            repo.find_remote("origin")
+                .map(|remote| remote.url() == Some(url))
+                .unwrap_or(false)
+        });
+        let repo = if let Some(repo) = resumable {
+            repo
+        } else {
+            let repo = Repository::init(directory)?;
+            repo.remote("origin", url)?;
+            repo
+        };
+        {
+            let mut config = repo.config()?;
+            config.set_str("receive.advertisePushOptions", "true")?;
+            config.set_str("sendpack.sideband", "false")?;
+        }
+
+        let (heads, default_branch) = with_retries(options, || {
+            let mut remote = repo.find_remote("origin")?;
+            let connection = remote.connect_auth(git2::Direction::Fetch, None, None)?;
+            let heads = connection
+                .list()?
+                .iter()
+                .map(|head| (head.name().to_owned(), head.oid()))
+                .collect::<Vec<_>>();
+            let default_branch = connection
+                .default_branch()
+                .ok()
+                .and_then(|branch| branch.as_str().map(|branch| branch.to_owned()));
+            Ok((heads, default_branch))
+        })?;
+        for (name, oid) in &heads {
+            let branch = if let Some(branch) = name.strip_prefix("refs/heads/") {
+                branch
+            } else {
+                continue;
+            };
+            let tracking = format!("refs/remotes/origin/{branch}");
+            if repo.refname_to_id(&tracking).ok() == Some(*oid) {
+                continue;
+            }
+            with_retries(options, || {
+                fetch_with_progress(&repo, &format!("+{name}:{tracking}"), options)
+            })?;
+        }
+        with_retries(options, || {
+            fetch_with_progress(&repo, "+refs/tags/*:refs/tags/*", options)
+        })?;
+
+        // Check out what `HEAD` of the remote points to.
+        if let Some(branch) = default_branch
+            .as_deref()
+            .and_then(|branch| branch.strip_prefix("refs/heads/"))
+        {
+            let tracking = format!("refs/remotes/origin/{branch}");
+            let commit = repo.find_reference(&tracking)?.peel_to_commit()?;
+            repo.branch(branch, &commit, true)?
+                .set_upstream(Some(&format!("origin/{branch}")))?;
+            repo.set_head(&format!("refs/heads/{branch}"))?;
+        } else if let Some((_, oid)) = heads.iter().find(|(name, _)| name == "HEAD") {
+            repo.set_head_detached(*oid)?;
+        }
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
         Ok(Self { repo })
     }
 
@@ -116,20 +185,28 @@ impl RawRepositoryInner {
     }
 
     pub(crate) fn locate_branch(&self, branch: Branch) -> Result<CommitHash, Error> {
-        let branch = self
+        let git2_branch = self
             .repo
             .find_branch(&branch, BranchType::Local)
             .map_err(|err| {
                 if err.code() == git2::ErrorCode::NotFound {
                     Error::NotFound("branch not found".to_string())
+                } else if err.class() == git2::ErrorClass::Reference {
+                    Error::CorruptReference(format!("branch {branch}: {}", err.message()))
                 } else {
-                    Error::Unknown("err".to_string())
+                    Error::from(err)
                 }
             })?;
-        let oid = branch
-            .get()
-            .target()
-            .ok_or_else(|| Error::Unknown("err".to_string()))?;
+        let oid = git2_branch.get().target().ok_or_else(|| {
+            Error::CorruptReference(format!("branch {branch} doesn't point to a commit"))
+        })?;
+        if let Err(err) = self.repo.find_commit(oid) {
+            return Err(if err.code() == git2::ErrorCode::NotFound {
+                Error::CorruptReference(format!("branch {branch} points to a missing commit {oid}"))
+            } else {
+                Error::from(err)
+            });
+        }
         let hash =
             <[u8; 20]>::try_from(oid.as_bytes()).map_err(|_| Error::Unknown("err".to_string()))?;
         Ok(CommitHash { hash })
@@ -216,6 +293,44 @@ impl RawRepositoryInner {
         Ok(())
     }
 
+    pub(crate) fn create_signed_tag(&mut self, signed_tag: SignedTag) -> Result<(), Error> {
+        let oid = Oid::from_bytes(&signed_tag.target.commit_hash.hash)?;
+        let object = self.repo.find_object(oid, Some(ObjectType::Commit))?;
+        let time = git2::Time::new(signed_tag.target.timestamp / 1000, 540);
+        let tagger = git2::Signature::new(SIMPERBY_COMMIT_AUTHOR, SIMPERBY_COMMIT_EMAIL, &time)?;
+        let encoded = serde_spb::to_string(&signed_tag)
+            .map_err(|e| Error::Unknown(format!("failed to encode the signed tag: {e}")))?;
+        let message = format!("{}\n\n{}", signed_tag.target.message, encoded);
+        self.repo
+            .tag(&signed_tag.target.tag, &object, &tagger, &message, false)?;
+        Ok(())
+    }
+
+    pub(crate) fn read_signed_tag(&self, tag: Tag) -> Result<SignedTag, Error> {
+        let reference = self
+            .repo
+            .find_reference(&("refs/tags/".to_owned() + &tag))
+            .map_err(|e| {
+                if e.code() == git2::ErrorCode::NotFound {
+                    Error::NotFound(format!("tag not found: {tag}"))
+                } else {
+                    Error::from(e)
+                }
+            })?;
+        let tag_object = reference
+            .peel_to_tag()
+            .map_err(|_| Error::InvalidSignedTag(format!("{tag} is not an annotated tag")))?;
+        let message = tag_object
+            .message()
+            .ok_or_else(|| Error::InvalidSignedTag(format!("{tag} has a non-UTF-8 message")))?;
+        // The encoded signed tag never contains an empty line, so the last one is the separator.
+        let (_, encoded) = message
+            .rsplit_once("\n\n")
+            .ok_or_else(|| Error::InvalidSignedTag(format!("{tag} is not signed")))?;
+        serde_spb::from_str(encoded)
+            .map_err(|e| Error::InvalidSignedTag(format!("{tag} has a malformed signature: {e}")))
+    }
+
     pub(crate) fn locate_tag(&self, tag: Tag) -> Result<CommitHash, Error> {
         let reference = self
             .repo
@@ -235,9 +350,8 @@ impl RawRepositoryInner {
         let references = references
             .into_iter()
             .map(|reference| {
-                let oid = reference
-                    .target()
-                    .ok_or_else(|| Error::Unknown("err".to_string()))?;
+                // Peel to the commit so that annotated tags are also found.
+                let oid = reference.peel(ObjectType::Commit)?.id();
 
                 Ok((reference, oid))
             })
@@ -409,12 +523,10 @@ impl RawRepositoryInner {
     ) -> Result<CommitHash, Error> {
         let time = git2::Time::new(commit.timestamp / 1000, 540);
         let sig = if authored_by_simperby {
-            git2::Signature::new("Simperby", "hi@simperby.net", &time)?
+            git2::Signature::new(SIMPERBY_COMMIT_AUTHOR, SIMPERBY_COMMIT_EMAIL, &time)?
         } else {
-            let config = self.repo.config()?;
-            let name = config.get_string("user.name")?;
-            let email = config.get_string("user.email")?;
-            git2::Signature::new(name.as_str(), email.as_str(), &time)?
+            let author = self.get_author()?;
+            git2::Signature::new(&author.name, &author.email, &time)?
         };
         match commit.diff {
             Diff::None => {
@@ -438,7 +550,14 @@ impl RawRepositoryInner {
                     .map_err(|_| Error::Unknown("err".to_string()))?;
                 Ok(CommitHash { hash })
             }
-            Diff::Reserved(reserved_state) => {
+            Diff::Reserved(reserved_state, base) => {
+                // The base is not stored, but derived from the parent on read.
+                let head_base = self.reserved_state_base(self.get_head()?)?;
+                if base != head_base {
+                    return Err(Error::ReservedStateInvalid(format!(
+                        "the reserved diff is based on {base}, not on the reserved state of HEAD ({head_base})"
+                    )));
+                }
                 let path = self.get_working_directory_path()?;
                 tokio::runtime::Handle::current()
                     .block_on(async move {
@@ -469,7 +588,7 @@ impl RawRepositoryInner {
                     .map_err(|_| Error::Unknown("err".to_string()))?;
                 Ok(CommitHash { hash })
             }
-            Diff::General(_, _) => Err(Error::InvalidRepository(
+            Diff::General(..) => Err(Error::InvalidRepository(
                 "diff is Diff::General()".to_string(),
             )),
             Diff::NonReserved(_) => Err(Error::InvalidRepository(
@@ -485,23 +604,45 @@ impl RawRepositoryInner {
         let oid = Oid::from_bytes(&commit_hash.hash)?;
         let commit = self.repo.find_commit(oid)?;
         let tree = commit.tree()?;
-        let parent_tree = commit.parent(0)?.tree()?;
+        let parent = commit.parent(0)?;
+        let parent_tree = parent.tree()?;
 
         // Create diff by verifying the commit made files or not.
         let diff = self
             .repo
             .diff_tree_to_tree(Some(&tree), Some(&parent_tree), None)?;
+        let (mut reserved, mut non_reserved) = (false, false);
+        for delta in diff.deltas() {
+            let changed = |file: git2::DiffFile| {
+                file.path()
+                    .map_or(false, |path| path.starts_with(RESERVED_STATE_DIRECTORY))
+            };
+            if changed(delta.old_file()) || changed(delta.new_file()) {
+                reserved = true;
+            } else {
+                non_reserved = true;
+            }
+        }
 
-        let diff = if diff.deltas().len() == 0 {
+        let diff = if !reserved && !non_reserved {
             Diff::None
         } else {
-            let patch = self.show_commit(commit_hash)?;
-            let hash = patch.to_hash256();
-            Diff::NonReserved(hash)
+            let patch_hash = || Ok::<_, Error>(self.show_commit(commit_hash)?.to_hash256());
+            if reserved {
+                let reserved_state = Box::new(self.read_reserved_state_at_commit(commit_hash)?);
+                let base = self.reserved_state_base(CommitHash {
+                    hash: <[u8; 20]>::try_from(parent.id().as_bytes())
+                        .map_err(|_| Error::Unknown("err".to_string()))?,
+                })?;
+                if non_reserved {
+                    Diff::General(reserved_state, patch_hash()?, base)
+                } else {
+                    Diff::Reserved(reserved_state, base)
+                }
+            } else {
+                Diff::NonReserved(patch_hash()?)
+            }
         };
-        /* TODO: If reserved state
-        let reserved_state = self.read_reserved_state()?;
-        Diff::Reserved(Box::new(reserved_state))*/
 
         let title = commit.summary();
         let title = if let Some(msg_title) = title {
@@ -533,7 +674,73 @@ impl RawRepositoryInner {
     }
 
     pub(crate) fn run_garbage_collection(&mut self) -> Result<(), Error> {
-        todo!()
+        // The reflog keeps the orphaned commits reachable otherwise.
+        self.run_git(&["reflog", "expire", "--expire-unreachable=now", "--all"])?;
+        self.run_git(&["gc", "--prune=now", "--aggressive", "--quiet"])
+    }
+
+    /// Runs a git command in the working directory.
+    fn run_git(&self, args: &[&str]) -> Result<(), Error> {
+        let output = std::process::Command::new("git")
+            .current_dir(self.get_working_directory_path()?)
+            .args(args)
+            .output()
+            .map_err(|e| Error::Unknown(format!("failed to execute git {}: {e}", args[0])))?;
+        if !output.status.success() {
+            return Err(Error::Unknown(format!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn count_loose_objects(&self) -> Result<usize, Error> {
+        let mut count = 0;
+        let objects = self.repo.path().join("objects");
+        for entry in std::fs::read_dir(objects).map_err(|e| Error::Unknown(e.to_string()))? {
+            let entry = entry.map_err(|e| Error::Unknown(e.to_string()))?;
+            // Loose objects are fanned out into the directories named by the first 2 hex digits.
+            let name = entry.file_name();
+            if name.len() == 2
+                && name
+                    .to_string_lossy()
+                    .chars()
+                    .all(|c| c.is_ascii_hexdigit())
+            {
+                count += std::fs::read_dir(entry.path())
+                    .map_err(|e| Error::Unknown(e.to_string()))?
+                    .count();
+            }
+        }
+        Ok(count)
+    }
+
+    pub(crate) fn run_garbage_collection_if_needed(
+        &mut self,
+        loose_objects_threshold: usize,
+    ) -> Result<bool, Error> {
+        if self.count_loose_objects()? <= loose_objects_threshold {
+            return Ok(false);
+        }
+        self.run_garbage_collection()?;
+        Ok(true)
+    }
+
+    pub(crate) fn set_author(&mut self, author: CommitAuthor) -> Result<(), Error> {
+        let mut config = self.repo.config()?;
+        config.set_str("user.name", &author.name)?;
+        config.set_str("user.email", &author.email)?;
+        Ok(())
+    }
+
+    pub(crate) fn get_author(&self) -> Result<CommitAuthor, Error> {
+        let config = self.repo.config()?;
+        Ok(CommitAuthor {
+            name: config.get_string("user.name")?,
+            email: config.get_string("user.email")?,
+        })
     }
 
     pub(crate) fn checkout_clean(&mut self) -> Result<(), Error> {
@@ -684,6 +891,14 @@ impl RawRepositoryInner {
         }
     }
 
+    pub(crate) fn get_head_state(&self) -> Result<HeadState, Error> {
+        let head = self.get_head()?;
+        Ok(match self.get_currently_checkout_branch()? {
+            Some(branch) => HeadState::Attached(branch, head),
+            None => HeadState::Detached(head),
+        })
+    }
+
     pub(crate) fn get_initial_commit(&self) -> Result<CommitHash, Error> {
         // Check if the repository is empty
         self.repo
@@ -842,10 +1057,29 @@ impl RawRepositoryInner {
         let path = self.get_working_directory_path()?;
         let reserved_state = tokio::runtime::Handle::current()
             .block_on(async move { reserved_state::read_reserved_state(&format!("{path}/")).await })
-            .map_err(|e| Error::Unknown(e.to_string()))?;
+            .map_err(|e| Error::ReservedStateInvalid(e.to_string()))?;
+        verify::verify_protocol_version(&reserved_state.version)
+            .and_then(|_| reserved_state.verify_structure())
+            .map_err(|e| Error::ReservedStateInvalid(e.to_string()))?;
         Ok(reserved_state)
     }
 
+    /// Returns the hash of the reserved state at the given commit, or zero if there is none,
+    /// which is the base of a reserved diff made on top of the commit.
+    fn reserved_state_base(&self, commit_hash: CommitHash) -> Result<Hash256, Error> {
+        let commit = self.repo.find_commit(Oid::from_bytes(&commit_hash.hash)?)?;
+        if commit
+            .tree()?
+            .get_path(std::path::Path::new(RESERVED_STATE_DIRECTORY))
+            .is_err()
+        {
+            return Ok(Hash256::zero());
+        }
+        Ok(self
+            .read_reserved_state_at_commit(commit_hash)?
+            .to_hash256())
+    }
+
     pub(crate) fn read_reserved_state_at_commit(
         &self,
         commit_hash: CommitHash,
@@ -905,11 +1139,29 @@ impl RawRepositoryInner {
         let version: String =
             serde_spb::from_str(content).map_err(|e| Error::Unknown(e.to_string()))?;
 
+        // The governance policy is optional; a tree without it uses the default one.
+        let path = std::path::Path::new("reserved/governance_policy.json");
+        let governance_policy = match tree.get_path(path) {
+            Ok(entry) => {
+                let blob = entry.to_object(&self.repo)?;
+                let blob = blob
+                    .as_blob()
+                    .ok_or_else(|| Error::Unknown("failed to get a blob".to_string()))?;
+                let content = std::str::from_utf8(blob.content()).map_err(|_| {
+                    Error::Unknown("content of governance_policy.json is not UTF-8".to_string())
+                })?;
+                serde_spb::from_str(content).map_err(|e| Error::Unknown(e.to_string()))?
+            }
+            Err(e) if e.code() == git2::ErrorCode::NotFound => GovernancePolicy::default(),
+            Err(e) => return Err(e.into()),
+        };
+
         Ok(ReservedState {
             genesis_info,
             members,
             consensus_leader_order,
             version,
+            governance_policy,
         })
     }
 
@@ -942,7 +1194,13 @@ impl RawRepositoryInner {
         fetch_options.prune(git2::FetchPrune::On);
         for name in remotes {
             let mut remote = self.repo.find_remote(name)?;
-            if prune {
+            if remote.url().map_or(false, is_ipv6_git_url) {
+                let mut args = vec!["fetch", "--quiet", name];
+                if prune {
+                    args.push("--prune");
+                }
+                self.run_git(&args)?;
+            } else if prune {
                 remote.fetch(&[] as &[&str], Some(&mut fetch_options), None)?;
             } else {
                 remote.fetch(&[] as &[&str], None, None)?;
@@ -958,20 +1216,34 @@ impl RawRepositoryInner {
         option: Option<String>,
     ) -> Result<(), Error> {
         let workdir = self.get_working_directory_path()?;
+        let mut command = std::process::Command::new("git");
+        command.current_dir(workdir).args([
+            "push",
+            "--quiet",
+            remote_name.as_str(),
+            branch.as_str(),
+        ]);
         if let Some(option_string) = option {
-            run_command(format!(
-                "cd {workdir} && git push --quiet {remote_name} {branch} --push-option='{option_string}'"
-            ))?
+            command.arg(format!("--push-option={option_string}"));
+        }
+        let output = command
+            .output()
+            .map_err(|e| Error::Unknown(format!("failed to execute git push: {e}")))?;
+        if output.status.success() {
+            Ok(())
         } else {
-            run_command(format!(
-                "cd {workdir} && git push --quiet {remote_name} {branch}"
-            ))?
-        };
-        Ok(())
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(Error::Push(PushError::from_git_output(&stderr)))
+        }
     }
 
     pub(crate) fn ping_remote(&self, remote_name: String) -> Result<bool, Error> {
         let mut remote = self.repo.find_remote(remote_name.as_str())?;
+        if remote.url().map_or(false, is_ipv6_git_url) {
+            return Ok(self
+                .run_git(&["ls-remote", "--quiet", &remote_name])
+                .is_ok());
+        }
         let is_open = remote.connect(git2::Direction::Fetch).ok();
         let is_open = if is_open.is_some() {
             remote.disconnect()?;
@@ -1068,3 +1340,58 @@ impl RawRepositoryInner {
         Ok(CommitHash { hash })
     }
 }
+
+/// Runs `f` until it succeeds, at most `CloneOptions::max_retries` more times.
+fn with_retries<T>(
+    options: &CloneOptions,
+    mut f: impl FnMut() -> Result<T, git2::Error>,
+) -> Result<T, Error> {
+    let mut retries = 0;
+    loop {
+        match f() {
+            Ok(result) => return Ok(result),
+            Err(e) if retries < options.max_retries => {
+                retries += 1;
+                log::warn!(
+                    "failed to transfer ({e}); retrying ({retries}/{})",
+                    options.max_retries
+                );
+                std::thread::sleep(std::time::Duration::from_millis(options.retry_interval_ms));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn fetch_with_progress(
+    repo: &Repository,
+    refspec: &str,
+    options: &CloneOptions,
+) -> Result<(), git2::Error> {
+    let mut callbacks = git2::RemoteCallbacks::new();
+    if let Some(progress) = &options.progress {
+        callbacks.transfer_progress(|stats| {
+            progress(TransferProgress {
+                received_objects: stats.received_objects(),
+                total_objects: stats.total_objects(),
+                received_bytes: stats.received_bytes(),
+            });
+            true
+        });
+    }
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    repo.find_remote("origin")?
+        .fetch(&[refspec], Some(&mut fetch_options), None)
+}
+
+/// The directory of the reserved state in the working tree.
+const RESERVED_STATE_DIRECTORY: &str = "reserved";
+
+/// Whether the URL is of a `git://` remote at an IPv6 address (e.g., `git://[::1]:9418/`).
+///
+/// libgit2 sends a malformed host for it, which the git daemon rejects,
+/// so such a remote is fetched with the git CLI instead.
+fn is_ipv6_git_url(url: &str) -> bool {
+    url.starts_with("git://[")
+}
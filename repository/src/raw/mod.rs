@@ -12,8 +12,10 @@ use git2::{
     StatusOptions, StatusShow,
 };
 use implementation::RawRepositoryInner;
+use lru::LruCache;
 use simperby_core::reserved::ReservedState;
 use std::convert::TryFrom;
+use std::num::NonZeroUsize;
 use std::str;
 use templates::*;
 use thiserror::Error;
@@ -29,6 +31,19 @@ pub enum Error {
     /// (e.g., there is no merge commit, there must be a merge base, ..) is violated.
     #[error("the repository is invalid: {0}")]
     InvalidRepository(String),
+    /// The tag is not a valid signed tag.
+    #[error("invalid signed tag: {0}")]
+    InvalidSignedTag(String),
+    /// Failed to push to the remote repository.
+    #[error("failed to push: {0}")]
+    Push(PushError),
+    /// The reference exists but is broken (e.g., it points to a missing object),
+    /// so it has to be repaired (e.g., deleted and recreated) to be used.
+    #[error("corrupt reference: {0}")]
+    CorruptReference(String),
+    /// The reserved state is malformed, of an incompatible version, or breaks its invariants.
+    #[error("invalid reserved state: {0}")]
+    ReservedStateInvalid(String),
     #[error("unknown error: {0}")]
     Unknown(String),
 }
@@ -39,6 +54,71 @@ impl From<git2::Error> for Error {
     }
 }
 
+/// The classified reason of a failed push.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PushError {
+    /// The push hook of the server (i.e., the `PushVerifier`) declined the push.
+    ///
+    /// Contains the messages that the server sent while rejecting it.
+    #[error("rejected by the verifier: {0}")]
+    RejectedByVerifier(String),
+    /// The remote branch contains commits that the local branch doesn't have.
+    #[error("non-fast-forward update")]
+    NonFastForward,
+    /// Failed to reach or talk to the remote repository.
+    #[error("transport error: {0}")]
+    Transport(String),
+    /// The remote repository refused the credentials.
+    #[error("authentication failed: {0}")]
+    Auth(String),
+}
+
+impl PushError {
+    /// Classifies a failed push from the standard error output of `git push`.
+    pub fn from_git_output(stderr: &str) -> Self {
+        if stderr.contains("[remote rejected]") {
+            let reason = stderr
+                .lines()
+                .filter_map(|line| line.strip_prefix("remote:"))
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            return PushError::RejectedByVerifier(reason);
+        }
+        if stderr.contains("[rejected]")
+            && (stderr.contains("non-fast-forward") || stderr.contains("fetch first"))
+        {
+            return PushError::NonFastForward;
+        }
+        let message = stderr.trim().to_string();
+        let lowercase = message.to_lowercase();
+        if [
+            "authentication failed",
+            "permission denied",
+            "could not read username",
+            "could not read password",
+            "terminal prompts disabled",
+        ]
+        .iter()
+        .any(|pattern| lowercase.contains(pattern))
+        {
+            PushError::Auth(message)
+        } else {
+            PushError::Transport(message)
+        }
+    }
+}
+
+/// The state of `HEAD`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeadState {
+    /// `HEAD` is on the branch, which points to the commit.
+    Attached(Branch, CommitHash),
+    /// `HEAD` directly points to the commit.
+    Detached(CommitHash),
+}
+
 /// A commit with abstracted diff.
 /// - `author` is a member name, not a git commit author.
 /// - `timestamp` is generated by `get_timestamp()` which represents up to milliseconds.
@@ -66,12 +146,99 @@ pub struct RawCommit {
     pub timestamp: Timestamp,
 }
 
+/// The git identity that the commits not authored by Simperby are made under
+/// (i.e., `user.name` and `user.email` of the repository).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+impl Default for CommitAuthor {
+    fn default() -> Self {
+        Self {
+            name: "user".to_owned(),
+            email: "user@simperby.net".to_owned(),
+        }
+    }
+}
+
+/// The data that a signed tag commits to.
+/// - `timestamp` is generated by `get_timestamp()` which represents up to milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagSignTarget {
+    pub tag: Tag,
+    pub commit_hash: CommitHash,
+    pub message: String,
+    pub timestamp: Timestamp,
+}
+
+impl ToHash256 for TagSignTarget {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
+impl SignatureDomain for TagSignTarget {
+    const SIGNATURE_DOMAIN: &'static str = "simperby-tag";
+}
+
+/// An annotated tag which is signed by a Simperby key.
+///
+/// It is stored as an annotated git tag whose message is the original message
+/// followed by this structure encoded in `serde_spb`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTag {
+    pub target: TagSignTarget,
+    pub signature: TypedSignature<TagSignTarget>,
+}
+
+/// The progress of fetching objects from a remote repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferProgress {
+    pub received_objects: usize,
+    pub total_objects: usize,
+    pub received_bytes: usize,
+}
+
+/// How `RawRepository::clone_with_options()` deals with a slow or flaky remote.
+#[derive(Clone)]
+pub struct CloneOptions {
+    /// How many times a failed transfer is retried before the clone fails.
+    pub max_retries: usize,
+    /// How long to wait before each retry.
+    pub retry_interval_ms: u64,
+    /// Called whenever more objects are received.
+    pub progress: Option<Arc<dyn Fn(TransferProgress) + Send + Sync>>,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            retry_interval_ms: 1000,
+            progress: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RawRepository {
     inner: tokio::sync::Mutex<Option<RawRepositoryInner>>,
+    /// The cache of the reserved states at finalized commits, which never change.
+    reserved_state_cache: std::sync::Mutex<LruCache<CommitHash, ReservedState>>,
 }
 
 impl RawRepository {
+    fn from_inner(repo: RawRepositoryInner) -> Self {
+        Self {
+            inner: tokio::sync::Mutex::new(Some(repo)),
+            reserved_state_cache: std::sync::Mutex::new(LruCache::new(
+                NonZeroUsize::new(RESERVED_STATE_CACHE_SIZE).expect("nonzero cache size"),
+            )),
+        }
+    }
+
     /// Initialize the genesis repository from the genesis working tree.
     ///
     /// Fails if there is already a repository.
@@ -83,10 +250,29 @@ impl RawRepository {
     where
         Self: Sized,
     {
-        let repo = RawRepositoryInner::init(directory, init_commit_message, init_commit_branch)?;
-        let inner = tokio::sync::Mutex::new(Some(repo));
+        let repo =
+            RawRepositoryInner::init(directory, init_commit_message, init_commit_branch, None)?;
+        Ok(Self::from_inner(repo))
+    }
 
-        Ok(Self { inner })
+    /// Initialize the genesis repository like `init()`, making the commits under the given author
+    /// instead of the one from the git config (or the default).
+    pub async fn init_with_author(
+        directory: &str,
+        init_commit_message: &str,
+        init_commit_branch: &Branch,
+        author: CommitAuthor,
+    ) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let repo = RawRepositoryInner::init(
+            directory,
+            init_commit_message,
+            init_commit_branch,
+            Some(author),
+        )?;
+        Ok(Self::from_inner(repo))
     }
 
     /// Loads an exisitng repository.
@@ -95,22 +281,33 @@ impl RawRepository {
         Self: Sized,
     {
         let repo = RawRepositoryInner::open(directory)?;
-        let inner = tokio::sync::Mutex::new(Some(repo));
-
-        Ok(Self { inner })
+        Ok(Self::from_inner(repo))
     }
 
-    /// Clones an exisitng repository.
+    /// Clones an exisitng repository, retrying a failed transfer with the default `CloneOptions`.
     ///
     /// Fails if there is no repository with url.
     pub async fn clone(directory: &str, url: &str) -> Result<Self, Error>
     where
         Self: Sized,
     {
-        let repo = RawRepositoryInner::clone(directory, url)?;
-        let inner = tokio::sync::Mutex::new(Some(repo));
+        Self::clone_with_options(directory, url, CloneOptions::default()).await
+    }
 
-        Ok(Self { inner })
+    /// Clones an exisitng repository like `clone()`, with the given options.
+    ///
+    /// The branches are fetched one by one, so a retry after a disconnection
+    /// (or another clone into the same directory) doesn't fetch the finished ones again.
+    pub async fn clone_with_options(
+        directory: &str,
+        url: &str,
+        options: CloneOptions,
+    ) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let repo = RawRepositoryInner::clone(directory, url, &options)?;
+        Ok(Self::from_inner(repo))
     }
 
     /// Returns the full commit hash from the revision selection string.
@@ -153,6 +350,9 @@ impl RawRepository {
     }
 
     /// Gets the commit that the branch points to.
+    ///
+    /// Fails with `Error::NotFound` if there is no such branch,
+    /// or with `Error::CorruptReference` if the branch is broken (e.g., points to a missing commit).
     pub async fn locate_branch(&self, branch: Branch) -> Result<CommitHash, Error> {
         helper_1(self, RawRepositoryInner::locate_branch, branch).await
     }
@@ -190,6 +390,58 @@ impl RawRepository {
         helper_2_mut(self, RawRepositoryInner::create_tag, tag, commit_hash).await
     }
 
+    /// Creates an annotated tag on the given commit, signed by the given key.
+    ///
+    /// Use `verify_signed_tag()` to read it back.
+    pub async fn create_signed_tag(
+        &mut self,
+        tag: Tag,
+        commit_hash: CommitHash,
+        private_key: &PrivateKey,
+        message: String,
+    ) -> Result<(), Error> {
+        let target = TagSignTarget {
+            tag,
+            commit_hash,
+            message,
+            timestamp: simperby_core::utils::get_timestamp(),
+        };
+        let signature = TypedSignature::sign(&target, private_key)
+            .map_err(|e| Error::Unknown(format!("failed to sign the tag: {e}")))?;
+        helper_1_mut(
+            self,
+            RawRepositoryInner::create_signed_tag,
+            SignedTag { target, signature },
+        )
+        .await
+    }
+
+    /// Reads the signed tag and verifies its signature.
+    ///
+    /// It fails if the tag is a lightweight tag, is not signed,
+    /// or doesn't match the commit that it points to.
+    /// The signer can be resolved to a member with `ReservedState::query_name()`.
+    pub async fn verify_signed_tag(&self, tag: Tag) -> Result<SignedTag, Error> {
+        let signed_tag = helper_1(self, RawRepositoryInner::read_signed_tag, tag.clone()).await?;
+        if signed_tag.target.tag != tag {
+            return Err(Error::InvalidSignedTag(format!(
+                "tag {tag} is signed with a different name: {}",
+                signed_tag.target.tag
+            )));
+        }
+        if signed_tag.target.commit_hash != self.locate_tag(tag.clone()).await? {
+            return Err(Error::InvalidSignedTag(format!(
+                "tag {tag} is signed for a different commit: {}",
+                signed_tag.target.commit_hash
+            )));
+        }
+        signed_tag
+            .signature
+            .verify(&signed_tag.target)
+            .map_err(|e| Error::InvalidSignedTag(format!("invalid signature on {tag}: {e}")))?;
+        Ok(signed_tag)
+    }
+
     /// Gets the commit that the tag points to.
     pub async fn locate_tag(&self, tag: Tag) -> Result<CommitHash, Error> {
         helper_1(self, RawRepositoryInner::locate_tag, tag).await
@@ -257,15 +509,47 @@ impl RawRepository {
         helper_1(self, RawRepositoryInner::read_semantic_commit, commit_hash).await
     }
 
-    /// Removes orphaned commits. Same as `git gc --prune=now --aggressive`
+    /// Removes orphaned commits. Same as `git gc --prune=now --aggressive`,
+    /// after expiring the reflog entries of the unreachable commits.
     pub async fn run_garbage_collection(&mut self) -> Result<(), Error> {
         helper_0_mut(self, RawRepositoryInner::run_garbage_collection).await
     }
 
+    /// Returns the number of loose (unpacked) objects, which grows with orphaned commits.
+    pub async fn count_loose_objects(&self) -> Result<usize, Error> {
+        helper_0(self, RawRepositoryInner::count_loose_objects).await
+    }
+
+    /// Runs `run_garbage_collection()` only if there are more loose objects than the threshold,
+    /// returning whether it ran.
+    ///
+    /// Use `GC_LOOSE_OBJECTS_THRESHOLD` unless there is a reason not to.
+    pub async fn run_garbage_collection_if_needed(
+        &mut self,
+        loose_objects_threshold: usize,
+    ) -> Result<bool, Error> {
+        helper_1_mut(
+            self,
+            RawRepositoryInner::run_garbage_collection_if_needed,
+            loose_objects_threshold,
+        )
+        .await
+    }
+
     // ----------------------------
     // Working-tree-related methods
     // ----------------------------
 
+    /// Sets the author of the commits that are not authored by Simperby.
+    pub async fn set_author(&mut self, author: CommitAuthor) -> Result<(), Error> {
+        helper_1_mut(self, RawRepositoryInner::set_author, author).await
+    }
+
+    /// Returns the author of the commits that are not authored by Simperby.
+    pub async fn get_author(&self) -> Result<CommitAuthor, Error> {
+        helper_0(self, RawRepositoryInner::get_author).await
+    }
+
     /// Checkouts and cleans the current working tree.
     /// This is same as `git checkout . && git clean -fd`.
     pub async fn checkout_clean(&mut self) -> Result<(), Error> {
@@ -329,6 +613,27 @@ impl RawRepository {
         helper_0(self, RawRepositoryInner::get_currently_checkout_branch).await
     }
 
+    /// Returns the state of `HEAD`, with the checked-out branch if any.
+    pub async fn get_head_state(&self) -> Result<HeadState, Error> {
+        helper_0(self, RawRepositoryInner::get_head_state).await
+    }
+
+    /// Checks that `HEAD` is on the given branch, returning the commit hash of it.
+    ///
+    /// Use this before creating a commit that must be on the branch,
+    /// so that a detached `HEAD` can't leave the commit orphaned.
+    pub async fn check_head_on_branch(&self, branch: Branch) -> Result<CommitHash, Error> {
+        match self.get_head_state().await? {
+            HeadState::Attached(current, commit_hash) if current == branch => Ok(commit_hash),
+            HeadState::Attached(current, _) => Err(Error::InvalidRepository(format!(
+                "HEAD is on branch {current}, not on {branch}"
+            ))),
+            HeadState::Detached(commit_hash) => Err(Error::InvalidRepository(format!(
+                "HEAD is detached at {commit_hash}, not on {branch}"
+            ))),
+        }
+    }
+
     /// Returns the commit hash of the initial commit.
     ///
     /// Fails if the repository is empty.
@@ -398,21 +703,43 @@ impl RawRepository {
     }
 
     /// Reads the reserved state from the currently checked out branch.
+    ///
+    /// Fails with `Error::ReservedStateInvalid` if it can't be parsed,
+    /// its protocol version is incompatible, or it doesn't hold `ReservedState::verify_structure()`.
     pub async fn read_reserved_state(&self) -> Result<ReservedState, Error> {
         helper_0(self, RawRepositoryInner::read_reserved_state).await
     }
 
     /// Reads the reserved state at given commit hash.
+    ///
+    /// The reserved states at finalized commits (the ancestors of the `finalized` branch) are cached,
+    /// so reading them again doesn't touch the git backend.
     pub async fn read_reserved_state_at_commit(
         &self,
         commit_hash: CommitHash,
     ) -> Result<ReservedState, Error> {
-        helper_1(
+        if let Some(reserved_state) = self.reserved_state_cache.lock().unwrap().get(&commit_hash) {
+            return Ok(reserved_state.clone());
+        }
+        let reserved_state = helper_1(
             self,
             RawRepositoryInner::read_reserved_state_at_commit,
             commit_hash,
         )
-        .await
+        .await?;
+        // Commits that are not finalized yet may be discarded, so we don't cache them.
+        let finalized = match self.locate_branch(FINALIZED_BRANCH_NAME.into()).await {
+            Ok(finalized) => finalized,
+            Err(Error::NotFound(_)) => return Ok(reserved_state),
+            Err(e) => return Err(e),
+        };
+        if self.find_merge_base(commit_hash, finalized).await? == commit_hash {
+            self.reserved_state_cache
+                .lock()
+                .unwrap()
+                .put(commit_hash, reserved_state.clone());
+        }
+        Ok(reserved_state)
     }
 
     // ----------------------
@@ -446,6 +773,8 @@ impl RawRepository {
 
     /// Pushes to the remote repository with the push option.
     /// This is same as `git push <remote_name> <branch_name> --push-option=<string>`.
+    ///
+    /// If the push fails, returns `Error::Push` with the reason classified from git's output.
     pub async fn push_option(
         &self,
         remote_name: String,
@@ -31,11 +31,21 @@ pub async fn read_reserved_state(path: &str) -> Result<ReservedState, Error> {
     let version = fs::read_to_string(format!("{}/{}", path, "reserved/version")).await?;
     let version: String = serde_spb::from_str(version.as_str())?;
 
+    // The governance policy is optional; a tree without it uses the default one.
+    let governance_policy_path = format!("{}/{}", path, "reserved/governance_policy.json");
+    let governance_policy = if Path::new(governance_policy_path.as_str()).exists() {
+        let governance_policy = fs::read_to_string(governance_policy_path).await?;
+        serde_spb::from_str(governance_policy.as_str())?
+    } else {
+        GovernancePolicy::default()
+    };
+
     let reserved_state = ReservedState {
         genesis_info,
         members,
         consensus_leader_order,
         version,
+        governance_policy,
     };
 
     Ok(reserved_state)
@@ -46,6 +56,7 @@ pub async fn write_reserved_state(path: &str, state: &ReservedState) -> Result<(
     let genesis_info = serde_spb::to_string(&state.genesis_info)?;
     let consensus_leader_order = serde_spb::to_string(&state.consensus_leader_order)?;
     let version = serde_spb::to_string(&state.version)?;
+    let governance_policy = serde_spb::to_string(&state.governance_policy)?;
 
     // Create files of reserved state.
     let path = format!("{}/{}", path, "reserved");
@@ -68,6 +79,11 @@ pub async fn write_reserved_state(path: &str, state: &ReservedState) -> Result<(
     )
     .await?;
     fs::write(format!("{}/{}", path.as_str(), "version"), version).await?;
+    fs::write(
+        format!("{}/{}", path.as_str(), "governance_policy.json"),
+        governance_policy,
+    )
+    .await?;
 
     let path = format!("{}/{}", path.as_str(), "members");
     let members_path = Path::new(path.as_str());
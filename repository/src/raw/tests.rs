@@ -1,9 +1,13 @@
 use super::SemanticCommit;
+use crate::format::{raw_commit_to_semantic_commit, semantic_commit_to_raw_commit};
 use crate::raw::Error;
-use crate::raw::{CommitHash, RawCommit, RawRepository};
+use crate::raw::{CommitAuthor, CommitHash, HeadState, PushError, RawCommit, RawRepository};
+use crate::{SIMPERBY_COMMIT_AUTHOR, SIMPERBY_COMMIT_EMAIL};
 
 use simperby_core::utils::get_timestamp;
-use simperby_core::{test_utils::generate_standard_genesis, Diff, ToHash256};
+use simperby_core::{
+    reserved::ReservedState, test_utils::generate_standard_genesis, Diff, Hash256, ToHash256,
+};
 use std::path::Path;
 use tempfile::TempDir;
 
@@ -40,6 +44,56 @@ async fn init() {
         .unwrap_err();
 }
 
+/// Commits not authored by Simperby are made under the configured author.
+#[tokio::test]
+async fn commit_author() {
+    let td = TempDir::new().unwrap();
+    let path = td.path();
+
+    let author = CommitAuthor {
+        name: "member-0000".to_owned(),
+        email: "member-0000@example.com".to_owned(),
+    };
+    let mut repo = RawRepository::init_with_author(
+        path.to_str().unwrap(),
+        "initial",
+        &MAIN.into(),
+        author.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(repo.get_author().await.unwrap(), author);
+    let initial_commit = repo
+        .read_commit(repo.get_head().await.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        (initial_commit.author, initial_commit.email),
+        (author.name.clone(), author.email.clone())
+    );
+
+    let author = CommitAuthor {
+        name: "member-0001".to_owned(),
+        email: "member-0001@example.com".to_owned(),
+    };
+    repo.set_author(author.clone()).await.unwrap();
+    let commit_hash = repo
+        .create_semantic_commit(
+            SemanticCommit {
+                title: "test".to_owned(),
+                body: "test-body".to_owned(),
+                diff: Diff::None,
+                author: "doesn't matter".to_owned(),
+                timestamp: 0,
+            },
+            false,
+        )
+        .await
+        .unwrap();
+    let commit = repo.read_commit(commit_hash).await.unwrap();
+    assert_eq!((commit.author, commit.email), (author.name, author.email));
+}
+
 /// Open existed repository and verifies whether it opens well.
 #[tokio::test]
 async fn open() {
@@ -162,6 +216,53 @@ async fn tag() {
     assert_eq!(tag_list, vec![TAG_B.to_owned()]);
 }
 
+/// Create a signed vote tag and resolve its signer to a member.
+#[tokio::test]
+async fn signed_tag() {
+    let td = TempDir::new().unwrap();
+    let path = td.path();
+    let mut repo = init_repository_with_initial_commit(path).await.unwrap();
+    let (rs, keys) = generate_standard_genesis(4);
+
+    let first_commit_hash = repo.locate_branch(MAIN.into()).await.unwrap();
+    repo.create_signed_tag(
+        "vote-12345678".into(),
+        first_commit_hash,
+        &keys[2].1,
+        "vote for the agenda".into(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        repo.locate_tag("vote-12345678".into()).await.unwrap(),
+        first_commit_hash
+    );
+    assert_eq!(
+        repo.get_tag(first_commit_hash).await.unwrap(),
+        vec!["vote-12345678".to_owned()]
+    );
+
+    let signed_tag = repo
+        .verify_signed_tag("vote-12345678".into())
+        .await
+        .unwrap();
+    assert_eq!(signed_tag.target.commit_hash, first_commit_hash);
+    assert_eq!(signed_tag.target.message, "vote for the agenda");
+    assert_eq!(
+        rs.query_name(signed_tag.signature.signer()),
+        Some(rs.members[2].name.clone())
+    );
+
+    // A lightweight tag is not a signed tag.
+    repo.create_tag(TAG_A.into(), first_commit_hash)
+        .await
+        .unwrap();
+    assert!(matches!(
+        repo.verify_signed_tag(TAG_A.into()).await,
+        Err(Error::InvalidSignedTag(_))
+    ));
+}
+
 /*
     c3 (HEAD -> main)   c3 (HEAD -> main)     c3 (main)                   c3 (HEAD -> main)
     |                   |                     |                           |
@@ -261,12 +362,26 @@ async fn checkout_detach() {
     let cur_head_commit_hash = repo.get_head().await.unwrap();
     assert_eq!(cur_head_commit_hash, first_commit_hash);
 
-    // TODO: Create a function of getting head name(see below).
-    // This means the current head is at a detached mode,
-    // otherwise this should be "refs/heads/main".
-    //
-    // let cur_head_name = repo.head().unwrap().name().unwrap().to_string();
-    // assert_eq!(cur_head_name, "HEAD");
+    assert_eq!(
+        repo.get_head_state().await.unwrap(),
+        HeadState::Detached(first_commit_hash)
+    );
+    // A commit that must be on "main" is caught rather than orphaned.
+    assert!(matches!(
+        repo.check_head_on_branch(MAIN.into()).await,
+        Err(Error::InvalidRepository(_))
+    ));
+
+    repo.checkout(MAIN.into()).await.unwrap();
+    let second_commit_hash = repo.get_head().await.unwrap();
+    assert_eq!(
+        repo.get_head_state().await.unwrap(),
+        HeadState::Attached(MAIN.to_owned(), second_commit_hash)
+    );
+    assert_eq!(
+        repo.check_head_on_branch(MAIN.into()).await.unwrap(),
+        second_commit_hash
+    );
 }
 
 /// Reset the repository to the latest commit and delete any untracked files or directories.
@@ -633,7 +748,7 @@ async fn reserved_state() {
             SemanticCommit {
                 title: "test".to_owned(),
                 body: "test-body".to_owned(),
-                diff: Diff::Reserved(Box::new(rs1.clone())),
+                diff: Diff::Reserved(Box::new(rs1.clone()), Hash256::zero()),
                 author: "doesn't matter".to_owned(),
                 timestamp: 0,
             },
@@ -648,23 +763,34 @@ async fn reserved_state() {
         .await
         .unwrap();
     assert_eq!(rs1, rs1_retrieve);
-    // TODO: Check if semantic_commit_retrieve after implementation
-    // let semantic_commit_retrieve = repo.read_semantic_commit(commit_hash1).await.unwrap();
+    assert_eq!(
+        repo.read_semantic_commit(commit_hash1).await.unwrap().diff,
+        Diff::Reserved(Box::new(rs1.clone()), Hash256::zero())
+    );
 
     let (rs2, _) = generate_standard_genesis(5);
+    let semantic_commit = SemanticCommit {
+        title: "test".to_owned(),
+        body: "test-body".to_owned(),
+        diff: Diff::Reserved(Box::new(rs2.clone()), rs1.to_hash256()),
+        author: "doesn't matter".to_owned(),
+        timestamp: 0,
+    };
+    // A diff that is not based on the reserved state of HEAD is rejected.
+    let mut wrong_base = semantic_commit.clone();
+    wrong_base.diff = Diff::Reserved(Box::new(rs2.clone()), Hash256::zero());
+    assert!(matches!(
+        repo.create_semantic_commit(wrong_base, false).await,
+        Err(Error::ReservedStateInvalid(_))
+    ));
     let commit_hash2 = repo
-        .create_semantic_commit(
-            SemanticCommit {
-                title: "test".to_owned(),
-                body: "test-body".to_owned(),
-                diff: Diff::Reserved(Box::new(rs2.clone())),
-                author: "doesn't matter".to_owned(),
-                timestamp: 0,
-            },
-            false,
-        )
+        .create_semantic_commit(semantic_commit.clone(), false)
         .await
         .unwrap();
+    assert_eq!(
+        repo.read_semantic_commit(commit_hash2).await.unwrap().diff,
+        semantic_commit.diff
+    );
     let rs2_retrieve = repo.read_reserved_state().await.unwrap();
     assert_eq!(rs2, rs2_retrieve);
     let rs2_retrieve = repo
@@ -679,6 +805,133 @@ async fn reserved_state() {
     assert_eq!(rs1, rs1_retrieve);
 }
 
+#[tokio::test]
+async fn reserved_state_cache() {
+    let td = TempDir::new().unwrap();
+    let path = td.path();
+    let mut repo = init_repository_with_initial_commit(path).await.unwrap();
+
+    let mut commits = Vec::new();
+    let mut previous_hash = Hash256::zero();
+    for members in [10, 5] {
+        let (rs, _) = generate_standard_genesis(members);
+        let commit_hash = repo
+            .create_semantic_commit(
+                SemanticCommit {
+                    title: "test".to_owned(),
+                    body: "test-body".to_owned(),
+                    diff: Diff::Reserved(Box::new(rs.clone()), previous_hash),
+                    author: "doesn't matter".to_owned(),
+                    timestamp: 0,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        previous_hash = rs.to_hash256();
+        commits.push((commit_hash, rs));
+    }
+    // Only the first commit is finalized.
+    repo.create_branch(crate::FINALIZED_BRANCH_NAME.into(), commits[0].0)
+        .await
+        .unwrap();
+    for (commit_hash, rs) in &commits {
+        assert_eq!(
+            &repo
+                .read_reserved_state_at_commit(*commit_hash)
+                .await
+                .unwrap(),
+            rs
+        );
+    }
+
+    // Remove all the git objects; only the cached (finalized) one can still be read.
+    std::fs::remove_dir_all(path.join(".git/objects")).unwrap();
+    std::fs::create_dir(path.join(".git/objects")).unwrap();
+    assert_eq!(
+        repo.read_reserved_state_at_commit(commits[0].0)
+            .await
+            .unwrap(),
+        commits[0].1
+    );
+    assert!(repo
+        .read_reserved_state_at_commit(commits[1].0)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn dangling_branch() {
+    let td = TempDir::new().unwrap();
+    let path = td.path();
+    let mut repo = init_repository_with_initial_commit(path).await.unwrap();
+
+    // The branch points to a commit that doesn't exist.
+    std::fs::write(
+        path.join(".git/refs/heads/dangling"),
+        "0123456789abcdef0123456789abcdef01234567\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        repo.locate_branch("dangling".to_owned()).await,
+        Err(Error::CorruptReference(_))
+    ));
+    assert!(matches!(
+        repo.locate_branch("missing".to_owned()).await,
+        Err(Error::NotFound(_))
+    ));
+    repo.locate_branch(MAIN.into()).await.unwrap();
+
+    // It can be repaired by deleting it.
+    repo.delete_branch("dangling".to_owned()).await.unwrap();
+    assert!(matches!(
+        repo.locate_branch("dangling".to_owned()).await,
+        Err(Error::NotFound(_))
+    ));
+}
+
+/// Commits the given reserved state on a fresh repository and reads it back.
+async fn commit_reserved_state(rs: ReservedState) -> Result<ReservedState, Error> {
+    let td = TempDir::new().unwrap();
+    let mut repo = init_repository_with_initial_commit(td.path())
+        .await
+        .unwrap();
+    repo.create_semantic_commit(
+        SemanticCommit {
+            title: "test".to_owned(),
+            body: "test-body".to_owned(),
+            diff: Diff::Reserved(Box::new(rs), Hash256::zero()),
+            author: "doesn't matter".to_owned(),
+            timestamp: 0,
+        },
+        false,
+    )
+    .await
+    .unwrap();
+    repo.read_reserved_state().await
+}
+
+#[tokio::test]
+async fn invalid_reserved_state() {
+    // Too few members.
+    let (rs, _) = generate_standard_genesis(3);
+    assert!(matches!(
+        commit_reserved_state(rs).await,
+        Err(Error::ReservedStateInvalid(_))
+    ));
+
+    // An incompatible protocol version.
+    let (mut rs, _) = generate_standard_genesis(4);
+    rs.version = "99.0.0".to_owned();
+    assert!(matches!(
+        commit_reserved_state(rs).await,
+        Err(Error::ReservedStateInvalid(_))
+    ));
+
+    let (rs, _) = generate_standard_genesis(4);
+    assert_eq!(commit_reserved_state(rs.clone()).await.unwrap(), rs);
+}
+
 #[tokio::test]
 async fn clone() {
     let td = TempDir::new().unwrap();
@@ -855,3 +1108,171 @@ async fn patch() {
     // assert_eq!(patch, patch_retrieve);
     assert!(patch_retrieve.contains("patch_file"));
 }
+
+#[test]
+fn classify_push_error() {
+    let rejected = "remote: the signer is not eligible        \n\
+                    remote: check-push failed        \n\
+                    To git://127.0.0.1:9418/\n \
+                    ! [remote rejected] test -> test (pre-receive hook declined)\n\
+                    error: failed to push some refs to 'git://127.0.0.1:9418/'\n";
+    assert_eq!(
+        PushError::from_git_output(rejected),
+        PushError::RejectedByVerifier("the signer is not eligible\ncheck-push failed".to_string())
+    );
+    let non_fast_forward = "To git://127.0.0.1:9418/\n \
+                            ! [rejected]        main -> main (fetch first)\n\
+                            error: failed to push some refs to 'git://127.0.0.1:9418/'\n";
+    assert_eq!(
+        PushError::from_git_output(non_fast_forward),
+        PushError::NonFastForward
+    );
+    let auth = "fatal: Authentication failed for 'https://example.com/repo.git/'\n";
+    assert!(matches!(
+        PushError::from_git_output(auth),
+        PushError::Auth(_)
+    ));
+    let transport = "fatal: unable to connect to 127.0.0.1:\n\
+                     127.0.0.1[0: 127.0.0.1]: errno=Connection refused\n";
+    assert!(matches!(
+        PushError::from_git_output(transport),
+        PushError::Transport(_)
+    ));
+}
+
+/// A commit authored by Simperby survives `raw -> semantic -> raw` with the same commit hash.
+#[tokio::test]
+async fn semantic_commit_round_trip() {
+    let td = TempDir::new().unwrap();
+    let path = td.path();
+    let mut repo = init_repository_with_initial_commit(path).await.unwrap();
+    let base = repo.get_head().await.unwrap();
+
+    // The timestamp in milliseconds is not a multiple of 1000 on purpose.
+    let semantic_commit = SemanticCommit {
+        title: ">agenda: 1".to_string(),
+        body: "first line\n\nsecond paragraph".to_string(),
+        diff: Diff::None,
+        author: "member-0000".to_string(),
+        timestamp: 1_234_567,
+    };
+    let commit_hash = repo
+        .create_semantic_commit(semantic_commit.clone(), true)
+        .await
+        .unwrap();
+
+    let raw_commit = repo.read_commit(commit_hash).await.unwrap();
+    let converted = raw_commit_to_semantic_commit(raw_commit.clone()).unwrap();
+    assert_eq!(converted.title, semantic_commit.title);
+    assert_eq!(converted.body, semantic_commit.body);
+    assert_eq!(converted.timestamp, 1_234_000);
+    assert_eq!(converted.author, SIMPERBY_COMMIT_AUTHOR);
+    assert_eq!(
+        semantic_commit_to_raw_commit(&converted, SIMPERBY_COMMIT_EMAIL).unwrap(),
+        raw_commit
+    );
+
+    repo.checkout_detach(base).await.unwrap();
+    let recreated = repo
+        .create_commit(semantic_commit_to_raw_commit(&converted, SIMPERBY_COMMIT_EMAIL).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(recreated, commit_hash);
+}
+
+/// A commit with a custom author survives `raw -> semantic -> raw` with its author and timestamp,
+/// while a commit with a diff is rejected instead of losing the diff.
+#[tokio::test]
+async fn semantic_commit_round_trip_with_diff() {
+    let td = TempDir::new().unwrap();
+    let path = td.path();
+    let author = CommitAuthor {
+        name: "member-0001".to_owned(),
+        email: "member-0001@example.com".to_owned(),
+    };
+    let mut repo = RawRepository::init_with_author(
+        path.to_str().unwrap(),
+        "initial",
+        &MAIN.into(),
+        author.clone(),
+    )
+    .await
+    .unwrap();
+    let base = repo.get_head().await.unwrap();
+
+    std::fs::write(path.join("file"), "hello\n").unwrap();
+    let commit = RawCommit {
+        message: "add a file\n\nwith a body".to_owned(),
+        diff: None,
+        author: author.name.clone(),
+        email: author.email.clone(),
+        timestamp: 1_234,
+    };
+    let commit_hash = repo.create_commit_all(commit.clone()).await.unwrap();
+    let raw_commit = repo.read_commit(commit_hash).await.unwrap();
+    assert!(raw_commit.diff.is_some());
+    assert!(raw_commit_to_semantic_commit(raw_commit).is_err());
+    let semantic_commit = repo.read_semantic_commit(commit_hash).await.unwrap();
+    assert!(semantic_commit_to_raw_commit(&semantic_commit, &author.email).is_err());
+
+    repo.checkout_detach(base).await.unwrap();
+    let commit_hash = repo.create_commit(commit).await.unwrap();
+    let raw_commit = repo.read_commit(commit_hash).await.unwrap();
+    let converted = raw_commit_to_semantic_commit(raw_commit.clone()).unwrap();
+    assert_eq!(converted.author, author.name);
+    assert_eq!(converted.timestamp, 1_234_000);
+    assert_eq!(
+        semantic_commit_to_raw_commit(&converted, &author.email).unwrap(),
+        raw_commit
+    );
+    repo.checkout_detach(base).await.unwrap();
+    let recreated = repo
+        .create_commit(semantic_commit_to_raw_commit(&converted, &author.email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(recreated, commit_hash);
+}
+
+/// Orphaned commits of deleted branches are collected only over the threshold.
+#[tokio::test]
+async fn garbage_collection_if_needed() {
+    let td = TempDir::new().unwrap();
+    let path = td.path();
+    let mut repo = init_repository_with_initial_commit(path).await.unwrap();
+    let initial_commit_hash = repo.get_head().await.unwrap();
+
+    for i in 0..20 {
+        let branch = format!("b-{i}");
+        repo.create_branch(branch.clone(), initial_commit_hash)
+            .await
+            .unwrap();
+        repo.checkout(branch.clone()).await.unwrap();
+        repo.create_commit(RawCommit {
+            message: format!("commit {i}"),
+            diff: None,
+            author: "name".to_string(),
+            email: "test@email.com".to_string(),
+            timestamp: get_timestamp() / 1000,
+        })
+        .await
+        .unwrap();
+        repo.checkout(MAIN.into()).await.unwrap();
+        repo.delete_branch(branch).await.unwrap();
+    }
+    let loose_objects = repo.count_loose_objects().await.unwrap();
+    assert!(loose_objects >= 20, "{loose_objects}");
+
+    assert!(!repo
+        .run_garbage_collection_if_needed(loose_objects)
+        .await
+        .unwrap());
+    assert_eq!(repo.count_loose_objects().await.unwrap(), loose_objects);
+
+    assert!(repo
+        .run_garbage_collection_if_needed(loose_objects - 1)
+        .await
+        .unwrap());
+    assert!(repo.count_loose_objects().await.unwrap() < loose_objects);
+    assert_eq!(repo.get_head().await.unwrap(), initial_commit_hash);
+    repo.read_commit(initial_commit_hash).await.unwrap();
+}
@@ -1,16 +1,24 @@
-use crate::raw;
+use crate::interpret::PushSignTarget;
+use futures::future::BoxFuture;
 use log::info;
 use path_slash::PathExt as _;
+use simperby_core::*;
+use std::sync::Arc;
 use std::{self, path::Path};
 use tokio::fs;
 
 pub struct GitServer {
     child: std::process::Child,
     daemon_pid: u32,
+    /// The tasks answering the hooks for the `PushVerifier::VerifierFn`s.
+    verifier_tasks: Vec<tokio::task::JoinHandle<()>>,
 }
 
 impl Drop for GitServer {
     fn drop(&mut self) {
+        for task in &self.verifier_tasks {
+            task.abort();
+        }
         info!("killing git daemon ({})..", self.daemon_pid);
         let _ = self.child.kill();
         let _ = self.child.wait();
@@ -37,6 +45,7 @@ impl Drop for GitServer {
     }
 }
 
+/// Runs a read-only git daemon serving the repository at `path`, without any push hook.
 pub async fn run_server_legacy(path: &str, port: u16) -> GitServer {
     let td = tempfile::TempDir::new().unwrap();
     let pid_path = format!("{}/pid", td.path().to_slash().unwrap().into_owned());
@@ -46,8 +55,9 @@ pub async fn run_server_legacy(path: &str, port: u16) -> GitServer {
         .arg("--export-all")
         .arg(format!("--port={port}"))
         .arg(format!("--pid-file={pid_path}"))
-        .arg("--enable=receive-pack")
         .arg("--reuseaddr")
+        .arg("--")
+        .arg(path)
         .spawn()
         .unwrap();
     tokio::time::sleep(std::time::Duration::from_secs(1)).await;
@@ -55,13 +65,59 @@ pub async fn run_server_legacy(path: &str, port: u16) -> GitServer {
     // remove new line character
     let daemon_pid = daemon_pid[0..daemon_pid.len() - 1].parse::<u32>().unwrap();
     println!("PID: {daemon_pid}");
-    GitServer { child, daemon_pid }
+    GitServer {
+        child,
+        daemon_pid,
+        verifier_tasks: Vec::new(),
+    }
+}
+
+/// A push request, given by the push option of a `git push`.
+#[derive(Debug, Clone)]
+pub struct PushRequest {
+    pub commit_hash: CommitHash,
+    pub branch: String,
+    pub timestamp: Timestamp,
+    pub signature: TypedSignature<PushSignTarget>,
 }
 
+impl PushRequest {
+    /// Parses the arguments of `check-push`: the commit, the branch, the timestamp,
+    /// the signature and the signer.
+    fn parse(args: &[&str]) -> Result<Self, crate::Error> {
+        let [commit, branch, timestamp, signature, signer] = args else {
+            return Err(eyre::eyre!("expected 5 arguments, got {}", args.len()));
+        };
+        let commit_hash = CommitHash {
+            hash: hex::decode(commit)?
+                .try_into()
+                .map_err(|_| eyre::eyre!("invalid commit hash: {commit}"))?,
+        };
+        let signature: Signature = serde_spb::from_str(signature)?;
+        let signer: PublicKey = serde_spb::from_str(signer)?;
+        Ok(Self {
+            commit_hash,
+            branch: branch.to_string(),
+            timestamp: timestamp.parse()?,
+            signature: TypedSignature::new(signature, signer),
+        })
+    }
+}
+
+/// An in-process verifier which decides whether to accept the given push request.
+pub type PushVerifierFn =
+    Arc<dyn Fn(PushRequest) -> BoxFuture<'static, Result<bool, crate::Error>> + Send + Sync>;
+
 pub enum PushVerifier {
     AlwaysAccept,
     AlwaysReject,
     VerifierExecutable(String),
+    /// Verifies the pushes in this process (e.g., with `DistributedRepository::test_push_eligibility`),
+    /// accepting the other requests of the hooks like `AlwaysAccept`.
+    ///
+    /// It must be used within a Tokio runtime and is supported only on Unix;
+    /// elsewhere, running a server with it fails before installing the hooks.
+    VerifierFn(PushVerifierFn),
 }
 
 /// Builds `simple_git_server.rs` and returns the path of the executable.
@@ -85,27 +141,63 @@ pub fn build_simple_git_server() -> String {
 /// - `path` is the path to the root directory of a Simperby blockchain (not the repository path)
 /// - `port` is the port to run the server on
 /// - `verifier` is the verifier that accepts or rejects pushes.
-pub async fn run_server(path: &str, port: u16, verifier: PushVerifier) -> GitServer {
-    // Make a pre-receive hook file and give it an execution permission.
-    let hooks = [
-        ("pre-receive", include_str!("pre_receive.sh")),
-        ("update", include_str!("update.sh")),
-        ("post-receive", include_str!("post_receive.sh")),
-    ];
-    for (hook_type, hook_script) in hooks.iter() {
-        let path_hook = format!("{path}/.git/hooks/{hook_type}");
-        let is_hook_exist = Path::new(&path_hook).exists();
-        if !is_hook_exist {
-            fs::File::create(&path_hook).await.unwrap();
-        }
-        fs::write(&path_hook, hook_script).await.unwrap();
-        raw::run_command(format!("chmod +x {path_hook}")).unwrap();
+pub async fn run_server(
+    path: &str,
+    port: u16,
+    verifier: PushVerifier,
+) -> Result<GitServer, crate::Error> {
+    let verifier_task = install_hooks(path, verifier).await?;
+    let mut server = run_daemon(path, port, &[path.to_owned()]).await?;
+    server.verifier_tasks.extend(verifier_task);
+    Ok(server)
+}
+
+/// Runs a Simperby Git server hosting multiple repositories under one port,
+/// each served at `git://<host>:<port>/<name>/`.
+///
+/// - `base_path` is the directory that contains the root directories of the blockchains,
+///   each named after the `name` of its repository
+/// - `port` is the port to run the server on
+/// - `repositories` are the names of the repositories to host with their own verifiers.
+///
+/// The other directories under `base_path` are not served.
+pub async fn run_multi_server(
+    base_path: &str,
+    port: u16,
+    repositories: Vec<(String, PushVerifier)>,
+) -> Result<GitServer, crate::Error> {
+    let mut verifier_tasks = Vec::new();
+    let mut paths = Vec::new();
+    for (name, verifier) in repositories {
+        let path = format!("{base_path}/{name}");
+        verifier_tasks.extend(install_hooks(&path, verifier).await?);
+        paths.push(path);
     }
+    let mut server = run_daemon(base_path, port, &paths).await?;
+    server.verifier_tasks = verifier_tasks;
+    Ok(server)
+}
 
+/// The push hooks installed to a served repository.
+const HOOKS: [(&str, &str); 3] = [
+    ("pre-receive", include_str!("pre_receive.sh")),
+    ("update", include_str!("update.sh")),
+    ("post-receive", include_str!("post_receive.sh")),
+];
+
+/// Installs the push hooks to the repository, which run the given verifier.
+///
+/// Returns the task answering the hooks if the verifier is a `PushVerifier::VerifierFn`.
+async fn install_hooks(
+    path: &str,
+    verifier: PushVerifier,
+) -> Result<Option<tokio::task::JoinHandle<()>>, crate::Error> {
+    if !Path::new(&format!("{path}/.git/hooks")).is_dir() {
+        return Err(eyre::eyre!("{path} is not a git repository"));
+    }
     let td_ = tempfile::TempDir::new().unwrap();
     let td = td_.path().to_slash().unwrap().into_owned();
     std::mem::forget(td_);
-    let pid_path = format!("{td}/pid");
     let path_true = format!("{td}/true.sh");
     let path_false = format!("{td}/false.sh");
     fs::File::create(&path_true).await.unwrap();
@@ -116,40 +208,203 @@ exit 0
     let content_false = r#"#!/bin/sh
 exit 1
 "#;
-    fs::write(&path_true, content_true).await.unwrap();
-    fs::write(&path_false, content_false).await.unwrap();
-    raw::run_command(format!("chmod +x {path_true}")).unwrap();
-    raw::run_command(format!("chmod +x {path_false}")).unwrap();
+    fs::write(&path_true, content_true).await?;
+    fs::write(&path_false, content_false).await?;
+    set_executable(&path_true)?;
+    set_executable(&path_false)?;
 
+    let mut verifier_task = None;
     let verifier_path = match verifier {
         PushVerifier::AlwaysAccept => path_true,
         PushVerifier::AlwaysReject => path_false,
         PushVerifier::VerifierExecutable(x) => x,
+        PushVerifier::VerifierFn(verifier) => {
+            let (path_fn, task) = serve_verifier_fn(&td, verifier).await?;
+            verifier_task = Some(task);
+            path_fn
+        }
     };
+    // Make a pre-receive hook file and give it an execution permission.
+    for (hook_type, hook_script) in HOOKS.iter() {
+        let path_hook = format!("{path}/.git/hooks/{hook_type}");
+        fs::write(&path_hook, hook_script).await?;
+        set_executable(&path_hook)?;
+    }
+    // The hooks read them from the repository config,
+    // so that each repository of the same server runs its own verifier.
+    for (key, value) in [
+        ("simperby.executablePath", verifier_path.as_str()),
+        ("simperby.rootPath", path),
+    ] {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .arg("config")
+            .arg(key)
+            .arg(value)
+            .status()?;
+        if !status.success() {
+            return Err(eyre::eyre!("failed to set {key} of {path}"));
+        }
+    }
+    Ok(verifier_task)
+}
+
+/// Checks that the push hooks are installed to the repository, so that it can be served.
+fn check_hooks(path: &str) -> Result<(), crate::Error> {
+    for (hook_type, hook_script) in HOOKS.iter() {
+        let path_hook = format!("{path}/.git/hooks/{hook_type}");
+        if std::fs::read_to_string(&path_hook).ok().as_deref() != Some(*hook_script) {
+            return Err(eyre::eyre!(
+                "the {hook_type} hook is not installed to {path}"
+            ));
+        }
+    }
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .arg("config")
+        .arg("simperby.executablePath")
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre::eyre!("the push verifier is not set for {path}"));
+    }
+    Ok(())
+}
+
+/// Gives the file an execution permission.
+fn set_executable(path: &str) -> Result<(), crate::Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        std::fs::set_permissions(path, permissions)?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Makes a verifier executable in `dir` that forwards `check-push` to the given function,
+/// returning its path with the task that runs the function.
+///
+/// The executable sends the arguments through a named pipe,
+/// along with another named pipe to receive the decision from.
+#[cfg(unix)]
+async fn serve_verifier_fn(
+    dir: &str,
+    verifier: PushVerifierFn,
+) -> Result<(String, tokio::task::JoinHandle<()>), crate::Error> {
+    use tokio::io::AsyncBufReadExt;
+    use tokio::net::unix::pipe;
+
+    let path_requests = format!("{dir}/requests");
+    let status = std::process::Command::new("mkfifo")
+        .arg(&path_requests)
+        .status()?;
+    if !status.success() {
+        return Err(eyre::eyre!("failed to make {path_requests}"));
+    }
+    let path_fn = format!("{dir}/verifier_fn.sh");
+    let content_fn = format!(
+        r#"#!/bin/sh
+if [ "$2" != "check-push" ]; then
+    exit 0
+fi
+response="$(mktemp -u)"
+mkfifo "$response"
+echo "$response $3 $4 $5 $6 $7" > {path_requests}
+decision="$(cat "$response")"
+rm -f "$response"
+[ "$decision" = "accept" ]
+"#
+    );
+    fs::write(&path_fn, content_fn).await?;
+    set_executable(&path_fn)?;
+
+    let receiver = pipe::OpenOptions::new().open_receiver(&path_requests)?;
+    // Keep a writer so that the receiver doesn't reach the end between the hooks.
+    let sender = pipe::OpenOptions::new().open_sender(&path_requests)?;
+    let task = tokio::spawn(async move {
+        let _sender = sender;
+        let mut lines = tokio::io::BufReader::new(receiver).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let args = line.split(' ').collect::<Vec<_>>();
+            let accepted = match PushRequest::parse(&args[1..]) {
+                Ok(request) => verifier(request).await.unwrap_or_else(|e| {
+                    log::warn!("push verifier failed: {e}");
+                    false
+                }),
+                Err(e) => {
+                    log::warn!("invalid push request: {e}");
+                    false
+                }
+            };
+            let response = args[0].to_owned();
+            // Opening the pipe for writing waits for the hook to read the decision.
+            let _ = tokio::task::spawn_blocking(move || {
+                std::fs::write(response, if accepted { "accept\n" } else { "reject\n" })
+            })
+            .await;
+        }
+    });
+    Ok((path_fn, task))
+}
+
+#[cfg(not(unix))]
+async fn serve_verifier_fn(
+    _dir: &str,
+    _verifier: PushVerifierFn,
+) -> Result<(String, tokio::task::JoinHandle<()>), crate::Error> {
+    Err(eyre::eyre!(
+        "`PushVerifier::VerifierFn` is supported only on Unix"
+    ))
+}
+
+/// Runs a git daemon serving the given repositories under `base_path`.
+///
+/// Only the repositories with the push hooks installed are exported,
+/// so that nothing else under `base_path` can be pushed to.
+async fn run_daemon(
+    base_path: &str,
+    port: u16,
+    repositories: &[String],
+) -> Result<GitServer, crate::Error> {
+    for path in repositories {
+        check_hooks(path)?;
+    }
+    let td_ = tempfile::TempDir::new().unwrap();
+    let td = td_.path().to_slash().unwrap().into_owned();
+    std::mem::forget(td_);
+    let pid_path = format!("{td}/pid");
     let child = std::process::Command::new("git")
         .arg("daemon")
-        .arg(format!("--base-path={path}"))
+        .arg(format!("--base-path={base_path}"))
         .arg("--export-all")
         .arg("--enable=receive-pack")
         .arg(format!("--port={port}"))
         .arg(format!("--pid-file={pid_path}"))
         .arg("--reuseaddr")
-        .env("SIMPERBY_EXECUTABLE_PATH", verifier_path)
-        .env("SIMPERBY_ROOT_PATH", path)
-        .spawn()
-        .unwrap();
+        .arg("--")
+        .args(repositories)
+        .spawn()?;
     tokio::time::sleep(std::time::Duration::from_secs(1)).await;
     let daemon_pid = std::fs::read_to_string(pid_path).unwrap();
     // remove new line character
     let daemon_pid = daemon_pid[0..daemon_pid.len() - 1].parse::<u32>().unwrap();
     println!("PID: {daemon_pid}");
-    GitServer { child, daemon_pid }
+    Ok(GitServer {
+        child,
+        daemon_pid,
+        verifier_tasks: Vec::new(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::raw::RawRepository;
+    use crate::raw::{self, RawRepository};
     use simperby_test_suite::*;
     use tempfile::TempDir;
 
@@ -176,6 +431,50 @@ mod tests {
         run_command(format!("cd {path2} && git clone git://127.0.0.1:{port}/")).await;
     }
 
+    #[tokio::test]
+    async fn git_server_ipv6() {
+        setup_test();
+        let port = dispense_port();
+
+        let td = TempDir::new().unwrap();
+        let path = td.path().to_slash().unwrap().into_owned();
+        run_command(format!("cd {path} && git init")).await;
+        run_command(format!("cd {path} && echo 'hello' > hello.txt")).await;
+        run_command(format!("cd {path} && git add -A")).await;
+        run_command(format!(
+            "cd {path} && git config user.name 'Test' && git config user.email 'test@test.com'"
+        ))
+        .await;
+        run_command(format!("cd {path} && git commit -m 'hello'")).await;
+        let head = RawRepository::open(&path)
+            .await
+            .unwrap()
+            .get_head()
+            .await
+            .unwrap();
+        let _server = run_server_legacy(&path, port).await;
+
+        // A peer at an IPv6 address is reached with the `git://[..]` remote URL.
+        let td2 = TempDir::new().unwrap();
+        let path2 = td2.path().to_slash().unwrap().into_owned();
+        run_command(format!("cd {path2} && git init")).await;
+        let mut repo = RawRepository::open(&path2).await.unwrap();
+        repo.add_remote("peer".to_owned(), format!("git://[::1]:{port}/"))
+            .await
+            .unwrap();
+        assert!(repo.ping_remote("peer".to_owned()).await.unwrap());
+        repo.fetch_all(true).await.unwrap();
+        assert_eq!(
+            repo.list_remote_tracking_branches()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(remote, _, commit_hash)| (remote, commit_hash))
+                .collect::<Vec<_>>(),
+            vec![("peer".to_owned(), head)]
+        );
+    }
+
     #[ignore]
     #[tokio::test]
     async fn git_server_basic2() {
@@ -212,7 +511,9 @@ mod tests {
 
         // Open a git server with simperby executable which always returns true.
         let path_server_clone = path_server.to_owned();
-        let server = run_server(&path_server_clone, port, PushVerifier::AlwaysAccept).await;
+        let server = run_server(&path_server_clone, port, PushVerifier::AlwaysAccept)
+            .await
+            .unwrap();
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
 
         // Make a local repository by cloning above server repository.
@@ -255,7 +556,9 @@ mod tests {
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
 
         // Open a git server with simperby executable which always returns false.
-        let _server = run_server(&path_server, port, PushVerifier::AlwaysReject).await;
+        let _server = run_server(&path_server, port, PushVerifier::AlwaysReject)
+            .await
+            .unwrap();
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
         run_command(format!(
             "cd {path_local}/repo && echo 'hello2' > hello2.txt && git add . && git commit -m 'hello2'"
@@ -273,4 +576,177 @@ mod tests {
         .await
         .unwrap_err();
     }
+
+    /// Waits until the git daemon on the port serves the repository.
+    async fn wait_for_git_daemon(port: u16) {
+        for _ in 0..100 {
+            let output = tokio::process::Command::new("git")
+                .arg("ls-remote")
+                .arg(format!("git://127.0.0.1:{port}/"))
+                .output()
+                .await
+                .unwrap();
+            if output.status.success() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("git daemon on port {port} is not ready");
+    }
+
+    #[tokio::test]
+    async fn push_rejected_by_verifier() {
+        setup_test();
+        let port = dispense_port();
+
+        let td_server = TempDir::new().unwrap();
+        let path_server = td_server.path().to_slash().unwrap().into_owned();
+        run_command(format!(
+            "cd {path_server} && git init && git config user.name 'Test' && git config user.email 'test@test.com'"
+        ))
+        .await;
+        run_command(format!(
+            "cd {path_server} && git config receive.advertisePushOptions true"
+        ))
+        .await;
+        run_command(format!(
+            "cd {path_server} && echo 'init' > init.txt && git add -A && git commit -m 'init'"
+        ))
+        .await;
+
+        // A verifier which explains why it rejects the push.
+        let td_verifier = TempDir::new().unwrap();
+        let path_verifier = format!(
+            "{}/verifier.sh",
+            td_verifier.path().to_slash().unwrap().into_owned()
+        );
+        fs::write(
+            &path_verifier,
+            "#!/bin/sh\necho 'the signer is not eligible'\nexit 1\n",
+        )
+        .await
+        .unwrap();
+        run_command(format!("chmod +x {path_verifier}")).await;
+        let _server = run_server(
+            &path_server,
+            port,
+            PushVerifier::VerifierExecutable(path_verifier),
+        )
+        .await
+        .unwrap();
+        wait_for_git_daemon(port).await;
+
+        let td_local = TempDir::new().unwrap();
+        let path_local = td_local.path().to_slash().unwrap().into_owned();
+        run_command(format!(
+            "cd {path_local} && git clone git://127.0.0.1:{port}/ repo"
+        ))
+        .await;
+        run_command(format!(
+            "cd {path_local}/repo && git config user.name 'Test2' && git config user.email 'test2@test.com'"
+        ))
+        .await;
+        run_command(format!(
+            "cd {path_local}/repo && git checkout -b test && echo 'hello' > hello.txt && git add . && git commit -m 'hello'"
+        ))
+        .await;
+
+        let repo = RawRepository::open(format!("{path_local}/repo").as_str())
+            .await
+            .unwrap();
+        let error = repo
+            .push_option(
+                "origin".to_string(),
+                "test".to_string(),
+                Some("arg1 arg2 arg3 arg4 arg5".to_string()),
+            )
+            .await
+            .unwrap_err();
+        match error {
+            raw::Error::Push(raw::PushError::RejectedByVerifier(reason)) => {
+                assert!(reason.contains("the signer is not eligible"), "{reason}");
+                assert!(reason.contains("check-push failed"), "{reason}");
+            }
+            _ => panic!("unexpected error: {error}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn multi_repository_server() {
+        setup_test();
+        let port = dispense_port();
+
+        let td_server = TempDir::new().unwrap();
+        let path_server = td_server.path().to_slash().unwrap().into_owned();
+        for name in ["chain-a", "chain-b", "unregistered"] {
+            run_command(format!(
+                "mkdir {path_server}/{name} && cd {path_server}/{name} && git init && git config user.name 'Test' && git config user.email 'test@test.com'"
+            ))
+            .await;
+            run_command(format!(
+                "cd {path_server}/{name} && git config receive.advertisePushOptions true"
+            ))
+            .await;
+            run_command(format!(
+                "cd {path_server}/{name} && echo '{name}' > {name}.txt && git add -A && git commit -m 'init'"
+            ))
+            .await;
+        }
+        // A repository that doesn't exist can't be served.
+        assert!(run_multi_server(
+            &path_server,
+            port,
+            vec![("missing".to_owned(), PushVerifier::AlwaysAccept)],
+        )
+        .await
+        .is_err());
+        let _server = run_multi_server(
+            &path_server,
+            port,
+            vec![
+                ("chain-a".to_owned(), PushVerifier::AlwaysAccept),
+                ("chain-b".to_owned(), PushVerifier::AlwaysReject),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let td_local = TempDir::new().unwrap();
+        let path_local = td_local.path().to_slash().unwrap().into_owned();
+        // The repository without the hooks is not exported.
+        let status = tokio::process::Command::new("git")
+            .arg("clone")
+            .arg(format!("git://127.0.0.1:{port}/unregistered/"))
+            .arg(format!("{path_local}/unregistered"))
+            .status()
+            .await
+            .unwrap();
+        assert!(!status.success());
+        for (name, accepted) in [("chain-a", true), ("chain-b", false)] {
+            run_command(format!(
+                "cd {path_local} && git clone git://127.0.0.1:{port}/{name}/ {name}"
+            ))
+            .await;
+            assert!(Path::new(&format!("{path_local}/{name}/{name}.txt")).exists());
+            run_command(format!(
+                "cd {path_local}/{name} && git config user.name 'Test2' && git config user.email 'test2@test.com'"
+            ))
+            .await;
+            run_command(format!(
+                "cd {path_local}/{name} && git checkout -b test && echo 'hello' > hello.txt && git add . && git commit -m 'hello'"
+            ))
+            .await;
+            let repo = RawRepository::open(&format!("{path_local}/{name}"))
+                .await
+                .unwrap();
+            let result = repo
+                .push_option(
+                    "origin".to_string(),
+                    "test".to_string(),
+                    Some("arg1 arg2 arg3 arg4 arg5".to_string()),
+                )
+                .await;
+            assert_eq!(result.is_ok(), accepted, "{name}: {result:?}");
+        }
+    }
 }
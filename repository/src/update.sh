@@ -1,6 +1,6 @@
 #!/bin/sh
-simperby_executable_path="$SIMPERBY_EXECUTABLE_PATH"
-simperby_root_path="$SIMPERBY_ROOT_PATH"
+simperby_executable_path="$(git config --get simperby.executablePath)"
+simperby_root_path="$(git config --get simperby.rootPath)"
 refname="$1"
 oldrev="$2"
 newrev="$3"
@@ -2,6 +2,7 @@ use simperby_core::*;
 use simperby_network::{dms, ClientNetworkConfig, Dms};
 use simperby_repository::{format::from_semantic_commit, raw::*, server::*, *};
 use simperby_test_suite::*;
+use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -103,7 +104,7 @@ async fn sync_by_dms() {
     // Step 0: create an agenda and let the client push that
     let (agenda, agenda_commit) = client_nodes[0]
         .0
-        .create_agenda(rs.query_name(&keys[0].0).unwrap())
+        .create_agenda(rs.query_name(&keys[0].0).unwrap(), false)
         .await
         .unwrap();
     sync_dms(client_nodes.as_mut_slice()).await;
@@ -261,7 +262,7 @@ async fn sync_by_fetch() {
 
     // Step 0: create an agenda and let the client update that
     let (agenda, agenda_commit) = server_node_repo
-        .create_agenda(rs.query_name(&keys[3].0).unwrap())
+        .create_agenda(rs.query_name(&keys[3].0).unwrap(), false)
         .await
         .unwrap();
     simperby_test_suite::run_command(format!("cd {client_node_dir} && git fetch --all")).await;
@@ -375,7 +376,8 @@ async fn sync_by_push() {
         port,
         PushVerifier::VerifierExecutable(build_simple_git_server()),
     )
-    .await;
+    .await
+    .unwrap();
     tokio::time::sleep(std::time::Duration::from_secs(1)).await;
 
     let client_node_dir = create_temp_dir();
@@ -398,7 +400,7 @@ async fn sync_by_push() {
 
     // Step 0: create an agenda and let the client push that
     let (agenda, agenda_commit) = client_node_repo
-        .create_agenda(rs.query_name(&keys[0].0).unwrap())
+        .create_agenda(rs.query_name(&keys[0].0).unwrap(), false)
         .await
         .unwrap();
     client_node_repo.broadcast().await.unwrap();
@@ -490,6 +492,89 @@ async fn sync_by_push() {
     );
 }
 
+#[tokio::test]
+async fn push_with_in_process_verifier() {
+    setup_test();
+    let port = dispense_port();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let server_node_dir = create_temp_dir();
+    setup_pre_genesis_repository(&server_node_dir, rs.clone()).await;
+    simperby_test_suite::run_command(format!(
+        "cd {server_node_dir} && git config receive.advertisePushOptions true"
+    ))
+    .await;
+    simperby_test_suite::run_command(format!(
+        "cd {server_node_dir} && git config sendpack.sideband false"
+    ))
+    .await;
+    DistributedRepository::genesis(RawRepository::open(&server_node_dir).await.unwrap())
+        .await
+        .unwrap();
+    let server_node_repo = Arc::new(
+        DistributedRepository::new(
+            None,
+            Arc::new(RwLock::new(
+                RawRepository::open(&server_node_dir).await.unwrap(),
+            )),
+            config.clone(),
+            None,
+        )
+        .await
+        .unwrap(),
+    );
+    let decisions = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let decisions_ = Arc::clone(&decisions);
+    let verifier: PushVerifierFn = Arc::new(move |request: PushRequest| {
+        let server_node_repo = Arc::clone(&server_node_repo);
+        let decisions = Arc::clone(&decisions_);
+        Box::pin(async move {
+            let accepted = server_node_repo
+                .test_push_eligibility(
+                    request.commit_hash,
+                    request.branch,
+                    request.timestamp,
+                    request.signature,
+                    0,
+                )
+                .await?;
+            decisions.lock().unwrap().push(accepted);
+            Ok(accepted)
+        })
+    });
+    let _git_server = run_server(&server_node_dir, port, PushVerifier::VerifierFn(verifier))
+        .await
+        .unwrap();
+
+    // A member pushes the finalization proof branch, and a non-member does the same.
+    for (key, eligible) in [
+        (keys[0].1.clone(), true),
+        (generate_keypair("other").1, false),
+    ] {
+        let client_node_dir = create_temp_dir();
+        simperby_test_suite::run_command(format!("cp -a {server_node_dir}/. {client_node_dir}/"))
+            .await;
+        simperby_test_suite::run_command(format!(
+            "cd {client_node_dir} && git remote add peer git://127.0.0.1:{port}/"
+        ))
+        .await;
+        let mut client_node_repo = DistributedRepository::new(
+            None,
+            Arc::new(RwLock::new(
+                RawRepository::open(&client_node_dir).await.unwrap(),
+            )),
+            config.clone(),
+            Some(key),
+        )
+        .await
+        .unwrap();
+        assert_eq!(client_node_repo.broadcast().await.is_ok(), eligible);
+    }
+    assert_eq!(*decisions.lock().unwrap(), vec![true, false]);
+}
+
 async fn sync_each_other(paths: &[String], client_drepos: &mut [DistributedRepository]) {
     for client_drepo in client_drepos.iter_mut() {
         client_drepo.broadcast().await.unwrap();
@@ -574,13 +659,14 @@ async fn sync_by_push_and_fetch() {
         port,
         PushVerifier::VerifierExecutable(build_simple_git_server()),
     )
-    .await;
+    .await
+    .unwrap();
     tokio::time::sleep(std::time::Duration::from_secs(1)).await;
 
     // Make a first block with multiple agendas, Step 0 ~ 2.
     // Step 0: create multiple agendas at different times and let the client update that
     let (agenda1, agenda_commit1) = client_drepos[0]
-        .create_agenda(rs.query_name(&keys[0].0).unwrap())
+        .create_agenda(rs.query_name(&keys[0].0).unwrap(), false)
         .await
         .unwrap();
 
@@ -589,12 +675,12 @@ async fn sync_by_push_and_fetch() {
         drepo.vote(agenda_commit1).await.unwrap();
     }
     let (_agenda2, _agenda_commit2) = client_drepos[1]
-        .create_agenda(rs.query_name(&keys[1].0).unwrap())
+        .create_agenda(rs.query_name(&keys[1].0).unwrap(), false)
         .await
         .unwrap();
     sync_each_other(&client_dirs, &mut client_drepos).await;
     let (_agenda3, _agenda_commit3) = client_drepos[2]
-        .create_agenda(rs.query_name(&keys[2].0).unwrap())
+        .create_agenda(rs.query_name(&keys[2].0).unwrap(), false)
         .await
         .unwrap();
     for (timestamp, client_drepo) in client_drepos.iter_mut().enumerate() {
@@ -678,7 +764,7 @@ async fn sync_by_push_and_fetch() {
     // Make a second block, Step 3 ~ 5.
     // Step 3: create an agenda and let the client update that
     let (agenda1, agenda_commit1) = client_drepos[1]
-        .create_agenda(rs.query_name(&keys[1].0).unwrap())
+        .create_agenda(rs.query_name(&keys[1].0).unwrap(), false)
         .await
         .unwrap();
     sync_each_other(&client_dirs, &mut client_drepos).await;
@@ -771,3 +857,1244 @@ async fn sync_by_push_and_fetch() {
         );
     }
 }
+
+#[tokio::test]
+async fn read_payload_branch() {
+    setup_test();
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(drepo.read_payload_branch().await.unwrap().is_none());
+
+    // Stage transactions on the `p` branch.
+    let finalized = drepo
+        .read_last_finalization_info()
+        .await
+        .unwrap()
+        .commit_hash;
+    {
+        let raw = drepo.get_raw();
+        let mut raw = raw.write().await;
+        raw.create_branch(PAYLOAD_BRANCH_NAME.into(), finalized)
+            .await
+            .unwrap();
+        raw.checkout(PAYLOAD_BRANCH_NAME.into()).await.unwrap();
+    }
+    assert_eq!(
+        drepo.read_payload_branch().await.unwrap(),
+        Some((finalized, Vec::new()))
+    );
+    let transactions = (0..3)
+        .map(|i| Transaction {
+            author: "doesn't matter".to_owned(),
+            timestamp: i,
+            head: format!("tx {i}"),
+            body: "".to_owned(),
+            diff: Diff::None,
+            author_signature: None,
+        })
+        .collect::<Vec<_>>();
+    let mut tip = finalized;
+    for transaction in transactions.iter() {
+        tip = drepo.create_transaction(transaction.clone()).await.unwrap();
+    }
+
+    let (payload_tip, commits) = drepo.read_payload_branch().await.unwrap().unwrap();
+    assert_eq!(payload_tip, tip);
+    assert_eq!(
+        commits
+            .into_iter()
+            .map(|commit| match commit {
+                Commit::Transaction(transaction) => transaction.head,
+                _ => panic!("not a transaction commit"),
+            })
+            .collect::<Vec<_>>(),
+        transactions
+            .into_iter()
+            .map(|transaction| transaction.head)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn veto_and_unveto() {
+    setup_test();
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+    let finalized = drepo
+        .read_last_finalization_info()
+        .await
+        .unwrap()
+        .commit_hash;
+    let veto_tags = |tags: Vec<Tag>| {
+        tags.into_iter()
+            .filter(|tag| tag.starts_with("veto-"))
+            .collect::<Vec<_>>()
+    };
+
+    // Removing a veto tag that doesn't exist is a no-op.
+    drepo.unveto(finalized).await.unwrap();
+
+    drepo.veto(finalized).await.unwrap();
+    let tags = veto_tags(drepo.get_raw().read().await.list_tags().await.unwrap());
+    assert_eq!(tags.len(), 1);
+    assert_eq!(
+        drepo
+            .get_raw()
+            .read()
+            .await
+            .locate_tag(tags[0].clone())
+            .await
+            .unwrap(),
+        finalized
+    );
+
+    drepo.unveto(finalized).await.unwrap();
+    let tags = veto_tags(drepo.get_raw().read().await.list_tags().await.unwrap());
+    assert!(tags.is_empty());
+}
+
+async fn open_genesis_repository() -> DistributedRepository {
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+#[cfg(debug_assertions)]
+#[tokio::test]
+#[should_panic(expected = "re-entrant lock of the raw repository")]
+async fn reentrant_raw_lock() {
+    setup_test();
+    let drepo = open_genesis_repository().await;
+    let _guard = drepo.write_raw().await;
+    // Panics immediately instead of deadlocking.
+    let _ = drepo.read_agendas().await;
+}
+
+#[tokio::test]
+async fn shared_raw_lock() {
+    setup_test();
+    let drepo = Arc::new(open_genesis_repository().await);
+    // Reads of the same task may be held together.
+    let (first, second) = tokio::join!(drepo.read_raw(), drepo.read_raw());
+    drop((first, second));
+
+    // The lock held by another task is just waited for.
+    let (locked_send, locked_recv) = tokio::sync::oneshot::channel();
+    let task = tokio::spawn({
+        let drepo = Arc::clone(&drepo);
+        async move {
+            let _guard = drepo.write_raw().await;
+            locked_send.send(()).unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    });
+    locked_recv.await.unwrap();
+    drepo.read_agendas().await.unwrap();
+    task.await.unwrap();
+}
+
+#[tokio::test]
+async fn extra_agenda_transaction_before_agenda_proof() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let data = DelegationTransactionData {
+        delegator: rs.members[0].name.clone(),
+        delegatee: rs.members[1].name.clone(),
+        governance: true,
+        block_height: 0,
+        timestamp: 0,
+        chain_name: rs.genesis_info.chain_name.clone(),
+    };
+    let proof = TypedSignature::sign(&data, &keys[0].1).unwrap();
+    let transaction = ExtraAgendaTransaction::Delegate(TxDelegate { data, proof });
+
+    // HEAD is on the genesis block, where no agenda proof exists yet.
+    let head = drepo.get_raw().read().await.get_head().await.unwrap();
+    let err = drepo
+        .create_extra_agenda_transaction(&transaction)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("only after an agenda proof"));
+    assert_eq!(drepo.get_raw().read().await.get_head().await.unwrap(), head);
+}
+
+#[tokio::test]
+async fn branch_and_tag_names() {
+    setup_test();
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (agenda, agenda_commit) = drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    drepo.vote(agenda_commit).await.unwrap();
+
+    let agenda_hash = agenda.to_hash256();
+    assert_eq!(agenda_hash.short(BRANCH_NAME_HASH_DIGITS).len(), 8);
+    assert_eq!(agenda_hash.short(TAG_NAME_HASH_DIGITS).len(), 8);
+    let raw = drepo.get_raw();
+    let raw = raw.read().await;
+    assert_eq!(
+        raw.locate_branch(format!("a-{}", agenda_hash.short(BRANCH_NAME_HASH_DIGITS)))
+            .await
+            .unwrap(),
+        agenda_commit
+    );
+    assert_eq!(
+        raw.locate_tag(format!("vote-{}", agenda_hash.short(TAG_NAME_HASH_DIGITS)))
+            .await
+            .unwrap(),
+        agenda_commit
+    );
+    // The short form of a commit hash resolves back to the full one.
+    assert_eq!(
+        raw.retrieve_commit_hash(agenda_commit.short(BRANCH_NAME_HASH_DIGITS))
+            .await
+            .unwrap(),
+        agenda_commit
+    );
+}
+
+#[tokio::test]
+async fn sync_outcome() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let server_node_dir = create_temp_dir();
+    setup_pre_genesis_repository(&server_node_dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&server_node_dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut server_node_repo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(
+            RawRepository::open(&server_node_dir).await.unwrap(),
+        )),
+        config.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let client_node_dir = create_temp_dir();
+    simperby_test_suite::run_command(format!("cp -a {server_node_dir}/. {client_node_dir}/")).await;
+    simperby_test_suite::run_command(format!(
+        "cd {client_node_dir} && git remote add peer {server_node_dir}"
+    ))
+    .await;
+    let mut client_node_repo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(
+            RawRepository::open(&client_node_dir).await.unwrap(),
+        )),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+    let fetch =
+        || simperby_test_suite::run_command(format!("cd {client_node_dir} && git fetch --all"));
+
+    // Agenda
+    let (agenda, agenda_commit) = server_node_repo
+        .create_agenda(rs.query_name(&keys[3].0).unwrap(), false)
+        .await
+        .unwrap();
+    fetch().await;
+    assert_eq!(
+        client_node_repo.sync(agenda_commit).await.unwrap(),
+        SyncOutcome::AgendaObserved(agenda_commit)
+    );
+    assert_eq!(
+        client_node_repo.sync(agenda_commit).await.unwrap(),
+        SyncOutcome::NoChange
+    );
+
+    // Agenda proof
+    let agenda_proof_commit = server_node_repo
+        .approve(
+            &agenda.to_hash256(),
+            keys.iter()
+                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .collect(),
+            0,
+        )
+        .await
+        .unwrap();
+    fetch().await;
+    assert_eq!(
+        client_node_repo.sync(agenda_proof_commit).await.unwrap(),
+        SyncOutcome::AgendaObserved(agenda_proof_commit)
+    );
+
+    // Block
+    let (block, block_commit) = server_node_repo
+        .create_block(keys[3].0.clone())
+        .await
+        .unwrap();
+    fetch().await;
+    assert_eq!(
+        client_node_repo.sync(block_commit).await.unwrap(),
+        SyncOutcome::BlockObserved(block_commit)
+    );
+
+    // Finalization
+    let signatures = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(
+                &FinalizationSignTarget {
+                    round: 0,
+                    block_hash: block.to_hash256(),
+                },
+                private_key,
+            )
+            .unwrap()
+        })
+        .collect();
+    let fp_commit = server_node_repo
+        .finalize(
+            block_commit,
+            FinalizationProof {
+                signatures,
+                round: 0,
+            },
+        )
+        .await
+        .unwrap();
+    fetch().await;
+    assert_eq!(
+        client_node_repo.sync(fp_commit).await.unwrap(),
+        SyncOutcome::Finalized(block.height)
+    );
+    assert_eq!(
+        client_node_repo.sync(fp_commit).await.unwrap(),
+        SyncOutcome::NoChange
+    );
+
+    // A commit behind the last finalized block
+    assert!(matches!(
+        client_node_repo.sync(agenda_commit).await.unwrap(),
+        SyncOutcome::Rejected(_)
+    ));
+}
+
+#[tokio::test]
+async fn rejected_sync_keeps_finalization() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let server_node_dir = create_temp_dir();
+    setup_pre_genesis_repository(&server_node_dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&server_node_dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut server_node_repo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(
+            RawRepository::open(&server_node_dir).await.unwrap(),
+        )),
+        config.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let client_node_dir = create_temp_dir();
+    simperby_test_suite::run_command(format!("cp -a {server_node_dir}/. {client_node_dir}/")).await;
+    simperby_test_suite::run_command(format!(
+        "cd {client_node_dir} && git remote add peer {server_node_dir}"
+    ))
+    .await;
+    let mut client_node_repo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(
+            RawRepository::open(&client_node_dir).await.unwrap(),
+        )),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Two blocks (so that the first one can be finalized) followed by a transaction.
+    let mut first_block_commit = None;
+    for finalize in [true, false] {
+        let (agenda, _) = server_node_repo
+            .create_agenda(rs.query_name(&keys[3].0).unwrap(), false)
+            .await
+            .unwrap();
+        server_node_repo
+            .approve(
+                &agenda.to_hash256(),
+                keys.iter()
+                    .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                    .collect(),
+                0,
+            )
+            .await
+            .unwrap();
+        let (block, block_commit) = server_node_repo
+            .create_block(keys[3].0.clone())
+            .await
+            .unwrap();
+        if !finalize {
+            break;
+        }
+        first_block_commit = Some(block_commit);
+        let signatures = keys
+            .iter()
+            .map(|(_, private_key)| {
+                TypedSignature::sign(
+                    &FinalizationSignTarget {
+                        round: 0,
+                        block_hash: block.to_hash256(),
+                    },
+                    private_key,
+                )
+                .unwrap()
+            })
+            .collect();
+        server_node_repo
+            .finalize(
+                block_commit,
+                FinalizationProof {
+                    signatures,
+                    round: 0,
+                },
+            )
+            .await
+            .unwrap();
+    }
+    let transaction = Commit::Transaction(Transaction {
+        author: "doesn't matter".to_owned(),
+        timestamp: 0,
+        head: "tx".to_owned(),
+        body: "".to_owned(),
+        diff: Diff::None,
+        author_signature: None,
+    });
+    let semantic_commit = format::to_semantic_commit(&transaction, rs).unwrap();
+    let tip = server_node_repo
+        .get_raw()
+        .write()
+        .await
+        .create_semantic_commit(semantic_commit, true)
+        .await
+        .unwrap();
+    simperby_test_suite::run_command(format!("cd {client_node_dir} && git fetch --all")).await;
+
+    let client_raw = client_node_repo.get_raw();
+    let read_state = || async {
+        let raw = client_raw.read().await;
+        (
+            raw.get_head_state().await.unwrap(),
+            interpret::read_local_branches(&raw).await.unwrap(),
+        )
+    };
+    let without_finalization = |branches: HashSet<(String, CommitHash)>| {
+        branches
+            .into_iter()
+            .filter(|(branch, _)| branch != FINALIZED_BRANCH_NAME && branch != FP_BRANCH_NAME)
+            .collect::<HashSet<_>>()
+    };
+    let (_, before) = read_state().await;
+    // The branch finalizes the first block, but it ends with a transaction.
+    assert!(matches!(
+        client_node_repo.sync(tip).await.unwrap(),
+        SyncOutcome::Rejected(_)
+    ));
+    // The verified finalization stays, while nothing else of the branch is taken.
+    let (_, after) = read_state().await;
+    assert_eq!(without_finalization(after), without_finalization(before));
+    assert_eq!(
+        client_node_repo
+            .get_raw()
+            .read()
+            .await
+            .locate_branch(FINALIZED_BRANCH_NAME.into())
+            .await
+            .unwrap(),
+        first_block_commit.unwrap()
+    );
+    assert_eq!(
+        client_node_repo
+            .read_last_finalization_info()
+            .await
+            .unwrap()
+            .header
+            .height,
+        1
+    );
+}
+
+#[tokio::test]
+async fn read_commit_cache() {
+    setup_test();
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let finalized_commit = drepo
+        .get_raw()
+        .read()
+        .await
+        .locate_branch(FINALIZED_BRANCH_NAME.into())
+        .await
+        .unwrap();
+    let (_, agenda_commit) = drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    let block = drepo.read_commit(finalized_commit).await.unwrap();
+    let agenda = drepo.read_commit(agenda_commit).await.unwrap();
+    // The parent of the genesis block, which has not been read.
+    let unread_commit = drepo
+        .get_raw()
+        .read()
+        .await
+        .list_ancestors(finalized_commit, Some(1))
+        .await
+        .unwrap()[0];
+
+    // Remove all the git objects; only the cached commits can still be read.
+    std::fs::remove_dir_all(format!("{dir}/.git/objects")).unwrap();
+    std::fs::create_dir(format!("{dir}/.git/objects")).unwrap();
+    assert_eq!(drepo.read_commit(finalized_commit).await.unwrap(), block);
+    assert_eq!(drepo.read_commit(agenda_commit).await.unwrap(), agenda);
+    assert!(drepo.read_commit(unread_commit).await.is_err());
+}
+
+#[tokio::test]
+async fn create_duplicate_agenda() {
+    setup_test();
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let base = drepo.get_raw().read().await.get_head().await.unwrap();
+    let (agenda, agenda_commit) = drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+
+    // Creating the same agenda again returns the existing one.
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(base)
+        .await
+        .unwrap();
+    let (agenda_, agenda_commit_) = drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    assert_eq!(agenda_commit_, agenda_commit);
+    assert_eq!(agenda_, agenda);
+    assert_eq!(drepo.read_agendas().await.unwrap().len(), 1);
+
+    // It creates another one if forced.
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(base)
+        .await
+        .unwrap();
+    let (_, forced_agenda_commit) = drepo
+        .create_agenda(rs.members[0].name.clone(), true)
+        .await
+        .unwrap();
+    assert_ne!(forced_agenda_commit, agenda_commit);
+    assert_eq!(drepo.read_agendas().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn stale_agendas_after_finalization() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Two agendas for height 1, one of which gets finalized.
+    let base = drepo.get_raw().read().await.get_head().await.unwrap();
+    let (agenda, agenda_commit) = drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(base)
+        .await
+        .unwrap();
+    let (_, other_agenda_commit) = drepo
+        .create_agenda(rs.members[1].name.clone(), true)
+        .await
+        .unwrap();
+    assert_eq!(drepo.read_agendas().await.unwrap().len(), 2);
+
+    let agenda_proof_commit = drepo
+        .approve(
+            &agenda.to_hash256(),
+            keys.iter()
+                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .collect(),
+            simperby_core::utils::get_timestamp(),
+        )
+        .await
+        .unwrap();
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(agenda_proof_commit)
+        .await
+        .unwrap();
+    let (block, block_commit) = drepo.create_block(keys[0].0.clone()).await.unwrap();
+    let signatures = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(
+                &FinalizationSignTarget {
+                    round: 0,
+                    block_hash: block.to_hash256(),
+                },
+                private_key,
+            )
+            .unwrap()
+        })
+        .collect();
+    drepo
+        .finalize(
+            block_commit,
+            FinalizationProof {
+                signatures,
+                round: 0,
+            },
+        )
+        .await
+        .unwrap();
+
+    // The agenda branches of height 1 linger, but they are no longer returned.
+    {
+        let raw = drepo.get_raw();
+        let raw = raw.write().await;
+        for (branch, commit_hash) in [
+            ("a-stale-0", agenda_commit),
+            ("a-stale-1", other_agenda_commit),
+        ] {
+            raw.create_branch(branch.into(), commit_hash).await.unwrap();
+        }
+    }
+    assert!(drepo.read_agendas().await.unwrap().is_empty());
+
+    // An agenda for height 2 is returned.
+    let finalized = drepo
+        .read_last_finalization_info()
+        .await
+        .unwrap()
+        .commit_hash;
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(finalized)
+        .await
+        .unwrap();
+    let (agenda, agenda_commit) = drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    assert_eq!(agenda.height, 2);
+    assert_eq!(
+        drepo.read_agendas().await.unwrap(),
+        vec![(agenda_commit, agenda.to_hash256())]
+    );
+}
+
+#[tokio::test]
+async fn interrupted_finalization() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let genesis_commit = drepo
+        .read_last_finalization_info()
+        .await
+        .unwrap()
+        .commit_hash;
+    let (agenda, _) = drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    let agenda_proof_commit = drepo
+        .approve(
+            &agenda.to_hash256(),
+            keys.iter()
+                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .collect(),
+            simperby_core::utils::get_timestamp(),
+        )
+        .await
+        .unwrap();
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(agenda_proof_commit)
+        .await
+        .unwrap();
+    let (block, block_commit) = drepo.create_block(keys[0].0.clone()).await.unwrap();
+    let sign = |round| {
+        keys.iter()
+            .map(|(_, private_key)| {
+                TypedSignature::sign(
+                    &FinalizationSignTarget {
+                        round,
+                        block_hash: block.to_hash256(),
+                    },
+                    private_key,
+                )
+                .unwrap()
+            })
+            .collect()
+    };
+    drop(drepo);
+
+    // Simulate a crash of `finalize()` between moving `fp` and moving `finalized`.
+    let crash_after_moving_fp = |proof: FinalizationProof| {
+        let dir = dir.clone();
+        async move {
+            let mut raw = RawRepository::open(&dir).await.unwrap();
+            raw.checkout_detach(block_commit).await.unwrap();
+            let fp_commit = raw
+                .create_semantic_commit(
+                    format::fp_to_semantic_commit(&LastFinalizationProof {
+                        height: block.height,
+                        proof,
+                    }),
+                    true,
+                )
+                .await
+                .unwrap();
+            raw.move_branch(FP_BRANCH_NAME.into(), fp_commit)
+                .await
+                .unwrap();
+            assert_eq!(
+                raw.locate_branch(FINALIZED_BRANCH_NAME.into())
+                    .await
+                    .unwrap(),
+                genesis_commit
+            );
+            fp_commit
+        }
+    };
+    let open = || async {
+        DistributedRepository::new(
+            None,
+            Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+            config.clone(),
+            None,
+        )
+        .await
+    };
+
+    // An invalid proof under `fp` is not taken for a finalization.
+    crash_after_moving_fp(FinalizationProof {
+        round: 1,
+        signatures: sign(0),
+    })
+    .await;
+    assert!(open().await.is_err());
+
+    // The interrupted finalization is completed on the next open.
+    let fp_commit = crash_after_moving_fp(FinalizationProof {
+        round: 0,
+        signatures: sign(0),
+    })
+    .await;
+    let drepo = open().await.unwrap();
+    let lfi = drepo.read_last_finalization_info().await.unwrap();
+    assert_eq!(lfi.header, block);
+    assert_eq!(lfi.commit_hash, block_commit);
+    let raw = drepo.get_raw();
+    assert_eq!(
+        raw.read()
+            .await
+            .locate_branch(FP_BRANCH_NAME.into())
+            .await
+            .unwrap(),
+        fp_commit
+    );
+    drop(drepo);
+    // Nothing to recover anymore.
+    assert_eq!(
+        open()
+            .await
+            .unwrap()
+            .read_last_finalization_info()
+            .await
+            .unwrap()
+            .commit_hash,
+        block_commit
+    );
+}
+
+#[tokio::test]
+async fn out_of_order_branch_arrival() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let server_node_dir = create_temp_dir();
+    setup_pre_genesis_repository(&server_node_dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&server_node_dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut server_node_repo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(
+            RawRepository::open(&server_node_dir).await.unwrap(),
+        )),
+        config.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let client_node_dir = create_temp_dir();
+    simperby_test_suite::run_command(format!("cp -a {server_node_dir}/. {client_node_dir}/")).await;
+    simperby_test_suite::run_command(format!(
+        "cd {client_node_dir} && git remote add peer {server_node_dir}"
+    ))
+    .await;
+    let mut client_node_repo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(
+            RawRepository::open(&client_node_dir).await.unwrap(),
+        )),
+        config,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // The server finalizes the block of height 1 and proposes an agenda for height 2.
+    let (agenda, _) = server_node_repo
+        .create_agenda(rs.query_name(&keys[3].0).unwrap(), false)
+        .await
+        .unwrap();
+    server_node_repo
+        .approve(
+            &agenda.to_hash256(),
+            keys.iter()
+                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .collect(),
+            0,
+        )
+        .await
+        .unwrap();
+    let (block, block_commit) = server_node_repo
+        .create_block(keys[3].0.clone())
+        .await
+        .unwrap();
+    let signatures = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(
+                &FinalizationSignTarget {
+                    round: 0,
+                    block_hash: block.to_hash256(),
+                },
+                private_key,
+            )
+            .unwrap()
+        })
+        .collect();
+    let fp_commit = server_node_repo
+        .finalize(
+            block_commit,
+            FinalizationProof {
+                signatures,
+                round: 0,
+            },
+        )
+        .await
+        .unwrap();
+    server_node_repo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(block_commit)
+        .await
+        .unwrap();
+    let (next_agenda, next_agenda_commit) = server_node_repo
+        .create_agenda(rs.query_name(&keys[3].0).unwrap(), false)
+        .await
+        .unwrap();
+    assert_eq!(next_agenda.height, 2);
+    simperby_test_suite::run_command(format!("cd {client_node_dir} && git fetch --all")).await;
+
+    // The agenda for height 2 arrives before the finalization of height 1.
+    // It is kept, but not read as an agenda of the next height yet.
+    assert_eq!(
+        client_node_repo.sync(next_agenda_commit).await.unwrap(),
+        SyncOutcome::AgendaObserved(next_agenda_commit)
+    );
+    assert!(!client_node_repo
+        .read_agendas()
+        .await
+        .unwrap()
+        .contains(&(next_agenda_commit, next_agenda.to_hash256())));
+
+    // Once height 1 is finalized, the agenda is applied without being received again.
+    assert_eq!(
+        client_node_repo.sync(fp_commit).await.unwrap(),
+        SyncOutcome::Finalized(1)
+    );
+    assert_eq!(
+        client_node_repo.read_agendas().await.unwrap(),
+        vec![(next_agenda_commit, next_agenda.to_hash256())]
+    );
+}
+
+/// Forwards the connections on `port` to `target_port`,
+/// except the `drop_nth`-th (0-based) one which is closed right after being accepted.
+///
+/// Returns the number of connections accepted so far.
+async fn run_flaky_proxy(
+    port: u16,
+    target_port: u16,
+    drop_nth: usize,
+) -> Arc<std::sync::atomic::AtomicUsize> {
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .unwrap();
+    let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let accepted_ = Arc::clone(&accepted);
+    tokio::spawn(async move {
+        loop {
+            let (mut inbound, _) = listener.accept().await.unwrap();
+            let n = accepted_.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if n == drop_nth {
+                drop(inbound);
+                continue;
+            }
+            tokio::spawn(async move {
+                let mut outbound = tokio::net::TcpStream::connect(("127.0.0.1", target_port))
+                    .await
+                    .unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+            });
+        }
+    });
+    accepted
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn clone_retries_after_disconnection() {
+    setup_test();
+    let (rs, _) = test_utils::generate_standard_genesis(4);
+    let server_node_dir = create_temp_dir();
+    setup_pre_genesis_repository(&server_node_dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&server_node_dir).await.unwrap())
+        .await
+        .unwrap();
+    let server_raw = RawRepository::open(&server_node_dir).await.unwrap();
+
+    let port = dispense_port();
+    let proxy_port = dispense_port();
+    let server_node_dir_clone = server_node_dir.clone();
+    let git_server = tokio::spawn(async move {
+        let _server = server::run_server_legacy(&server_node_dir_clone, port).await;
+        sleep_ms(12000).await;
+    });
+    sleep_ms(500).await;
+    // The first connection lists the remote branches; the second one, dropped, fetches one of them.
+    let accepted = run_flaky_proxy(proxy_port, port, 1).await;
+
+    let progress_reports = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let progress_reports_ = Arc::clone(&progress_reports);
+    let client_node_dir = create_temp_dir();
+    let client_raw = RawRepository::clone_with_options(
+        &client_node_dir,
+        &format!("git://127.0.0.1:{proxy_port}/"),
+        CloneOptions {
+            max_retries: 2,
+            retry_interval_ms: 100,
+            progress: Some(Arc::new(move |_| {
+                progress_reports_.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            })),
+        },
+    )
+    .await
+    .unwrap();
+
+    assert!(accepted.load(std::sync::atomic::Ordering::SeqCst) > 2);
+    assert!(progress_reports.load(std::sync::atomic::Ordering::SeqCst) > 0);
+    let mut server_branches = server_raw.list_branches().await.unwrap();
+    server_branches.sort();
+    let mut cloned_branches = client_raw
+        .list_remote_tracking_branches()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(remote, branch, commit_hash)| {
+            assert_eq!(remote, "origin");
+            (branch, commit_hash)
+        })
+        .collect::<Vec<_>>();
+    cloned_branches.sort();
+    let mut expected_branches = Vec::new();
+    for branch in server_branches {
+        let commit_hash = server_raw.locate_branch(branch.clone()).await.unwrap();
+        expected_branches.push((branch, commit_hash));
+    }
+    assert_eq!(cloned_branches, expected_branches);
+    assert_eq!(
+        client_raw.get_head().await.unwrap(),
+        server_raw.get_head().await.unwrap()
+    );
+    git_server.await.unwrap();
+}
+
+#[tokio::test]
+async fn flush_only_new_branches() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let config = Config {
+        long_range_attack_distance: 1,
+    };
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let dms = Arc::new(RwLock::new(
+        create_test_dms(
+            "repository".to_owned(),
+            keys.iter()
+                .map(|(public_key, _)| public_key.clone())
+                .collect(),
+            keys[0].1.clone(),
+        )
+        .await,
+    ));
+    let mut drepo = DistributedRepository::new(
+        Some(Arc::clone(&dms)),
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        config,
+        Some(keys[0].1.clone()),
+    )
+    .await
+    .unwrap();
+    let branch_messages = || async {
+        dms.read()
+            .await
+            .read_messages()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|message| matches!(message.message, RepositoryMessage::Branch(_)))
+            .count()
+    };
+
+    let base = drepo.get_raw().read().await.get_head().await.unwrap();
+    drepo
+        .create_agenda(rs.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    drepo.flush().await.unwrap();
+    let messages = dms.read().await.read_messages().await.unwrap().len();
+    assert_eq!(branch_messages().await, 1);
+
+    // Nothing new to commit.
+    drepo.flush().await.unwrap();
+    assert_eq!(
+        dms.read().await.read_messages().await.unwrap().len(),
+        messages
+    );
+
+    // Only the new branch is committed.
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(base)
+        .await
+        .unwrap();
+    drepo
+        .create_agenda(rs.members[1].name.clone(), false)
+        .await
+        .unwrap();
+    drepo.flush().await.unwrap();
+    assert_eq!(branch_messages().await, 2);
+    assert_eq!(
+        dms.read().await.read_messages().await.unwrap().len(),
+        messages + 1
+    );
+
+    // A branch removed from the DMS is committed again.
+    let message_hash = dms
+        .read()
+        .await
+        .read_messages()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|message| matches!(message.message, RepositoryMessage::Branch(_)))
+        .unwrap()
+        .message
+        .to_hash256();
+    dms.write()
+        .await
+        .remove_message(message_hash, None)
+        .await
+        .unwrap();
+    assert_eq!(branch_messages().await, 1);
+    drepo.flush().await.unwrap();
+    assert_eq!(branch_messages().await, 2);
+    assert_eq!(
+        dms.read().await.read_messages().await.unwrap().len(),
+        messages + 1
+    );
+}
@@ -61,6 +61,7 @@ pub fn create_execution_transaction(
         head,
         body,
         diff: Diff::None,
+        author_signature: None,
     })
 }
 
@@ -117,3 +118,71 @@ pub fn convert_transaction_to_execution(transaction: &Transaction) -> Result<Exe
     }
     Ok(execution)
 }
+
+/// A finalized execution transaction packed with its commitment proof,
+/// which is what a relayer submits to the treasury contract.
+///
+/// The treasury contract checks `merkle_proof` against the `commit_merkle_root`
+/// of the header at `block_height`, which its light client has already verified.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ExecutionPayload {
+    /// The execution transaction created by `create_execution_transaction()`.
+    pub transaction: Transaction,
+    /// The merkle proof of the transaction in the block.
+    pub merkle_proof: MerkleProof,
+    /// The height of the block that the transaction is included in.
+    pub block_height: BlockHeight,
+}
+
+impl ExecutionPayload {
+    /// Encodes the payload into the bytes that the treasury contract expects.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_spb::to_vec(self).unwrap()
+    }
+
+    /// Decodes the payload from the bytes produced by `to_bytes()`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        serde_spb::from_slice(bytes).map_err(|e| e.to_string())
+    }
+}
+
+/// Assembles the payload to deliver a finalized execution transaction to its target chain.
+///
+/// Fails if the given transaction is not a valid execution transaction.
+pub fn build_execution_transaction(
+    treasury_transaction: Transaction,
+    merkle_proof: MerkleProof,
+    block_height: BlockHeight,
+) -> Result<ExecutionPayload, String> {
+    convert_transaction_to_execution(&treasury_transaction)?;
+    Ok(ExecutionPayload {
+        transaction: treasury_transaction,
+        merkle_proof,
+        block_height,
+    })
+}
+
+/// Verifies the payload against the header of the block that the transaction is included in,
+/// returning the execution to perform.
+///
+/// Note that whether the header itself is finalized must be checked separately
+/// (e.g., by `LightClient`).
+pub fn verify_execution_payload(
+    payload: &ExecutionPayload,
+    header: &BlockHeader,
+) -> Result<Execution, String> {
+    if payload.block_height != header.height {
+        return Err(format!(
+            "Invalid block height: expected {}, got {}",
+            header.height, payload.block_height
+        ));
+    }
+    payload
+        .merkle_proof
+        .verify(
+            header.commit_merkle_root,
+            &serde_spb::to_vec(&payload.transaction).unwrap(),
+        )
+        .map_err(|e| e.to_string())?;
+    convert_transaction_to_execution(&payload.transaction)
+}
@@ -1,11 +1,13 @@
 pub mod execution;
 pub mod tests;
 
-use eyre::Error;
+use eyre::{eyre, Error};
+use light_client::CommitmentBundle;
 use merkle_tree::*;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use simperby_core::*;
+use simperby_repository::DistributedRepository;
 
 /// An abstract information about a block from a settlement chain.
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
@@ -67,14 +69,9 @@ pub trait SettlementChain: Send + Sync {
 
     /// Delivers an execution transaction to the settlement chain with the commitment proof.
     ///
-    /// - `transaction`: The transaction to deliver.
-    /// - `block_height`: The height of the block that the transaction is included in.
-    async fn execute(
-        &self,
-        transaction: Transaction,
-        block_height: u64,
-        proof: MerkleProof,
-    ) -> Result<(), Error>;
+    /// The bundle carries the transaction, the header of the block that the transaction is included in,
+    /// and the proofs for both.
+    async fn execute(&self, bundle: CommitmentBundle) -> Result<(), Error>;
 
     /// Returns the current sequence number of the given externally owned account.
     async fn eoa_get_sequence(&self, address: HexSerializedVec) -> Result<u128, Error>;
@@ -97,3 +94,39 @@ pub trait SettlementChain: Send + Sync {
         amount: Decimal,
     ) -> Result<(), Error>;
 }
+
+/// Assembles the headers and their finalization proofs that the light client of the treasury lacks,
+/// from the one after its current header up to the last finalized block of the repository.
+///
+/// A relayer submits them in order with `SettlementChain::update_treasury_light_client()`.
+/// Fails if the header of the light client is not the one finalized in the repository.
+pub async fn compute_update_sequence(
+    chain: &impl SettlementChain,
+    repo: &DistributedRepository,
+) -> Result<Vec<(BlockHeader, FinalizationProof)>, Error> {
+    let light_client_header = chain.get_light_client_header().await?;
+    let last_height = repo.read_last_finalization_info().await?.header.height;
+    if light_client_header.height > last_height {
+        return Err(eyre!(
+            "the light client is at height {}, ahead of the repository at {}",
+            light_client_header.height,
+            last_height
+        ));
+    }
+    let finalized_header = repo
+        .read_finalization_info(light_client_header.height)
+        .await?
+        .header;
+    if finalized_header != light_client_header {
+        return Err(eyre!(
+            "the light client header at height {} is not the finalized one",
+            light_client_header.height
+        ));
+    }
+    let mut sequence = Vec::new();
+    for height in light_client_header.height + 1..=last_height {
+        let finalization_info = repo.read_finalization_info(height).await?;
+        sequence.push((finalization_info.header, finalization_info.proof));
+    }
+    Ok(sequence)
+}
@@ -3,6 +3,7 @@
 use super::*;
 use execution::*;
 use simperby_core::{verify::CommitSequenceVerifier, *};
+use std::sync::Mutex;
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -36,6 +37,107 @@ impl ChainInfo {
     }
 }
 
+/// An in-memory settlement chain whose treasury only runs the light client.
+///
+/// It is useful to test relaying the headers without a real chain;
+/// the other operations are not supported.
+pub struct MockSettlementChain {
+    chain_name: String,
+    light_client: Mutex<light_client::LightClient>,
+}
+
+impl MockSettlementChain {
+    pub fn new(chain_name: String, initial_header: BlockHeader) -> Self {
+        Self {
+            chain_name,
+            light_client: Mutex::new(light_client::LightClient::new(initial_header)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SettlementChain for MockSettlementChain {
+    async fn get_chain_name(&self) -> String {
+        self.chain_name.clone()
+    }
+
+    async fn check_connection(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn get_last_block(&self) -> Result<SettlementChainBlock, Error> {
+        Ok(SettlementChainBlock {
+            height: 0,
+            timestamp: 0,
+        })
+    }
+
+    async fn get_contract_sequence(&self) -> Result<u128, Error> {
+        Ok(0)
+    }
+
+    async fn get_relayer_account_info(&self) -> Result<(HexSerializedVec, Decimal), Error> {
+        Ok((HexSerializedVec::from(Vec::new()), Decimal::ZERO))
+    }
+
+    async fn get_light_client_header(&self) -> Result<BlockHeader, Error> {
+        Ok(self.light_client.lock().unwrap().last_header.clone())
+    }
+
+    async fn get_treasury_fungible_token_balance(
+        &self,
+        _address: HexSerializedVec,
+    ) -> Result<Decimal, Error> {
+        Ok(Decimal::ZERO)
+    }
+
+    async fn get_treasury_non_fungible_token_balance(
+        &self,
+        _address: HexSerializedVec,
+    ) -> Result<Vec<HexSerializedVec>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn update_treasury_light_client(
+        &self,
+        header: BlockHeader,
+        proof: FinalizationProof,
+    ) -> Result<(), Error> {
+        self.light_client
+            .lock()
+            .unwrap()
+            .update(header, proof)
+            .map_err(|e| eyre!(e))
+    }
+
+    async fn execute(&self, _bundle: CommitmentBundle) -> Result<(), Error> {
+        Err(eyre!("execution is not supported by the mock chain"))
+    }
+
+    async fn eoa_get_sequence(&self, _address: HexSerializedVec) -> Result<u128, Error> {
+        Err(eyre!("accounts are not supported by the mock chain"))
+    }
+
+    async fn eoa_get_fungible_token_balance(
+        &self,
+        _address: HexSerializedVec,
+        _token_address: HexSerializedVec,
+    ) -> Result<Decimal, Error> {
+        Err(eyre!("accounts are not supported by the mock chain"))
+    }
+
+    async fn eoa_transfer_fungible_token(
+        &self,
+        _address: HexSerializedVec,
+        _sender_private_key: HexSerializedVec,
+        _token_address: HexSerializedVec,
+        _receiver_address: HexSerializedVec,
+        _amount: Decimal,
+    ) -> Result<(), Error> {
+        Err(eyre!("accounts are not supported by the mock chain"))
+    }
+}
+
 /// A simple transfer scneario.
 ///
 /// The treasury contract must be synchronized with the given `chain_info`
@@ -78,6 +180,7 @@ pub async fn scenario_1(
             head: format!("commit {i}"),
             body: "".to_owned(),
             diff: Diff::None,
+            author_signature: None,
         };
         csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
         transactions.push(tx);
@@ -106,6 +209,7 @@ pub async fn scenario_1(
             head: format!("commit {i}"),
             body: "".to_owned(),
             diff: Diff::None,
+            author_signature: None,
         };
         csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
         transactions.push(tx);
@@ -167,7 +271,7 @@ pub async fn scenario_1(
     csv.verify_last_header_finalization(&fp).unwrap();
 
     // Update light client
-    sc.update_treasury_light_client(block_header.clone(), fp)
+    sc.update_treasury_light_client(block_header.clone(), fp.clone())
         .await
         .unwrap();
     sleep(transaction_finalization_wait).await;
@@ -184,7 +288,14 @@ pub async fn scenario_1(
     let merkle_proof = merkle_tree
         .create_merkle_proof(execute_tx.to_hash256())
         .unwrap();
-    sc.execute(execute_tx, 1, merkle_proof).await.unwrap();
+    sc.execute(CommitmentBundle {
+        header: block_header,
+        proof: fp,
+        transaction: execute_tx,
+        merkle_proof,
+    })
+    .await
+    .unwrap();
     sleep(transaction_finalization_wait).await;
 
     // Check the result
@@ -0,0 +1,104 @@
+use merkle_tree::*;
+use rust_decimal::Decimal;
+use simperby_core::verify::CommitSequenceVerifier;
+use simperby_core::*;
+use simperby_settlement::execution::*;
+
+/// Creates a block of the given transactions on top of the standard genesis,
+/// returning its header and the merkle tree of its commits.
+fn finalize_transactions(transactions: Vec<Transaction>) -> (BlockHeader, OneshotMerkleTree) {
+    let (reserved_state, keys) = test_utils::generate_standard_genesis(4);
+    let genesis_info = reserved_state.genesis_info.clone();
+    let mut csv =
+        CommitSequenceVerifier::new(genesis_info.header.clone(), reserved_state.clone()).unwrap();
+    for transaction in &transactions {
+        csv.apply_commit(&Commit::Transaction(transaction.clone()))
+            .unwrap();
+    }
+    let agenda = Agenda {
+        height: 1,
+        author: reserved_state.query_name(&keys[0].0).unwrap(),
+        timestamp: 0,
+        transactions_hash: Agenda::calculate_transactions_hash(&transactions),
+        previous_block_hash: genesis_info.header.to_hash256(),
+    };
+    csv.apply_commit(&Commit::Agenda(agenda.clone())).unwrap();
+    csv.apply_commit(&Commit::AgendaProof(AgendaProof {
+        height: 1,
+        agenda_hash: agenda.to_hash256(),
+        proof: keys
+            .iter()
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+            .collect::<Vec<_>>(),
+        timestamp: 0,
+    }))
+    .unwrap();
+    let commits = csv.get_total_commits()[1..].to_vec();
+    let header = BlockHeader {
+        author: keys[0].0.clone(),
+        prev_block_finalization_proof: genesis_info.genesis_proof,
+        previous_hash: genesis_info.header.to_hash256(),
+        height: 1,
+        timestamp: 0,
+        commit_merkle_root: BlockHeader::calculate_commit_merkle_root(&commits),
+        repository_merkle_root: Hash256::zero(),
+        validator_set: reserved_state.get_validator_set().unwrap(),
+        version: genesis_info.header.version,
+    };
+    csv.apply_commit(&Commit::Block(header.clone())).unwrap();
+    let merkle_tree =
+        OneshotMerkleTree::create(commits.iter().map(|commit| commit.to_hash256()).collect());
+    (header, merkle_tree)
+}
+
+fn execution(contract_sequence: u128) -> Execution {
+    Execution {
+        target_chain: "mythereum".to_string(),
+        contract_sequence,
+        message: ExecutionMessage::TransferFungibleToken(TransferFungibleToken {
+            token_address: HexSerializedVec::from(b"tether-address".to_vec()),
+            amount: Decimal::new(100, 0),
+            receiver_address: HexSerializedVec::from(b"receiver-address".to_vec()),
+        }),
+    }
+}
+
+#[test]
+fn build_and_verify_payload() {
+    let tx1 = create_execution_transaction(&execution(0), "member-0000".to_owned(), 0).unwrap();
+    let tx2 = create_execution_transaction(&execution(1), "member-0000".to_owned(), 0).unwrap();
+    let (header, merkle_tree) = finalize_transactions(vec![tx1.clone(), tx2.clone()]);
+
+    let merkle_proof = merkle_tree.create_merkle_proof(tx2.to_hash256()).unwrap();
+    let payload = build_execution_transaction(tx2, merkle_proof, header.height).unwrap();
+    let payload = ExecutionPayload::from_bytes(&payload.to_bytes()).unwrap();
+    assert_eq!(
+        verify_execution_payload(&payload, &header).unwrap(),
+        execution(1)
+    );
+
+    // A payload with a different transaction or height must be rejected.
+    let mut tampered = payload.clone();
+    tampered.transaction = tx1;
+    verify_execution_payload(&tampered, &header).unwrap_err();
+    let mut tampered = payload;
+    tampered.block_height = 2;
+    verify_execution_payload(&tampered, &header).unwrap_err();
+}
+
+#[test]
+fn build_payload_for_non_execution_transaction() {
+    let transaction = Transaction {
+        author: "member-0000".to_owned(),
+        timestamp: 0,
+        head: "not an execution".to_owned(),
+        body: "".to_owned(),
+        diff: Diff::None,
+        author_signature: None,
+    };
+    let (header, merkle_tree) = finalize_transactions(vec![transaction.clone()]);
+    let merkle_proof = merkle_tree
+        .create_merkle_proof(transaction.to_hash256())
+        .unwrap();
+    build_execution_transaction(transaction, merkle_proof, header.height).unwrap_err();
+}
@@ -0,0 +1,122 @@
+use simperby_core::*;
+use simperby_repository::{raw::RawRepository, *};
+use simperby_settlement::{tests::MockSettlementChain, *};
+use simperby_test_suite::*;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Finalizes a block on top of the last finalized one, with an empty agenda.
+async fn finalize_next_block(drepo: &mut DistributedRepository, keys: &[(PublicKey, PrivateKey)]) {
+    let reserved_state = drepo
+        .read_last_finalization_info()
+        .await
+        .unwrap()
+        .reserved_state;
+    let (agenda, _) = drepo
+        .create_agenda(reserved_state.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    let agenda_proof_commit = drepo
+        .approve(
+            &agenda.to_hash256(),
+            keys.iter()
+                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .collect(),
+            utils::get_timestamp(),
+        )
+        .await
+        .unwrap();
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(agenda_proof_commit)
+        .await
+        .unwrap();
+    let (block, block_commit) = drepo.create_block(keys[0].0.clone()).await.unwrap();
+    let signatures = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(
+                &FinalizationSignTarget {
+                    round: 0,
+                    block_hash: block.to_hash256(),
+                },
+                private_key,
+            )
+            .unwrap()
+        })
+        .collect();
+    drepo
+        .finalize(
+            block_commit,
+            FinalizationProof {
+                signatures,
+                round: 0,
+            },
+        )
+        .await
+        .unwrap();
+    let finalized = drepo
+        .read_last_finalization_info()
+        .await
+        .unwrap()
+        .commit_hash;
+    drepo
+        .get_raw()
+        .write()
+        .await
+        .checkout_detach(finalized)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn update_sequence_brings_light_client_current() {
+    setup_test();
+    let (rs, keys) = test_utils::generate_standard_genesis(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, rs.clone()).await;
+    DistributedRepository::genesis(RawRepository::open(&dir).await.unwrap())
+        .await
+        .unwrap();
+    let mut drepo = DistributedRepository::new(
+        None,
+        Arc::new(RwLock::new(RawRepository::open(&dir).await.unwrap())),
+        Config {
+            long_range_attack_distance: 1,
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    let chain = MockSettlementChain::new("mock".to_owned(), rs.genesis_info.header.clone());
+
+    // The light client falls 3 heights behind.
+    for _ in 0..3 {
+        finalize_next_block(&mut drepo, &keys).await;
+    }
+    let sequence = compute_update_sequence(&chain, &drepo).await.unwrap();
+    assert_eq!(
+        sequence
+            .iter()
+            .map(|(header, _)| header.height)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+
+    for (header, proof) in sequence {
+        chain
+            .update_treasury_light_client(header, proof)
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        chain.get_light_client_header().await.unwrap(),
+        drepo.read_last_finalization_info().await.unwrap().header
+    );
+    assert!(compute_update_sequence(&chain, &drepo)
+        .await
+        .unwrap()
+        .is_empty());
+}
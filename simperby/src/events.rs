@@ -0,0 +1,78 @@
+//! The high-level state transitions of a node.
+//!
+//! Every transition is logged at the info level with the [`LOG_TARGET`] target,
+//! as `<event>: <key>=<value> ...`, so that a node's behavior can be followed from its logs.
+
+use simperby_core::*;
+use std::net::SocketAddr;
+
+/// The log target of the node events.
+pub const LOG_TARGET: &str = "simperby::event";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeEvent {
+    /// A block has been finalized, so the node has moved on to the next height.
+    HeightAdvanced {
+        height: BlockHeight,
+        block_hash: Hash256,
+    },
+    /// `Client::catch_up` has verified and finalized a window of heights.
+    CatchUpWindowFinalized {
+        from: BlockHeight,
+        to: BlockHeight,
+    },
+    /// This node has voted for an agenda.
+    AgendaVoted {
+        height: BlockHeight,
+        agenda_hash: Hash256,
+    },
+    /// An agenda has collected enough votes and its agenda-proof has been committed.
+    AgendaApproved {
+        height: BlockHeight,
+        agenda_hash: Hash256,
+    },
+    /// The consensus has moved on to another round.
+    RoundChanged {
+        height: BlockHeight,
+        round: ConsensusRound,
+    },
+    PeerAdded {
+        name: MemberName,
+        address: SocketAddr,
+    },
+    PeerRemoved {
+        name: MemberName,
+    },
+}
+
+impl std::fmt::Display for NodeEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeEvent::HeightAdvanced { height, block_hash } => {
+                write!(f, "height advanced: height={height} block={block_hash}")
+            }
+            NodeEvent::CatchUpWindowFinalized { from, to } => {
+                write!(f, "catch-up window finalized: from={from} to={to}")
+            }
+            NodeEvent::AgendaVoted {
+                height,
+                agenda_hash,
+            } => write!(f, "agenda voted: height={height} agenda={agenda_hash}"),
+            NodeEvent::AgendaApproved {
+                height,
+                agenda_hash,
+            } => write!(f, "agenda approved: height={height} agenda={agenda_hash}"),
+            NodeEvent::RoundChanged { height, round } => {
+                write!(f, "round changed: height={height} round={round}")
+            }
+            NodeEvent::PeerAdded { name, address } => {
+                write!(f, "peer added: name={name} address={address}")
+            }
+            NodeEvent::PeerRemoved { name } => write!(f, "peer removed: name={name}"),
+        }
+    }
+}
+
+pub(crate) fn log_event(event: NodeEvent) {
+    log::info!(target: LOG_TARGET, "{event}");
+}
@@ -1,6 +1,8 @@
+pub mod events;
 mod storage;
 pub mod types;
 
+use events::{log_event, NodeEvent};
 use eyre::eyre;
 use eyre::Result;
 use simperby_consensus::*;
@@ -10,9 +12,10 @@ use simperby_governance::*;
 use simperby_network::dms::PeerStatus;
 use simperby_network::peers::Peers;
 use simperby_network::*;
-use simperby_repository::raw::RawRepository;
+use simperby_repository::raw::{HeadState, RawRepository};
 use simperby_repository::*;
-use std::net::SocketAddrV4;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -24,20 +27,36 @@ pub use simperby_repository;
 
 pub use crate::types::*;
 
+/// The prefix of the remotes added for `Config::public_repo_url`.
+const MIRROR_REMOTE_PREFIX: &str = "public_";
+
 /// A client for a single height.
 struct ClientInner {
     config: types::Config,
     auth: Auth,
+    /// The member name of this node in the last finalized reserved state.
+    member_name: Option<MemberName>,
+    /// Whether this node is a validator of the current height.
+    role: NodeRole,
     path: String,
     repository: DistributedRepository,
     governance: Governance,
     consensus: Consensus,
     peers: Peers,
+    /// The blocks proposed without their bodies, with the round of the proposal,
+    /// which are fetched on every progress until registered or the round changes.
+    missing_blocks: Vec<(Hash256, ConsensusRound)>,
+    /// Held until the client is dropped; declared last so that it is released last.
+    node_lock: Arc<storage::NodeLock>,
 }
 
+/// A callback that is invoked with the newly finalized block header and its finalization proof.
+pub type FinalizationCallback = Box<dyn FnMut(&BlockHeader, &FinalizationProof) + Send + Sync>;
+
 /// An instance of Simperby client (a.k.a. a 'node').
 pub struct Client {
     inner: Option<ClientInner>,
+    finalization_callbacks: Vec<FinalizationCallback>,
 }
 
 impl Client {
@@ -52,47 +71,122 @@ impl Client {
         Ok(())
     }
 
+    /// Opens the client for the current height.
+    ///
+    /// The DMSes left from a previous height are reinitialized for the current one.
+    /// Fails with `NodeAlreadyOpen` if another instance has opened the same node.
+    ///
+    /// A node whose key is not in the validator set is opened as a `NodeRole::Observer`,
+    /// whether it is a member having delegated its consensus right or not a member at all.
+    /// If `Config::expect_validator` is set, it fails early instead.
+    ///
+    /// The config and the auth are validated before anything is opened.
     pub async fn open(path: &str, config: types::Config, auth: Auth) -> Result<Self> {
+        config.validate()?;
+        auth.verify_consistency()?;
+        let node_lock = Arc::new(storage::lock_node(path)?);
+        let raw = RawRepository::open(path).await?;
+        let lfi = interpret::read_last_finalization_info(&raw).await?;
+        let identity = Self::identify(&lfi.reserved_state, &config, &auth)?;
+        Ok(Self {
+            inner: Some(Self::open_inner(path, config, auth, node_lock, raw, lfi, identity).await?),
+            finalization_callbacks: Vec::new(),
+        })
+    }
+
+    /// Finds the member name and the role of this node in the given reserved state,
+    /// checking them against `Config::expect_validator`.
+    fn identify(
+        reserved_state: &ReservedState,
+        config: &types::Config,
+        auth: &Auth,
+    ) -> Result<(Option<MemberName>, NodeRole)> {
+        let public_key = auth.private_key.public_key();
+        let role = if reserved_state
+            .get_validator_set()
+            .map_err(|e| eyre!("invalid validator set: {e}"))?
+            .iter()
+            .any(|(validator, _)| *validator == public_key)
+        {
+            NodeRole::Validator
+        } else {
+            NodeRole::Observer
+        };
+        let member_name = reserved_state.query_name(&public_key);
+        if config.expect_validator {
+            if member_name.is_none() {
+                return Err(eyre!("the key of this node ({public_key}) is not a member"));
+            }
+            if role != NodeRole::Validator {
+                return Err(eyre!(
+                    "the key of this node ({public_key}) is not in the validator set"
+                ));
+            }
+        }
+        Ok((member_name, role))
+    }
+
+    /// Opens the client for the height of the given last finalization,
+    /// with the node directory already locked.
+    async fn open_inner(
+        path: &str,
+        config: types::Config,
+        auth: Auth,
+        node_lock: Arc<storage::NodeLock>,
+        raw: RawRepository,
+        lfi: FinalizationInfo,
+        (member_name, role): (Option<MemberName>, NodeRole),
+    ) -> Result<ClientInner> {
         let (governance_dms, consensus_dms, consensus_state, repository_dms, peers) =
-            storage::open(path, config.clone(), auth.clone()).await?;
+            storage::open(path, auth.clone(), &lfi).await?;
         let repository = DistributedRepository::new(
             Some(Arc::new(RwLock::new(repository_dms))),
-            Arc::new(RwLock::new(RawRepository::open(path).await?)),
+            Arc::new(RwLock::new(raw)),
             simperby_repository::Config {
                 long_range_attack_distance: 3,
             },
             Some(auth.private_key.clone()),
         )
         .await?;
+        repository.check(0).await?;
 
-        let lfi = repository.read_last_finalization_info().await?;
-        let agendas = repository.read_agendas().await?;
-        Ok(Self {
-            inner: Some(ClientInner {
-                config,
-                auth: auth.clone(),
-                path: path.to_string(),
-                repository,
-                governance: Governance::new(
-                    Arc::new(RwLock::new(governance_dms)),
-                    lfi.clone(),
-                    agendas.into_iter().map(|(_, hash)| hash).collect(),
-                )
-                .await?,
-                consensus: Consensus::new(
-                    Arc::new(RwLock::new(consensus_dms)),
-                    consensus_state,
-                    lfi.header,
-                    ConsensusParams {
-                        timeout_ms: 10000000,
-                        repeat_round_for_first_leader: 100,
-                    },
-                    get_timestamp(),
-                    Some(auth.private_key),
-                )
-                .await?,
-                peers,
-            }),
+        let mut agendas = BTreeMap::new();
+        for (agenda_commit, agenda_hash) in repository.read_agendas().await? {
+            agendas.insert(
+                agenda_hash,
+                repository.read_agenda_type(agenda_commit).await?,
+            );
+        }
+        Ok(ClientInner {
+            config,
+            auth: auth.clone(),
+            member_name,
+            role,
+            path: path.to_string(),
+            repository,
+            governance: Governance::new(
+                Arc::new(RwLock::new(governance_dms)),
+                lfi.clone(),
+                agendas,
+            )
+            .await?,
+            consensus: Consensus::new(
+                Arc::new(RwLock::new(consensus_dms)),
+                consensus_state,
+                lfi,
+                ConsensusParams {
+                    timeout_ms: 10000000,
+                    repeat_round_for_first_leader: 100,
+                },
+                match role {
+                    NodeRole::Validator => Some(auth.private_key),
+                    NodeRole::Observer => None,
+                },
+            )
+            .await?,
+            peers,
+            missing_blocks: Vec::new(),
+            node_lock,
         })
     }
 
@@ -104,8 +198,32 @@ impl Client {
         &self.inner.as_ref().unwrap().auth
     }
 
-    pub async fn clean(&mut self, _hard: bool) -> Result<()> {
-        todo!()
+    /// Returns the member name of this node, or `None` if the key of this node is not a member.
+    ///
+    /// It is resolved from the last finalized reserved state when the client is opened.
+    pub fn my_member_name(&self) -> Option<MemberName> {
+        self.inner.as_ref().unwrap().member_name.clone()
+    }
+
+    /// Returns the role of this node in the consensus of the current height.
+    ///
+    /// It is decided from the last finalized reserved state when the client is opened.
+    pub fn role(&self) -> NodeRole {
+        self.inner.as_ref().unwrap().role
+    }
+
+    /// Cleans the repository (see `DistributedRepository::clean`),
+    /// and collects the garbage if the orphaned objects have piled up.
+    pub async fn clean(&mut self, hard: bool) -> Result<()> {
+        let this = self.inner.as_mut().unwrap();
+        this.repository.clean(hard).await?;
+        this.repository
+            .get_raw()
+            .write()
+            .await
+            .run_garbage_collection_if_needed(GC_LOOSE_OBJECTS_THRESHOLD)
+            .await?;
+        Ok(())
     }
 
     pub fn repository(&self) -> &DistributedRepository {
@@ -116,18 +234,50 @@ impl Client {
         &mut self.inner.as_mut().unwrap().repository
     }
 
+    /// Registers a callback that will be invoked whenever a block is finalized by this client.
+    ///
+    /// This is the extension point for the embedding application to relay
+    /// the new header to the settlement chains
+    /// (e.g., `SettlementChain::update_treasury_light_client`).
+    pub fn on_finalize(
+        &mut self,
+        callback: impl FnMut(&BlockHeader, &FinalizationProof) + Send + Sync + 'static,
+    ) {
+        self.finalization_callbacks.push(Box::new(callback));
+    }
+
     /// Makes a progress for the consensus, returning the result.
     ///
+    /// If nothing happened, it tells why the consensus is stalled instead
+    /// (e.g., no block candidate is selected although this node leads the round).
+    ///
+    /// An observer makes a progress only with the messages of the validators,
+    /// finalizing the block as soon as they do.
+    ///
     /// TODO: it has to consume the object if finalized.
-    pub async fn progress_for_consensus(&mut self) -> Result<String> {
-        let mut this = self.inner.take().unwrap();
+    pub async fn progress_for_consensus(&mut self) -> Result<ConsensusProgress> {
+        let this = self.inner.as_mut().unwrap();
+        let round = this.consensus.get_round().await?;
         let result = this.consensus.progress(get_timestamp()).await?;
-        let report = format!("{result:?}");
+        let progress = if result.is_empty() {
+            ConsensusProgress::Stalled {
+                reason: this.consensus.diagnose_stall().await?,
+            }
+        } else {
+            ConsensusProgress::Progressed(result.clone())
+        };
+        let missing_blocks = result
+            .iter()
+            .filter_map(|x| match x {
+                ProgressResult::NeedBlockBody(block_hash) => Some(*block_hash),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
         for result in result {
-            if let ProgressResult::Finalized(Finalization {
-                block_hash, proof, ..
-            }) = result
-            {
+            if let ProgressResult::Finalized(finalization) = result {
+                let Finalization {
+                    block_hash, proof, ..
+                } = finalization.clone();
                 let commit_hash = this
                     .repository
                     .read_blocks()
@@ -137,25 +287,121 @@ impl Client {
                     .ok_or_else(|| eyre::eyre!("finalized block can't be found in repository"))?
                     .0;
                 this.repository.finalize(commit_hash, proof).await?;
-                let path = this.path.clone();
-                let config = this.config.clone();
-                let auth = this.auth.clone();
-                let peers = this.peers.list_peers().await?;
-                drop(this);
-                storage::clear(&path).await?;
-                storage::init(&path).await?;
-                let mut this = Self::open(&path, config, auth).await?.inner.unwrap();
-                for peer in peers {
-                    this.peers.add_peer(peer.name, peer.address).await?;
+                storage::write_last_finalization(&this.path, &finalization).await?;
+                self.reopen().await?;
+                let lfi = self.repository().read_last_finalization_info().await?;
+                log_event(NodeEvent::HeightAdvanced {
+                    height: lfi.header.height,
+                    block_hash: lfi.header.to_hash256(),
+                });
+                for callback in self.finalization_callbacks.iter_mut() {
+                    callback(&lfi.header, &lfi.proof);
                 }
-                self.inner = Some(this);
-                return Ok(report);
+                return Ok(progress);
             }
         }
-        self.inner = Some(this);
-        Ok(report)
+        let new_round = this.consensus.get_round().await?;
+        if new_round != round {
+            log_event(NodeEvent::RoundChanged {
+                height: this.consensus.get_block_header().await?.height + 1,
+                round: new_round,
+            });
+        }
+        for block_hash in missing_blocks {
+            if !this.missing_blocks.iter().any(|(h, _)| *h == block_hash) {
+                this.missing_blocks.push((block_hash, new_round));
+            }
+        }
+        // A block proposed in a previous round is not needed anymore.
+        let missing_blocks = std::mem::take(&mut this.missing_blocks)
+            .into_iter()
+            .filter(|(_, round)| *round == new_round)
+            .collect::<Vec<_>>();
+        let mut still_missing = Vec::new();
+        for (block_hash, round) in missing_blocks {
+            self.fetch_block_body(block_hash).await?;
+            let this = self.inner.as_ref().unwrap();
+            if this
+                .consensus
+                .get_block_verdict(block_hash)
+                .await?
+                .is_none()
+            {
+                log::warn!("the proposed block {block_hash} is not available from the peers yet");
+                still_missing.push((block_hash, round));
+            }
+        }
+        self.inner.as_mut().unwrap().missing_blocks = still_missing;
+        Ok(progress)
+    }
+
+    /// Fetches the block branches from the peers to get the body of the given block,
+    /// which the consensus has been proposed without having it.
+    ///
+    /// Returns whether the block has been received; if so, it is registered to the consensus
+    /// so that this node can vote for it in the next progress.
+    pub async fn fetch_block_body(&mut self, block_hash: Hash256) -> Result<bool> {
+        if let Some(valid) = self
+            .inner
+            .as_ref()
+            .unwrap()
+            .consensus
+            .get_block_verdict(block_hash)
+            .await?
+        {
+            return Ok(valid);
+        }
+        self.add_remote_repositories().await?;
+        let this = self.inner.as_mut().unwrap();
+        let remote_block_branches = {
+            let raw = this.repository.get_raw();
+            let mut raw = raw.write().await;
+            raw.fetch_all(true).await?;
+            raw.list_remote_tracking_branches()
+                .await?
+                .into_iter()
+                .filter(|(_, branch, _)| branch.starts_with("b-"))
+                .collect::<Vec<_>>()
+        };
+        // A broken branch of a peer must not keep this node from the others.
+        for (remote, branch, commit_hash) in remote_block_branches {
+            if let Err(e) = this.repository.sync(commit_hash).await {
+                log::warn!("failed to sync the block branch {branch} of {remote}: {e}");
+            }
+        }
+        let commit_hash = if let Some((commit_hash, _)) = this
+            .repository
+            .read_blocks()
+            .await?
+            .into_iter()
+            .find(|(_, h)| *h == block_hash)
+        {
+            commit_hash
+        } else {
+            return Ok(false);
+        };
+        this.consensus
+            .register_verified_block(read_block_header(&this.repository, commit_hash).await?)
+            .await?;
+        // The proposal has been dropped while the block was missing, so read it again.
+        this.consensus.update().await?;
+        Ok(true)
+    }
+
+    /// Returns the finalization of the last finalized block,
+    /// with the block hash and the finalization proof, without reading the repository.
+    ///
+    /// It is recorded whenever the node advances, either by its own consensus
+    /// or by `update()` (with the time of the sync as its timestamp).
+    pub async fn latest_finalization_proof(&self) -> Result<Option<Finalization>> {
+        let this = self.inner.as_ref().unwrap();
+        storage::read_last_finalization(&this.path).await
     }
 
+    /// Votes for the given agenda.
+    ///
+    /// If `Config::auto_approve` is set and the agenda becomes eligible with this vote,
+    /// the agenda-proof commit is created right away instead of waiting for `update()`.
     pub async fn vote(&mut self, agenda_commit: CommitHash) -> Result<()> {
         let this = self.inner.as_mut().unwrap();
         let agendas = this.repository.read_agendas().await?;
@@ -168,18 +414,339 @@ impl Client {
             ));
         };
         this.repository.vote(agenda_commit).await?;
+        let agenda_type = this.repository.read_agenda_type(agenda_commit).await?;
+        this.governance
+            .register_verified_agenda_hash(agenda_hash, agenda_type)
+            .await?;
         this.governance.vote(agenda_hash).await?;
+        let height = this
+            .repository
+            .read_last_finalization_info()
+            .await?
+            .header
+            .height
+            + 1;
+        log_event(NodeEvent::AgendaVoted {
+            height,
+            agenda_hash,
+        });
+        if this.config.auto_approve
+            && !read_approved_agenda_hashes(&this.repository)
+                .await?
+                .contains(&agenda_hash)
+        {
+            if let Some((_, agenda_proof)) = this
+                .governance
+                .get_eligible_agendas()
+                .await?
+                .into_iter()
+                .find(|(hash, _)| *hash == agenda_hash)
+            {
+                this.repository
+                    .approve(&agenda_hash, agenda_proof.proof, get_timestamp())
+                    .await?;
+                log_event(NodeEvent::AgendaApproved {
+                    height,
+                    agenda_hash,
+                });
+            }
+        }
         Ok(())
     }
 
-    /// Vetoes the current round.
-    pub async fn veto_round(&mut self) -> Result<()> {
-        unimplemented!()
+    /// Creates an agenda from the transactions staged on the `p` (payload) branch,
+    /// and votes for it right away if `vote` is set.
+    ///
+    /// The agenda commit is created on top of the tip of the `p` branch
+    /// while the branch itself is left unchanged, and `HEAD` is restored afterwards.
+    /// Returns `None` without creating anything if there is no staged transaction.
+    pub async fn propose_agenda_from_staged(
+        &mut self,
+        vote: bool,
+    ) -> Result<Option<(Agenda, CommitHash)>> {
+        let this = self.inner.as_mut().unwrap();
+        let author = this
+            .member_name
+            .clone()
+            .ok_or_else(|| eyre!("this node is not a member"))?;
+        let (tip, commits) = match this.repository.read_payload_branch().await? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        if !commits
+            .iter()
+            .any(|commit| matches!(commit, Commit::Transaction(_)))
+        {
+            return Ok(None);
+        }
+        let raw = this.repository.get_raw();
+        let head = raw.read().await.get_head_state().await?;
+        raw.write().await.checkout_detach(tip).await?;
+        let result = this.repository.create_agenda(author, false).await;
+        {
+            let mut raw = raw.write().await;
+            raw.checkout_clean().await?;
+            match head {
+                HeadState::Attached(branch, _) => raw.checkout(branch).await?,
+                HeadState::Detached(commit_hash) => raw.checkout_detach(commit_hash).await?,
+            }
+        }
+        let (agenda, agenda_commit) = result?;
+        if vote {
+            self.vote(agenda_commit).await?;
+        }
+        Ok(Some((agenda, agenda_commit)))
+    }
+
+    /// Creates a reserved-diff transaction on top of the HEAD that adds the given member.
+    ///
+    /// The new member is appended to the consensus leader order.
+    pub async fn propose_add_member(
+        &mut self,
+        member: Member,
+    ) -> Result<(Transaction, CommitHash)> {
+        let name = member.name.clone();
+        self.propose_reserved_state_change(format!("add member: {name}"), |reserved_state| {
+            reserved_state
+                .consensus_leader_order
+                .push(member.name.clone());
+            reserved_state.members.push(member);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Creates a reserved-diff transaction on top of the HEAD that expels the given member.
+    ///
+    /// The member is removed from the consensus leader order, but stays in the members.
+    pub async fn propose_expel_member(
+        &mut self,
+        name: MemberName,
+    ) -> Result<(Transaction, CommitHash)> {
+        self.propose_reserved_state_change(format!("expel member: {name}"), |reserved_state| {
+            let member = find_active_member(reserved_state, &name)?;
+            member.expelled = true;
+            reserved_state
+                .consensus_leader_order
+                .retain(|leader| *leader != name);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Creates a reserved-diff transaction on top of the HEAD
+    /// that changes the voting powers of the given member.
+    pub async fn propose_change_voting_power(
+        &mut self,
+        name: MemberName,
+        governance_voting_power: VotingPower,
+        consensus_voting_power: VotingPower,
+    ) -> Result<(Transaction, CommitHash)> {
+        self.propose_reserved_state_change(
+            format!("change voting power: {name}"),
+            |reserved_state| {
+                let member = find_active_member(reserved_state, &name)?;
+                member.governance_voting_power = governance_voting_power;
+                member.consensus_voting_power = consensus_voting_power;
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    /// Applies the change to the reserved state of the HEAD,
+    /// and creates a reserved-diff transaction of it if the new reserved state is valid.
+    ///
+    /// Nothing is committed if the change or the new reserved state is invalid.
+    async fn propose_reserved_state_change(
+        &mut self,
+        head: String,
+        change: impl FnOnce(&mut ReservedState) -> Result<()>,
+    ) -> Result<(Transaction, CommitHash)> {
+        let this = self.inner.as_mut().unwrap();
+        let author = this
+            .member_name
+            .clone()
+            .ok_or_else(|| eyre!("this node is not a member"))?;
+        let reserved_state = this
+            .repository
+            .get_raw()
+            .read()
+            .await
+            .read_reserved_state()
+            .await?;
+        let mut new_reserved_state = reserved_state.clone();
+        change(&mut new_reserved_state)?;
+        // The same check that `CommitSequenceVerifier` runs on the reserved-diff transaction.
+        let last_header = this.repository.read_last_finalization_info().await?.header;
+        verify::CommitSequenceVerifier::new(last_header, reserved_state.clone())?
+            .verify_reserved_state(&new_reserved_state)?;
+        let mut transaction = Transaction {
+            author,
+            timestamp: get_timestamp(),
+            head,
+            body: String::new(),
+            diff: Diff::Reserved(Box::new(new_reserved_state), reserved_state.to_hash256()),
+            author_signature: None,
+        };
+        transaction.sign(&this.auth.private_key)?;
+        let commit_hash = this
+            .repository
+            .create_transaction(transaction.clone())
+            .await?;
+        Ok((transaction, commit_hash))
+    }
+
+    /// Returns the valid agendas that are neither governance-approved nor voted
+    /// (or vetoed) by this node yet.
+    pub async fn pending_votes(&self) -> Result<Vec<(CommitHash, Hash256)>> {
+        let this = self.inner.as_ref().unwrap();
+        let approved_agendas = read_approved_agenda_hashes(&this.repository).await?;
+        let status = this.governance.read().await?;
+        let public_key = this.auth.private_key.public_key();
+        Ok(this
+            .repository
+            .read_agendas()
+            .await?
+            .into_iter()
+            .filter(|(_, agenda_hash)| !approved_agendas.contains(agenda_hash))
+            .filter(|(_, agenda_hash)| {
+                ![&status.votes, &status.vetoes].iter().any(|tally| {
+                    tally
+                        .get(agenda_hash)
+                        .is_some_and(|voters| voters.contains_key(&public_key))
+                })
+            })
+            .collect())
+    }
+
+    /// Checks the finalization reported by a peer against the local chain.
+    ///
+    /// Fails with `ForkDetected` if the peer carries a valid finalization proof
+    /// for a different block at a height that this node has already finalized.
+    /// A peer ahead of this node is ignored here; it is received by `update()`.
+    pub async fn reconcile(&self, peer_finalization_info: &FinalizationInfo) -> Result<()> {
+        let this = self.inner.as_ref().unwrap();
+        let height = peer_finalization_info.header.height;
+        if height
+            > this
+                .repository
+                .read_last_finalization_info()
+                .await?
+                .header
+                .height
+        {
+            return Ok(());
+        }
+        let local = this.repository.read_finalization_info(height).await?;
+        let peer_header = &peer_finalization_info.header;
+        if local.header == *peer_header {
+            return Ok(());
+        }
+        if height == 0 {
+            if peer_header.validator_set != local.header.validator_set {
+                return Err(eyre!("the peer has a different genesis validator set"));
+            }
+        } else {
+            let previous = this.repository.read_finalization_info(height - 1).await?;
+            verify::verify_header_to_header(&previous.header, peer_header)
+                .map_err(|e| eyre!("the peer's block header is invalid: {e}"))?;
+        }
+        verify::verify_finalization_proof(peer_header, &peer_finalization_info.proof)
+            .map_err(|e| eyre!("the peer's finalization proof is invalid: {e}"))?;
+
+        let local_signers = local
+            .proof
+            .signatures
+            .iter()
+            .map(|signature| signature.signer().clone())
+            .collect::<BTreeSet<_>>();
+        let equivocators = peer_finalization_info
+            .proof
+            .signatures
+            .iter()
+            .map(|signature| signature.signer().clone())
+            .filter(|signer| local_signers.contains(signer))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        Err(ForkDetected {
+            height,
+            local_block_hash: local.header.to_hash256(),
+            peer_block_hash: peer_header.to_hash256(),
+            equivocators,
+        }
+        .into())
+    }
+
+    /// Returns the status of the consensus in progress.
+    pub async fn get_consensus_status(&self) -> Result<ConsensusStatus> {
+        let this = self.inner.as_ref().unwrap();
+        Ok(ConsensusStatus {
+            height: this.consensus.get_block_header().await?.height + 1,
+            round: this.consensus.get_round().await?,
+            vetoed_rounds: this.consensus.get_vetoed_rounds().await?,
+            vetoed_blocks: this.consensus.get_vetoed_block_hashes().await?,
+        })
+    }
+
+    /// Vetoes the current round, broadcasting the resulting consensus messages right away.
+    pub async fn veto_round(&mut self) -> Result<VetoReceipt> {
+        // Catch up with the timeouts (and the start of the height) so that the round is current.
+        self.progress_for_consensus().await?;
+        let this = self.inner.as_mut().unwrap();
+        let round = this.consensus.get_round().await?;
+        let messages_before = this
+            .consensus
+            .get_dms()
+            .read()
+            .await
+            .read_messages()
+            .await?
+            .len();
+        this.consensus.veto_round(round, get_timestamp()).await?;
+        self.progress_for_consensus().await?;
+        self.broadcast_services(BroadcastServices {
+            consensus: true,
+            ..Default::default()
+        })
+        .await?;
+        let this = self.inner.as_ref().unwrap();
+        let messages_after = this
+            .consensus
+            .get_dms()
+            .read()
+            .await
+            .read_messages()
+            .await?
+            .len();
+        Ok(VetoReceipt {
+            target: VetoTarget::Round(round),
+            round: this.consensus.get_round().await?,
+            broadcasted: messages_after > messages_before,
+        })
     }
 
-    /// Vetoes the given block.
-    pub async fn veto_block(&mut self, _block_commit: CommitHash) -> Result<()> {
-        unimplemented!()
+    /// Vetoes the given block, tagging it in the repository.
+    ///
+    /// Nothing is broadcasted; the veto only affects the votes of this node.
+    pub async fn veto_block(&mut self, block_commit: CommitHash) -> Result<VetoReceipt> {
+        let this = self.inner.as_mut().unwrap();
+        let block_hash = this
+            .repository
+            .read_blocks()
+            .await?
+            .into_iter()
+            .find(|(commit_hash, _)| *commit_hash == block_commit)
+            .ok_or_else(|| eyre!("given commit is not a block in consensus: {}", block_commit))?
+            .1;
+        this.repository.veto(block_commit).await?;
+        this.consensus.veto_block(block_hash).await?;
+        Ok(VetoReceipt {
+            target: VetoTarget::Block(block_hash),
+            round: this.consensus.get_round().await?,
+            broadcasted: false,
+        })
     }
 
     /// Shows information about the given commit.
@@ -191,22 +758,132 @@ impl Client {
     /// For an agenda, show the governance status.
     /// For a block, show the consensus status projected on this block.
     /// For an extra-agenda transaction and a chat log, TODO.
-    pub async fn show(&self, _commit_hash: CommitHash) -> Result<CommitInfo> {
-        todo!()
+    pub async fn show(&self, commit_hash: CommitHash) -> Result<CommitInfo> {
+        let this = self.inner.as_ref().unwrap();
+        let semantic_commit = this
+            .repository
+            .get_raw()
+            .read()
+            .await
+            .read_semantic_commit(commit_hash)
+            .await?;
+        let commit = match format::from_semantic_commit(semantic_commit.clone()) {
+            Ok(commit) => commit,
+            Err(e) => {
+                // It is not a commit of the history but the one of the `fp` branch.
+                if let Ok(fp) = format::fp_from_semantic_commit(semantic_commit.clone()) {
+                    let finalized_block_hash = this
+                        .repository
+                        .read_finalization_info(fp.height)
+                        .await?
+                        .header
+                        .to_hash256();
+                    return Ok(CommitInfo::FinalizationProof {
+                        semantic_commit,
+                        finalized_block_hash,
+                        round: fp.proof.round,
+                        signer_count: fp.proof.signatures.len(),
+                        proof: fp.proof,
+                    });
+                }
+                return Ok(CommitInfo::Unknown {
+                    semantic_commit,
+                    msg: e.to_string(),
+                });
+            }
+        };
+        let info = match commit {
+            Commit::Block(block_header) => {
+                let last_header = this.repository.read_last_finalization_info().await?.header;
+                let consensus_status = if block_header.height <= last_header.height {
+                    let fi = this
+                        .repository
+                        .read_finalization_info(block_header.height)
+                        .await?;
+                    // Another block of a finalized height was never finalized.
+                    (fi.commit_hash == commit_hash).then_some(BlockConsensusStatus::Finalized {
+                        round: fi.proof.round,
+                        signers: fi.proof.signatures.len(),
+                    })
+                } else if block_header.height == last_header.height + 1 {
+                    Some(BlockConsensusStatus::InProgress(
+                        this.consensus
+                            .get_block_status(block_header.to_hash256())
+                            .await?,
+                    ))
+                } else {
+                    None
+                };
+                CommitInfo::Block {
+                    semantic_commit,
+                    block_header,
+                    consensus_status,
+                }
+            }
+            Commit::Agenda(agenda) => {
+                let reserved_state = this
+                    .repository
+                    .read_last_finalization_info()
+                    .await?
+                    .reserved_state;
+                let votes = this.governance.read().await?.votes;
+                let voters = votes
+                    .get(&agenda.to_hash256())
+                    .into_iter()
+                    .flat_map(|votes| votes.keys())
+                    .filter_map(|voter| reserved_state.query_name(voter))
+                    .map(|name| (name, None))
+                    .collect();
+                CommitInfo::Agenda {
+                    semantic_commit,
+                    agenda,
+                    voters,
+                }
+            }
+            Commit::AgendaProof(agenda_proof) => CommitInfo::AgendaProof {
+                semantic_commit,
+                agenda_proof,
+            },
+            Commit::Transaction(transaction) => CommitInfo::Transaction {
+                semantic_commit,
+                transaction,
+            },
+            commit => CommitInfo::Unknown {
+                semantic_commit,
+                msg: format!("showing a {:?} commit is not supported yet", commit.kind()),
+            },
+        };
+        Ok(info)
     }
 
+    /// Serves the peer, governance, consensus and repository servers.
+    ///
+    /// The returned task finishes with an error as soon as any of the servers fails.
+    /// Fails immediately if the server config is invalid (e.g., colliding ports).
     pub async fn serve(
         self,
         config: ServerConfig,
         git_hook_verifier: simperby_repository::server::PushVerifier,
     ) -> Result<tokio::task::JoinHandle<Result<()>>> {
+        self.serve_with_shutdown(config, git_hook_verifier, std::future::pending())
+            .await
+    }
+
+    /// Same as `serve()`, but stops all the servers once `shutdown` completes.
+    pub async fn serve_with_shutdown(
+        self,
+        config: ServerConfig,
+        git_hook_verifier: simperby_repository::server::PushVerifier,
+        shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+    ) -> Result<tokio::task::JoinHandle<Result<()>>> {
+        config.validate()?;
         let this = self.inner.unwrap();
 
         // Serve peers
         let peers = Arc::new(RwLock::new(this.peers));
         let port_map = vec![
             (
-                simperby_network::keys::port_key_dms::<simperby_governance::Vote>(),
+                simperby_network::keys::port_key_dms::<simperby_governance::GovernanceMessage>(),
                 config.governance_port,
             ),
             (
@@ -219,22 +896,26 @@ impl Client {
         .collect();
         let network_config = ServerNetworkConfig {
             port: config.peers_port,
+            rate_limit: None,
         };
-        let t0 = async move { Peers::serve(peers, port_map, network_config).await.unwrap() };
+        let address = SocketAddr::new(config.address, config.peers_port);
+        let t0 = async move { Peers::serve(peers, address, port_map, network_config).await };
 
         // Serve governance
         let network_config = ServerNetworkConfig {
             port: config.governance_port,
+            rate_limit: None,
         };
         let dms = this.governance.get_dms();
-        let t1 = async move { Dms::serve(dms, network_config).await.unwrap() };
+        let t1 = async move { Dms::serve(dms, network_config).await };
 
         // Serve consensus
         let network_config = ServerNetworkConfig {
             port: config.consensus_port,
+            rate_limit: None,
         };
         let dms = this.consensus.get_dms();
-        let t2 = async move { Dms::serve(dms, network_config).await.unwrap() };
+        let t2 = async move { Dms::serve(dms, network_config).await };
 
         // Serve repository
         let t3 = async move {
@@ -243,78 +924,289 @@ impl Client {
                 config.repository_port,
                 git_hook_verifier,
             )
-            .await;
+            .await?;
             std::future::pending::<()>().await;
+            Ok(())
         };
 
+        // Aborts the servers when the serving task finishes or is aborted.
+        struct AbortOnDrop(Vec<tokio::task::JoinHandle<Result<()>>>);
+        impl Drop for AbortOnDrop {
+            fn drop(&mut self) {
+                for task in self.0.iter() {
+                    task.abort();
+                }
+            }
+        }
+
+        let names = ["peers", "governance", "consensus", "repository"];
+        let mut tasks = AbortOnDrop(vec![
+            tokio::spawn(t0),
+            tokio::spawn(t1),
+            tokio::spawn(t2),
+            tokio::spawn(t3),
+        ]);
+        // The node stays locked while being served.
+        let node_lock = this.node_lock;
         Ok(tokio::spawn(async move {
-            futures::future::join4(t0, t1, t2, t3).await;
-            Ok(())
+            let _node_lock = node_lock;
+            tokio::select! {
+                (result, index, _) = futures::future::select_all(tasks.0.iter_mut()) => {
+                    let name = names[index];
+                    match result {
+                        Ok(Ok(())) => Err(eyre!("the {name} server stopped unexpectedly")),
+                        Ok(Err(e)) => Err(e.wrap_err(format!("the {name} server failed"))),
+                        Err(e) => Err(eyre!("the {name} server panicked: {e}")),
+                    }
+                }
+                _ = shutdown => Ok(()),
+            }
         }))
     }
 
     pub async fn update(&mut self) -> Result<()> {
+        self.update_windowed(None).await
+    }
+
+    /// Performs `update()`, finalizing at most `max_heights` heights if given.
+    async fn update_windowed(&mut self, max_heights: Option<usize>) -> Result<()> {
+        self.add_remote_repositories().await?;
+        // The furthest-ahead peers are synced first, so that they take the new heights.
+        let peers_by_height = self
+            .get_peer_heights()
+            .await?
+            .into_iter()
+            .map(|(peer, _)| peer.name)
+            .collect::<Vec<_>>();
         let this = self.inner.as_mut().unwrap();
         let network_config = ClientNetworkConfig {
             peers: this.peers.list_peers().await?,
         };
         Dms::fetch(this.governance.get_dms(), &network_config).await?;
-        Dms::fetch(this.consensus.get_dms(), &network_config).await?;
+        // The peers serve the consensus messages only to the validators.
+        if this.role == NodeRole::Validator {
+            Dms::fetch(this.consensus.get_dms(), &network_config).await?;
+        }
         this.repository
             .get_raw()
             .write()
             .await
             .fetch_all(true)
             .await?;
-        this.repository.sync_all().await?;
+        let last_finalization_info = this.repository.read_last_finalization_info().await?;
+        // The branches of mirrors are verified by `sync()` just like the ones of peers,
+        // so a mirror can't move `finalized` without a valid finalization proof.
+        let mirror_strategy = this.config.mirror_strategy;
+        let remote_rank = |remote: &str| {
+            let is_mirror = remote.starts_with(MIRROR_REMOTE_PREFIX);
+            let preference = match mirror_strategy {
+                MirrorStrategy::PreferPeers => is_mirror as usize,
+                MirrorStrategy::PreferMirrors => !is_mirror as usize,
+            };
+            // The mirrors and the peers without a known height come after the ranked peers.
+            let position = peers_by_height
+                .iter()
+                .position(|name| name == remote)
+                .unwrap_or(peers_by_height.len());
+            preference * (peers_by_height.len() + 1) + position
+        };
+        if let Some(max_heights) = max_heights {
+            this.repository
+                .sync_all_windowed(remote_rank, max_heights)
+                .await?;
+        } else {
+            this.repository.sync_all_ranked(remote_rank).await?;
+        }
+        let new_last_header = this.repository.read_last_finalization_info().await?.header;
+        if new_last_header != last_finalization_info.header {
+            log_event(NodeEvent::HeightAdvanced {
+                height: new_last_header.height,
+                block_hash: new_last_header.to_hash256(),
+            });
+            let finalized =
+                read_finalized_since(&this.repository, last_finalization_info.commit_hash).await?;
+            if let Some((header, proof)) = finalized.last() {
+                storage::write_last_finalization(
+                    &this.path,
+                    &Finalization {
+                        block_hash: header.to_hash256(),
+                        header: header.clone(),
+                        timestamp: get_timestamp(),
+                        proof: proof.clone(),
+                    },
+                )
+                .await?;
+            }
+            // The consensus and the governance are still at the previous height.
+            self.reopen().await?;
+            for (header, proof) in finalized {
+                for callback in self.finalization_callbacks.iter_mut() {
+                    callback(&header, &proof);
+                }
+            }
+        }
+        let this = self.inner.as_mut().unwrap();
 
         let agendas = this.repository.read_agendas().await?;
-        for (_, agenda_hash) in agendas {
+        for (agenda_commit, agenda_hash) in agendas {
+            let agenda_type = this.repository.read_agenda_type(agenda_commit).await?;
             this.governance
-                .register_verified_agenda_hash(agenda_hash)
+                .register_verified_agenda_hash(agenda_hash, agenda_type)
                 .await?;
         }
 
         // Update governance
         this.governance.update().await?;
+        // An agenda stays eligible after its approval, which must not be repeated.
+        let approved_agendas = read_approved_agenda_hashes(&this.repository).await?;
         for (agenda_hash, agenda_proof) in this.governance.get_eligible_agendas().await? {
+            if approved_agendas.contains(&agenda_hash) {
+                continue;
+            }
             this.repository
                 .approve(&agenda_hash, agenda_proof.proof, get_timestamp())
                 .await?;
+            log_event(NodeEvent::AgendaApproved {
+                height: new_last_header.height + 1,
+                agenda_hash,
+            });
         }
 
         // Update consensus
         this.consensus.update().await?;
-        for (_, block_hash) in this.repository.read_blocks().await? {
-            this.consensus
-                .register_verified_block_hash(block_hash)
-                .await?;
+        for (commit_hash, block_hash) in this.repository.read_blocks().await? {
+            // A block verified in an earlier round keeps its verdict.
+            if this
+                .consensus
+                .get_block_verdict(block_hash)
+                .await?
+                .is_none()
+            {
+                this.consensus
+                    .register_verified_block(
+                        read_block_header(&this.repository, commit_hash).await?,
+                    )
+                    .await?;
+            }
         }
+        // Only the leader of the current round gets its proposal candidate set.
+        this.consensus
+            .select_proposal_candidate(get_timestamp())
+            .await?;
+        Ok(())
+    }
+
+    /// Repeats `update()` until the last finalized block stops advancing
+    /// or `max_heights` heights have been advanced, returning the new last finalized height.
+    ///
+    /// Each iteration verifies and finalizes a window of at most `Config::catch_up_window`
+    /// heights (and never beyond `max_heights`), so that a long history is synced
+    /// window by window with bounded memory. Without the window, a single iteration takes
+    /// as many heights as the peers serve.
+    ///
+    /// After every window, the progress is logged. As in `update()`, the client is reopened
+    /// for the new height and the finalization callbacks are invoked for every finalized block.
+    pub async fn catch_up(&mut self, max_heights: Option<usize>) -> Result<BlockHeight> {
+        let start_height = self.last_finalized_height().await?;
+        let mut height = start_height;
+        while max_heights.map_or(true, |max| height - start_height < max as BlockHeight) {
+            let window = self.inner.as_ref().unwrap().config.catch_up_window;
+            let remaining = max_heights.map(|max| max - (height - start_height) as usize);
+            self.update_windowed(match (window, remaining) {
+                (Some(window), Some(remaining)) => Some(window.min(remaining)),
+                (window, remaining) => window.or(remaining),
+            })
+            .await?;
+            let new_height = self.last_finalized_height().await?;
+            if new_height == height {
+                break;
+            }
+            log_event(NodeEvent::CatchUpWindowFinalized {
+                from: height,
+                to: new_height,
+            });
+            height = new_height;
+        }
+        Ok(height)
+    }
+
+    async fn last_finalized_height(&self) -> Result<BlockHeight> {
+        let this = self.inner.as_ref().unwrap();
+        Ok(this
+            .repository
+            .read_last_finalization_info()
+            .await?
+            .header
+            .height)
+    }
+
+    /// Reopens the client for the height of the last finalized block,
+    /// keeping the registered finalization callbacks.
+    ///
+    /// This node is identified in the new reserved state before the client is closed,
+    /// so that it is left open for the previous height if that fails.
+    /// The peers are verified again against the new reserved state.
+    async fn reopen(&mut self) -> Result<()> {
+        let this = self.inner.as_ref().unwrap();
+        let path = this.path.clone();
+        let config = this.config.clone();
+        let auth = this.auth.clone();
+        let node_lock = Arc::clone(&this.node_lock);
+        let raw = RawRepository::open(&path).await?;
+        let lfi = interpret::read_last_finalization_info(&raw).await?;
+        let identity = Self::identify(&lfi.reserved_state, &config, &auth)?;
+        // The storages can be opened only after the previous client releases them.
+        self.inner = None;
+        let inner =
+            Self::open_inner(&path, config, auth, node_lock, raw, lfi.clone(), identity).await?;
+        let this = self.inner.insert(inner);
+        this.peers.update_block(lfi).await?;
         Ok(())
     }
 
     pub async fn broadcast(&mut self) -> Result<()> {
+        self.broadcast_services(BroadcastServices::all()).await
+    }
+
+    /// Flushes and broadcasts only the given services,
+    /// leaving the others untouched until the next broadcast of them.
+    ///
+    /// An observer has no consensus messages to broadcast, so it skips the consensus.
+    pub async fn broadcast_services(&mut self, services: BroadcastServices) -> Result<()> {
         let this = self.inner.as_mut().unwrap();
         let network_config = ClientNetworkConfig {
             peers: this.peers.list_peers().await?,
         };
-        this.governance.flush().await?;
-        Dms::broadcast(this.governance.get_dms(), &network_config).await?;
-        this.consensus.flush().await?;
-        Dms::broadcast(this.consensus.get_dms(), &network_config).await?;
-        this.repository.broadcast().await?;
+        if services.governance {
+            this.governance.flush().await?;
+            Dms::broadcast(this.governance.get_dms(), &network_config).await?;
+        }
+        if services.consensus && this.role == NodeRole::Validator {
+            this.consensus.flush().await?;
+            Dms::broadcast(this.consensus.get_dms(), &network_config).await?;
+        }
+        if services.repository {
+            this.repository.broadcast().await?;
+        }
         Ok(())
     }
 
-    pub async fn add_peer(&mut self, name: MemberName, address: SocketAddrV4) -> Result<()> {
+    /// Adds a peer, which must be serving at the given address
+    /// to announce itself with a signature of the member.
+    pub async fn add_peer(&mut self, name: MemberName, address: SocketAddr) -> Result<()> {
         let this = self.inner.as_mut().unwrap();
-        this.peers.add_peer(name, address).await?;
+        let announcement = Peers::fetch_announcement(address).await?;
+        this.peers
+            .add_peer(name.clone(), address, announcement)
+            .await?;
+        log_event(NodeEvent::PeerAdded { name, address });
         Ok(())
     }
 
     pub async fn remove_peer(&mut self, name: MemberName) -> Result<()> {
         let this = self.inner.as_mut().unwrap();
-        this.peers.remove_peer(name).await?;
+        this.peers.remove_peer(name.clone()).await?;
+        log_event(NodeEvent::PeerRemoved { name });
         Ok(())
     }
 
@@ -330,16 +1222,26 @@ impl Client {
         Ok(())
     }
 
-    /// Adds remote repositories according to current peer information.
+    /// Adds remote repositories according to current peer information and the mirrors in the config.
     async fn add_remote_repositories(&mut self) -> Result<()> {
         let this = self.inner.as_mut().unwrap();
+        for (i, url) in this.config.public_repo_url.iter().enumerate() {
+            // TODO: skip only "already exists" error
+            let _ = this
+                .repository
+                .get_raw()
+                .write()
+                .await
+                .add_remote(format!("{MIRROR_REMOTE_PREFIX}{i}"), url.clone())
+                .await;
+        }
         for peer in this.peers.list_peers().await? {
-            let port = if let Some(p) = peer.ports.get("repository") {
-                p
+            let address = if let Some(address) = peer.service_address("repository") {
+                address
             } else {
                 continue;
             };
-            let url = format!("git://{}:{port}/", peer.address.ip());
+            let url = format!("git://{address}/");
             // TODO: skip only "already exists" error
             let _ = this
                 .repository
@@ -360,4 +1262,107 @@ impl Client {
         let result = Dms::get_peer_status(this.governance.get_dms(), &network_config).await?;
         Ok(result)
     }
+
+    /// Returns the last finalized height that each peer advertises, the furthest-ahead peer first.
+    ///
+    /// `update()` and `catch_up()` sync the branches of the peers in this order.
+    ///
+    /// An advertised block at or below the height of this node is verified against
+    /// the finalized block of the repository at that height, and the peer is left out
+    /// if they differ. The peers that can't be reached are left out as well.
+    pub async fn get_peer_heights(&self) -> Result<Vec<(Peer, BlockHeight)>> {
+        let this = self.inner.as_ref().unwrap();
+        let last_height = this
+            .repository
+            .read_last_finalization_info()
+            .await?
+            .header
+            .height;
+        let mut heights = Vec::new();
+        for (peer, header) in this.peers.query_last_finalized_headers().await? {
+            if header.height <= last_height
+                && this
+                    .repository
+                    .read_finalization_info(header.height)
+                    .await?
+                    .header
+                    != header
+            {
+                log::warn!(
+                    "peer {} advertises a block at height {} that is not finalized here",
+                    peer.name,
+                    header.height
+                );
+                continue;
+            }
+            heights.push((peer, header.height));
+        }
+        heights.sort_by_key(|(_, height)| std::cmp::Reverse(*height));
+        Ok(heights)
+    }
+}
+
+/// Finds the member of the given name who is not expelled.
+fn find_active_member<'a>(
+    reserved_state: &'a mut ReservedState,
+    name: &MemberName,
+) -> Result<&'a mut Member> {
+    reserved_state
+        .members
+        .iter_mut()
+        .find(|member| member.name == *name && !member.expelled)
+        .ok_or_else(|| eyre!("no such active member: {}", name))
+}
+
+/// Returns the hashes of the agendas that already have an agenda-proof commit.
+async fn read_approved_agenda_hashes(
+    repository: &DistributedRepository,
+) -> Result<BTreeSet<Hash256>> {
+    let mut approved_agendas = BTreeSet::new();
+    for (commit_hash, _) in repository.read_governance_approved_agendas().await? {
+        if let Commit::AgendaProof(agenda_proof) = repository.read_commit(commit_hash).await? {
+            approved_agendas.insert(agenda_proof.agenda_hash);
+        }
+    }
+    Ok(approved_agendas)
+}
+
+/// Reads the header of the block at the given commit.
+async fn read_block_header(
+    repository: &DistributedRepository,
+    commit_hash: CommitHash,
+) -> Result<BlockHeader> {
+    match repository.read_commit(commit_hash).await? {
+        Commit::Block(block_header) => Ok(block_header),
+        _ => Err(eyre!("{commit_hash} is not a block commit")),
+    }
+}
+
+/// Reads the headers and the finalization proofs of the blocks finalized
+/// after the block at `last_commit_hash`, in the order of their heights.
+async fn read_finalized_since(
+    repository: &DistributedRepository,
+    last_commit_hash: CommitHash,
+) -> Result<Vec<(BlockHeader, FinalizationProof)>> {
+    let lfi = repository.read_last_finalization_info().await?;
+    let commits = repository
+        .get_raw()
+        .read()
+        .await
+        .query_commit_path(last_commit_hash, lfi.commit_hash)
+        .await?;
+    let mut headers = Vec::new();
+    for commit_hash in commits {
+        if let Commit::Block(header) = repository.read_commit(commit_hash).await? {
+            headers.push(header);
+        }
+    }
+    // A block is finalized by the proof in the next block, and the last one by the fp branch.
+    let proofs = headers
+        .iter()
+        .skip(1)
+        .map(|header| header.prev_block_finalization_proof.clone())
+        .chain(std::iter::once(lfi.proof))
+        .collect::<Vec<_>>();
+    Ok(headers.into_iter().zip(proofs).collect())
 }
@@ -1,4 +1,5 @@
 use crate::peers::Peers;
+use fs2::FileExt;
 use simperby_network::keys;
 use tokio::io::AsyncWriteExt;
 
@@ -24,6 +25,57 @@ fn peers_path(path: &str) -> String {
     format!("{path}/.simperby/peers.json")
 }
 
+fn last_finalization_path(path: &str) -> String {
+    format!("{path}/.simperby/last_finalization.json")
+}
+
+fn node_lock_path(path: &str) -> String {
+    format!("{path}/.simperby/lock")
+}
+
+/// An exclusive lock on the whole node directory, released on drop.
+pub(crate) struct NodeLock(std::fs::File);
+
+impl Drop for NodeLock {
+    fn drop(&mut self) {
+        // Unlock right away (not in a blocking task) so that the node can be reopened at once.
+        if let Err(e) = self.0.unlock() {
+            log::error!("failed to unlock the node: {}", e);
+        }
+    }
+}
+
+/// Locks the node directory, failing with `NodeAlreadyOpen` if another instance holds it.
+pub(crate) fn lock_node(path: &str) -> Result<NodeLock> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(node_lock_path(path))?;
+    if file.try_lock_exclusive().is_err() {
+        return Err(NodeAlreadyOpen {
+            path: path.to_owned(),
+        }
+        .into());
+    }
+    Ok(NodeLock(file))
+}
+
+/// Opens the storage of a DMS, clearing it if it was created for another DMS key
+/// (i.e., for a height before the last finalized one).
+///
+/// Returns whether the storage has been cleared.
+async fn open_dms_storage<M: DmsMessage>(path: &str, dms_key: &str) -> Result<(StorageImpl, bool)> {
+    let mut storage = StorageImpl::open(path).await?;
+    let stale = match Dms::<M>::read_stored_config(&storage).await? {
+        Some(config) => config.dms_key != dms_key,
+        None => false,
+    };
+    if stale {
+        storage.remove_all_files().await?;
+    }
+    Ok((storage, stale))
+}
+
 pub(crate) async fn init(path: &str) -> Result<()> {
     let mut repository = DistributedRepository::new(
         None,
@@ -46,32 +98,25 @@ pub(crate) async fn init(path: &str) -> Result<()> {
     let mut file = tokio::fs::File::create(&peers_path(path)).await?;
     file.write_all(serde_spb::to_string(&Vec::<Peer>::new())?.as_bytes())
         .await?;
+    file.flush().await?;
     Ok(())
 }
 
-/// `(Governance DMS, Consensus DMS, ConsensusState, Repository DMS, Peers)`.
+/// `(Governance DMS, Consensus DMS, ConsensusState, Repository DMS, Peers)`
+/// for the height of the given last finalization.
+///
+/// The DMSes of which the key of this node is not a member are opened as read-only.
 pub(crate) async fn open(
     path: &str,
-    _config: types::Config,
     auth: Auth,
+    lfi: &FinalizationInfo,
 ) -> Result<(
-    Dms<simperby_governance::Vote>,
+    Dms<simperby_governance::GovernanceMessage>,
     Dms<simperby_consensus::ConsensusMessage>,
     StorageImpl,
     Dms<simperby_repository::RepositoryMessage>,
     Peers,
 )> {
-    let repository = DistributedRepository::new(
-        None,
-        Arc::new(RwLock::new(RawRepository::open(path).await?)),
-        simperby_repository::Config {
-            long_range_attack_distance: 3,
-        },
-        Some(auth.private_key.clone()),
-    )
-    .await?;
-    repository.check(0).await?;
-    let lfi = repository.read_last_finalization_info().await?;
     let dms_members: Vec<_> = lfi
         .reserved_state
         .get_governance_set()
@@ -79,52 +124,115 @@ pub(crate) async fn open(
         .into_iter()
         .map(|x| x.0)
         .collect();
+    let is_member = dms_members.contains(&auth.private_key.public_key());
 
-    let storage = StorageImpl::open(&governance_dms_path(path)).await?;
-    let governance_dms = Dms::<simperby_governance::Vote>::new(
-        storage,
-        dms::Config {
-            dms_key: keys::dms_key::<simperby_governance::Vote>(&lfi.header),
-            members: dms_members.clone(),
-        },
-        auth.private_key.clone(),
+    let dms_key = keys::dms_key::<simperby_governance::GovernanceMessage>(&lfi.header);
+    let (storage, _) = open_dms_storage::<simperby_governance::GovernanceMessage>(
+        &governance_dms_path(path),
+        &dms_key,
     )
     .await?;
-    let storage = StorageImpl::open(&consensus_dms_path(path)).await?;
-    let consensus_dms = Dms::<simperby_consensus::ConsensusMessage>::new(
-        storage,
-        dms::Config {
-            dms_key: keys::dms_key::<simperby_consensus::ConsensusMessage>(&lfi.header),
-            members: dms_members.clone(),
-        },
-        auth.private_key.clone(),
+    let governance_dms_config = dms::Config {
+        dms_key,
+        members: dms_members.clone(),
+    };
+    // A non-member keeps the governance messages without being able to sign one.
+    let governance_dms = if is_member {
+        Dms::<simperby_governance::GovernanceMessage>::new(
+            storage,
+            governance_dms_config,
+            auth.private_key.clone(),
+        )
+        .await?
+    } else {
+        Dms::<simperby_governance::GovernanceMessage>::new_readonly(storage, governance_dms_config)
+            .await?
+    };
+    // Only the validators sign consensus messages, which may differ from the governance set
+    // if a member has delegated one of the rights.
+    let validators: Vec<_> = lfi
+        .reserved_state
+        .get_validator_set()
+        .map_err(simperby_repository::IntegrityError::new)?
+        .into_iter()
+        .map(|x| x.0)
+        .collect();
+    let dms_key = keys::dms_key::<simperby_consensus::ConsensusMessage>(&lfi.header);
+    let (storage, stale) = open_dms_storage::<simperby_consensus::ConsensusMessage>(
+        &consensus_dms_path(path),
+        &dms_key,
     )
     .await?;
-    let consensus_state = StorageImpl::open(&consensus_state_path(path)).await?;
-    let storage = StorageImpl::open(&repository_dms_path(path)).await?;
-    let repository_dms = Dms::<simperby_repository::RepositoryMessage>::new(
-        storage,
-        dms::Config {
-            dms_key: keys::dms_key::<simperby_repository::RepositoryMessage>(&lfi.header),
-            members: dms_members.clone(),
-        },
-        auth.private_key.clone(),
+    let consensus_dms_config = dms::Config {
+        dms_key,
+        members: validators,
+    };
+    // An observer keeps the consensus messages without being able to sign one.
+    let consensus_dms = if consensus_dms_config
+        .members
+        .contains(&auth.private_key.public_key())
+    {
+        Dms::<simperby_consensus::ConsensusMessage>::new(
+            storage,
+            consensus_dms_config,
+            auth.private_key.clone(),
+        )
+        .await?
+    } else {
+        Dms::<simperby_consensus::ConsensusMessage>::new_readonly(storage, consensus_dms_config)
+            .await?
+    };
+    let mut consensus_state = StorageImpl::open(&consensus_state_path(path)).await?;
+    // The consensus state belongs to the same height as the consensus DMS.
+    if stale {
+        consensus_state.remove_all_files().await?;
+    }
+    let dms_key = keys::dms_key::<simperby_repository::RepositoryMessage>(&lfi.header);
+    let (storage, _) = open_dms_storage::<simperby_repository::RepositoryMessage>(
+        &repository_dms_path(path),
+        &dms_key,
     )
     .await?;
-    let lfi = repository.read_last_finalization_info().await?;
+    let repository_dms_config = dms::Config {
+        dms_key,
+        members: dms_members,
+    };
+    let repository_dms = if is_member {
+        Dms::<simperby_repository::RepositoryMessage>::new(
+            storage,
+            repository_dms_config,
+            auth.private_key.clone(),
+        )
+        .await?
+    } else {
+        Dms::<simperby_repository::RepositoryMessage>::new_readonly(storage, repository_dms_config)
+            .await?
+    };
     Ok((
         governance_dms,
         consensus_dms,
         consensus_state,
         repository_dms,
-        Peers::new(&peers_path(path), lfi, auth.private_key.clone()).await?,
+        Peers::new(&peers_path(path), lfi.clone(), auth.private_key.clone()).await?,
     ))
 }
 
-pub(crate) async fn clear(path: &str) -> Result<()> {
-    let _ = tokio::fs::remove_dir_all(&governance_dms_path(path)).await;
-    let _ = tokio::fs::remove_dir_all(&consensus_dms_path(path)).await;
-    let _ = tokio::fs::remove_dir_all(&consensus_state_path(path)).await;
-    let _ = tokio::fs::remove_file(&peers_path(path)).await;
+/// Reads the finalization recorded by `write_last_finalization()`, if any.
+///
+/// It is kept by `open()`, which resets the storages for the next height.
+pub(crate) async fn read_last_finalization(path: &str) -> Result<Option<Finalization>> {
+    match tokio::fs::read_to_string(&last_finalization_path(path)).await {
+        Ok(x) => Ok(Some(serde_spb::from_str(&x)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(crate) async fn write_last_finalization(path: &str, finalization: &Finalization) -> Result<()> {
+    tokio::fs::write(
+        &last_finalization_path(path),
+        serde_spb::to_string(finalization)?,
+    )
+    .await?;
     Ok(())
 }
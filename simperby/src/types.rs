@@ -2,9 +2,42 @@ use super::*;
 use serde::{Deserialize, Serialize};
 use simperby_repository::raw::SemanticCommit;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ConsensusStatus {
-    // TODO
+    /// The height in consensus.
+    pub height: BlockHeight,
+    /// The current round of this node.
+    pub round: ConsensusRound,
+    /// The rounds that this node has vetoed to skip.
+    pub vetoed_rounds: BTreeSet<ConsensusRound>,
+    /// The hashes of the blocks that this node has vetoed.
+    pub vetoed_blocks: BTreeSet<Hash256>,
+}
+
+/// The result of `Client::progress_for_consensus`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ConsensusProgress {
+    /// The consensus made a progress, in order of occurrence.
+    Progressed(Vec<ProgressResult>),
+    /// Nothing happened, for the given reason.
+    Stalled { reason: StallReason },
+}
+
+/// What a veto was cast against.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum VetoTarget {
+    Round(ConsensusRound),
+    Block(Hash256),
+}
+
+/// The result of `Client::veto_round` or `Client::veto_block`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct VetoReceipt {
+    pub target: VetoTarget,
+    /// The round of this node right after the veto.
+    pub round: ConsensusRound,
+    /// Whether the veto produced a consensus message that was broadcasted to the peers.
+    pub broadcasted: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,12 +50,16 @@ pub enum CommitInfo {
     Block {
         semantic_commit: SemanticCommit,
         block_header: BlockHeader,
-        // TODO: block-specific consensus status
+        /// `None` if the block is neither finalized nor of the height in consensus.
+        consensus_status: Option<BlockConsensusStatus>,
     },
     Agenda {
         semantic_commit: SemanticCommit,
         agenda: Agenda,
-        voters: Vec<(MemberName, Timestamp)>,
+        /// The members who voted for the agenda, with the time of each vote if it is known.
+        ///
+        /// The votes in the governance DMS don't carry when they were cast, so it is `None` for them.
+        voters: Vec<(MemberName, Option<Timestamp>)>,
     },
     AgendaProof {
         semantic_commit: SemanticCommit,
@@ -32,6 +69,15 @@ pub enum CommitInfo {
         semantic_commit: SemanticCommit,
         transaction: Transaction,
     },
+    /// The finalization proof commit on the `fp` branch.
+    FinalizationProof {
+        semantic_commit: SemanticCommit,
+        proof: FinalizationProof,
+        /// The hash of the block that the proof finalizes.
+        finalized_block_hash: Hash256,
+        round: ConsensusRound,
+        signer_count: usize,
+    },
     PreGenesisCommit {
         title: String,
     },
@@ -41,13 +87,141 @@ pub enum CommitInfo {
     }, // TODO
 }
 
+/// The consensus status of a block commit.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum BlockConsensusStatus {
+    /// The block is finalized with a proof of the given round, signed by `signers` validators.
+    Finalized {
+        round: ConsensusRound,
+        signers: usize,
+    },
+    /// The block is a candidate of the height in consensus.
+    InProgress(simperby_consensus::BlockStatus),
+}
+
+/// Two different blocks are finalized at the same height, each with a valid finalization proof.
+///
+/// This must never happen under the BFT assumption, so the node must halt
+/// and wait for the operator's intervention instead of following either of them.
+#[derive(thiserror::Error, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[error(
+    "fork detected at height {height}: {local_block_hash} (local) and {peer_block_hash} (peer) are both finalized"
+)]
+pub struct ForkDetected {
+    pub height: BlockHeight,
+    /// The hash of the block finalized by this node.
+    pub local_block_hash: Hash256,
+    /// The hash of the conflicting block finalized by the peer.
+    pub peer_block_hash: Hash256,
+    /// The validators who signed the finalization proofs of both blocks.
+    pub equivocators: Vec<PublicKey>,
+}
+
+/// Another `Client` instance (of this or another process) holds the node directory.
+///
+/// Only one instance may open a node at a time,
+/// since they would otherwise write to the same repository and storages.
+#[derive(thiserror::Error, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[error("the node at {path} is already open by another instance")]
+pub struct NodeAlreadyOpen {
+    pub path: String,
+}
+
+/// The role of a node in the consensus of the current height.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum NodeRole {
+    /// The key of the node is in the validator set; it proposes and votes for blocks.
+    Validator,
+    /// The node only fetches, relays and verifies the consensus of the validators,
+    /// never signing a consensus message.
+    ///
+    /// This includes a node whose key is not a member at all.
+    Observer,
+}
+
+/// Whose branches are synced first in `Client::update`, between peers and mirrors.
+///
+/// Every branch is verified regardless of where it comes from;
+/// this only decides which one is taken when branches conflict.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MirrorStrategy {
+    #[default]
+    PreferPeers,
+    PreferMirrors,
+}
+
+/// The services to flush and broadcast in `Client::broadcast_services`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BroadcastServices {
+    pub governance: bool,
+    pub consensus: bool,
+    pub repository: bool,
+}
+
+impl BroadcastServices {
+    /// All the services, which is what `Client::broadcast` uses.
+    pub fn all() -> Self {
+        Self {
+            governance: true,
+            consensus: true,
+            repository: true,
+        }
+    }
+}
+
 /// A configuration for a node.
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Config {}
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Config {
+    /// If set, `Client::vote` immediately creates the agenda-proof commit
+    /// once the voted agenda reaches the governance threshold.
+    #[serde(default)]
+    pub auto_approve: bool,
+    /// The URLs of read-only public mirrors of the repository,
+    /// which are added as `public_#` remotes.
+    #[serde(default)]
+    pub public_repo_url: Vec<String>,
+    #[serde(default)]
+    pub mirror_strategy: MirrorStrategy,
+    /// The maximum number of heights that `Client::catch_up` verifies and finalizes at once.
+    ///
+    /// If not set, `Client::catch_up` takes as many heights as the peers serve at once,
+    /// which may load a very long history into memory.
+    #[serde(default)]
+    pub catch_up_window: Option<usize>,
+    /// If set, `Client::open` fails unless the key of this node is a validator
+    /// (and so a member) of the last finalized reserved state, to catch a misconfigured key early.
+    ///
+    /// Otherwise a node with any other key is opened as an observer.
+    #[serde(default)]
+    pub expect_validator: bool,
+}
+
+impl Config {
+    /// Checks that `catch_up_window`, if set, is non-zero,
+    /// and that the mirror URLs are non-empty and distinct,
+    /// since each of them becomes a remote of the repository.
+    pub fn validate(&self) -> Result<()> {
+        if self.catch_up_window == Some(0) {
+            return Err(eyre!("config: catch_up_window must not be zero"));
+        }
+        let mut urls = BTreeSet::new();
+        for url in &self.public_repo_url {
+            if url.trim().is_empty() {
+                return Err(eyre!("config: a public repository URL is empty"));
+            }
+            if !urls.insert(url) {
+                return Err(eyre!("config: duplicate public repository URL {url}"));
+            }
+        }
+        Ok(())
+    }
+}
 
 /// Hosting a server node requires extra configuration.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ServerConfig {
+    /// The IP address at which the peers reach this node, which it announces to them.
+    pub address: std::net::IpAddr,
     pub peers_port: u16,
     pub governance_port: u16,
     pub consensus_port: u16,
@@ -57,7 +231,62 @@ pub struct ServerConfig {
     pub fetch_interval_ms: Option<u64>,
 }
 
+impl ServerConfig {
+    /// Checks that the ports are non-zero and distinct, and that the intervals are non-zero.
+    pub fn validate(&self) -> Result<()> {
+        let ports = [
+            ("peers_port", self.peers_port),
+            ("governance_port", self.governance_port),
+            ("consensus_port", self.consensus_port),
+            ("repository_port", self.repository_port),
+        ];
+        for (i, (name, port)) in ports.iter().enumerate() {
+            if *port == 0 {
+                return Err(eyre!("server config: {name} must not be zero"));
+            }
+            if let Some((other, _)) = ports[..i].iter().find(|(_, other)| other == port) {
+                return Err(eyre!(
+                    "server config: {name} collides with {other} (both {port})"
+                ));
+            }
+        }
+        for (name, interval) in [
+            ("broadcast_interval_ms", self.broadcast_interval_ms),
+            ("fetch_interval_ms", self.fetch_interval_ms),
+        ] {
+            if interval == Some(0) {
+                return Err(eyre!("server config: {name} must not be zero"));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Auth {
     pub private_key: PrivateKey,
+    /// The public key of `private_key`, if it is stored alongside (e.g., in `auth.json`).
+    ///
+    /// It is what the peers see as this node, so it must match the private key.
+    #[serde(default)]
+    pub public_key: Option<PublicKey>,
+}
+
+impl Auth {
+    /// Checks that the private key is a valid secp256k1 key and that the stored public key,
+    /// if any, is the one derived from it.
+    ///
+    /// Otherwise every signature of this node would be attributed to a wrong key.
+    pub fn verify_consistency(&self) -> Result<()> {
+        Signature::sign(Hash256::zero(), &self.private_key)
+            .map_err(|_| eyre!("auth: the private key is not a valid secp256k1 key"))?;
+        let derived = self.private_key.public_key();
+        match &self.public_key {
+            Some(public_key) if *public_key != derived => Err(eyre!(
+                "auth: the public key {public_key} does not match the private key, \
+                 whose public key is {derived}"
+            )),
+            _ => Ok(()),
+        }
+    }
 }
@@ -0,0 +1,254 @@
+//! An in-process cluster of nodes for the tests.
+//!
+//! It lives here rather than in `simperby-test-suite`, since the other crates dev-depend on
+//! the test suite, which would then have to build the whole node stack for their tests.
+//! Add `mod cluster;` to an integration test of this crate to use it.
+
+use eyre::Result;
+use simperby::simperby_repository::server::PushVerifier;
+use simperby::types::{Auth, Config, ServerConfig};
+use simperby::Client;
+use simperby_core::*;
+use simperby_test_suite::*;
+
+/// A cluster of full `Client` nodes running in this process, sharing a genesis.
+///
+/// The nodes are connected to each other through a relay server,
+/// which serves the DMSes and the repository with the key of the last member
+/// (just like the server node of the `simperby` integration tests).
+/// The relay is restarted for every new height so that its DMSes follow the nodes.
+pub struct LocalCluster {
+    nodes: Vec<Client>,
+    member_names: Vec<MemberName>,
+    relay_dir: String,
+    relay_auth: Auth,
+    relay_config: ServerConfig,
+    /// The path of the `simple_git_server` executable verifying the pushes to the relay.
+    push_verifier: String,
+    relay_task: tokio::task::JoinHandle<Result<()>>,
+    relay_height: BlockHeight,
+}
+
+impl LocalCluster {
+    /// Creates `n` nodes sharing a genesis and connects them as peers.
+    pub async fn new(n: usize) -> Result<Self> {
+        let (fi, keys) = test_utils::generate_fi_at(n, simperby_core::utils::get_timestamp());
+        let relay_dir = create_temp_dir();
+        setup_pre_genesis_repository(&relay_dir, fi.reserved_state.clone()).await;
+        Client::genesis(&relay_dir).await?;
+        Client::init(&relay_dir).await?;
+        run_command(format!(
+            "cd {relay_dir} && git config receive.advertisePushOptions true"
+        ))
+        .await;
+        run_command(format!(
+            "cd {relay_dir} && git config sendpack.sideband false"
+        ))
+        .await;
+
+        let relay_name = fi.reserved_state.members[n - 1].name.clone();
+        let relay_config = ServerConfig {
+            address: "127.0.0.1".parse().unwrap(),
+            peers_port: dispense_port(),
+            governance_port: dispense_port(),
+            consensus_port: dispense_port(),
+            repository_port: dispense_port(),
+            broadcast_interval_ms: None,
+            fetch_interval_ms: None,
+        };
+        let mut nodes = Vec::new();
+        for (_, key) in keys.iter() {
+            let dir = create_temp_dir();
+            run_command(format!("cp -a {relay_dir}/. {dir}/")).await;
+            let auth = Auth {
+                private_key: key.clone(),
+                public_key: None,
+            };
+            nodes.push(Client::open(&dir, Config::default(), auth).await?);
+        }
+
+        let relay_auth = Auth {
+            private_key: keys[n - 1].1.clone(),
+            public_key: None,
+        };
+        let push_verifier = simperby::simperby_repository::server::build_simple_git_server();
+        let relay_task =
+            serve_relay(&relay_dir, &relay_auth, &relay_config, &push_verifier).await?;
+        for node in nodes.iter_mut() {
+            node.add_peer(
+                relay_name.clone(),
+                format!("127.0.0.1:{}", relay_config.peers_port).parse()?,
+            )
+            .await?;
+            node.update_peer().await?;
+        }
+        Ok(Self {
+            nodes,
+            member_names: fi
+                .reserved_state
+                .members
+                .iter()
+                .map(|member| member.name.clone())
+                .collect(),
+            relay_dir,
+            relay_auth,
+            relay_config,
+            push_verifier,
+            relay_task,
+            relay_height: 0,
+        })
+    }
+
+    pub fn nodes(&self) -> &[Client] {
+        &self.nodes
+    }
+
+    /// Drives all the nodes by one step of the block production cycle.
+    ///
+    /// The nodes exchange their works and update, and then
+    /// 1. the first node creates an agenda if there is none, or a block on the approved agenda,
+    /// 2. every node votes for the agendas that it hasn't voted for,
+    /// 3. every node that has a block makes a progress for the consensus.
+    ///
+    /// Repeating this produces a block in a few steps.
+    pub async fn step(&mut self) -> Result<()> {
+        self.sync().await?;
+
+        let author = self.member_names[0].clone();
+        let node = &mut self.nodes[0];
+        if node.repository().read_blocks().await?.is_empty() {
+            if !node
+                .repository()
+                .read_governance_approved_agendas()
+                .await?
+                .is_empty()
+            {
+                let public_key = node.auth().private_key.public_key();
+                node.repository_mut().create_block(public_key).await?;
+            } else if node.repository().read_agendas().await?.is_empty() {
+                node.repository_mut().create_agenda(author, false).await?;
+            }
+        }
+        for node in self.nodes.iter_mut() {
+            for (agenda_commit, _) in node.pending_votes().await? {
+                node.vote(agenda_commit).await?;
+            }
+        }
+        self.sync().await?;
+
+        for node in self.nodes.iter_mut() {
+            if !node.repository().read_blocks().await?.is_empty() {
+                node.progress_for_consensus().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the last finalized height that all the nodes have reached.
+    pub async fn finalized_height(&self) -> Result<BlockHeight> {
+        let mut height = BlockHeight::MAX;
+        for node in self.nodes.iter() {
+            height = height.min(node_height(node).await?);
+        }
+        Ok(height)
+    }
+
+    /// Broadcasts the works of every node and then updates every node.
+    async fn sync(&mut self) -> Result<()> {
+        for node in self.nodes.iter_mut() {
+            node.broadcast().await?;
+        }
+        let mut height = 0;
+        for node in self.nodes.iter() {
+            height = height.max(node_height(node).await?);
+        }
+        if height > self.relay_height {
+            self.restart_relay().await?;
+            self.relay_height = height;
+        }
+        // The broadcasts have been received by the relay once they return.
+        for node in self.nodes.iter_mut() {
+            node.update().await?;
+        }
+        Ok(())
+    }
+
+    /// Restarts the relay so that its DMSes are reopened for the last finalized height,
+    /// which the pushes of the nodes have brought to its repository.
+    async fn restart_relay(&mut self) -> Result<()> {
+        self.relay_task.abort();
+        wait_for_relay(&self.relay_config, false).await?;
+        self.relay_task = serve_relay(
+            &self.relay_dir,
+            &self.relay_auth,
+            &self.relay_config,
+            &self.push_verifier,
+        )
+        .await?;
+        // The nodes have been reopened on their finalization, losing the ports of the relay.
+        for node in self.nodes.iter_mut() {
+            node.update_peer().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LocalCluster {
+    fn drop(&mut self) {
+        self.relay_task.abort();
+    }
+}
+
+async fn serve_relay(
+    dir: &str,
+    auth: &Auth,
+    config: &ServerConfig,
+    push_verifier: &str,
+) -> Result<tokio::task::JoinHandle<Result<()>>> {
+    let relay = Client::open(dir, Config::default(), auth.clone()).await?;
+    let task = relay
+        .serve(
+            config.clone(),
+            PushVerifier::VerifierExecutable(push_verifier.to_owned()),
+        )
+        .await?;
+    wait_for_relay(config, true).await?;
+    Ok(task)
+}
+
+/// Polls the ports of the relay until all of them accept connections,
+/// or refuse them if `open` is false.
+async fn wait_for_relay(config: &ServerConfig, open: bool) -> Result<()> {
+    let ports = [
+        config.peers_port,
+        config.governance_port,
+        config.consensus_port,
+        config.repository_port,
+    ];
+    for _ in 0..200 {
+        let mut ready = true;
+        for port in ports {
+            let connected = tokio::net::TcpStream::connect(("127.0.0.1", port))
+                .await
+                .is_ok();
+            ready &= connected == open;
+        }
+        if ready {
+            return Ok(());
+        }
+        sleep_ms(50).await;
+    }
+    Err(eyre::eyre!(
+        "the relay has not {} in 10 seconds",
+        if open { "started" } else { "stopped" }
+    ))
+}
+
+async fn node_height(node: &Client) -> Result<BlockHeight> {
+    Ok(node
+        .repository()
+        .read_last_finalization_info()
+        .await?
+        .header
+        .height)
+}
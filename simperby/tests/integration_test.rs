@@ -1,10 +1,13 @@
+use simperby::simperby_consensus::{ProgressResult, StallReason};
 use simperby::types::{Auth, Config};
 use simperby::*;
 use simperby_core::*;
 use simperby_test_suite::*;
+use std::sync::{Arc, Mutex};
 
 fn generate_server_config() -> ServerConfig {
     ServerConfig {
+        address: "127.0.0.1".parse().unwrap(),
         peers_port: dispense_port(),
         governance_port: dispense_port(),
         consensus_port: dispense_port(),
@@ -25,6 +28,67 @@ async fn sync_each_other(clients: &mut [Client]) {
     sleep_ms(200).await;
 }
 
+/// Signs the finalization of the given block at round 0 with all the given keys.
+fn sign_finalization(block_hash: Hash256, keys: &[(PublicKey, PrivateKey)]) -> FinalizationProof {
+    FinalizationProof {
+        round: 0,
+        signatures: keys
+            .iter()
+            .map(|(_, private_key)| {
+                TypedSignature::sign(
+                    &FinalizationSignTarget {
+                        block_hash,
+                        round: 0,
+                    },
+                    private_key,
+                )
+                .unwrap()
+            })
+            .collect(),
+    }
+}
+
+/// Finalizes `n` blocks on the repository of the given client, without running the consensus.
+///
+/// The client creates the agendas and the blocks, which the given keys approve and finalize.
+/// `HEAD` is left on the last finalized block.
+async fn finalize_blocks(client: &mut Client, keys: &[(PublicKey, PrivateKey)], n: usize) {
+    use simperby_repository::FINALIZED_BRANCH_NAME;
+
+    let author = client.my_member_name().unwrap();
+    let public_key = client.auth().private_key.public_key();
+    let repository = client.repository_mut();
+    let raw = repository.get_raw();
+    for _ in 0..n {
+        let (agenda, _) = repository
+            .create_agenda(author.clone(), false)
+            .await
+            .unwrap();
+        repository
+            .approve(
+                &agenda.to_hash256(),
+                keys.iter()
+                    .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                    .collect(),
+                simperby_core::utils::get_timestamp(),
+            )
+            .await
+            .unwrap();
+        let (block, block_commit) = repository.create_block(public_key.clone()).await.unwrap();
+        repository
+            .finalize(block_commit, sign_finalization(block.to_hash256(), keys))
+            .await
+            .unwrap();
+        let finalized = raw
+            .read()
+            .await
+            .locate_branch(FINALIZED_BRANCH_NAME.to_owned())
+            .await
+            .unwrap();
+        raw.write().await.checkout_detach(finalized).await.unwrap();
+    }
+}
+
 fn build_simple_git_server() -> String {
     let mut cmd = std::process::Command::new("cargo");
     cmd.arg("build");
@@ -48,7 +112,7 @@ fn build_simple_git_server() -> String {
 #[tokio::test]
 async fn normal_1() {
     setup_test();
-    let (fi, keys) = test_utils::generate_fi(4);
+    let (fi, keys) = test_utils::generate_fi_at(4, simperby_core::utils::get_timestamp());
     let server_config = generate_server_config();
 
     // Setup repository and server.
@@ -67,33 +131,37 @@ async fn normal_1() {
     .await;
 
     // Setup clients.
+    let finalizations = Arc::new(Mutex::new(Vec::new()));
     let mut clients = Vec::new();
     for (_, key) in keys.iter().take(3) {
         let dir = create_temp_dir();
         run_command(format!("cp -a {server_dir}/. {dir}/")).await;
         let auth = Auth {
             private_key: key.clone(),
+            public_key: None,
         };
-        let port = server_config.peers_port;
-        let mut client = Client::open(&dir, Config {}, auth).await.unwrap();
-        client
-            .add_peer(
-                fi.reserved_state.members[3].name.clone(),
-                format!("127.0.0.1:{port}").parse().unwrap(),
-            )
-            .await
-            .unwrap();
+        let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+        let finalizations_ = Arc::clone(&finalizations);
+        client.on_finalize(move |header, proof| {
+            finalizations_
+                .lock()
+                .unwrap()
+                .push((header.clone(), proof.clone()))
+        });
         clients.push(client);
     }
 
     // Run server.
     let auth = Auth {
         private_key: keys[3].1.clone(),
+        public_key: None,
     };
     let server_config_ = server_config.clone();
     let server_dir_ = server_dir.clone();
     tokio::spawn(async move {
-        let client = Client::open(&server_dir_, Config {}, auth).await.unwrap();
+        let client = Client::open(&server_dir_, Config::default(), auth)
+            .await
+            .unwrap();
         let task = client
             .serve(
                 server_config_,
@@ -108,15 +176,29 @@ async fn normal_1() {
 
     // Setup peer network.
     sleep_ms(500).await;
+    let port = server_config.peers_port;
     for client in clients.iter_mut() {
+        client
+            .add_peer(
+                fi.reserved_state.members[3].name.clone(),
+                format!("127.0.0.1:{port}").parse().unwrap(),
+            )
+            .await
+            .unwrap();
         client.update_peer().await.unwrap();
     }
 
+    assert!(clients[0]
+        .latest_finalization_proof()
+        .await
+        .unwrap()
+        .is_none());
+
     // Step 1: create an agenda and propagate it.
     log::info!("STEP 1");
     let (_, agenda_commit) = clients[0]
         .repository_mut()
-        .create_agenda(fi.reserved_state.members[0].name.clone())
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
         .await
         .unwrap();
 
@@ -154,6 +236,16 @@ async fn normal_1() {
 
     // Step 3: check the result.
     for client in clients {
+        // The proof exported by the consensus is the one in the `fp` branch.
+        let lfi = client
+            .repository()
+            .read_last_finalization_info()
+            .await
+            .unwrap();
+        let finalization = client.latest_finalization_proof().await.unwrap().unwrap();
+        assert_eq!(finalization.block_hash, lfi.header.to_hash256());
+        assert_eq!(finalization.proof, lfi.proof);
+
         let raw_repo = client.repository().get_raw();
         let raw_repo_ = raw_repo.read().await;
         let finalized = raw_repo_
@@ -167,243 +259,612 @@ async fn normal_1() {
             .title;
         assert_eq!(title, ">block: 1");
     }
-}
 
-async fn remove_state_file(dir: String) {
-    run_command(format!(
-        "cd {dir}/.simperby/governance/dms/ && rm state.json"
-    ))
-    .await;
-    run_command(format!(
-        "cd {dir}/.simperby/consensus/dms/ && rm state.json"
-    ))
-    .await;
-    run_command(format!(
-        "cd {dir}/.simperby/consensus/state/ && rm state.json"
-    ))
-    .await;
-    run_command(format!(
-        "cd {dir}/.simperby/repository/dms/ && rm state.json"
-    ))
-    .await;
+    // Every client must have been notified of the finalization.
+    let finalizations = finalizations.lock().unwrap();
+    assert_eq!(finalizations.len(), 3);
+    for (header, proof) in finalizations.iter() {
+        assert_eq!(header.height, 1);
+        verify::verify_finalization_proof(header, proof).unwrap();
+    }
 }
 
-/// Make two blocks with server participation.
 #[tokio::test]
-async fn normal_2() {
+async fn vote_with_auto_approve() {
     setup_test();
-    let (fi, keys) = test_utils::generate_fi(4);
-    let server_config = generate_server_config();
-
-    // Setup repository and server.
-    let server_dir = create_temp_dir();
-    setup_pre_genesis_repository(&server_dir, fi.reserved_state.clone()).await;
-    Client::genesis(&server_dir).await.unwrap();
-    Client::init(&server_dir).await.unwrap();
-    // Add push configs to server repository.
-    run_command(format!(
-        "cd {server_dir} && git config receive.advertisePushOptions true"
-    ))
-    .await;
-    run_command(format!(
-        "cd {server_dir} && git config sendpack.sideband false"
-    ))
-    .await;
-
-    // Setup clients.
-    let mut clients = Vec::new();
-    for (_, key) in keys.iter() {
-        let dir = create_temp_dir();
-        run_command(format!("cp -a {server_dir}/. {dir}/")).await;
-        let auth = Auth {
-            private_key: key.clone(),
-        };
-        let port = server_config.peers_port;
-        let mut client = Client::open(&dir, Config {}, auth).await.unwrap();
-        client
-            .add_peer(
-                fi.reserved_state.members[3].name.clone(),
-                format!("127.0.0.1:{port}").parse().unwrap(),
-            )
-            .await
-            .unwrap();
-        clients.push(client);
+    // The other members delegate their governance voting power to the first one.
+    let (mut fi, keys) = test_utils::generate_fi(4);
+    let delegatee = fi.reserved_state.members[0].name.clone();
+    for member in fi.reserved_state.members.iter_mut().skip(1) {
+        member.governance_delegatee = Some(delegatee.clone());
     }
-
-    // Run server.
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
     let auth = Auth {
-        private_key: keys[3].1.clone(),
+        private_key: keys[0].1.clone(),
+        public_key: None,
     };
-    let client = Client::open(&server_dir.clone(), Config {}, auth.clone())
+    let config = Config {
+        auto_approve: true,
+        ..Default::default()
+    };
+    let mut client = Client::open(&dir, config, auth).await.unwrap();
+
+    let (_, agenda_commit) = client
+        .repository_mut()
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
         .await
         .unwrap();
-    let server_task =
-        client
-            .serve(
-                server_config.clone(),
-                simperby_repository::server::PushVerifier::VerifierExecutable(
-                    build_simple_git_server(),
-                ),
-            )
-            .await
-            .unwrap();
-
-    // Setup peer network.
-    sleep_ms(500).await;
-    for client in clients.iter_mut() {
-        client.update_peer().await.unwrap();
-    }
+    assert!(client
+        .repository()
+        .read_governance_approved_agendas()
+        .await
+        .unwrap()
+        .is_empty());
 
-    // Make a first block, Step 1 ~ 3.
-    // Step 1: create an agenda and propagate it.
-    log::info!("STEP 1");
-    let (_, agenda_commit) = clients[0]
-        .repository_mut()
-        .create_agenda(fi.reserved_state.members[0].name.clone())
+    // The delegated vote reaches the threshold, which creates the agenda proof.
+    client.vote(agenda_commit).await.unwrap();
+    let approved_agendas = client
+        .repository()
+        .read_governance_approved_agendas()
         .await
         .unwrap();
+    assert_eq!(approved_agendas.len(), 1);
+}
 
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.vote(agenda_commit).await.unwrap();
+#[tokio::test]
+async fn update_approves_agenda_once() {
+    setup_test();
+    // The other members delegate their governance voting power to the first one.
+    let (mut fi, keys) = test_utils::generate_fi(4);
+    let delegatee = fi.reserved_state.members[0].name.clone();
+    for member in fi.reserved_state.members.iter_mut().skip(1) {
+        member.governance_delegatee = Some(delegatee.clone());
     }
-    sync_each_other(&mut clients).await;
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
 
-    // Step 2: create block and run consensus.
-    log::info!("STEP 2");
-    let proposer_public_key = clients[0].auth().private_key.public_key();
-    clients[0]
+    let (_, agenda_commit) = client
         .repository_mut()
-        .create_block(proposer_public_key)
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
         .await
         .unwrap();
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
+    client.vote(agenda_commit).await.unwrap();
+
+    // The agenda stays eligible, but the second update doesn't approve it again.
+    for _ in 0..2 {
+        client.update().await.unwrap();
+        sleep_ms(10).await;
     }
-    sync_each_other(&mut clients).await;
+    let approved_agendas = client
+        .repository()
+        .read_governance_approved_agendas()
+        .await
+        .unwrap();
+    assert_eq!(approved_agendas.len(), 1);
+}
 
-    // Step 3: check the result.
-    log::info!("STEP 3");
-    for client in clients.iter() {
-        let raw_repo = client.repository().get_raw();
-        let raw_repo_ = raw_repo.read().await;
-        let finalized = raw_repo_
-            .locate_branch("finalized".to_owned())
+#[tokio::test]
+async fn pending_votes() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    // Create two agendas on the same base.
+    let raw = client.repository().get_raw();
+    let base = raw.read().await.get_head().await.unwrap();
+    let mut agendas = Vec::new();
+    for member in fi.reserved_state.members.iter().take(2) {
+        raw.write().await.checkout_detach(base).await.unwrap();
+        let (agenda, agenda_commit) = client
+            .repository_mut()
+            .create_agenda(member.name.clone(), false)
             .await
             .unwrap();
-        let title = raw_repo_
-            .read_semantic_commit(finalized)
-            .await
-            .unwrap()
-            .title;
-        assert_eq!(title, ">block: 1");
+        agendas.push((agenda_commit, agenda.to_hash256()));
     }
+    let mut pending_votes = client.pending_votes().await.unwrap();
+    pending_votes.sort();
+    let mut expected = agendas.clone();
+    expected.sort();
+    assert_eq!(pending_votes, expected);
 
-    // Stop and restart the server.
-    server_task.abort();
-    remove_state_file(server_dir.clone()).await;
-    tokio::spawn(async move {
-        let client = Client::open(&server_dir, Config {}, auth).await.unwrap();
-        let task = client
-            .serve(
-                server_config,
-                simperby_repository::server::PushVerifier::VerifierExecutable(
-                    build_simple_git_server(),
-                ),
-            )
-            .await
-            .unwrap();
-        task.await.unwrap().unwrap();
-    });
+    // A single vote out of four doesn't approve the agenda, but it's no longer pending.
+    client.vote(agendas[0].0).await.unwrap();
+    assert_eq!(client.pending_votes().await.unwrap(), vec![agendas[1]]);
+    let CommitInfo::Agenda { voters, .. } = client.show(agendas[0].0).await.unwrap() else {
+        panic!("not an agenda")
+    };
+    assert_eq!(
+        voters,
+        vec![(fi.reserved_state.members[0].name.clone(), None)]
+    );
+}
 
-    // Setup peer network.
-    sleep_ms(500).await;
-    for client in clients.iter_mut() {
-        client.update_peer().await.unwrap();
-    }
+#[tokio::test]
+async fn update_verifies_mirror_branches() {
+    use simperby_repository::{raw::RawCommit, FINALIZED_BRANCH_NAME};
 
-    // Make a second block, Step 4 ~ 6.
-    // Step 4: create an agenda and propagate it.
-    log::info!("STEP 4");
-    let (_, agenda_commit) = clients[1]
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let mirror_dir = create_temp_dir();
+    setup_pre_genesis_repository(&mirror_dir, fi.reserved_state.clone()).await;
+    Client::genesis(&mirror_dir).await.unwrap();
+    Client::init(&mirror_dir).await.unwrap();
+    let dir = create_temp_dir();
+    run_command(format!("cp -a {mirror_dir}/. {dir}/")).await;
+
+    // The mirror serves a valid agenda branch and an invalid branch.
+    let mut mirror = Client::open(
+        &mirror_dir,
+        Config::default(),
+        Auth {
+            private_key: keys[1].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let (agenda, _) = mirror
         .repository_mut()
-        .create_agenda(fi.reserved_state.members[1].name.clone())
+        .create_agenda(fi.reserved_state.members[1].name.clone(), false)
         .await
         .unwrap();
-
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.vote(agenda_commit).await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
-
-    // Step 5: create block and run consensus.
-    log::info!("STEP 5");
-    let proposer_public_key = clients[1].auth().private_key.public_key();
-    clients[1]
-        .repository_mut()
-        .create_block(proposer_public_key)
+    let raw = mirror.repository().get_raw();
+    let mut raw = raw.write().await;
+    let finalized = raw
+        .locate_branch(FINALIZED_BRANCH_NAME.to_owned())
         .await
         .unwrap();
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
-    for client in clients.iter_mut() {
-        client.progress_for_consensus().await.unwrap();
-    }
-    sync_each_other(&mut clients).await;
+    raw.checkout_clean().await.unwrap();
+    raw.checkout_detach(finalized).await.unwrap();
+    let invalid_commit = raw
+        .create_commit(RawCommit {
+            message: "not a semantic commit".to_owned(),
+            diff: None,
+            author: "mirror".to_owned(),
+            email: "mirror@example.com".to_owned(),
+            timestamp: 0,
+        })
+        .await
+        .unwrap();
+    raw.create_branch("invalid".to_owned(), invalid_commit)
+        .await
+        .unwrap();
+    drop(raw);
+    drop(mirror);
 
-    // Step 6: check the result.
-    log::info!("STEP 6");
-    for client in clients.iter() {
-        let raw_repo = client.repository().get_raw();
-        let raw_repo_ = raw_repo.read().await;
-        let finalized = raw_repo_
-            .locate_branch("finalized".to_owned())
-            .await
-            .unwrap();
-        let title = raw_repo_
-            .read_semantic_commit(finalized)
+    let mut client = Client::open(
+        &dir,
+        Config {
+            public_repo_url: vec![mirror_dir],
+            ..Default::default()
+        },
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    client.update().await.unwrap();
+    assert!(client
+        .repository()
+        .read_agendas()
+        .await
+        .unwrap()
+        .iter()
+        .any(|(_, agenda_hash)| *agenda_hash == agenda.to_hash256()));
+    let raw = client.repository().get_raw();
+    let raw = raw.read().await;
+    assert!(raw.get_branches(invalid_commit).await.unwrap().is_empty());
+    assert_eq!(
+        raw.locate_branch(FINALIZED_BRANCH_NAME.to_owned())
             .await
-            .unwrap()
-            .title;
-        assert_eq!(title, ">block: 2");
+            .unwrap(),
+        finalized
+    );
+}
+
+#[tokio::test]
+async fn my_member_name() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+
+    let client = Client::open(
+        &dir,
+        Config::default(),
+        Auth {
+            private_key: keys[1].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        client.my_member_name(),
+        Some(fi.reserved_state.members[1].name.clone())
+    );
+    drop(client);
+
+    // A non-member is detected only if the node is expected to be a validator.
+    let (_, non_member_key) = generate_keypair("non-member");
+    let auth = Auth {
+        private_key: non_member_key,
+        public_key: None,
+    };
+    let error = Client::open(
+        &dir,
+        Config {
+            expect_validator: true,
+            ..Default::default()
+        },
+        auth.clone(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("is not a member"), "{error}");
+    let client = Client::open(&dir, Config::default(), auth).await.unwrap();
+    assert_eq!(client.my_member_name(), None);
+}
+
+#[tokio::test]
+async fn reconcile_detects_fork() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    // The same finalization is not a fork.
+    client.reconcile(&fi).await.unwrap();
+
+    // A conflicting block at the same height, finalized by three of the four validators.
+    let mut peer_fi = fi.clone();
+    peer_fi.header.timestamp = 1;
+
+    // An insufficient proof is just invalid.
+    peer_fi.proof = sign_finalization(peer_fi.header.to_hash256(), &keys[..2]);
+    let error = client.reconcile(&peer_fi).await.unwrap_err();
+    assert!(error.downcast_ref::<ForkDetected>().is_none());
+
+    peer_fi.proof = sign_finalization(peer_fi.header.to_hash256(), &keys[..3]);
+    let error = client.reconcile(&peer_fi).await.unwrap_err();
+    let fork = error.downcast_ref::<ForkDetected>().unwrap();
+    assert_eq!(fork.height, 0);
+    assert_eq!(fork.local_block_hash, fi.header.to_hash256());
+    assert_eq!(fork.peer_block_hash, peer_fi.header.to_hash256());
+    let mut equivocators = keys[..3]
+        .iter()
+        .map(|(public_key, _)| public_key.clone())
+        .collect::<Vec<_>>();
+    equivocators.sort();
+    assert_eq!(fork.equivocators, equivocators);
+}
+
+#[tokio::test]
+async fn leader_selects_proposal_candidate_on_update() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    let (agenda, _) = client
+        .repository_mut()
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    let proof = keys
+        .iter()
+        .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+        .collect();
+    client
+        .repository_mut()
+        .approve(&agenda.to_hash256(), proof, 0)
+        .await
+        .unwrap();
+    let (block_header, _) = client
+        .repository_mut()
+        .create_block(keys[0].0.clone())
+        .await
+        .unwrap();
+
+    // The first member leads the first round, so `update()` sets the block as its candidate.
+    client.update().await.unwrap();
+    let progress = client.progress_for_consensus().await.unwrap();
+    let block_hash = block_header.to_hash256();
+    assert!(matches!(
+        progress,
+        ConsensusProgress::Progressed(results) if results.iter().any(
+            |result| matches!(result, ProgressResult::Proposed(0, hash, _) if *hash == block_hash)
+        )
+    ));
+}
+
+#[tokio::test]
+async fn leader_without_block_candidate_stalls() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    // The first member leads the first round, but there is no block to propose.
+    assert_eq!(
+        client.progress_for_consensus().await.unwrap(),
+        ConsensusProgress::Stalled {
+            reason: StallReason::NoBlockCandidate { round: 0 }
+        }
+    );
+}
+
+#[tokio::test]
+async fn show_block_consensus_status() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    let genesis = client
+        .repository()
+        .read_last_finalization_info()
+        .await
+        .unwrap();
+    let info = client.show(genesis.commit_hash).await.unwrap();
+    assert!(matches!(
+        info,
+        CommitInfo::Block {
+            consensus_status: Some(BlockConsensusStatus::Finalized { round: 0, signers }),
+            ..
+        } if signers == genesis.proof.signatures.len()
+    ));
+
+    let (agenda, _) = client
+        .repository_mut()
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    let proof = keys
+        .iter()
+        .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+        .collect();
+    client
+        .repository_mut()
+        .approve(&agenda.to_hash256(), proof, 0)
+        .await
+        .unwrap();
+    let (_, block_commit_hash) = client
+        .repository_mut()
+        .create_block(keys[0].0.clone())
+        .await
+        .unwrap();
+    // The leader proposes the block and prevotes for it, alone among the four validators.
+    client.update().await.unwrap();
+    client.progress_for_consensus().await.unwrap();
+    client.broadcast().await.unwrap();
+
+    let info = client.show(block_commit_hash).await.unwrap();
+    let status = if let CommitInfo::Block {
+        consensus_status: Some(BlockConsensusStatus::InProgress(status)),
+        ..
+    } = info
+    {
+        status
+    } else {
+        panic!("unexpected commit info: {info:?}");
+    };
+    assert_eq!(status.round, 0);
+    assert!(status.proposed);
+    assert_eq!(status.total_voting_power, 4);
+    assert_eq!(status.votes[&0].prevote, 1);
+    assert_eq!(status.votes[&0].precommit, 0);
+}
+
+#[tokio::test]
+async fn show_finalization_proof() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    let genesis = client
+        .repository()
+        .read_last_finalization_info()
+        .await
+        .unwrap();
+    let fp_commit_hash = client
+        .repository()
+        .get_raw()
+        .read()
+        .await
+        .locate_branch(simperby_repository::FP_BRANCH_NAME.into())
+        .await
+        .unwrap();
+    let info = client.show(fp_commit_hash).await.unwrap();
+    if let CommitInfo::FinalizationProof {
+        proof,
+        finalized_block_hash,
+        round,
+        signer_count,
+        ..
+    } = info
+    {
+        assert_eq!(finalized_block_hash, genesis.header.to_hash256());
+        assert_eq!(round, 0);
+        assert_eq!(signer_count, 4);
+        assert_eq!(proof, genesis.proof);
+    } else {
+        panic!("unexpected commit info: {info:?}");
     }
 }
 
-/// Make two blocks with server participation and premade one-block repository.
+async fn read_consensus_messages(dir: &str, private_key: PrivateKey) -> usize {
+    use simperby_consensus::ConsensusMessage;
+    use simperby_network::{Dms, Storage, StorageImpl};
+
+    let storage = StorageImpl::open(&format!("{dir}/.simperby/consensus/dms"))
+        .await
+        .unwrap();
+    let config = Dms::<ConsensusMessage>::read_stored_config(&storage)
+        .await
+        .unwrap()
+        .unwrap();
+    let dms = Dms::<ConsensusMessage>::new(storage, config, private_key)
+        .await
+        .unwrap();
+    dms.read_messages().await.unwrap().len()
+}
+
 #[tokio::test]
-async fn normal_2_premade() {
+async fn broadcast_only_governance() {
     setup_test();
     let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth.clone())
+        .await
+        .unwrap();
+
+    let (agenda, _) = client
+        .repository_mut()
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    let proof = keys
+        .iter()
+        .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+        .collect();
+    client
+        .repository_mut()
+        .approve(&agenda.to_hash256(), proof, 0)
+        .await
+        .unwrap();
+    client
+        .repository_mut()
+        .create_block(keys[0].0.clone())
+        .await
+        .unwrap();
+    client.update().await.unwrap();
+    client.progress_for_consensus().await.unwrap();
+
+    // The proposal of the leader stays unflushed.
+    client
+        .broadcast_services(BroadcastServices {
+            governance: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    drop(client);
+    assert_eq!(read_consensus_messages(&dir, keys[0].1.clone()).await, 0);
+
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+    client.broadcast().await.unwrap();
+    drop(client);
+    assert!(read_consensus_messages(&dir, keys[0].1.clone()).await > 0);
+}
+
+async fn remove_state_file(dir: String) {
+    run_command(format!(
+        "cd {dir}/.simperby/governance/dms/ && rm state.json"
+    ))
+    .await;
+    run_command(format!(
+        "cd {dir}/.simperby/consensus/dms/ && rm state.json"
+    ))
+    .await;
+    run_command(format!(
+        "cd {dir}/.simperby/consensus/state/ && rm state.json"
+    ))
+    .await;
+    run_command(format!(
+        "cd {dir}/.simperby/repository/dms/ && rm state.json"
+    ))
+    .await;
+}
+
+/// Make two blocks with server participation.
+#[tokio::test]
+async fn normal_2() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi_at(4, simperby_core::utils::get_timestamp());
     let server_config = generate_server_config();
 
     // Setup repository and server.
     let server_dir = create_temp_dir();
-    make_repository_with_one_block(fi.clone(), keys.clone(), server_dir.clone()).await;
+    setup_pre_genesis_repository(&server_dir, fi.reserved_state.clone()).await;
+    Client::genesis(&server_dir).await.unwrap();
+    Client::init(&server_dir).await.unwrap();
+    // Add push configs to server repository.
+    run_command(format!(
+        "cd {server_dir} && git config receive.advertisePushOptions true"
+    ))
+    .await;
+    run_command(format!(
+        "cd {server_dir} && git config sendpack.sideband false"
+    ))
+    .await;
 
     // Setup clients.
     let mut clients = Vec::new();
@@ -412,36 +873,18 @@ async fn normal_2_premade() {
         run_command(format!("cp -a {server_dir}/. {dir}/")).await;
         let auth = Auth {
             private_key: key.clone(),
+            public_key: None,
         };
-        let port = server_config.peers_port;
-        remove_state_file(dir.clone()).await;
-        let mut client = Client::open(&dir, Config {}, auth).await.unwrap();
-        client
-            .add_peer(
-                fi.reserved_state.members[3].name.clone(),
-                format!("127.0.0.1:{port}").parse().unwrap(),
-            )
-            .await
-            .unwrap();
+        let client = Client::open(&dir, Config::default(), auth).await.unwrap();
         clients.push(client);
     }
 
-    // Add push configs to server repository.
-    run_command(format!(
-        "cd {server_dir} && git config receive.advertisePushOptions true"
-    ))
-    .await;
-    run_command(format!(
-        "cd {server_dir} && git config sendpack.sideband false"
-    ))
-    .await;
-    remove_state_file(server_dir.clone()).await;
-
     // Run server.
     let auth = Auth {
         private_key: keys[3].1.clone(),
+        public_key: None,
     };
-    let client = Client::open(&server_dir.clone(), Config {}, auth.clone())
+    let client = Client::open(&server_dir.clone(), Config::default(), auth.clone())
         .await
         .unwrap();
     let server_task =
@@ -457,18 +900,30 @@ async fn normal_2_premade() {
 
     // Setup peer network.
     sleep_ms(500).await;
+    let port = server_config.peers_port;
     for client in clients.iter_mut() {
+        client
+            .add_peer(
+                fi.reserved_state.members[3].name.clone(),
+                format!("127.0.0.1:{port}").parse().unwrap(),
+            )
+            .await
+            .unwrap();
         client.update_peer().await.unwrap();
     }
 
-    sync_each_other(&mut clients).await;
-
     // Make a first block, Step 1 ~ 3.
+    assert!(clients[0]
+        .latest_finalization_proof()
+        .await
+        .unwrap()
+        .is_none());
+
     // Step 1: create an agenda and propagate it.
     log::info!("STEP 1");
     let (_, agenda_commit) = clients[0]
         .repository_mut()
-        .create_agenda(fi.reserved_state.members[0].name.clone())
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
         .await
         .unwrap();
 
@@ -518,14 +973,16 @@ async fn normal_2_premade() {
             .await
             .unwrap()
             .title;
-        assert_eq!(title, ">block: 2");
+        assert_eq!(title, ">block: 1");
     }
 
     // Stop and restart the server.
     server_task.abort();
     remove_state_file(server_dir.clone()).await;
     tokio::spawn(async move {
-        let client = Client::open(&server_dir, Config {}, auth).await.unwrap();
+        let client = Client::open(&server_dir, Config::default(), auth)
+            .await
+            .unwrap();
         let task = client
             .serve(
                 server_config,
@@ -549,7 +1006,7 @@ async fn normal_2_premade() {
     log::info!("STEP 4");
     let (_, agenda_commit) = clients[1]
         .repository_mut()
-        .create_agenda(fi.reserved_state.members[1].name.clone())
+        .create_agenda(fi.reserved_state.members[1].name.clone(), false)
         .await
         .unwrap();
 
@@ -599,29 +1056,249 @@ async fn normal_2_premade() {
             .await
             .unwrap()
             .title;
-        assert_eq!(title, ">block: 3");
+        assert_eq!(title, ">block: 2");
     }
 }
 
-async fn make_repository_with_one_block(
-    fi: FinalizationInfo,
-    keys: Vec<(PublicKey, PrivateKey)>,
-    dir: String,
-) {
-    use simperby_core::verify::CommitSequenceVerifier;
-    use simperby_repository::format::{fp_to_semantic_commit, to_semantic_commit};
-    use simperby_repository::{
-        BRANCH_NAME_HASH_DIGITS, FINALIZED_BRANCH_NAME, FP_BRANCH_NAME, TAG_NAME_HASH_DIGITS,
-    };
+/// Make two blocks with server participation and premade one-block repository.
+#[tokio::test]
+async fn normal_2_premade() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi_at(4, simperby_core::utils::get_timestamp());
+    let server_config = generate_server_config();
 
-    // Setup clients
-    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
-    Client::genesis(&dir).await.unwrap();
-    Client::init(&dir).await.unwrap();
-    let auth = Auth {
-        private_key: keys[3].1.clone(),
-    };
-    let mut client = Client::open(&dir, Config {}, auth).await.unwrap();
+    // Setup repository and server.
+    let server_dir = create_temp_dir();
+    make_repository_with_one_block(fi.clone(), keys.clone(), server_dir.clone()).await;
+
+    // Setup clients.
+    let mut clients = Vec::new();
+    for (_, key) in keys.iter() {
+        let dir = create_temp_dir();
+        run_command(format!("cp -a {server_dir}/. {dir}/")).await;
+        let auth = Auth {
+            private_key: key.clone(),
+            public_key: None,
+        };
+        remove_state_file(dir.clone()).await;
+        let client = Client::open(&dir, Config::default(), auth).await.unwrap();
+        clients.push(client);
+    }
+
+    // Add push configs to server repository.
+    run_command(format!(
+        "cd {server_dir} && git config receive.advertisePushOptions true"
+    ))
+    .await;
+    run_command(format!(
+        "cd {server_dir} && git config sendpack.sideband false"
+    ))
+    .await;
+    remove_state_file(server_dir.clone()).await;
+
+    // Run server.
+    let auth = Auth {
+        private_key: keys[3].1.clone(),
+        public_key: None,
+    };
+    let client = Client::open(&server_dir.clone(), Config::default(), auth.clone())
+        .await
+        .unwrap();
+    let server_task =
+        client
+            .serve(
+                server_config.clone(),
+                simperby_repository::server::PushVerifier::VerifierExecutable(
+                    build_simple_git_server(),
+                ),
+            )
+            .await
+            .unwrap();
+
+    // Setup peer network.
+    sleep_ms(500).await;
+    let port = server_config.peers_port;
+    for client in clients.iter_mut() {
+        client
+            .add_peer(
+                fi.reserved_state.members[3].name.clone(),
+                format!("127.0.0.1:{port}").parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        client.update_peer().await.unwrap();
+    }
+
+    sync_each_other(&mut clients).await;
+
+    // Make a first block, Step 1 ~ 3.
+    assert!(clients[0]
+        .latest_finalization_proof()
+        .await
+        .unwrap()
+        .is_none());
+
+    // Step 1: create an agenda and propagate it.
+    log::info!("STEP 1");
+    let (_, agenda_commit) = clients[0]
+        .repository_mut()
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
+        .await
+        .unwrap();
+
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.vote(agenda_commit).await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+
+    // Step 2: create block and run consensus.
+    log::info!("STEP 2");
+    let proposer_public_key = clients[0].auth().private_key.public_key();
+    clients[0]
+        .repository_mut()
+        .create_block(proposer_public_key)
+        .await
+        .unwrap();
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+
+    // Step 3: check the result.
+    log::info!("STEP 3");
+    for client in clients.iter() {
+        let raw_repo = client.repository().get_raw();
+        let raw_repo_ = raw_repo.read().await;
+        let finalized = raw_repo_
+            .locate_branch("finalized".to_owned())
+            .await
+            .unwrap();
+        let title = raw_repo_
+            .read_semantic_commit(finalized)
+            .await
+            .unwrap()
+            .title;
+        assert_eq!(title, ">block: 2");
+    }
+
+    // Stop and restart the server.
+    server_task.abort();
+    remove_state_file(server_dir.clone()).await;
+    tokio::spawn(async move {
+        let client = Client::open(&server_dir, Config::default(), auth)
+            .await
+            .unwrap();
+        let task = client
+            .serve(
+                server_config,
+                simperby_repository::server::PushVerifier::VerifierExecutable(
+                    build_simple_git_server(),
+                ),
+            )
+            .await
+            .unwrap();
+        task.await.unwrap().unwrap();
+    });
+
+    // Setup peer network.
+    sleep_ms(500).await;
+    for client in clients.iter_mut() {
+        client.update_peer().await.unwrap();
+    }
+
+    // Make a second block, Step 4 ~ 6.
+    // Step 4: create an agenda and propagate it.
+    log::info!("STEP 4");
+    let (_, agenda_commit) = clients[1]
+        .repository_mut()
+        .create_agenda(fi.reserved_state.members[1].name.clone(), false)
+        .await
+        .unwrap();
+
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.vote(agenda_commit).await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+
+    // Step 5: create block and run consensus.
+    log::info!("STEP 5");
+    let proposer_public_key = clients[1].auth().private_key.public_key();
+    clients[1]
+        .repository_mut()
+        .create_block(proposer_public_key)
+        .await
+        .unwrap();
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+    for client in clients.iter_mut() {
+        client.progress_for_consensus().await.unwrap();
+    }
+    sync_each_other(&mut clients).await;
+
+    // Step 6: check the result.
+    log::info!("STEP 6");
+    for client in clients.iter() {
+        let raw_repo = client.repository().get_raw();
+        let raw_repo_ = raw_repo.read().await;
+        let finalized = raw_repo_
+            .locate_branch("finalized".to_owned())
+            .await
+            .unwrap();
+        let title = raw_repo_
+            .read_semantic_commit(finalized)
+            .await
+            .unwrap()
+            .title;
+        assert_eq!(title, ">block: 3");
+    }
+}
+
+async fn make_repository_with_one_block(
+    fi: FinalizationInfo,
+    keys: Vec<(PublicKey, PrivateKey)>,
+    dir: String,
+) {
+    use simperby_core::verify::CommitSequenceVerifier;
+    use simperby_repository::format::{fp_to_semantic_commit, to_semantic_commit};
+    use simperby_repository::{
+        BRANCH_NAME_HASH_DIGITS, FINALIZED_BRANCH_NAME, FP_BRANCH_NAME, TAG_NAME_HASH_DIGITS,
+    };
+
+    // Setup clients
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[3].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
 
     let rs = fi.reserved_state;
     let genesis_info = rs.genesis_info.clone();
@@ -649,8 +1326,7 @@ async fn make_repository_with_one_block(
         .create_semantic_commit(semantic_commit, true)
         .await
         .unwrap();
-    let mut agenda_branch_name = agenda_commit.to_hash256().to_string();
-    agenda_branch_name.truncate(BRANCH_NAME_HASH_DIGITS);
+    let agenda_branch_name = agenda_commit.to_hash256().short(BRANCH_NAME_HASH_DIGITS);
     let agenda_branch_name = format!("a-{agenda_branch_name}");
     raw.write()
         .await
@@ -660,8 +1336,7 @@ async fn make_repository_with_one_block(
     csv.apply_commit(&agenda_commit).unwrap();
 
     // Create tag
-    let mut vote_tag_name = agenda_commit.to_hash256().to_string();
-    vote_tag_name.truncate(TAG_NAME_HASH_DIGITS);
+    let vote_tag_name = agenda_commit.to_hash256().short(TAG_NAME_HASH_DIGITS);
     let vote_tag_name = format!("vote-{vote_tag_name}");
     raw.write()
         .await
@@ -702,7 +1377,9 @@ async fn make_repository_with_one_block(
 
         let agenda_proof_branch_name = format!(
             "a-{}",
-            &agenda_proof_commit.to_hash256().to_string()[0..BRANCH_NAME_HASH_DIGITS]
+            agenda_proof_commit
+                .to_hash256()
+                .short(BRANCH_NAME_HASH_DIGITS)
         );
         raw.write()
             .await
@@ -722,7 +1399,7 @@ async fn make_repository_with_one_block(
         prev_block_finalization_proof: genesis_info.genesis_proof,
         previous_hash: csv.get_header().to_hash256(),
         height: 1,
-        timestamp: 0,
+        timestamp: simperby_core::utils::get_timestamp(),
         commit_merkle_root: BlockHeader::calculate_commit_merkle_root(
             &csv.get_total_commits()[1..],
         ),
@@ -742,8 +1419,7 @@ async fn make_repository_with_one_block(
         .create_semantic_commit(semantic_commit, true)
         .await
         .unwrap();
-    let mut block_branch_name = block_commit.to_hash256().to_string();
-    block_branch_name.truncate(BRANCH_NAME_HASH_DIGITS);
+    let block_branch_name = block_commit.to_hash256().short(BRANCH_NAME_HASH_DIGITS);
     let block_branch_name = format!("b-{block_branch_name}");
     raw.write()
         .await
@@ -759,23 +1435,7 @@ async fn make_repository_with_one_block(
         .unwrap();
 
     // Create fp commit
-    let signatures = keys
-        .iter()
-        .map(|(_, private_key)| {
-            TypedSignature::sign(
-                &FinalizationSignTarget {
-                    block_hash: block_header.to_hash256(),
-                    round: 0,
-                },
-                private_key,
-            )
-            .unwrap()
-        })
-        .collect::<Vec<_>>();
-    let fp = FinalizationProof {
-        round: 0,
-        signatures,
-    };
+    let fp = sign_finalization(block_header.to_hash256(), &keys);
     raw.write()
         .await
         .move_branch(FP_BRANCH_NAME.to_string(), result)
@@ -809,3 +1469,840 @@ async fn make_repository_with_one_block(
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn open_reinitializes_stale_dms() {
+    use simperby_consensus::ConsensusMessage;
+    use simperby_governance::GovernanceMessage;
+    use simperby_network::{keys, Dms, Storage, StorageImpl};
+
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    // The DMSes are created at the genesis, and then a block is finalized.
+    make_repository_with_one_block(fi, keys.clone(), dir.clone()).await;
+
+    let client = Client::open(
+        &dir,
+        Config::default(),
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let lfi = client
+        .repository()
+        .read_last_finalization_info()
+        .await
+        .unwrap();
+    assert_eq!(lfi.header.height, 1);
+    // Release the storage locks held by the client.
+    drop(client);
+    let governance_storage = StorageImpl::open(&format!("{dir}/.simperby/governance/dms"))
+        .await
+        .unwrap();
+    assert_eq!(
+        Dms::<GovernanceMessage>::read_stored_config(&governance_storage)
+            .await
+            .unwrap()
+            .unwrap()
+            .dms_key,
+        keys::dms_key::<GovernanceMessage>(&lfi.header)
+    );
+    let consensus_storage = StorageImpl::open(&format!("{dir}/.simperby/consensus/dms"))
+        .await
+        .unwrap();
+    assert_eq!(
+        Dms::<ConsensusMessage>::read_stored_config(&consensus_storage)
+            .await
+            .unwrap()
+            .unwrap()
+            .dms_key,
+        keys::dms_key::<ConsensusMessage>(&lfi.header)
+    );
+}
+
+#[tokio::test]
+async fn open_twice_fails() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let client = Client::open(&dir, Config::default(), auth.clone())
+        .await
+        .unwrap();
+
+    let error = Client::open(&dir, Config::default(), auth.clone())
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.downcast_ref::<NodeAlreadyOpen>(),
+        Some(&NodeAlreadyOpen { path: dir.clone() })
+    );
+
+    // The lock is released on drop.
+    drop(client);
+    Client::open(&dir, Config::default(), auth).await.unwrap();
+}
+
+#[tokio::test]
+async fn serve_fails_on_bind_error() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let server_config = generate_server_config();
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    // Occupy the governance port so that its server fails to bind.
+    let _listener =
+        std::net::TcpListener::bind(("0.0.0.0", server_config.governance_port)).unwrap();
+    let task = client
+        .serve(
+            server_config,
+            simperby_repository::server::PushVerifier::AlwaysAccept,
+        )
+        .await
+        .unwrap();
+    let result = tokio::time::timeout(std::time::Duration::from_secs(10), task)
+        .await
+        .expect("serve should stop instead of hanging")
+        .unwrap();
+    let error = result.unwrap_err();
+    assert!(
+        error.to_string().contains("the governance server failed"),
+        "{error:?}"
+    );
+    assert_eq!(
+        error
+            .root_cause()
+            .downcast_ref::<std::io::Error>()
+            .map(std::io::Error::kind),
+        Some(std::io::ErrorKind::AddrInUse),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn invalid_configs_are_rejected() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+
+    let invalid_auth = Auth {
+        private_key: PrivateKey::zero(),
+        public_key: None,
+    };
+    let error = Client::open(&dir, Config::default(), invalid_auth)
+        .await
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("private key"), "{error}");
+
+    let config = Config {
+        public_repo_url: vec!["https://a.com/repo".to_owned(); 2],
+        ..Default::default()
+    };
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let error = Client::open(&dir, config, auth.clone())
+        .await
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("duplicate"), "{error}");
+
+    let client = Client::open(&dir, Config::default(), auth).await.unwrap();
+    let mut server_config = generate_server_config();
+    server_config.consensus_port = server_config.governance_port;
+    let error = client
+        .serve(
+            server_config,
+            simperby_repository::server::PushVerifier::AlwaysAccept,
+        )
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains("consensus_port collides with governance_port"),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn mismatched_auth_is_rejected() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+
+    // The public key of another member is stored by mistake.
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: Some(keys[1].0.clone()),
+    };
+    let error = Client::open(&dir, Config::default(), auth)
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        error.to_string().contains(&format!(
+            "the public key {} does not match the private key",
+            keys[1].0
+        )),
+        "{error}"
+    );
+
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: Some(keys[0].0.clone()),
+    };
+    Client::open(&dir, Config::default(), auth).await.unwrap();
+}
+
+#[tokio::test]
+async fn propose_agenda_from_staged() {
+    use simperby_repository::PAYLOAD_BRANCH_NAME;
+
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+
+    // Nothing is staged yet.
+    assert!(client
+        .propose_agenda_from_staged(true)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Stage transactions on the `p` branch.
+    // Note that the `.gitignore` commit made by `init()` is a staged transaction too.
+    let raw = client.repository().get_raw();
+    {
+        let mut raw = raw.write().await;
+        let head = raw.get_head().await.unwrap();
+        raw.create_branch(PAYLOAD_BRANCH_NAME.into(), head)
+            .await
+            .unwrap();
+        raw.checkout(PAYLOAD_BRANCH_NAME.into()).await.unwrap();
+    }
+    let transactions = (0..3)
+        .map(|i| Transaction {
+            author: fi.reserved_state.members[0].name.clone(),
+            timestamp: simperby_core::utils::get_timestamp() + i,
+            head: format!("tx {i}"),
+            body: "".to_owned(),
+            diff: Diff::None,
+            author_signature: None,
+        })
+        .collect::<Vec<_>>();
+    for transaction in transactions.iter() {
+        client
+            .repository_mut()
+            .create_transaction(transaction.clone())
+            .await
+            .unwrap();
+    }
+    let (payload_tip, staged) = client
+        .repository()
+        .read_payload_branch()
+        .await
+        .unwrap()
+        .unwrap();
+    let staged = staged
+        .into_iter()
+        .filter_map(|commit| match commit {
+            Commit::Transaction(transaction) => Some(transaction),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        staged[staged.len() - transactions.len()..]
+            .iter()
+            .map(|t| t.head.clone())
+            .collect::<Vec<_>>(),
+        transactions
+            .iter()
+            .map(|t| t.head.clone())
+            .collect::<Vec<_>>()
+    );
+
+    let (agenda, agenda_commit) = client
+        .propose_agenda_from_staged(true)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        agenda.transactions_hash,
+        Agenda::calculate_transactions_hash(&staged)
+    );
+    assert_eq!(agenda.height, fi.header.height + 1);
+    // The `p` branch is left as it is.
+    assert_eq!(
+        raw.read()
+            .await
+            .locate_branch(PAYLOAD_BRANCH_NAME.into())
+            .await
+            .unwrap(),
+        payload_tip
+    );
+    // `HEAD` is back on the `p` branch.
+    assert_eq!(
+        raw.read().await.get_head_state().await.unwrap(),
+        simperby_repository::raw::HeadState::Attached(PAYLOAD_BRANCH_NAME.into(), payload_tip)
+    );
+    // The agenda is voted by this node.
+    assert!(client.pending_votes().await.unwrap().is_empty());
+    assert!(client
+        .repository()
+        .read_agendas()
+        .await
+        .unwrap()
+        .contains(&(agenda_commit, agenda.to_hash256())));
+}
+
+#[tokio::test]
+async fn catch_up() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let mirror_dir = create_temp_dir();
+    setup_pre_genesis_repository(&mirror_dir, fi.reserved_state.clone()).await;
+    Client::genesis(&mirror_dir).await.unwrap();
+    Client::init(&mirror_dir).await.unwrap();
+    let dir = create_temp_dir();
+    run_command(format!("cp -a {mirror_dir}/. {dir}/")).await;
+
+    // The mirror finalizes 5 blocks that the client doesn't have.
+    let mut mirror = Client::open(
+        &mirror_dir,
+        Config::default(),
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    finalize_blocks(&mut mirror, &keys, 5).await;
+    assert_eq!(
+        mirror
+            .repository()
+            .read_last_finalization_info()
+            .await
+            .unwrap()
+            .header
+            .height,
+        5
+    );
+    drop(mirror);
+
+    let mut client = Client::open(
+        &dir,
+        Config {
+            public_repo_url: vec![mirror_dir],
+            ..Default::default()
+        },
+        Auth {
+            private_key: keys[1].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let finalized_heights = Arc::new(Mutex::new(Vec::new()));
+    let finalized_heights_ = Arc::clone(&finalized_heights);
+    client.on_finalize(move |header, _| finalized_heights_.lock().unwrap().push(header.height));
+    assert!(client.latest_finalization_proof().await.unwrap().is_none());
+    assert_eq!(client.catch_up(None).await.unwrap(), 5);
+    let lfi = client
+        .repository()
+        .read_last_finalization_info()
+        .await
+        .unwrap();
+    assert_eq!(lfi.header.height, 5);
+    assert_eq!(*finalized_heights.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+    // The exported proof follows the blocks finalized by the sync.
+    let finalization = client.latest_finalization_proof().await.unwrap().unwrap();
+    assert_eq!(finalization.header, lfi.header);
+    assert_eq!(finalization.proof, lfi.proof);
+
+    // Nothing more to catch up.
+    assert_eq!(client.catch_up(None).await.unwrap(), 5);
+}
+
+#[tokio::test]
+async fn peer_heights() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let server_config = generate_server_config();
+    let server_dir = create_temp_dir();
+    setup_pre_genesis_repository(&server_dir, fi.reserved_state.clone()).await;
+    Client::genesis(&server_dir).await.unwrap();
+    Client::init(&server_dir).await.unwrap();
+    let dir = create_temp_dir();
+    run_command(format!("cp -a {server_dir}/. {dir}/")).await;
+
+    // The server finalizes 2 blocks that the client doesn't have.
+    let auth = Auth {
+        private_key: keys[3].1.clone(),
+        public_key: None,
+    };
+    let mut server = Client::open(&server_dir, Config::default(), auth.clone())
+        .await
+        .unwrap();
+    finalize_blocks(&mut server, &keys, 2).await;
+    drop(server);
+
+    // Serve the server at its new height.
+    let server_config_ = server_config.clone();
+    let server_dir_ = server_dir.clone();
+    tokio::spawn(async move {
+        let server = Client::open(&server_dir_, Config::default(), auth)
+            .await
+            .unwrap();
+        let task = server
+            .serve(
+                server_config_,
+                simperby_repository::server::PushVerifier::AlwaysReject,
+            )
+            .await
+            .unwrap();
+        task.await.unwrap().unwrap();
+    });
+    sleep_ms(500).await;
+
+    let mut client = Client::open(
+        &dir,
+        Config::default(),
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let port = server_config.peers_port;
+    client
+        .add_peer(
+            fi.reserved_state.members[3].name.clone(),
+            format!("127.0.0.1:{port}").parse().unwrap(),
+        )
+        .await
+        .unwrap();
+    client.update_peer().await.unwrap();
+    let heights = client.get_peer_heights().await.unwrap();
+    assert_eq!(heights.len(), 1);
+    assert_eq!(heights[0].0.name, fi.reserved_state.members[3].name);
+    assert_eq!(heights[0].1, 2);
+
+    // Once caught up, the advertised block is verified against the one finalized here.
+    assert_eq!(client.catch_up(None).await.unwrap(), 2);
+    let heights = client.get_peer_heights().await.unwrap();
+    assert_eq!(
+        heights
+            .into_iter()
+            .map(|(_, height)| height)
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+}
+
+/// A peer is added only with its announcement, which must be signed by the named member.
+#[tokio::test]
+async fn add_peer_verifies_announcement() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let server_config = generate_server_config();
+    let server_dir = create_temp_dir();
+    setup_pre_genesis_repository(&server_dir, fi.reserved_state.clone()).await;
+    Client::genesis(&server_dir).await.unwrap();
+    Client::init(&server_dir).await.unwrap();
+    let dir = create_temp_dir();
+    run_command(format!("cp -a {server_dir}/. {dir}/")).await;
+
+    let mut client = Client::open(
+        &dir,
+        Config::default(),
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let address: std::net::SocketAddr = format!("127.0.0.1:{}", server_config.peers_port)
+        .parse()
+        .unwrap();
+    // Nobody is serving to announce itself.
+    assert!(client
+        .add_peer(fi.reserved_state.members[3].name.clone(), address)
+        .await
+        .is_err());
+
+    let server = Client::open(
+        &server_dir,
+        Config::default(),
+        Auth {
+            private_key: keys[3].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let _server_task = server
+        .serve(
+            server_config.clone(),
+            simperby_repository::server::PushVerifier::AlwaysReject,
+        )
+        .await
+        .unwrap();
+    sleep_ms(500).await;
+
+    // The server announces itself with the key of member-0003, not of member-0001.
+    assert!(client
+        .add_peer(fi.reserved_state.members[1].name.clone(), address)
+        .await
+        .is_err());
+    assert!(client.get_peer_list().await.unwrap().is_empty());
+
+    client
+        .add_peer(fi.reserved_state.members[3].name.clone(), address)
+        .await
+        .unwrap();
+    client.update_peer().await.unwrap();
+    let peers = client.get_peer_list().await.unwrap();
+    assert_eq!(peers.len(), 1);
+    peers[0].verify_announcement().unwrap();
+    assert_eq!(
+        peers[0].service_address("repository").unwrap().port(),
+        server_config.repository_port
+    );
+}
+
+#[tokio::test]
+async fn catch_up_in_windows() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let mirror_dir = create_temp_dir();
+    setup_pre_genesis_repository(&mirror_dir, fi.reserved_state.clone()).await;
+    Client::genesis(&mirror_dir).await.unwrap();
+    Client::init(&mirror_dir).await.unwrap();
+    let dir = create_temp_dir();
+    run_command(format!("cp -a {mirror_dir}/. {dir}/")).await;
+
+    // The mirror finalizes 20 blocks that the client doesn't have.
+    let mut mirror = Client::open(
+        &mirror_dir,
+        Config::default(),
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    finalize_blocks(&mut mirror, &keys, 20).await;
+    drop(mirror);
+
+    let mut client = Client::open(
+        &dir,
+        Config {
+            public_repo_url: vec![mirror_dir],
+            catch_up_window: Some(5),
+            ..Default::default()
+        },
+        Auth {
+            private_key: keys[1].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let count_branches = {
+        let dir = dir.clone();
+        move || {
+            let output = std::process::Command::new("git")
+                .args(["-C", &dir, "for-each-ref", "refs/heads"])
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap().lines().count()
+        }
+    };
+    let initial_branches = count_branches();
+    let finalized = Arc::new(Mutex::new(Vec::new()));
+    let finalized_ = Arc::clone(&finalized);
+    client.on_finalize(move |header, proof| {
+        finalized_
+            .lock()
+            .unwrap()
+            .push((header.clone(), proof.clone(), count_branches()))
+    });
+    assert_eq!(client.catch_up(Some(12)).await.unwrap(), 12);
+    assert_eq!(client.get_consensus_status().await.unwrap().height, 13);
+    // `update()` finalizes the rest at once, reopening the client just like `catch_up()`.
+    client.update().await.unwrap();
+    assert_eq!(client.get_consensus_status().await.unwrap().height, 21);
+
+    // The callbacks are invoked once for every height, in order.
+    let finalized = finalized.lock().unwrap().clone();
+    assert_eq!(
+        finalized
+            .iter()
+            .map(|(header, _, _)| header.height)
+            .collect::<Vec<_>>(),
+        (1..=20).collect::<Vec<_>>()
+    );
+    for window in finalized.windows(2) {
+        let (header, proof, _) = &window[0];
+        let (next_header, _, _) = &window[1];
+        assert_eq!(next_header.previous_hash, header.to_hash256());
+        assert_eq!(&next_header.prev_block_finalization_proof, proof);
+    }
+    let lfi = client
+        .repository()
+        .read_last_finalization_info()
+        .await
+        .unwrap();
+    assert_eq!(finalized.last().unwrap().1, lfi.proof);
+    // The branches created for the windows of `catch_up()` don't pile up.
+    let peak_branches = finalized
+        .iter()
+        .filter(|(header, _, _)| header.height <= 12)
+        .map(|(_, _, branches)| *branches)
+        .max()
+        .unwrap();
+    assert!(
+        peak_branches <= initial_branches + 1,
+        "{initial_branches} -> {peak_branches}"
+    );
+}
+
+#[tokio::test]
+async fn observer_catch_up() {
+    setup_test();
+    // member-0000 delegates its consensus right, so it is not a validator.
+    let (rs, keys) = test_utils::generate_delegated_genesis(4, false);
+    let mirror_dir = create_temp_dir();
+    setup_pre_genesis_repository(&mirror_dir, rs.clone()).await;
+    Client::genesis(&mirror_dir).await.unwrap();
+    Client::init(&mirror_dir).await.unwrap();
+    let dir = create_temp_dir();
+    run_command(format!("cp -a {mirror_dir}/. {dir}/")).await;
+    let stranger_dir = create_temp_dir();
+    run_command(format!("cp -a {mirror_dir}/. {stranger_dir}/")).await;
+
+    let mut observer = Client::open(
+        &dir,
+        Config {
+            public_repo_url: vec![mirror_dir.clone()],
+            ..Default::default()
+        },
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(observer.role(), NodeRole::Observer);
+    observer.progress_for_consensus().await.unwrap();
+    assert!(!observer.veto_round().await.unwrap().broadcasted);
+    drop(observer);
+
+    // The validators finalize 2 blocks.
+    let mut mirror = Client::open(
+        &mirror_dir,
+        Config::default(),
+        Auth {
+            private_key: keys[1].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(mirror.role(), NodeRole::Validator);
+    finalize_blocks(&mut mirror, &keys[1..], 2).await;
+    drop(mirror);
+
+    let mut observer = Client::open(
+        &dir,
+        Config {
+            public_repo_url: vec![mirror_dir.clone()],
+            ..Default::default()
+        },
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(observer.catch_up(None).await.unwrap(), 2);
+    assert_eq!(observer.role(), NodeRole::Observer);
+    observer.progress_for_consensus().await.unwrap();
+    assert!(!observer.veto_round().await.unwrap().broadcasted);
+    drop(observer);
+
+    // A key absent from the reserved state follows the chain as well.
+    let (_, stranger_key) = generate_keypair_random();
+    let mut stranger = Client::open(
+        &stranger_dir,
+        Config {
+            public_repo_url: vec![mirror_dir],
+            ..Default::default()
+        },
+        Auth {
+            private_key: stranger_key,
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(stranger.my_member_name(), None);
+    assert_eq!(stranger.role(), NodeRole::Observer);
+    assert_eq!(stranger.catch_up(None).await.unwrap(), 2);
+    assert_eq!(stranger.role(), NodeRole::Observer);
+    stranger.progress_for_consensus().await.unwrap();
+    assert!(!stranger.veto_round().await.unwrap().broadcasted);
+}
+
+#[tokio::test]
+async fn veto_round_receipt() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi_at(4, simperby_core::utils::get_timestamp());
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    // Not the leader of the first round, so it is still waiting for a proposal.
+    let auth = Auth {
+        private_key: keys[1].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+    let status = client.get_consensus_status().await.unwrap();
+    assert_eq!(status.height, 1);
+    assert!(status.vetoed_rounds.is_empty());
+
+    let receipt = client.veto_round().await.unwrap();
+    assert_eq!(receipt.target, VetoTarget::Round(0));
+    assert!(receipt.broadcasted);
+
+    let status = client.get_consensus_status().await.unwrap();
+    assert_eq!(status.round, receipt.round);
+    assert_eq!(status.vetoed_rounds, [0].into_iter().collect());
+    assert!(status.vetoed_blocks.is_empty());
+}
+
+#[tokio::test]
+async fn propose_reserved_state_changes() {
+    setup_test();
+    let (fi, keys) = test_utils::generate_fi(4);
+    let dir = create_temp_dir();
+    setup_pre_genesis_repository(&dir, fi.reserved_state.clone()).await;
+    Client::genesis(&dir).await.unwrap();
+    Client::init(&dir).await.unwrap();
+    let auth = Auth {
+        private_key: keys[0].1.clone(),
+        public_key: None,
+    };
+    let mut client = Client::open(&dir, Config::default(), auth).await.unwrap();
+    let names = fi
+        .reserved_state
+        .members
+        .iter()
+        .map(|member| member.name.clone())
+        .collect::<Vec<_>>();
+
+    let (add, _) = client
+        .propose_add_member(Member {
+            public_key: generate_keypair_random().0,
+            name: "member-0004".to_owned(),
+            governance_voting_power: 1,
+            consensus_voting_power: 1,
+            governance_delegatee: None,
+            consensus_delegatee: None,
+            expelled: false,
+        })
+        .await
+        .unwrap();
+    let (expel, _) = client.propose_expel_member(names[1].clone()).await.unwrap();
+    let (change, _) = client
+        .propose_change_voting_power(names[2].clone(), 2, 3)
+        .await
+        .unwrap();
+    let lfi = client
+        .repository()
+        .read_last_finalization_info()
+        .await
+        .unwrap();
+    let mut csv =
+        verify::CommitSequenceVerifier::new(lfi.header, lfi.reserved_state.clone()).unwrap();
+    for transaction in [add, expel, change] {
+        csv.apply_commit(&Commit::Transaction(transaction)).unwrap();
+    }
+    let reserved_state = csv.get_reserved_state();
+    assert_eq!(reserved_state.members.len(), 5);
+    assert_eq!(reserved_state.is_expelled(&names[1]), Some(true));
+    assert!(!reserved_state.consensus_leader_order.contains(&names[1]));
+    assert_eq!(reserved_state.members[2].consensus_voting_power, 3);
+
+    // Expelling the last but one leader leaves a single point of failure.
+    client.propose_expel_member(names[2].clone()).await.unwrap();
+    client.propose_expel_member(names[3].clone()).await.unwrap();
+    let head = client
+        .repository()
+        .get_raw()
+        .read()
+        .await
+        .get_head()
+        .await
+        .unwrap();
+    assert!(client
+        .propose_expel_member("member-0004".to_owned())
+        .await
+        .is_err());
+    assert!(client.propose_expel_member(names[1].clone()).await.is_err());
+    assert_eq!(
+        client
+            .repository()
+            .get_raw()
+            .read()
+            .await
+            .get_head()
+            .await
+            .unwrap(),
+        head
+    );
+}
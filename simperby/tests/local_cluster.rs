@@ -0,0 +1,22 @@
+mod cluster;
+
+use cluster::LocalCluster;
+use simperby_test_suite::*;
+
+#[tokio::test]
+async fn local_cluster() {
+    setup_test();
+    let mut cluster = LocalCluster::new(4).await.unwrap();
+    assert_eq!(cluster.finalized_height().await.unwrap(), 0);
+    for _ in 0..30 {
+        if cluster.finalized_height().await.unwrap() >= 3 {
+            break;
+        }
+        cluster.step().await.unwrap();
+    }
+    assert_eq!(cluster.finalized_height().await.unwrap(), 3);
+    for node in cluster.nodes() {
+        let finalization = node.latest_finalization_proof().await.unwrap().unwrap();
+        assert_eq!(finalization.header.height, 3);
+    }
+}
@@ -0,0 +1,133 @@
+use simperby::events::{NodeEvent, LOG_TARGET};
+use simperby::types::{Auth, Config};
+use simperby::*;
+use simperby_core::*;
+use simperby_repository::FINALIZED_BRANCH_NAME;
+use simperby_test_suite::*;
+use std::sync::Mutex;
+
+struct CapturedLogs;
+
+static CAPTURED_LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+impl log::Log for CapturedLogs {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target() == LOG_TARGET
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            CAPTURED_LOGS
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", record.level(), record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[tokio::test]
+async fn finalization_is_logged() {
+    log::set_logger(&CapturedLogs).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    let (fi, keys) = test_utils::generate_fi(4);
+    let mirror_dir = create_temp_dir();
+    setup_pre_genesis_repository(&mirror_dir, fi.reserved_state.clone()).await;
+    Client::genesis(&mirror_dir).await.unwrap();
+    Client::init(&mirror_dir).await.unwrap();
+    let dir = create_temp_dir();
+    run_command(format!("cp -a {mirror_dir}/. {dir}/")).await;
+
+    // The mirror finalizes a block that the client doesn't have.
+    let mut mirror = Client::open(
+        &mirror_dir,
+        Config::default(),
+        Auth {
+            private_key: keys[0].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    let repository = mirror.repository_mut();
+    let (agenda, _) = repository
+        .create_agenda(fi.reserved_state.members[0].name.clone(), false)
+        .await
+        .unwrap();
+    repository
+        .approve(
+            &agenda.to_hash256(),
+            keys.iter()
+                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .collect(),
+            utils::get_timestamp(),
+        )
+        .await
+        .unwrap();
+    let (block, block_commit) = repository.create_block(keys[0].0.clone()).await.unwrap();
+    let signatures = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(
+                &FinalizationSignTarget {
+                    block_hash: block.to_hash256(),
+                    round: 0,
+                },
+                private_key,
+            )
+            .unwrap()
+        })
+        .collect();
+    repository
+        .finalize(
+            block_commit,
+            FinalizationProof {
+                signatures,
+                round: 0,
+            },
+        )
+        .await
+        .unwrap();
+    let raw = repository.get_raw();
+    let finalized = raw
+        .read()
+        .await
+        .locate_branch(FINALIZED_BRANCH_NAME.to_owned())
+        .await
+        .unwrap();
+    raw.write().await.checkout_detach(finalized).await.unwrap();
+    drop(mirror);
+
+    let mut client = Client::open(
+        &dir,
+        Config {
+            public_repo_url: vec![mirror_dir],
+            ..Default::default()
+        },
+        Auth {
+            private_key: keys[1].1.clone(),
+            public_key: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(client.catch_up(None).await.unwrap(), 1);
+
+    let expected = NodeEvent::HeightAdvanced {
+        height: 1,
+        block_hash: block.to_hash256(),
+    };
+    assert_eq!(
+        expected.to_string(),
+        format!("height advanced: height=1 block={}", block.to_hash256())
+    );
+    let logs = CAPTURED_LOGS.lock().unwrap().clone();
+    assert_eq!(
+        logs.iter()
+            .filter(|log| **log == format!("INFO {expected}"))
+            .count(),
+        1,
+        "{logs:?}"
+    );
+}
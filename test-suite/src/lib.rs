@@ -106,6 +106,7 @@ pub async fn setup_server_client_nodes(
     let (_, server_private_key) = keys.last().unwrap().clone();
     let server = ServerNetworkConfig {
         port: dispense_port(),
+        rate_limit: None,
     };
     let mut clients = Vec::new();
     for (_, private_key) in keys {
@@ -117,7 +118,7 @@ pub async fn setup_server_client_nodes(
                 ports: vec![(format!("dms-{dms_key}"), server.port)]
                     .into_iter()
                     .collect(),
-                message: "".to_owned(),
+                announcement: None,
                 recently_seen_timestamp: 0,
             }],
         };
@@ -15,6 +15,15 @@ pub type VotingPower = u64;
 /// A UNIX timestamp measured in milliseconds.
 pub type Timestamp = i64;
 
+/// An error that occurs when the given `HeightInfo` can't be used to run the consensus.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("the validator set is empty")]
+    EmptyValidatorSet,
+    #[error("this node index {index} is out of the validator set of size {size}")]
+    InvalidNodeIndex { index: ValidatorIndex, size: usize },
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct ConsensusParams {
     pub timeout_ms: u64,
@@ -42,8 +51,14 @@ pub enum ConsensusEvent {
         /// Whether this node is in favor of the proposal.
         favor: bool,
     },
-    /// Informs that the node wants to skip the specific round regardless of proposals (which may even not exist).
-    SkipRound { round: Round },
+    /// Informs that a validator (possibly this node) wants to skip the specific round
+    /// regardless of proposals (which may even not exist).
+    ///
+    /// Once the validators who want to skip the current round form a quorum, the round advances.
+    SkipRound {
+        signer: ValidatorIndex,
+        round: Round,
+    },
     /// Updates the block candidate in which this nodes wants to propose
     BlockCandidateUpdated { proposal: BlockIdentifier },
     /// Informs that the node has received a block prevote.
@@ -160,8 +175,11 @@ pub struct HeightInfo {
     /// The consensus parameters
     pub consensus_params: ConsensusParams,
 
-    /// The initial block candidate that this node wants to propose.
-    pub initial_block_candidate: BlockIdentifier,
+    /// The initial block candidate that this node wants to propose, if any.
+    ///
+    /// Without a candidate, this node doesn't propose in the rounds it leads
+    /// until `ConsensusEvent::BlockCandidateUpdated` gives one.
+    pub initial_block_candidate: Option<BlockIdentifier>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -170,22 +188,65 @@ pub struct Vetomint {
 }
 
 impl Vetomint {
+    /// Creates a new instance of the consensus state machine.
+    ///
+    /// Panics if the `height_info` is invalid. Use `try_new()` for untrusted height information.
     pub fn new(height_info: HeightInfo) -> Self {
-        Self {
-            state: state::ConsensusState::new(height_info),
+        Self::try_new(height_info).expect("invalid height info")
+    }
+
+    /// Creates a new instance of the consensus state machine,
+    /// failing if the validator set is empty or `this_node_index` is out of the validator set.
+    pub fn try_new(height_info: HeightInfo) -> Result<Self, Error> {
+        if height_info.validators.is_empty() {
+            return Err(Error::EmptyValidatorSet);
         }
+        if let Some(index) = height_info.this_node_index {
+            if index >= height_info.validators.len() {
+                return Err(Error::InvalidNodeIndex {
+                    index,
+                    size: height_info.validators.len(),
+                });
+            }
+        }
+        Ok(Self {
+            state: state::ConsensusState::new(height_info),
+        })
     }
 
     pub fn get_height_info(&self) -> &HeightInfo {
         &self.state.height_info
     }
 
+    /// Returns the round that the state machine is currently in.
+    pub fn get_round(&self) -> Round {
+        self.state.round
+    }
+
+    /// Returns whether the state machine is in the propose step of the current round,
+    /// which means that it hasn't prevoted in the round yet.
+    pub fn is_in_propose_step(&self) -> bool {
+        self.state.step == state::ConsensusStep::Propose
+    }
+
     pub fn progress(
         &mut self,
         event: ConsensusEvent,
         timestamp: Timestamp,
     ) -> Vec<ConsensusResponse> {
         let mut responses = progress::progress(&mut self.state, event, timestamp);
+        if self.state.height_info.this_node_index.is_none() {
+            // A non-validator node only follows the votes of the others.
+            responses.retain(|response| {
+                !matches!(
+                    response,
+                    ConsensusResponse::BroadcastProposal { .. }
+                        | ConsensusResponse::BroadcastPrevote { .. }
+                        | ConsensusResponse::BroadcastPrecommit { .. }
+                )
+            });
+            return responses;
+        }
         let mut final_responses = responses.clone();
         // feedback to myself
         loop {
@@ -244,15 +305,203 @@ impl Vetomint {
     }
 }
 
-pub fn decide_proposer(round: usize, height_info: &HeightInfo) -> ValidatorIndex {
-    if round < height_info.consensus_params.repeat_round_for_first_leader {
-        0
+/// Decides the proposer of the given round.
+///
+/// The first leader (index 0) proposes for the first `repeat_round_for_first_leader` rounds,
+/// and then the leadership rotates over the validators in the leader order.
+pub fn decide_proposer(round: usize, height_info: &HeightInfo) -> Result<ValidatorIndex, Error> {
+    let validators = height_info.validators.len();
+    if validators == 0 {
+        return Err(Error::EmptyValidatorSet);
+    }
+    if validators == 1 || round < height_info.consensus_params.repeat_round_for_first_leader {
+        Ok(0)
     } else {
-        (round - height_info.consensus_params.repeat_round_for_first_leader + 1)
-            % height_info.validators.len()
+        Ok((round - height_info.consensus_params.repeat_round_for_first_leader + 1) % validators)
     }
 }
 
+/// Decides the first round after the given one that has a different proposer.
+///
+/// It is where the node fast-forwards to when the proposer of the given round is unresponsive,
+/// instead of waiting for the same proposer to fail round after round.
+/// With a single validator, it is simply the next round.
+pub fn decide_next_leader_round(round: Round, height_info: &HeightInfo) -> Result<Round, Error> {
+    let proposer = decide_proposer(round, height_info)?;
+    if height_info.validators.len() == 1 {
+        return Ok(round + 1);
+    }
+    let mut next_round = round + 1;
+    while decide_proposer(next_round, height_info)? == proposer {
+        next_round += 1;
+    }
+    Ok(next_round)
+}
+
 pub fn decide_timeout(params: &ConsensusParams, _round: usize) -> Timestamp {
     params.timeout_ms as i64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn height_info(validators: usize, repeat_round_for_first_leader: usize) -> HeightInfo {
+        HeightInfo {
+            validators: vec![1; validators],
+            this_node_index: None,
+            timestamp: 0,
+            consensus_params: ConsensusParams {
+                timeout_ms: 100,
+                repeat_round_for_first_leader,
+            },
+            initial_block_candidate: Some(0),
+        }
+    }
+
+    #[test]
+    fn decide_proposer_empty() {
+        let height_info = height_info(0, 1);
+        assert_eq!(
+            decide_proposer(0, &height_info),
+            Err(Error::EmptyValidatorSet)
+        );
+        assert_eq!(
+            Vetomint::try_new(height_info).unwrap_err(),
+            Error::EmptyValidatorSet
+        );
+    }
+
+    #[test]
+    fn decide_proposer_single() {
+        let height_info = height_info(1, 2);
+        for round in 0..10 {
+            assert_eq!(decide_proposer(round, &height_info), Ok(0));
+        }
+    }
+
+    #[test]
+    fn decide_proposer_round_robin() {
+        let height_info = height_info(4, 3);
+        let proposers = (0..10)
+            .map(|round| decide_proposer(round, &height_info).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(proposers, vec![0, 0, 0, 1, 2, 3, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn decide_next_leader_round_skips_repeated_rounds() {
+        let next_rounds = (0..6)
+            .map(|round| decide_next_leader_round(round, &height_info(4, 3)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(next_rounds, vec![3, 3, 3, 4, 5, 6]);
+        assert_eq!(decide_next_leader_round(0, &height_info(1, 3)), Ok(1));
+    }
+
+    #[test]
+    fn try_new_invalid_node_index() {
+        let mut height_info = height_info(4, 1);
+        height_info.this_node_index = Some(4);
+        assert_eq!(
+            Vetomint::try_new(height_info).unwrap_err(),
+            Error::InvalidNodeIndex { index: 4, size: 4 }
+        );
+    }
+
+    #[test]
+    fn non_validator_never_broadcasts() {
+        let mut vetomint = Vetomint::new(height_info(4, 1));
+        let mut responses = vetomint.progress(ConsensusEvent::Start, 0);
+        // The proposal of the round 0 times out.
+        responses.extend(vetomint.progress(ConsensusEvent::Timer, 1000));
+        for signer in 0..4 {
+            responses.extend(vetomint.progress(
+                ConsensusEvent::Prevote {
+                    proposal: None,
+                    signer,
+                    round: 0,
+                },
+                1000,
+            ));
+        }
+        responses.extend(vetomint.progress(ConsensusEvent::Timer, 2000));
+        assert_eq!(responses, vec![]);
+    }
+
+    #[test]
+    fn proposal_for_later_round_is_buffered() {
+        let mut height_info = height_info(4, 1);
+        height_info.this_node_index = Some(3);
+        let mut vetomint = Vetomint::new(height_info);
+        assert_eq!(vetomint.progress(ConsensusEvent::Start, 0), vec![]);
+
+        // The proposer of round 1 re-proposes the block before this node leaves round 0.
+        let proposal = |round, proposer| ConsensusEvent::BlockProposalReceived {
+            proposal: 0,
+            valid: true,
+            valid_round: None,
+            proposer,
+            round,
+            favor: true,
+        };
+        assert_eq!(vetomint.progress(proposal(1, 1), 1), vec![]);
+        assert_eq!(vetomint.get_round(), 0);
+        assert_eq!(
+            vetomint.progress(proposal(0, 0), 2),
+            vec![ConsensusResponse::BroadcastPrevote {
+                proposal: Some(0),
+                round: 0,
+            }]
+        );
+
+        // Once round 0 fails, the buffered proposal is voted for right away.
+        let mut responses = Vec::new();
+        for signer in 0..3 {
+            responses.extend(vetomint.progress(
+                ConsensusEvent::Precommit {
+                    proposal: None,
+                    signer,
+                    round: 0,
+                },
+                3,
+            ));
+        }
+        assert_eq!(vetomint.get_round(), 1);
+        assert_eq!(
+            responses,
+            vec![ConsensusResponse::BroadcastPrevote {
+                proposal: Some(0),
+                round: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn leader_proposes_late_block_candidate() {
+        let mut height_info = height_info(4, 1);
+        height_info.this_node_index = Some(0);
+        height_info.initial_block_candidate = None;
+        let mut vetomint = Vetomint::new(height_info);
+        assert_eq!(vetomint.progress(ConsensusEvent::Start, 0), vec![]);
+        assert!(vetomint.is_in_propose_step());
+
+        let candidate = |proposal| ConsensusEvent::BlockCandidateUpdated { proposal };
+        assert_eq!(
+            vetomint.progress(candidate(1), 1),
+            vec![
+                ConsensusResponse::BroadcastProposal {
+                    proposal: 1,
+                    valid_round: None,
+                    round: 0,
+                },
+                ConsensusResponse::BroadcastPrevote {
+                    proposal: Some(1),
+                    round: 0,
+                }
+            ]
+        );
+        assert!(!vetomint.is_in_propose_step());
+        // It never proposes twice in the same round.
+        assert_eq!(vetomint.progress(candidate(2), 2), vec![]);
+    }
+}
@@ -13,6 +13,27 @@ pub(crate) fn progress(
             round,
         }];
     }
+    let event_round = match &event {
+        ConsensusEvent::BlockProposalReceived { round, .. }
+        | ConsensusEvent::SkipRound { round, .. }
+        | ConsensusEvent::Prevote { round, .. }
+        | ConsensusEvent::Precommit { round, .. } => Some(*round),
+        _ => None,
+    };
+    let mut response = process_event(state, event, timestamp);
+    if let Some(round) = event_round {
+        response.extend(on_f_plus_1_messages_in_higher_round(
+            state, round, timestamp,
+        ));
+    }
+    response
+}
+
+fn process_event(
+    state: &mut ConsensusState,
+    event: ConsensusEvent,
+    timestamp: Timestamp,
+) -> Vec<ConsensusResponse> {
     match event {
         ConsensusEvent::Start => start_round(state, 0, timestamp),
         ConsensusEvent::BlockProposalReceived {
@@ -24,7 +45,7 @@ pub(crate) fn progress(
             favor,
         } => {
             state.proposals.insert(
-                proposal,
+                (round, proposal),
                 Proposal {
                     proposal,
                     valid,
@@ -48,21 +69,24 @@ pub(crate) fn progress(
             response.extend(on_4f_non_nil_precommit(state, round, proposal));
             response
         }
-        ConsensusEvent::SkipRound { round } => progress(
-            state,
-            ConsensusEvent::BlockProposalReceived {
-                proposal: 0,
-                valid: false,
-                valid_round: None,
-                proposer: 0,
-                round,
-                favor: false,
-            },
-            timestamp,
-        ),
+        ConsensusEvent::SkipRound { signer, round } => {
+            state.skip_votes.insert((round, signer));
+            let mut response = Vec::new();
+            if Some(signer) == state.height_info.this_node_index {
+                response.extend(on_skip_round_by_this_node(state, round));
+            }
+            response.extend(on_4f_skip_round(state, round, timestamp));
+            response.extend(on_f_plus_1_leader_failure(state, round, timestamp));
+            response
+        }
         ConsensusEvent::BlockCandidateUpdated { proposal } => {
-            state.block_candidate = proposal;
-            Vec::new()
+            let had_candidate = state.block_candidate.replace(proposal).is_some();
+            if had_candidate {
+                // This node has already proposed with the previous one, if it leads the round.
+                Vec::new()
+            } else {
+                on_first_block_candidate(state)
+            }
         }
         ConsensusEvent::Prevote {
             proposal,
@@ -86,6 +110,9 @@ pub(crate) fn progress(
                 response.extend(on_4f_nil_prevote(state, round));
             }
             response.extend(on_5f_prevote(state, round, proposal));
+            if proposal.is_none() {
+                response.extend(on_f_plus_1_leader_failure(state, round, timestamp));
+            }
             response.extend(misbehavior::check_double_prevote(state, round));
             response
         }
@@ -133,6 +160,13 @@ pub(crate) fn progress(
     }
 }
 
+/// Decides the proposer of the round, given that the validator set
+/// has been checked to be non-empty when the state machine was created.
+fn proposer_of(state: &ConsensusState, round: Round) -> ValidatorIndex {
+    decide_proposer(round, &state.height_info)
+        .expect("the validator set is checked to be non-empty on creation")
+}
+
 fn start_round(
     state: &mut ConsensusState,
     round: usize,
@@ -140,25 +174,79 @@ fn start_round(
 ) -> Vec<ConsensusResponse> {
     state.round = round;
     state.step = ConsensusStep::Propose;
-    let proposer = decide_proposer(round, &state.height_info);
-    if Some(proposer) == state.height_info.this_node_index {
-        let proposal = if let Some(x) = state.valid_value {
-            x
+    let proposer = proposer_of(state, round);
+    let proposal = state.valid_value.or(state.block_candidate);
+    let mut response = match proposal {
+        Some(proposal) if Some(proposer) == state.height_info.this_node_index => {
+            vec![ConsensusResponse::BroadcastProposal {
+                proposal,
+                valid_round: state.valid_round,
+                round,
+            }]
+        }
+        // A leader without anything to propose waits like the others,
+        // until it gets a block candidate or the round times out.
+        _ => {
+            state.propose_timeout_schedules.insert((
+                round,
+                timestamp + decide_timeout(&state.height_info.consensus_params, round),
+            ));
+            Vec::new()
+        }
+    };
+    response.extend(on_buffered_proposals(state, round));
+    response
+}
+
+/// Proposes the first block candidate if this node leads the current round
+/// but had nothing to propose when it entered the round.
+fn on_first_block_candidate(state: &mut ConsensusState) -> Vec<ConsensusResponse> {
+    let round = state.round;
+    if state.step != ConsensusStep::Propose
+        || state.height_info.this_node_index != Some(proposer_of(state, round))
+    {
+        return Vec::new();
+    }
+    let proposal = match state.valid_value.or(state.block_candidate) {
+        Some(x) => x,
+        None => return Vec::new(),
+    };
+    vec![ConsensusResponse::BroadcastProposal {
+        proposal,
+        valid_round: state.valid_round,
+        round,
+    }]
+}
+
+/// Processes the proposals of the round that have arrived before this node entered it.
+fn on_buffered_proposals(
+    state: &mut ConsensusState,
+    target_round: Round,
+) -> Vec<ConsensusResponse> {
+    let proposals = state
+        .proposals
+        .values()
+        .filter(|proposal| proposal.round == target_round)
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut response = Vec::new();
+    for proposal in proposals {
+        if proposal.valid_round.is_some() {
+            response.extend(on_4f_non_nil_prevote_in_propose_step(
+                state,
+                target_round,
+                proposal.proposal,
+            ));
         } else {
-            state.block_candidate
-        };
-        vec![ConsensusResponse::BroadcastProposal {
-            proposal,
-            valid_round: state.valid_round,
-            round,
-        }]
-    } else {
-        state.propose_timeout_schedules.insert((
-            round,
-            timestamp + decide_timeout(&state.height_info.consensus_params, round),
+            response.extend(on_proposal(state, target_round, proposal.proposal));
+        }
+        response.extend(on_4f_non_nil_prevote_in_prevote_step(
+            state,
+            target_round,
+            proposal.proposal,
         ));
-        Vec::new()
     }
+    response
 }
 
 fn on_proposal(
@@ -174,8 +262,8 @@ fn on_proposal(
     let locked_value: i64 = state.locked_value.map(|x| x as i64).unwrap_or(-1);
     let locked_round: i64 = state.locked_round.map(|x| x as i64).unwrap_or(-1);
 
-    let valid_proposer = decide_proposer(target_round, &state.height_info);
-    let proposal = if let Some(proposal) = state.proposals.get(&target_proposal) {
+    let valid_proposer = proposer_of(state, target_round);
+    let proposal = if let Some(proposal) = state.proposals.get(&(target_round, target_proposal)) {
         proposal.clone()
     } else {
         return Vec::new();
@@ -215,8 +303,8 @@ fn on_4f_non_nil_prevote_in_propose_step(
     // take `None` as `-1` for simple comparison
     let locked_value: i64 = state.locked_value.map(|x| x as i64).unwrap_or(-1);
     let locked_round: i64 = state.locked_round.map(|x| x as i64).unwrap_or(-1);
-    let valid_proposer = decide_proposer(target_round, &state.height_info);
-    let proposal = if let Some(proposal) = state.proposals.get(&target_proposal) {
+    let valid_proposer = proposer_of(state, target_round);
+    let proposal = if let Some(proposal) = state.proposals.get(&(target_round, target_proposal)) {
         proposal.clone()
     } else {
         return Vec::new();
@@ -262,8 +350,8 @@ fn on_4f_non_nil_prevote_in_prevote_step(
     if target_round != state.round {
         return Vec::new();
     }
-    let valid_proposer = decide_proposer(target_round, &state.height_info);
-    let proposal = if let Some(proposal) = state.proposals.get(&target_proposal) {
+    let valid_proposer = proposer_of(state, target_round);
+    let proposal = if let Some(proposal) = state.proposals.get(&(target_round, target_proposal)) {
         proposal.clone()
     } else {
         return Vec::new();
@@ -381,8 +469,8 @@ fn on_4f_non_nil_precommit(
     target_round: Round,
     target_proposal: BlockIdentifier,
 ) -> Vec<ConsensusResponse> {
-    let valid_proposer = decide_proposer(target_round, &state.height_info);
-    let proposal = if let Some(proposal) = state.proposals.get(&target_proposal) {
+    let valid_proposer = proposer_of(state, target_round);
+    let proposal = if let Some(proposal) = state.proposals.get(&(target_round, target_proposal)) {
         proposal.clone()
     } else {
         return Vec::new();
@@ -414,3 +502,83 @@ fn on_4f_non_nil_precommit(
         Vec::new()
     }
 }
+
+/// This node doesn't want the current round; it prevotes nil without waiting for the proposal.
+fn on_skip_round_by_this_node(
+    state: &mut ConsensusState,
+    target_round: Round,
+) -> Vec<ConsensusResponse> {
+    if target_round != state.round || state.step != ConsensusStep::Propose {
+        return Vec::new();
+    }
+    state.step = ConsensusStep::Prevote;
+    vec![ConsensusResponse::BroadcastPrevote {
+        proposal: None,
+        round: target_round,
+    }]
+}
+
+fn on_4f_skip_round(
+    state: &mut ConsensusState,
+    target_round: Round,
+    timestamp: Timestamp,
+) -> Vec<ConsensusResponse> {
+    if target_round != state.round {
+        return Vec::new();
+    }
+    if state.get_total_skip_votes(target_round) * 3 > state.get_total_voting_power() * 2 {
+        start_round(state, target_round + 1, timestamp)
+    } else {
+        Vec::new()
+    }
+}
+
+/// Fast-forwards to the next round of another proposer once validators of more than 1/3 voting power
+/// have given up on the round (by a nil prevote or a skip vote) without a proposal from its proposer,
+/// who would otherwise keep the leadership for the next round.
+///
+/// The rest of the voting power can't form a quorum for any proposal in the round anyway,
+/// so a failed first leader doesn't stall the chain for `repeat_round_for_first_leader` timeouts.
+fn on_f_plus_1_leader_failure(
+    state: &mut ConsensusState,
+    target_round: Round,
+    timestamp: Timestamp,
+) -> Vec<ConsensusResponse> {
+    if target_round != state.round || state.step == ConsensusStep::Initial {
+        return Vec::new();
+    }
+    let proposer = proposer_of(state, target_round);
+    let next_round = decide_next_leader_round(target_round, &state.height_info)
+        .expect("the validator set is checked to be non-empty on creation");
+    if next_round == target_round + 1
+        || state
+            .proposals
+            .values()
+            .any(|proposal| proposal.round == target_round && proposal.proposer == proposer)
+        || state.get_total_nil_prevotes_or_skip_votes(target_round) * 3
+            <= state.get_total_voting_power()
+    {
+        return Vec::new();
+    }
+    start_round(state, next_round, timestamp)
+}
+
+/// Catches up with a higher round once validators of more than 1/3 voting power
+/// (so at least one of them is honest) have sent messages in it.
+fn on_f_plus_1_messages_in_higher_round(
+    state: &mut ConsensusState,
+    target_round: Round,
+    timestamp: Timestamp,
+) -> Vec<ConsensusResponse> {
+    if target_round <= state.round
+        || state.step == ConsensusStep::Initial
+        || state.finalized.is_some()
+        || state.get_total_participants(target_round) * 3 <= state.get_total_voting_power()
+    {
+        return Vec::new();
+    }
+    // The proposals of the new round that were ignored so far are processed on entering it.
+    let mut response = start_round(state, target_round, timestamp);
+    response.extend(on_4f_skip_round(state, target_round, timestamp));
+    response
+}
@@ -35,10 +35,15 @@ pub(crate) struct ConsensusState {
     pub locked_round: Option<Round>,
     pub valid_value: Option<BlockIdentifier>,
     pub valid_round: Option<Round>,
-    pub block_candidate: BlockIdentifier,
-    pub proposals: BTreeMap<BlockIdentifier, Proposal>,
+    pub block_candidate: Option<BlockIdentifier>,
+    /// The proposals received so far, keyed by their round and block.
+    ///
+    /// Proposals for a later round are kept until this node enters the round.
+    pub proposals: BTreeMap<(Round, BlockIdentifier), Proposal>,
     pub prevotes: BTreeSet<Vote>,
     pub precommits: BTreeSet<Vote>,
+    /// The validators who want to skip the round, as `(round, signer)`.
+    pub skip_votes: BTreeSet<(Round, ValidatorIndex)>,
     pub propose_timeout_schedules: BTreeSet<(Round, Timestamp)>,
     pub precommit_timeout_schedules: BTreeSet<(Round, Timestamp)>,
     pub for_the_first_time_1: BTreeSet<Round>,
@@ -49,6 +54,7 @@ pub(crate) struct ConsensusState {
 impl ConsensusState {
     pub(crate) fn new(height_info: HeightInfo) -> Self {
         ConsensusState {
+            block_candidate: height_info.initial_block_candidate,
             height_info,
             round: 0,
             step: ConsensusStep::Initial,
@@ -56,10 +62,10 @@ impl ConsensusState {
             locked_round: None,
             valid_value: None,
             valid_round: None,
-            block_candidate: BlockIdentifier::default(),
             proposals: Default::default(),
             prevotes: Default::default(),
             precommits: Default::default(),
+            skip_votes: Default::default(),
             propose_timeout_schedules: Default::default(),
             precommit_timeout_schedules: Default::default(),
             for_the_first_time_1: Default::default(),
@@ -127,6 +133,63 @@ impl ConsensusState {
             .map(|vote| self.height_info.validators[vote.signer])
             .sum()
     }
+
+    pub(crate) fn get_total_skip_votes(&self, round: Round) -> VotingPower {
+        self.skip_votes
+            .iter()
+            .filter(|(skip_round, _)| *skip_round == round)
+            .map(|(_, signer)| self.height_info.validators[*signer])
+            .sum()
+    }
+
+    /// Returns the total voting power of the validators who have given up on the given round,
+    /// either by prevoting nil or by voting to skip it.
+    pub(crate) fn get_total_nil_prevotes_or_skip_votes(&self, round: Round) -> VotingPower {
+        let signers = self
+            .prevotes
+            .iter()
+            .filter(|vote| vote.round == round && vote.proposal.is_none())
+            .map(|vote| vote.signer)
+            .chain(
+                self.skip_votes
+                    .iter()
+                    .filter(|(skip_round, _)| *skip_round == round)
+                    .map(|(_, signer)| *signer),
+            )
+            .collect::<BTreeSet<_>>();
+        signers
+            .into_iter()
+            .map(|signer| self.height_info.validators[signer])
+            .sum()
+    }
+
+    /// Returns the total voting power of the validators who have sent any message
+    /// (a proposal, a prevote, a precommit or a skip vote) in the given round.
+    pub(crate) fn get_total_participants(&self, round: Round) -> VotingPower {
+        let signers = self
+            .proposals
+            .values()
+            .filter(|proposal| proposal.round == round)
+            .map(|proposal| proposal.proposer)
+            .chain(
+                self.prevotes
+                    .iter()
+                    .chain(self.precommits.iter())
+                    .filter(|vote| vote.round == round)
+                    .map(|vote| vote.signer),
+            )
+            .chain(
+                self.skip_votes
+                    .iter()
+                    .filter(|(skip_round, _)| *skip_round == round)
+                    .map(|(_, signer)| *signer),
+            )
+            .collect::<BTreeSet<_>>();
+        signers
+            .into_iter()
+            .map(|signer| self.height_info.validators[signer])
+            .sum()
+    }
 }
 
 #[cfg(test)]
@@ -142,7 +205,7 @@ mod tests {
                 timeout_ms: 100,
                 repeat_round_for_first_leader: 1,
             },
-            initial_block_candidate: 0,
+            initial_block_candidate: Some(0),
         };
         ConsensusState::new(height_info)
     }
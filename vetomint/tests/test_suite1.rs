@@ -11,7 +11,7 @@ fn normal_1() {
             timeout_ms: 100,
             repeat_round_for_first_leader: 1,
         },
-        initial_block_candidate: 0,
+        initial_block_candidate: Some(0),
     };
     let mut proposer = Vetomint::new(height_info.clone());
     let mut nodes = Vec::new();
@@ -141,3 +141,201 @@ fn double_votes_1() {}
 #[ignore]
 #[test]
 fn timeout_prevote_1() {}
+
+fn height_info(this_node_index: ValidatorIndex) -> HeightInfo {
+    HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(this_node_index),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+        },
+        initial_block_candidate: Some(0),
+    }
+}
+
+/// More than 2/3 of the validators want to skip the round, so the round advances.
+#[test]
+fn skip_round_quorum() {
+    let mut node = Vetomint::new(height_info(1));
+    assert_eq!(node.progress(ConsensusEvent::Start, 0), vec![]);
+
+    let response = node.progress(
+        ConsensusEvent::SkipRound {
+            signer: 1,
+            round: 0,
+        },
+        1,
+    );
+    assert_eq!(
+        response,
+        vec![ConsensusResponse::BroadcastPrevote {
+            proposal: None,
+            round: 0,
+        }]
+    );
+    let response = node.progress(
+        ConsensusEvent::SkipRound {
+            signer: 2,
+            round: 0,
+        },
+        1,
+    );
+    assert_eq!(response, vec![]);
+    assert_eq!(node.get_round(), 0);
+
+    // This node is the proposer of the next round.
+    let response = node.progress(
+        ConsensusEvent::SkipRound {
+            signer: 3,
+            round: 0,
+        },
+        1,
+    );
+    assert_eq!(node.get_round(), 1);
+    assert_eq!(
+        response,
+        vec![
+            ConsensusResponse::BroadcastProposal {
+                proposal: 0,
+                valid_round: None,
+                round: 1,
+            },
+            ConsensusResponse::BroadcastPrevote {
+                proposal: Some(0),
+                round: 1,
+            }
+        ]
+    );
+}
+
+/// Validators of more than 1/3 voting power are in a higher round, so the node catches up.
+#[test]
+fn higher_round_catch_up() {
+    let mut node = Vetomint::new(height_info(3));
+    assert_eq!(node.progress(ConsensusEvent::Start, 0), vec![]);
+
+    let response = node.progress(
+        ConsensusEvent::BlockProposalReceived {
+            proposal: 0,
+            valid: true,
+            valid_round: None,
+            proposer: 1,
+            round: 5,
+            favor: true,
+        },
+        1,
+    );
+    assert_eq!(response, vec![]);
+    assert_eq!(node.get_round(), 0);
+
+    // The proposal of the round is processed right after catching up.
+    let response = node.progress(
+        ConsensusEvent::Prevote {
+            proposal: Some(0),
+            signer: 2,
+            round: 5,
+        },
+        1,
+    );
+    assert_eq!(node.get_round(), 5);
+    assert_eq!(
+        response,
+        vec![ConsensusResponse::BroadcastPrevote {
+            proposal: Some(0),
+            round: 5,
+        }]
+    );
+}
+
+/// The first leader never proposes, but the others fast-forward to the next leader
+/// instead of waiting for it to fail all the rounds it repeats.
+#[test]
+fn first_leader_failure_fast_forward() {
+    let repeat_round_for_first_leader = 10;
+    // The first leader (index 0) is offline.
+    let mut nodes = (1..4)
+        .map(|i| {
+            let mut height_info = height_info(i);
+            height_info.consensus_params.repeat_round_for_first_leader =
+                repeat_round_for_first_leader;
+            (i, Vetomint::new(height_info))
+        })
+        .collect::<Vec<_>>();
+    let mut messages = Vec::new();
+    for (i, node) in nodes.iter_mut() {
+        messages.extend(
+            node.progress(ConsensusEvent::Start, 0)
+                .into_iter()
+                .map(|response| (*i, response)),
+        );
+    }
+    assert!(messages.is_empty());
+
+    // The proposal of the round 0 times out once.
+    let mut finalized = Vec::new();
+    for (i, node) in nodes.iter_mut() {
+        messages.extend(
+            node.progress(ConsensusEvent::Timer, 100)
+                .into_iter()
+                .map(|response| (*i, response)),
+        );
+    }
+    while !messages.is_empty() {
+        let mut next_messages = Vec::new();
+        for (sender, message) in messages {
+            let event = match message {
+                ConsensusResponse::BroadcastProposal {
+                    proposal,
+                    valid_round,
+                    round,
+                } => ConsensusEvent::BlockProposalReceived {
+                    proposal,
+                    valid: true,
+                    valid_round,
+                    proposer: sender,
+                    round,
+                    favor: true,
+                },
+                ConsensusResponse::BroadcastPrevote { proposal, round } => {
+                    ConsensusEvent::Prevote {
+                        proposal,
+                        signer: sender,
+                        round,
+                    }
+                }
+                ConsensusResponse::BroadcastPrecommit { proposal, round } => {
+                    ConsensusEvent::Precommit {
+                        proposal,
+                        signer: sender,
+                        round,
+                    }
+                }
+                ConsensusResponse::FinalizeBlock { round, .. } => {
+                    finalized.push((sender, round));
+                    continue;
+                }
+                ConsensusResponse::ViolationReport { .. } => panic!("no one misbehaves"),
+            };
+            for (i, node) in nodes.iter_mut().filter(|(i, _)| *i != sender) {
+                next_messages.extend(
+                    node.progress(event.clone(), 101)
+                        .into_iter()
+                        .map(|response| (*i, response)),
+                );
+            }
+        }
+        messages = next_messages;
+    }
+
+    // Everyone finalizes in the first round of the second leader, with no more timeouts.
+    finalized.sort();
+    finalized.dedup();
+    assert_eq!(
+        finalized,
+        (1..4)
+            .map(|i| (i, repeat_round_for_first_leader))
+            .collect::<Vec<_>>()
+    );
+}